@@ -0,0 +1,1610 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{ed25519_program, instruction::Instruction, sysvar},
+    system_program, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{associated_token, token};
+use registry::{
+    accounts, instruction, pause::PauseGroup, pda::*, roles::Role, signatures,
+    state::ProposalAccountMeta, AgentParams,
+};
+
+use std::ops::Range;
+
+use crate::{AuditedService, RegistryClient, SlashTarget};
+
+impl RegistryClient {
+    /// Bond token accounts of a service bonded in `bond_mint`, with `counterparty`
+    /// owning the token account paying or receiving the moved amount. Services bonded
+    /// in lamports pass `None`.
+    fn bond_token(
+        &self,
+        service_id: u128,
+        bond_mint: Option<&Pubkey>,
+        counterparty: Option<&Pubkey>,
+    ) -> accounts::BondToken {
+        accounts::BondToken {
+            service_bond_token: service_bond_token_pda(
+                self.registry_seed(),
+                service_id,
+                &self.program_id,
+            )
+            .0,
+            bond_token_vault: bond_mint
+                .map(|mint| bond_token_vault_pda(&self.registry, mint, &self.program_id).0),
+            vault_token: bond_mint.map(|mint| self.vault_token(mint)),
+            escrow_token: bond_mint.map(|mint| self.escrow_token(service_id, mint)),
+            counterparty_token: bond_mint.zip(counterparty).map(|(mint, counterparty)| {
+                associated_token::get_associated_token_address(counterparty, mint)
+            }),
+            token_program: bond_mint.map(|_| token::ID),
+        }
+    }
+
+    fn instruction(&self, accounts: Vec<AccountMeta>, data: impl InstructionData) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: data.data(),
+        }
+    }
+
+    /// `registry` must sign next to `user`, as it is created by the instruction.
+    pub fn initialize(
+        &self,
+        user: &Pubkey,
+        name: String,
+        symbol: String,
+        base_uri: String,
+        manager: &Pubkey,
+        drainer: &Pubkey,
+    ) -> Instruction {
+        self.instruction(
+            accounts::Initialize {
+                registry: self.registry,
+                roles: self.roles(),
+                registry_wallet: self.registry_wallet(),
+                user: *user,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            instruction::Initialize {
+                name,
+                symbol,
+                base_uri,
+                manager: *manager,
+                drainer: *drainer,
+            },
+        )
+    }
+
+    /// Creates the roles account of a registry initialized before it, signed by the
+    /// registry owner.
+    pub fn initialize_roles(&self, owner: &Pubkey) -> Instruction {
+        self.instruction(
+            accounts::InitializeRoles {
+                registry: self.registry,
+                roles: self.roles(),
+                user: *owner,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            instruction::InitializeRoles {},
+        )
+    }
+
+    pub fn migrate_registry(&self, user: &Pubkey) -> Instruction {
+        self.instruction(
+            accounts::MigrateRegistry {
+                registry: self.registry,
+                user: *user,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            instruction::MigrateRegistry {},
+        )
+    }
+
+    fn roles_accounts(&self, user: &Pubkey) -> Vec<AccountMeta> {
+        accounts::UpdateRoles {
+            registry: self.registry,
+            roles: self.roles(),
+            user: *user,
+        }
+        .to_account_metas(None)
+    }
+
+    /// `admin` holds the admin role of `role`.
+    pub fn grant_role(&self, admin: &Pubkey, role: Role, account: &Pubkey) -> Instruction {
+        self.instruction(
+            self.roles_accounts(admin),
+            instruction::GrantRole {
+                role,
+                account: *account,
+            },
+        )
+    }
+
+    /// `admin` holds the admin role of `role`.
+    pub fn revoke_role(&self, admin: &Pubkey, role: Role, account: &Pubkey) -> Instruction {
+        self.instruction(
+            self.roles_accounts(admin),
+            instruction::RevokeRole {
+                role,
+                account: *account,
+            },
+        )
+    }
+
+    pub fn renounce_role(&self, holder: &Pubkey, role: Role) -> Instruction {
+        self.instruction(
+            self.roles_accounts(holder),
+            instruction::RenounceRole { role },
+        )
+    }
+
+    /// `admin` holds the `Admin` role.
+    pub fn set_role_admin(&self, admin: &Pubkey, role: Role, admin_role: Role) -> Instruction {
+        self.instruction(
+            self.roles_accounts(admin),
+            instruction::SetRoleAdmin { role, admin_role },
+        )
+    }
+
+    /// `service_id` is the id the service is created with, the registry `total_supply + 1`.
+    /// In permissionless mode `manager` can be the service owner, paying the creation fee.
+    pub fn create(
+        &self,
+        manager: &Pubkey,
+        service_id: u128,
+        config_hash: [u8; 32],
+        service_owner: &Pubkey,
+        threshold: Option<u32>,
+    ) -> Instruction {
+        let service = self.service(service_id);
+        self.instruction(
+            accounts::CreateService {
+                registry: self.registry,
+                roles: self.roles(),
+                service,
+                config_hash_history: config_hash_history_pda(&service, &self.program_id).0,
+                service_escrow: self.service_escrow(service_id),
+                service_mint: service_mint_pda(self.registry_seed(), service_id, &self.program_id)
+                    .0,
+                service_owner: *service_owner,
+                service_token: self.service_token(service_id, service_owner),
+                registry_wallet: self.registry_wallet(),
+                user: *manager,
+                system_program: system_program::ID,
+                token_program: token::ID,
+                associated_token_program: associated_token::ID,
+            }
+            .to_account_metas(None),
+            instruction::Create {
+                config_hash,
+                service_owner: *service_owner,
+                threshold,
+            },
+        )
+    }
+
+    /// `legacy_seed` is the first 7 bytes of the config hash the service was created with,
+    /// `service_owner` the owner recorded in the legacy service.
+    /// `operators` are the operators with instances in the legacy service, in any order.
+    /// The accounts of the migrated service keep their legacy seeds, see
+    /// [`RegistryClient::with_legacy_seeds`].
+    pub fn migrate_service(
+        &self,
+        manager: &Pubkey,
+        legacy_seed: [u8; 7],
+        service_id: u128,
+        service_owner: &Pubkey,
+        operators: &[Pubkey],
+    ) -> Instruction {
+        let program_id = &self.program_id;
+        let legacy = self.with_legacy_seeds();
+        let mut metas = accounts::MigrateService {
+            registry: self.registry,
+            roles: self.roles(),
+            legacy_service: legacy_service_pda(&legacy_seed, program_id).0,
+            service: self.service(service_id),
+            service_escrow: legacy.service_escrow(service_id),
+            service_mint: service_mint_pda(legacy.registry_seed(), service_id, program_id).0,
+            service_owner: *service_owner,
+            service_token: legacy.service_token(service_id, service_owner),
+            registry_wallet: self.registry_wallet(),
+            user: *manager,
+            system_program: system_program::ID,
+            token_program: token::ID,
+            associated_token_program: associated_token::ID,
+        }
+        .to_account_metas(None);
+        let mut operators = operators.to_vec();
+        operators.sort();
+        for operator in &operators {
+            metas.push(AccountMeta::new_readonly(
+                operator_bond_pda(legacy.registry_seed(), service_id, operator, program_id).0,
+                false,
+            ));
+            metas.push(AccountMeta::new_readonly(
+                operator_agent_instance_index_pda(
+                    legacy.registry_seed(),
+                    service_id,
+                    operator,
+                    program_id,
+                )
+                .0,
+                false,
+            ));
+        }
+
+        self.instruction(
+            metas,
+            instruction::MigrateService {
+                legacy_seed,
+                service_id,
+            },
+        )
+    }
+
+    pub fn update(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        config_hash: [u8; 32],
+        service_owner: &Pubkey,
+        threshold: Option<u32>,
+    ) -> Instruction {
+        self.instruction(
+            accounts::UpdateService {
+                registry: self.registry,
+                roles: self.roles(),
+                service: *service,
+                config_hash_history: config_hash_history_pda(service, &self.program_id).0,
+                service_token: self.service_token(service_id, service_owner),
+                user: *manager,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            instruction::Update {
+                config_hash,
+                service_owner: *service_owner,
+                threshold,
+            },
+        )
+    }
+
+    /// Read-only; the page comes back as the transaction return data.
+    pub fn get_config_hash_history(&self, service: &Pubkey, start: u32) -> Instruction {
+        self.instruction(
+            accounts::GetConfigHashHistory {
+                service: *service,
+                config_hash_history: config_hash_history_pda(service, &self.program_id).0,
+            }
+            .to_account_metas(None),
+            instruction::GetConfigHashHistory { start },
+        )
+    }
+
+    fn agent_ids_accounts(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        service_owner: &Pubkey,
+        agent_ids: &[u32],
+    ) -> Vec<AccountMeta> {
+        let mut metas = accounts::RegisterAgentIdsToService {
+            registry: self.registry,
+            roles: self.roles(),
+            service: *service,
+            service_agent_ids_index: service_agent_ids_index_pda(
+                self.registry_seed(),
+                service_id,
+                &self.program_id,
+            )
+            .0,
+            service_token: Some(self.service_token(service_id, service_owner)),
+            user: *manager,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+        metas.extend(self.agent_params_accounts(service_id, agent_ids));
+
+        metas
+    }
+
+    /// One agent_param PDA per agent id, created, updated or closed in place.
+    pub(crate) fn agent_params_accounts(
+        &self,
+        service_id: u128,
+        agent_ids: &[u32],
+    ) -> Vec<AccountMeta> {
+        agent_ids
+            .iter()
+            .map(|agent_id| {
+                AccountMeta::new(
+                    agent_param_pda(
+                        self.registry_seed(),
+                        service_id,
+                        *agent_id,
+                        &self.program_id,
+                    )
+                    .0,
+                    false,
+                )
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn register_agent_ids_to_service(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        service_owner: &Pubkey,
+        agent_ids: &[u32],
+        agent_params: &[AgentParams],
+        threshold: Option<u32>,
+    ) -> Instruction {
+        self.instruction(
+            self.agent_ids_accounts(manager, service, service_id, service_owner, agent_ids),
+            instruction::RegisterAgentIdsToService {
+                service_owner: *service_owner,
+                agent_ids: agent_ids.to_vec(),
+                agent_params: agent_params.to_vec(),
+                threshold,
+            },
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_agent_id_to_service(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        service_owner: &Pubkey,
+        agent_id: u32,
+        params: AgentParams,
+        threshold: Option<u32>,
+    ) -> Instruction {
+        self.instruction(
+            self.agent_ids_accounts(manager, service, service_id, service_owner, &[agent_id]),
+            instruction::AddAgentIdToService {
+                service_owner: *service_owner,
+                agent_id,
+                slots: params.slots,
+                bond: params.bond,
+                threshold,
+            },
+        )
+    }
+
+    pub fn delete_agent_id_to_service(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        service_owner: &Pubkey,
+        agent_id: u32,
+        threshold: Option<u32>,
+    ) -> Instruction {
+        self.instruction(
+            self.agent_ids_accounts(manager, service, service_id, service_owner, &[agent_id]),
+            instruction::DeleteAgentIdToService {
+                service_owner: *service_owner,
+                agent_id,
+                threshold,
+            },
+        )
+    }
+
+    pub fn check_service(&self, service: &Pubkey, service_id: u128) -> Instruction {
+        self.instruction(
+            accounts::CheckService {
+                service: *service,
+                service_agent_ids_index: service_agent_ids_index_pda(
+                    self.registry_seed(),
+                    service_id,
+                    &self.program_id,
+                )
+                .0,
+            }
+            .to_account_metas(None),
+            instruction::CheckService { service_id },
+        )
+    }
+
+    pub fn set_service_bond_token(
+        &self,
+        manager: &Pubkey,
+        service_id: u128,
+        bond_mint: &Pubkey,
+    ) -> Instruction {
+        self.instruction(
+            accounts::SetServiceBondToken {
+                registry: self.registry,
+                roles: self.roles(),
+                service: self.service(service_id),
+                service_bond_token: service_bond_token_pda(
+                    self.registry_seed(),
+                    service_id,
+                    &self.program_id,
+                )
+                .0,
+                bond_mint: *bond_mint,
+                bond_token_vault: bond_token_vault_pda(&self.registry, bond_mint, &self.program_id)
+                    .0,
+                vault_token: self.vault_token(bond_mint),
+                service_escrow: self.service_escrow(service_id),
+                escrow_token: self.escrow_token(service_id, bond_mint),
+                user: *manager,
+                system_program: system_program::ID,
+                token_program: token::ID,
+                associated_token_program: associated_token::ID,
+            }
+            .to_account_metas(None),
+            instruction::SetServiceBondToken { service_id },
+        )
+    }
+
+    /// Signed by `service_owner`, who also pays for the token account of `new_owner`
+    /// when it does not exist yet.
+    pub fn transfer_service(
+        &self,
+        service_owner: &Pubkey,
+        service_id: u128,
+        new_owner: &Pubkey,
+    ) -> Instruction {
+        self.instruction(
+            accounts::TransferService {
+                registry: self.registry,
+                service: self.service(service_id),
+                service_owner: *service_owner,
+                service_token: self.service_token(service_id, service_owner),
+                service_mint: service_mint_pda(self.registry_seed(), service_id, &self.program_id)
+                    .0,
+                new_service_owner: *new_owner,
+                new_service_token: self.service_token(service_id, new_owner),
+                system_program: system_program::ID,
+                token_program: token::ID,
+                associated_token_program: associated_token::ID,
+            }
+            .to_account_metas(None),
+            instruction::TransferService {
+                service_id,
+                new_owner: *new_owner,
+            },
+        )
+    }
+
+    /// Read-only; the URI comes back as the transaction return data. The same URI can
+    /// be computed offline with [`crate::token_uri`].
+    pub fn token_uri(&self, service_id: u128) -> Instruction {
+        self.instruction(
+            accounts::TokenUri {
+                registry: self.registry,
+                service: self.service(service_id),
+            }
+            .to_account_metas(None),
+            instruction::TokenUri { service_id },
+        )
+    }
+
+    pub fn set_instance_proofs(
+        &self,
+        manager: &Pubkey,
+        service_id: u128,
+        required: bool,
+    ) -> Instruction {
+        self.instruction(
+            accounts::SetInstanceProofs {
+                registry: self.registry,
+                roles: self.roles(),
+                service: self.service(service_id),
+                user: *manager,
+            }
+            .to_account_metas(None),
+            instruction::SetInstanceProofs {
+                service_id,
+                required,
+            },
+        )
+    }
+
+    /// Creates the operator whitelist of the service if needed, paid by the service
+    /// owner.
+    pub fn set_operators_check(
+        &self,
+        service_owner: &Pubkey,
+        service_id: u128,
+        check: bool,
+    ) -> Instruction {
+        self.instruction(
+            self.operator_whitelist_accounts(service_owner, service_id),
+            instruction::SetOperatorsCheck { service_id, check },
+        )
+    }
+
+    /// `statuses[i]` adds `operators[i]` to the whitelist when true and removes it
+    /// when false.
+    pub fn set_operators_statuses(
+        &self,
+        service_owner: &Pubkey,
+        service_id: u128,
+        operators: &[Pubkey],
+        statuses: &[bool],
+        check: bool,
+    ) -> Instruction {
+        self.instruction(
+            self.operator_whitelist_accounts(service_owner, service_id),
+            instruction::SetOperatorsStatuses {
+                service_id,
+                operators: operators.to_vec(),
+                statuses: statuses.to_vec(),
+                check,
+            },
+        )
+    }
+
+    fn operator_whitelist_accounts(
+        &self,
+        service_owner: &Pubkey,
+        service_id: u128,
+    ) -> Vec<AccountMeta> {
+        accounts::UpdateOperatorWhitelist {
+            registry: self.registry,
+            service: self.service(service_id),
+            operator_whitelist: self.operator_whitelist(service_id),
+            service_owner: *service_owner,
+            service_token: self.service_token(service_id, service_owner),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None)
+    }
+
+    /// `bond_mint` is the service bond token, `None` for deposits paid in lamports.
+    pub fn activate_registration(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        service_owner: &Pubkey,
+        bond_mint: Option<&Pubkey>,
+    ) -> Instruction {
+        self.instruction(
+            accounts::ActivateRegistration {
+                registry: self.registry,
+                roles: self.roles(),
+                service: *service,
+                service_token: self.service_token(service_id, service_owner),
+                service_escrow: self.service_escrow(service_id),
+                bond_token: self.bond_token(service_id, bond_mint, Some(manager)),
+                user: *manager,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            instruction::ActivateRegistration {
+                service_id,
+                service_owner: *service_owner,
+            },
+        )
+    }
+
+    /// Accounts of a registration paid by `payer`, followed by its remaining accounts.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn registration_accounts(
+        &self,
+        payer: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        operator: &Pubkey,
+        agent_instances: &[Pubkey],
+        agent_ids: &[u32],
+        bond_mint: Option<&Pubkey>,
+    ) -> (accounts::RegisterAgentInstances, Vec<AccountMeta>) {
+        let program_id = &self.program_id;
+        let accounts = accounts::RegisterAgentInstances {
+            registry: self.registry,
+            service: *service,
+            service_escrow: self.service_escrow(service_id),
+            bond_token: self.bond_token(service_id, bond_mint, Some(payer)),
+            operator_agent_instance_index: operator_agent_instance_index_pda(
+                self.registry_seed(),
+                service_id,
+                operator,
+                program_id,
+            )
+            .0,
+            operator_whitelist: self.operator_whitelist(service_id),
+            user: *payer,
+            instructions: sysvar::instructions::ID,
+            system_program: system_program::ID,
+        };
+
+        // 1. agent params, read to compute the bond
+        let mut metas: Vec<AccountMeta> = agent_ids
+            .iter()
+            .map(|agent_id| {
+                AccountMeta::new_readonly(
+                    agent_param_pda(self.registry_seed(), service_id, *agent_id, program_id).0,
+                    false,
+                )
+            })
+            .collect();
+
+        // 2. operator check, 3. service agent instances index
+        metas.push(AccountMeta::new(
+            operator_as_agent_pda(operator, program_id).0,
+            false,
+        ));
+        metas.push(AccountMeta::new(
+            agent_instances_index_pda(self.registry_seed(), service_id, program_id).0,
+            false,
+        ));
+
+        // 4. per instance: slot counter, service agent instance, operator agent instance
+        for (agent_id, agent_instance) in agent_ids.iter().zip(agent_instances) {
+            metas.push(AccountMeta::new(
+                service_agent_slot_counter_pda(
+                    self.registry_seed(),
+                    service_id,
+                    *agent_id,
+                    program_id,
+                )
+                .0,
+                false,
+            ));
+            metas.push(AccountMeta::new(
+                service_agent_instance_pda(
+                    self.registry_seed(),
+                    service_id,
+                    *agent_id,
+                    agent_instance,
+                    program_id,
+                )
+                .0,
+                false,
+            ));
+            metas.push(AccountMeta::new(
+                operator_agent_instance_pda(agent_instance, operator, program_id).0,
+                false,
+            ));
+        }
+
+        // 5. operator bond
+        metas.push(AccountMeta::new(
+            operator_bond_pda(self.registry_seed(), service_id, operator, program_id).0,
+            false,
+        ));
+
+        (accounts, metas)
+    }
+
+    /// Registration signed and paid by `operator` itself. `agent_ids[i]` is the agent
+    /// id `agent_instances[i]` registers for.
+    pub fn register_agents(
+        &self,
+        operator: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        agent_instances: &[Pubkey],
+        agent_ids: &[u32],
+        bond_mint: Option<&Pubkey>,
+    ) -> Instruction {
+        let (accounts, remaining_accounts) = self.registration_accounts(
+            operator,
+            service,
+            service_id,
+            operator,
+            agent_instances,
+            agent_ids,
+            bond_mint,
+        );
+        let mut metas = accounts.to_account_metas(None);
+        metas.extend(remaining_accounts);
+
+        self.instruction(
+            metas,
+            instruction::RegisterAgents {
+                operator: *operator,
+                agent_instances: agent_instances.to_vec(),
+                agent_ids: agent_ids.to_vec(),
+            },
+        )
+    }
+
+    /// Ed25519 program check of `signature`, the `agent_instance` signature of
+    /// [`RegistryClient::instance_proof_message`], to place before the registration of
+    /// `agent_instance` in a service requiring instance proofs.
+    pub fn instance_proof(
+        &self,
+        agent_instance: &Pubkey,
+        signature: &[u8; 64],
+        service_id: u128,
+        operator: &Pubkey,
+    ) -> Instruction {
+        let message = self.instance_proof_message(service_id, operator);
+        ed25519_instruction(agent_instance, signature, &message)
+    }
+
+    /// Registration of `operator` paid by the manager: the Ed25519 program check of
+    /// `signature`, the operator signature of [`RegistryClient::registration_message`]
+    /// at `nonce`, then the registration itself.
+    #[allow(clippy::too_many_arguments)]
+    pub fn register_agents_with_signature(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        operator: &Pubkey,
+        agent_instances: &[Pubkey],
+        agent_ids: &[u32],
+        bond_mint: Option<&Pubkey>,
+        signature: &[u8; 64],
+        nonce: u64,
+    ) -> [Instruction; 2] {
+        let message = self.registration_message(service_id, agent_instances, agent_ids, nonce);
+        let ed25519 = ed25519_instruction(operator, signature, &message);
+
+        let (registration, remaining_accounts) = self.registration_accounts(
+            manager,
+            service,
+            service_id,
+            operator,
+            agent_instances,
+            agent_ids,
+            bond_mint,
+        );
+        let mut metas = accounts::RegisterAgentInstancesWithSignature {
+            roles: self.roles(),
+            registration,
+            operator: *operator,
+            operator_nonce: operator_nonce_pda(operator, &self.program_id).0,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+        metas.extend(remaining_accounts);
+
+        let register = self.instruction(
+            metas,
+            instruction::RegisterAgentsWithSignature {
+                operator: *operator,
+                agent_instances: agent_instances.to_vec(),
+                agent_ids: agent_ids.to_vec(),
+            },
+        );
+
+        [ed25519, register]
+    }
+
+    /// `multisig` is the account created by `multisig_implementation`, see
+    /// [`RegistryClient::builtin_multisig`] for the registry built-in one.
+    #[allow(clippy::too_many_arguments)]
+    pub fn deploy(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        service_owner: &Pubkey,
+        multisig_implementation: &Pubkey,
+        multisig: &Pubkey,
+        agent_instances: &[Pubkey],
+        data: Vec<u8>,
+    ) -> Instruction {
+        let mut metas = accounts::Deploy {
+            registry: self.registry,
+            roles: self.roles(),
+            service: *service,
+            service_owner: *service_owner,
+            service_token: self.service_token(service_id, service_owner),
+            registry_multisig: self.registry_multisig(),
+            multisig_implementation: *multisig_implementation,
+            agent_instances_index: agent_instances_index_pda(
+                self.registry_seed(),
+                service_id,
+                &self.program_id,
+            )
+            .0,
+            user: *manager,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+        metas.extend(Self::deploy_accounts(multisig, agent_instances));
+
+        self.instruction(
+            metas,
+            instruction::Deploy {
+                service_id,
+                multisig_implementation: *multisig_implementation,
+                data,
+            },
+        )
+    }
+
+    /// The multisig, then the agent instances it is created for.
+    pub(crate) fn deploy_accounts(
+        multisig: &Pubkey,
+        agent_instances: &[Pubkey],
+    ) -> Vec<AccountMeta> {
+        let mut metas = vec![AccountMeta::new(*multisig, false)];
+        metas.extend(
+            agent_instances
+                .iter()
+                .map(|agent_instance| AccountMeta::new_readonly(*agent_instance, false)),
+        );
+
+        metas
+    }
+
+    pub fn terminate(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        service_owner: &Pubkey,
+        bond_mint: Option<&Pubkey>,
+    ) -> Instruction {
+        let program_id = &self.program_id;
+        self.instruction(
+            accounts::TerminateService {
+                registry: self.registry,
+                roles: self.roles(),
+                service: *service,
+                service_agent_ids_index: service_agent_ids_index_pda(
+                    self.registry_seed(),
+                    service_id,
+                    program_id,
+                )
+                .0,
+                service_escrow: self.service_escrow(service_id),
+                service_owner: *service_owner,
+                service_token: self.service_token(service_id, service_owner),
+                bond_token: self.bond_token(service_id, bond_mint, Some(service_owner)),
+                user: *manager,
+            }
+            .to_account_metas(None),
+            instruction::Terminate { service_id },
+        )
+    }
+
+    /// Closes the agent PDAs `range` of a terminated service, `range` starting at its
+    /// `cleanup_cursor`. See [`RegistryClient::cleanup_accounts`] for their order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cleanup_service(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        service_owner: &Pubkey,
+        agent_ids: &[u32],
+        agent_instances: &[(Pubkey, u32)],
+        range: Range<usize>,
+    ) -> Instruction {
+        let program_id = &self.program_id;
+        let mut metas = accounts::CleanupService {
+            registry: self.registry,
+            service: *service,
+            service_agent_ids_index: service_agent_ids_index_pda(
+                self.registry_seed(),
+                service_id,
+                program_id,
+            )
+            .0,
+            agent_instances_index: agent_instances_index_pda(
+                self.registry_seed(),
+                service_id,
+                program_id,
+            )
+            .0,
+            service_owner: *service_owner,
+            service_token: self.service_token(service_id, service_owner),
+            roles: self.roles(),
+            user: *manager,
+        }
+        .to_account_metas(None);
+        metas.extend_from_slice(
+            &self.cleanup_accounts(service_id, agent_ids, agent_instances)[range],
+        );
+
+        self.instruction(metas, instruction::CleanupService { service_id })
+    }
+
+    /// Agent PDAs of a service in cleanup order: the slot counter of each of `agent_ids`,
+    /// then the account of each of `agent_instances` with the agent id it is registered
+    /// for, both in on-chain index order.
+    pub fn cleanup_accounts(
+        &self,
+        service_id: u128,
+        agent_ids: &[u32],
+        agent_instances: &[(Pubkey, u32)],
+    ) -> Vec<AccountMeta> {
+        let program_id = &self.program_id;
+        let slot_counters = agent_ids.iter().map(|agent_id| {
+            service_agent_slot_counter_pda(self.registry_seed(), service_id, *agent_id, program_id)
+                .0
+        });
+        let instance_accounts = agent_instances.iter().map(|(agent_instance, agent_id)| {
+            service_agent_instance_pda(
+                self.registry_seed(),
+                service_id,
+                *agent_id,
+                agent_instance,
+                program_id,
+            )
+            .0
+        });
+
+        slot_counters
+            .chain(instance_accounts)
+            .map(|pda| AccountMeta::new(pda, false))
+            .collect()
+    }
+
+    /// Unbonds `agent_instances`, the next instances of the operator in
+    /// `OperatorAgentInstanceIndex` order with the agent id each one is registered for.
+    pub fn unbond(
+        &self,
+        manager: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        operator: &Pubkey,
+        agent_instances: &[(Pubkey, u32)],
+    ) -> Instruction {
+        let program_id = &self.program_id;
+        let mut metas = accounts::UnbondOperator {
+            registry: self.registry,
+            roles: self.roles(),
+            service: *service,
+            service_agent_ids_index: service_agent_ids_index_pda(
+                self.registry_seed(),
+                service_id,
+                program_id,
+            )
+            .0,
+            operator_agent_instance_index: operator_agent_instance_index_pda(
+                self.registry_seed(),
+                service_id,
+                operator,
+                program_id,
+            )
+            .0,
+            operator_bond: operator_bond_pda(
+                self.registry_seed(),
+                service_id,
+                operator,
+                program_id,
+            )
+            .0,
+            operator: *operator,
+            pending_withdrawal: pending_withdrawal_pda(
+                self.registry_seed(),
+                service_id,
+                operator,
+                program_id,
+            )
+            .0,
+            user: *manager,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+        metas.extend(self.unbond_accounts(service_id, operator, agent_instances));
+
+        self.instruction(metas, instruction::Unbond { service_id })
+    }
+
+    /// Signed by the operator itself, or by the manager claiming for it. `payer` paid the
+    /// rent of the pending withdrawal in `unbond`.
+    pub fn claim_unbonded(
+        &self,
+        caller: &Pubkey,
+        service_id: u128,
+        operator: &Pubkey,
+        payer: &Pubkey,
+        bond_mint: Option<&Pubkey>,
+    ) -> Instruction {
+        self.instruction(
+            accounts::ClaimUnbonded {
+                registry: self.registry,
+                service: self.service(service_id),
+                pending_withdrawal: pending_withdrawal_pda(
+                    self.registry_seed(),
+                    service_id,
+                    operator,
+                    &self.program_id,
+                )
+                .0,
+                payer: *payer,
+                operator: *operator,
+                service_escrow: self.service_escrow(service_id),
+                bond_token: self.bond_token(service_id, bond_mint, Some(operator)),
+                roles: self.roles(),
+                user: *caller,
+            }
+            .to_account_metas(None),
+            instruction::ClaimUnbonded { service_id },
+        )
+    }
+
+    /// Per agent instance, its operator agent instance PDA and the service agent instance
+    /// PDA that one references.
+    pub(crate) fn unbond_accounts(
+        &self,
+        service_id: u128,
+        operator: &Pubkey,
+        agent_instances: &[(Pubkey, u32)],
+    ) -> Vec<AccountMeta> {
+        let program_id = &self.program_id;
+        agent_instances
+            .iter()
+            .flat_map(|(agent_instance, agent_id)| {
+                [
+                    AccountMeta::new(
+                        operator_agent_instance_pda(agent_instance, operator, program_id).0,
+                        false,
+                    ),
+                    AccountMeta::new_readonly(
+                        service_agent_instance_pda(
+                            self.registry_seed(),
+                            service_id,
+                            *agent_id,
+                            agent_instance,
+                            program_id,
+                        )
+                        .0,
+                        false,
+                    ),
+                ]
+            })
+            .collect()
+    }
+
+    pub fn slash(
+        &self,
+        multisig: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        targets: &[SlashTarget],
+        bond_mint: Option<&Pubkey>,
+    ) -> Instruction {
+        let program_id = &self.program_id;
+        let mut metas = accounts::Slash {
+            registry: self.registry,
+            service: *service,
+            registry_wallet: self.registry_wallet(),
+            service_escrow: self.service_escrow(service_id),
+            bond_token: self.bond_token(service_id, bond_mint, None),
+            user: *multisig,
+        }
+        .to_account_metas(None);
+
+        // Per target: operator agent instance, then the operator bond it draws from
+        for target in targets {
+            metas.push(AccountMeta::new_readonly(
+                operator_agent_instance_pda(&target.agent_instance, &target.operator, program_id).0,
+                false,
+            ));
+            metas.push(AccountMeta::new(
+                operator_bond_pda(
+                    self.registry_seed(),
+                    service_id,
+                    &target.operator,
+                    program_id,
+                )
+                .0,
+                false,
+            ));
+        }
+
+        self.instruction(
+            metas,
+            instruction::Slash {
+                service_id,
+                agent_instances: targets.iter().map(|t| t.agent_instance).collect(),
+                amounts: targets.iter().map(|t| t.amount).collect(),
+            },
+        )
+    }
+
+    /// Slashes the pending withdrawals of `targets`, each an operator and the amount to
+    /// slash from it.
+    pub fn slash_pending_withdrawals(
+        &self,
+        multisig: &Pubkey,
+        service: &Pubkey,
+        service_id: u128,
+        targets: &[(Pubkey, u64)],
+        bond_mint: Option<&Pubkey>,
+    ) -> Instruction {
+        let program_id = &self.program_id;
+        let mut metas = accounts::Slash {
+            registry: self.registry,
+            service: *service,
+            registry_wallet: self.registry_wallet(),
+            service_escrow: self.service_escrow(service_id),
+            bond_token: self.bond_token(service_id, bond_mint, None),
+            user: *multisig,
+        }
+        .to_account_metas(None);
+        metas.extend(targets.iter().map(|(operator, _)| {
+            AccountMeta::new(
+                pending_withdrawal_pda(self.registry_seed(), service_id, operator, program_id).0,
+                false,
+            )
+        }));
+
+        self.instruction(
+            metas,
+            instruction::SlashPendingWithdrawals {
+                service_id,
+                operators: targets.iter().map(|(operator, _)| *operator).collect(),
+                amounts: targets.iter().map(|(_, amount)| *amount).collect(),
+            },
+        )
+    }
+
+    pub fn audit_solvency(&self, services: &[AuditedService]) -> Instruction {
+        let program_id = &self.program_id;
+        let mut metas = accounts::AuditSolvency {
+            registry: self.registry,
+            registry_wallet: self.registry_wallet(),
+        }
+        .to_account_metas(None);
+
+        // Per service: service, escrow, bond token setting, escrow token, operator bonds,
+        // pending withdrawals
+        for audited in services {
+            let service_id = audited.service_id;
+            metas.push(AccountMeta::new_readonly(self.service(service_id), false));
+            metas.push(AccountMeta::new_readonly(
+                self.service_escrow(service_id),
+                false,
+            ));
+            metas.push(AccountMeta::new_readonly(
+                service_bond_token_pda(self.registry_seed(), service_id, program_id).0,
+                false,
+            ));
+            if let Some(mint) = &audited.bond_mint {
+                metas.push(AccountMeta::new_readonly(
+                    self.escrow_token(service_id, mint),
+                    false,
+                ));
+            }
+            metas.extend(audited.operators.iter().map(|operator| {
+                AccountMeta::new_readonly(
+                    operator_bond_pda(self.registry_seed(), service_id, operator, program_id).0,
+                    false,
+                )
+            }));
+            metas.extend(audited.pending_withdrawals.iter().map(|operator| {
+                AccountMeta::new_readonly(
+                    pending_withdrawal_pda(self.registry_seed(), service_id, operator, program_id)
+                        .0,
+                    false,
+                )
+            }));
+        }
+
+        self.instruction(
+            metas,
+            instruction::AuditSolvency {
+                operator_bonds: services
+                    .iter()
+                    .map(|audited| audited.operators.len() as u8)
+                    .collect(),
+                pending_withdrawals: services
+                    .iter()
+                    .map(|audited| audited.pending_withdrawals.len() as u8)
+                    .collect(),
+            },
+        )
+    }
+
+    /// Drains the slashed lamports, or with `bond_mint` the slashed funds of that bond
+    /// token into the associated token account of `drainer`.
+    pub fn drain(&self, drainer: &Pubkey, bond_mint: Option<&Pubkey>) -> Instruction {
+        self.instruction(
+            accounts::Drain {
+                registry: self.registry,
+                roles: self.roles(),
+                drainer: *drainer,
+                registry_wallet: self.registry_wallet(),
+                system_program: system_program::ID,
+                bond_token_vault: bond_mint
+                    .map(|mint| bond_token_vault_pda(&self.registry, mint, &self.program_id).0),
+                vault_token: bond_mint.map(|mint| self.vault_token(mint)),
+                drainer_token: bond_mint
+                    .map(|mint| associated_token::get_associated_token_address(drainer, mint)),
+                token_program: bond_mint.map(|_| token::ID),
+            }
+            .to_account_metas(None),
+            instruction::Drain {},
+        )
+    }
+
+    pub fn propose_drainer(&self, owner: &Pubkey, new_drainer: &Pubkey) -> Instruction {
+        self.instruction(
+            self.change_drainer_accounts(owner),
+            instruction::ProposeDrainer {
+                new_drainer: *new_drainer,
+            },
+        )
+    }
+
+    /// Signed by the pending drainer.
+    pub fn accept_drainer(&self, new_drainer: &Pubkey) -> Instruction {
+        self.instruction(
+            self.change_drainer_accounts(new_drainer),
+            instruction::AcceptDrainer {},
+        )
+    }
+
+    pub fn cancel_drainer(&self, owner: &Pubkey) -> Instruction {
+        self.instruction(
+            self.change_drainer_accounts(owner),
+            instruction::CancelDrainer {},
+        )
+    }
+
+    fn change_drainer_accounts(&self, user: &Pubkey) -> Vec<AccountMeta> {
+        accounts::ChangeDrainer {
+            registry: self.registry,
+            roles: self.roles(),
+            user: *user,
+        }
+        .to_account_metas(None)
+    }
+
+    pub fn propose_owner(&self, owner: &Pubkey, new_owner: &Pubkey) -> Instruction {
+        self.instruction(
+            self.change_owner_accounts(owner),
+            instruction::ProposeOwner {
+                new_owner: *new_owner,
+            },
+        )
+    }
+
+    /// Signed by the pending owner.
+    pub fn accept_owner(&self, new_owner: &Pubkey) -> Instruction {
+        self.instruction(
+            self.change_owner_accounts(new_owner),
+            instruction::AcceptOwner {},
+        )
+    }
+
+    pub fn cancel_owner(&self, owner: &Pubkey) -> Instruction {
+        self.instruction(
+            self.change_owner_accounts(owner),
+            instruction::CancelOwner {},
+        )
+    }
+
+    fn change_owner_accounts(&self, user: &Pubkey) -> Vec<AccountMeta> {
+        accounts::ChangeOwner {
+            registry: self.registry,
+            roles: self.roles(),
+            user: *user,
+        }
+        .to_account_metas(None)
+    }
+
+    pub fn propose_manager(&self, owner: &Pubkey, new_manager: &Pubkey) -> Instruction {
+        self.instruction(
+            self.change_manager_accounts(owner),
+            instruction::ProposeManager {
+                new_manager: *new_manager,
+            },
+        )
+    }
+
+    /// Signed by the pending manager.
+    pub fn accept_manager(&self, new_manager: &Pubkey) -> Instruction {
+        self.instruction(
+            self.change_manager_accounts(new_manager),
+            instruction::AcceptManager {},
+        )
+    }
+
+    pub fn cancel_manager(&self, owner: &Pubkey) -> Instruction {
+        self.instruction(
+            self.change_manager_accounts(owner),
+            instruction::CancelManager {},
+        )
+    }
+
+    fn change_manager_accounts(&self, user: &Pubkey) -> Vec<AccountMeta> {
+        accounts::ChangeManager {
+            registry: self.registry,
+            roles: self.roles(),
+            user: *user,
+        }
+        .to_account_metas(None)
+    }
+
+    pub fn set_base_uri(&self, editor: &Pubkey, new_base_uri: String) -> Instruction {
+        self.instruction(
+            accounts::UpdateRegistry {
+                registry: self.registry,
+                roles: self.roles(),
+                user: *editor,
+            }
+            .to_account_metas(None),
+            instruction::SetBaseUri { new_base_uri },
+        )
+    }
+
+    /// `creation_fee` is charged in lamports to each service created by its owner.
+    pub fn set_permissionless(
+        &self,
+        owner: &Pubkey,
+        permissionless: bool,
+        creation_fee: u64,
+    ) -> Instruction {
+        self.instruction(
+            accounts::UpdateRegistry {
+                registry: self.registry,
+                roles: self.roles(),
+                user: *owner,
+            }
+            .to_account_metas(None),
+            instruction::SetPermissionless {
+                permissionless,
+                creation_fee,
+            },
+        )
+    }
+
+    pub fn set_paused(&self, pauser: &Pubkey, group: PauseGroup, paused: bool) -> Instruction {
+        self.instruction(
+            accounts::UpdateRegistry {
+                registry: self.registry,
+                roles: self.roles(),
+                user: *pauser,
+            }
+            .to_account_metas(None),
+            instruction::SetPaused { group, paused },
+        )
+    }
+
+    pub fn set_refunds_open(&self, pauser: &Pubkey, refunds_open: bool) -> Instruction {
+        self.instruction(
+            accounts::UpdateRegistry {
+                registry: self.registry,
+                roles: self.roles(),
+                user: *pauser,
+            }
+            .to_account_metas(None),
+            instruction::SetRefundsOpen { refunds_open },
+        )
+    }
+
+    pub fn set_unbonding_delay(&self, admin: &Pubkey, new_delay: i64) -> Instruction {
+        self.instruction(
+            accounts::UpdateRegistry {
+                registry: self.registry,
+                roles: self.roles(),
+                user: *admin,
+            }
+            .to_account_metas(None),
+            instruction::SetUnbondingDelay { new_delay },
+        )
+    }
+
+    pub fn change_multisig_permission(
+        &self,
+        owner: &Pubkey,
+        multisig_implementation: &Pubkey,
+        permission: bool,
+    ) -> Instruction {
+        self.instruction(
+            accounts::ChangeMultisigPermission {
+                registry: self.registry,
+                roles: self.roles(),
+                registry_multisig: self.registry_multisig(),
+                user: *owner,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            instruction::ChangeMultisigPermission {
+                multisig: *multisig_implementation,
+                permission,
+            },
+        )
+    }
+
+    /// `proposal_id` is the current `MultisigAccount::proposal_count`.
+    pub fn propose_multisig_transaction(
+        &self,
+        proposer: &Pubkey,
+        multisig: &Pubkey,
+        proposal_id: u64,
+        target: &Instruction,
+    ) -> Instruction {
+        self.instruction(
+            accounts::ProposeMultisigTransaction {
+                multisig: *multisig,
+                proposal: multisig_proposal_pda(multisig, proposal_id, &self.program_id).0,
+                proposer: *proposer,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            instruction::ProposeMultisigTransaction {
+                target_program: target.program_id,
+                accounts: target
+                    .accounts
+                    .iter()
+                    .map(|meta| ProposalAccountMeta {
+                        pubkey: meta.pubkey,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: target.data.clone(),
+            },
+        )
+    }
+
+    pub fn approve_multisig_transaction(
+        &self,
+        approver: &Pubkey,
+        multisig: &Pubkey,
+        proposal_id: u64,
+    ) -> Instruction {
+        self.instruction(
+            accounts::ApproveMultisigTransaction {
+                multisig: *multisig,
+                proposal: multisig_proposal_pda(multisig, proposal_id, &self.program_id).0,
+                approver: *approver,
+            }
+            .to_account_metas(None),
+            instruction::ApproveMultisigTransaction {},
+        )
+    }
+
+    /// `target` is the proposed instruction, its accounts are appended after its program.
+    pub fn execute_multisig_transaction(
+        &self,
+        user: &Pubkey,
+        multisig: &Pubkey,
+        proposal_id: u64,
+        target: &Instruction,
+    ) -> Instruction {
+        let mut metas = accounts::ExecuteMultisigTransaction {
+            multisig: *multisig,
+            proposal: multisig_proposal_pda(multisig, proposal_id, &self.program_id).0,
+            user: *user,
+        }
+        .to_account_metas(None);
+
+        metas.push(AccountMeta::new_readonly(target.program_id, false));
+        // The multisig PDA signs inside the registry, never at the transaction level
+        metas.extend(target.accounts.iter().map(|meta| AccountMeta {
+            pubkey: meta.pubkey,
+            is_signer: false,
+            is_writable: meta.is_writable,
+        }));
+
+        self.instruction(metas, instruction::ExecuteMultisigTransaction {})
+    }
+
+    pub fn migrate_multisig(&self, user: &Pubkey, multisig: &Pubkey) -> Instruction {
+        self.instruction(
+            accounts::MigrateMultisig {
+                multisig: *multisig,
+                user: *user,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            instruction::MigrateMultisig {},
+        )
+    }
+
+    pub fn initialize_timelock(&self, admin: &Pubkey, delay: i64) -> Instruction {
+        self.instruction(
+            accounts::InitializeTimelock {
+                registry: self.registry,
+                timelock: self.timelock(),
+                roles: self.roles(),
+                user: *admin,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            instruction::InitializeTimelock { delay },
+        )
+    }
+
+    /// Signed by the timelock authority, to be queued as a timelock operation.
+    pub fn set_timelock_delay(&self, new_delay: i64) -> Instruction {
+        self.instruction(
+            accounts::SetTimelockDelay {
+                timelock: self.timelock(),
+                user: self.timelock_authority(),
+            }
+            .to_account_metas(None),
+            instruction::SetTimelockDelay { new_delay },
+        )
+    }
+
+    /// `target` is a registry instruction signed by [`RegistryClient::timelock_authority`].
+    pub fn queue_timelock_operation(
+        &self,
+        proposer: &Pubkey,
+        operation_id: u64,
+        target: &Instruction,
+    ) -> Instruction {
+        let timelock = self.timelock();
+        self.instruction(
+            accounts::QueueTimelockOperation {
+                timelock,
+                operation: timelock_operation_pda(&timelock, operation_id, &self.program_id).0,
+                roles: self.roles(),
+                user: *proposer,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            instruction::QueueTimelockOperation {
+                accounts: target
+                    .accounts
+                    .iter()
+                    .map(|meta| ProposalAccountMeta {
+                        pubkey: meta.pubkey,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: target.data.clone(),
+            },
+        )
+    }
+
+    /// `target` is the queued instruction, its accounts are appended after the program.
+    pub fn execute_timelock_operation(
+        &self,
+        user: &Pubkey,
+        operation_id: u64,
+        target: &Instruction,
+    ) -> Instruction {
+        let timelock = self.timelock();
+        let mut metas = accounts::ExecuteTimelockOperation {
+            timelock,
+            operation: timelock_operation_pda(&timelock, operation_id, &self.program_id).0,
+            user: *user,
+        }
+        .to_account_metas(None);
+
+        metas.push(AccountMeta::new_readonly(self.program_id, false));
+        // The timelock authority signs inside the registry, never at the transaction level
+        metas.extend(target.accounts.iter().map(|meta| AccountMeta {
+            pubkey: meta.pubkey,
+            is_signer: false,
+            is_writable: meta.is_writable,
+        }));
+
+        self.instruction(metas, instruction::ExecuteTimelockOperation {})
+    }
+
+    pub fn cancel_timelock_operation(&self, guardian: &Pubkey, operation_id: u64) -> Instruction {
+        let timelock = self.timelock();
+        self.instruction(
+            accounts::CancelTimelockOperation {
+                timelock,
+                operation: timelock_operation_pda(&timelock, operation_id, &self.program_id).0,
+                roles: self.roles(),
+                user: *guardian,
+            }
+            .to_account_metas(None),
+            instruction::CancelTimelockOperation {},
+        )
+    }
+}
+
+fn ed25519_instruction(signer: &Pubkey, signature: &[u8; 64], message: &[u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: vec![],
+        data: signatures::ed25519_instruction_data(signer, signature, message),
+    }
+}
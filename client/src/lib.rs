@@ -0,0 +1,160 @@
+//! Off-chain helpers for the registry program.
+//!
+//! Every PDA derivation of the program is re-exported from [`registry::pda`], and
+//! [`RegistryClient`] builds each instruction together with the positional
+//! `remaining_accounts` the program expects. [`ServiceManagerClient`] builds the
+//! instructions of the service manager program driving the registry as its manager.
+
+mod instructions;
+mod service_manager;
+
+pub use service_manager::ServiceManagerClient;
+
+pub use registry::{
+    metadata::token_uri, pause::PauseGroup, pda, pda::*, roles::Role, service_state::ServiceState,
+    signatures, state, AgentParams, ID as REGISTRY_PROGRAM_ID,
+};
+
+use anchor_lang::prelude::Pubkey;
+
+/// Instruction builder bound to one registry account. With `legacy_seeds`, it
+/// addresses the accounts of services moved by `migrate_service`, seeded without the
+/// registry key.
+#[derive(Clone, Debug)]
+pub struct RegistryClient {
+    pub program_id: Pubkey,
+    pub registry: Pubkey,
+    pub legacy_seeds: bool,
+}
+
+impl RegistryClient {
+    pub fn new(registry: Pubkey) -> Self {
+        Self::with_program_id(registry, registry::ID)
+    }
+
+    pub fn with_program_id(registry: Pubkey, program_id: Pubkey) -> Self {
+        Self {
+            program_id,
+            registry,
+            legacy_seeds: false,
+        }
+    }
+
+    /// Builder for the services migrated from the legacy seeds of the same registry.
+    pub fn with_legacy_seeds(&self) -> Self {
+        Self {
+            legacy_seeds: true,
+            ..self.clone()
+        }
+    }
+
+    /// Registry part of the seeds of service accounts, as `ServiceAccount::registry_seed`.
+    pub fn registry_seed(&self) -> &[u8] {
+        if self.legacy_seeds {
+            &[]
+        } else {
+            self.registry.as_ref()
+        }
+    }
+
+    pub fn service(&self, service_id: u128) -> Pubkey {
+        service_pda(&self.registry, service_id, &self.program_id).0
+    }
+
+    pub fn registry_wallet(&self) -> Pubkey {
+        registry_wallet_pda(&self.registry, &self.program_id).0
+    }
+
+    /// Holders of each role of the registry.
+    pub fn roles(&self) -> Pubkey {
+        registry_roles_pda(&self.registry, &self.program_id).0
+    }
+
+    pub fn timelock(&self) -> Pubkey {
+        timelock_pda(&self.registry, &self.program_id).0
+    }
+
+    /// Signer of the operations the timelock executes, to be granted the delayed roles.
+    pub fn timelock_authority(&self) -> Pubkey {
+        timelock_authority_pda(&self.registry, &self.program_id).0
+    }
+
+    pub fn registry_multisig(&self) -> Pubkey {
+        registry_multisig_pda(&self.registry, &self.program_id).0
+    }
+
+    /// Token account the instruction builders pass as holding the service token: the
+    /// associated token account of `owner`.
+    pub fn service_token(&self, service_id: u128, owner: &Pubkey) -> Pubkey {
+        service_token_address(self.registry_seed(), service_id, owner, &self.program_id)
+    }
+
+    /// Escrow holding the security deposit and operator bonds of a service.
+    pub fn service_escrow(&self, service_id: u128) -> Pubkey {
+        service_escrow_pda(self.registry_seed(), service_id, &self.program_id).0
+    }
+
+    /// Operators allowed to register agent instances in a service.
+    pub fn operator_whitelist(&self, service_id: u128) -> Pubkey {
+        operator_whitelist_pda(self.registry_seed(), service_id, &self.program_id).0
+    }
+
+    /// Token account of the service escrow for a service bonded in `mint`.
+    pub fn escrow_token(&self, service_id: u128, mint: &Pubkey) -> Pubkey {
+        escrow_token_address(self.registry_seed(), service_id, mint, &self.program_id)
+    }
+
+    /// Token account of the treasury holding the slashed funds paid in `mint`.
+    pub fn vault_token(&self, mint: &Pubkey) -> Pubkey {
+        vault_token_address(&self.registry, mint, &self.program_id)
+    }
+
+    /// Message `operator` signs to have the manager register `agent_instances` for
+    /// `agent_ids` on its behalf, `nonce` being its current `OperatorNonce`.
+    pub fn registration_message(
+        &self,
+        service_id: u128,
+        agent_instances: &[Pubkey],
+        agent_ids: &[u32],
+        nonce: u64,
+    ) -> [u8; 32] {
+        signatures::registration_message(
+            &self.registry,
+            service_id,
+            agent_instances,
+            agent_ids,
+            nonce,
+        )
+    }
+
+    /// Message each agent instance signs to prove it holds its key when `operator`
+    /// registers it in a service requiring instance proofs.
+    pub fn instance_proof_message(&self, service_id: u128, operator: &Pubkey) -> [u8; 32] {
+        signatures::instance_proof_message(&self.registry, service_id, operator)
+    }
+
+    /// Address of the multisig created by the registry built-in implementation.
+    pub fn builtin_multisig(&self, agent_instances: &[Pubkey]) -> Pubkey {
+        multisig_pda(agent_instances, &self.program_id).0
+    }
+}
+
+/// One entry of a `slash` call.
+#[derive(Clone, Debug)]
+pub struct SlashTarget {
+    pub agent_instance: Pubkey,
+    pub operator: Pubkey,
+    pub amount: u64,
+}
+
+/// One service of an `audit_solvency` page.
+#[derive(Clone, Debug)]
+pub struct AuditedService {
+    pub service_id: u128,
+    /// Service bond token, `None` for services bonded in lamports
+    pub bond_mint: Option<Pubkey>,
+    /// Operators holding a bond in the service
+    pub operators: Vec<Pubkey>,
+    /// Operators with an unbonded amount not claimed yet
+    pub pending_withdrawals: Vec<Pubkey>,
+}
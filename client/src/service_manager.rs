@@ -0,0 +1,367 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{instruction::Instruction, sysvar},
+    system_program, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{associated_token, token};
+use registry::{pda::*, AgentParams};
+use service_manager::{accounts, instruction, manager_authority_pda};
+use std::ops::Range;
+
+use crate::RegistryClient;
+
+/// Instruction builder for the service manager program driving one registry, which
+/// must grant its manager role to [`ServiceManagerClient::manager_authority`].
+///
+/// Only services bonded in lamports go through the service manager.
+#[derive(Clone, Debug)]
+pub struct ServiceManagerClient {
+    pub program_id: Pubkey,
+    pub registry: RegistryClient,
+}
+
+impl ServiceManagerClient {
+    pub fn new(registry: RegistryClient) -> Self {
+        Self {
+            program_id: service_manager::ID,
+            registry,
+        }
+    }
+
+    /// Key the registry grants the manager role to.
+    pub fn manager_authority(&self) -> Pubkey {
+        manager_authority_pda(&self.registry.registry).0
+    }
+
+    fn instruction(
+        &self,
+        mut accounts: Vec<AccountMeta>,
+        remaining_accounts: Vec<AccountMeta>,
+        data: impl InstructionData,
+    ) -> Instruction {
+        accounts.extend(remaining_accounts);
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: data.data(),
+        }
+    }
+
+    /// Creates the service `service_id`, the registry `total_supply + 1`, owned by
+    /// `service_owner` and registers its agent ids.
+    pub fn create(
+        &self,
+        service_owner: &Pubkey,
+        service_id: u128,
+        config_hash: [u8; 32],
+        agent_ids: &[u32],
+        agent_params: &[AgentParams],
+        threshold: u32,
+    ) -> Instruction {
+        let registry_program = &self.registry.program_id;
+        let service = self.registry.service(service_id);
+        self.instruction(
+            accounts::Create {
+                registry: self.registry.registry,
+                roles: self.registry.roles(),
+                manager_authority: self.manager_authority(),
+                caller: *service_owner,
+                service,
+                config_hash_history: config_hash_history_pda(&service, registry_program).0,
+                service_escrow: self.registry.service_escrow(service_id),
+                service_mint: service_mint_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    registry_program,
+                )
+                .0,
+                service_token: self.registry.service_token(service_id, service_owner),
+                registry_wallet: self.registry.registry_wallet(),
+                service_agent_ids_index: service_agent_ids_index_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    registry_program,
+                )
+                .0,
+                registry_program: *registry_program,
+                system_program: system_program::ID,
+                token_program: token::ID,
+                associated_token_program: associated_token::ID,
+            }
+            .to_account_metas(None),
+            self.registry.agent_params_accounts(service_id, agent_ids),
+            instruction::Create {
+                config_hash,
+                agent_ids: agent_ids.to_vec(),
+                agent_params: agent_params.to_vec(),
+                threshold,
+            },
+        )
+    }
+
+    pub fn activate_registration(&self, service_owner: &Pubkey, service_id: u128) -> Instruction {
+        let registry_program = &self.registry.program_id;
+        self.instruction(
+            accounts::ActivateRegistration {
+                registry: self.registry.registry,
+                roles: self.registry.roles(),
+                manager_authority: self.manager_authority(),
+                caller: *service_owner,
+                service: self.registry.service(service_id),
+                service_token: self.registry.service_token(service_id, service_owner),
+                service_escrow: self.registry.service_escrow(service_id),
+                service_bond_token: service_bond_token_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    registry_program,
+                )
+                .0,
+                registry_program: *registry_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            Vec::new(),
+            instruction::ActivateRegistration { service_id },
+        )
+    }
+
+    /// Registration signed and paid by `operator`. `agent_ids[i]` is the agent id
+    /// `agent_instances[i]` registers for.
+    pub fn register_agents(
+        &self,
+        operator: &Pubkey,
+        service_id: u128,
+        agent_instances: &[Pubkey],
+        agent_ids: &[u32],
+    ) -> Instruction {
+        let registry_program = &self.registry.program_id;
+        let service = self.registry.service(service_id);
+        let (registration, remaining_accounts) = self.registry.registration_accounts(
+            operator,
+            &service,
+            service_id,
+            operator,
+            agent_instances,
+            agent_ids,
+            None,
+        );
+        self.instruction(
+            accounts::RegisterAgents {
+                registry: self.registry.registry,
+                caller: *operator,
+                service,
+                service_escrow: registration.service_escrow,
+                service_bond_token: registration.bond_token.service_bond_token,
+                operator_agent_instance_index: registration.operator_agent_instance_index,
+                operator_whitelist: registration.operator_whitelist,
+                instructions: sysvar::instructions::ID,
+                registry_program: *registry_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            remaining_accounts,
+            instruction::RegisterAgents {
+                agent_instances: agent_instances.to_vec(),
+                agent_ids: agent_ids.to_vec(),
+            },
+        )
+    }
+
+    /// `multisig` is the account created by `multisig_implementation`, see
+    /// [`RegistryClient::builtin_multisig`] for the registry built-in one.
+    pub fn deploy(
+        &self,
+        service_owner: &Pubkey,
+        service_id: u128,
+        multisig_implementation: &Pubkey,
+        multisig: &Pubkey,
+        agent_instances: &[Pubkey],
+        data: Vec<u8>,
+    ) -> Instruction {
+        let registry_program = &self.registry.program_id;
+        self.instruction(
+            accounts::Deploy {
+                registry: self.registry.registry,
+                roles: self.registry.roles(),
+                manager_authority: self.manager_authority(),
+                caller: *service_owner,
+                service: self.registry.service(service_id),
+                service_token: self.registry.service_token(service_id, service_owner),
+                registry_multisig: self.registry.registry_multisig(),
+                multisig_implementation: *multisig_implementation,
+                agent_instances_index: agent_instances_index_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    registry_program,
+                )
+                .0,
+                registry_program: *registry_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            RegistryClient::deploy_accounts(multisig, agent_instances),
+            instruction::Deploy {
+                service_id,
+                multisig_implementation: *multisig_implementation,
+                data,
+            },
+        )
+    }
+
+    pub fn terminate(&self, service_owner: &Pubkey, service_id: u128) -> Instruction {
+        let registry_program = &self.registry.program_id;
+        self.instruction(
+            accounts::Terminate {
+                registry: self.registry.registry,
+                roles: self.registry.roles(),
+                manager_authority: self.manager_authority(),
+                caller: *service_owner,
+                service: self.registry.service(service_id),
+                service_agent_ids_index: service_agent_ids_index_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    registry_program,
+                )
+                .0,
+                service_escrow: self.registry.service_escrow(service_id),
+                service_token: self.registry.service_token(service_id, service_owner),
+                service_bond_token: service_bond_token_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    registry_program,
+                )
+                .0,
+                registry_program: *registry_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            Vec::new(),
+            instruction::Terminate { service_id },
+        )
+    }
+
+    /// See [`RegistryClient::cleanup_service`].
+    pub fn cleanup(
+        &self,
+        service_owner: &Pubkey,
+        service_id: u128,
+        agent_ids: &[u32],
+        agent_instances: &[(Pubkey, u32)],
+        range: Range<usize>,
+    ) -> Instruction {
+        let registry_program = &self.registry.program_id;
+        self.instruction(
+            accounts::Cleanup {
+                registry: self.registry.registry,
+                roles: self.registry.roles(),
+                manager_authority: self.manager_authority(),
+                caller: *service_owner,
+                service: self.registry.service(service_id),
+                service_agent_ids_index: service_agent_ids_index_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    registry_program,
+                )
+                .0,
+                agent_instances_index: agent_instances_index_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    registry_program,
+                )
+                .0,
+                service_token: self.registry.service_token(service_id, service_owner),
+                registry_program: *registry_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            self.registry
+                .cleanup_accounts(service_id, agent_ids, agent_instances)[range]
+                .to_vec(),
+            instruction::Cleanup { service_id },
+        )
+    }
+
+    /// See [`RegistryClient::unbond`].
+    pub fn unbond(
+        &self,
+        operator: &Pubkey,
+        service_id: u128,
+        agent_instances: &[(Pubkey, u32)],
+    ) -> Instruction {
+        let registry_program = &self.registry.program_id;
+        self.instruction(
+            accounts::Unbond {
+                registry: self.registry.registry,
+                roles: self.registry.roles(),
+                manager_authority: self.manager_authority(),
+                caller: *operator,
+                service: self.registry.service(service_id),
+                service_agent_ids_index: service_agent_ids_index_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    registry_program,
+                )
+                .0,
+                operator_agent_instance_index: operator_agent_instance_index_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    operator,
+                    registry_program,
+                )
+                .0,
+                operator_bond: operator_bond_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    operator,
+                    registry_program,
+                )
+                .0,
+                pending_withdrawal: pending_withdrawal_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    operator,
+                    registry_program,
+                )
+                .0,
+                registry_program: *registry_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            self.registry
+                .unbond_accounts(service_id, operator, agent_instances),
+            instruction::Unbond { service_id },
+        )
+    }
+
+    pub fn claim_unbonded(&self, operator: &Pubkey, service_id: u128) -> Instruction {
+        let registry_program = &self.registry.program_id;
+        self.instruction(
+            accounts::ClaimUnbonded {
+                registry: self.registry.registry,
+                manager_authority: self.manager_authority(),
+                roles: self.registry.roles(),
+                caller: *operator,
+                service: self.registry.service(service_id),
+                pending_withdrawal: pending_withdrawal_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    operator,
+                    registry_program,
+                )
+                .0,
+                service_escrow: self.registry.service_escrow(service_id),
+                service_bond_token: service_bond_token_pda(
+                    self.registry.registry_seed(),
+                    service_id,
+                    registry_program,
+                )
+                .0,
+                registry_program: *registry_program,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            Vec::new(),
+            instruction::ClaimUnbonded { service_id },
+        )
+    }
+}
@@ -0,0 +1,95 @@
+#![allow(unexpected_cfgs)]
+use anchor_lang::{
+    prelude::*,
+    solana_program::{hash::hash, program::set_return_data},
+};
+
+declare_id!("2gYBGwstgjK7aspVNGkqSW8gVn8jewZ82SiACaa4m6FY");
+
+/// Reference implementation of the multisig interface the registry invokes from `deploy`.
+///
+/// The registry calls `create` with the payer, the multisig account and the system program,
+/// and reads the created multisig address back from the return data.
+#[program]
+pub mod multisig {
+    use super::*;
+
+    pub fn create(
+        ctx: Context<Create>,
+        agent_instances: Vec<Pubkey>,
+        threshold: u32,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        require!(
+            threshold > 0 && threshold as usize <= agent_instances.len(),
+            ErrorCode::WrongThreshold
+        );
+
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.owners = agent_instances;
+        multisig.threshold = threshold;
+        multisig.data = data;
+        multisig.bump = ctx.bumps.multisig;
+
+        // Hand the multisig address back to the caller
+        set_return_data(&multisig.key().to_bytes());
+
+        Ok(())
+    }
+}
+
+/// Seed binding a multisig to its ordered set of owners.
+pub fn owners_hash(agent_instances: &[Pubkey]) -> [u8; 32] {
+    let mut seed_data = vec![];
+    for agent in agent_instances {
+        seed_data.extend_from_slice(agent.as_ref());
+    }
+    hash(&seed_data).to_bytes()
+}
+
+/// PDA seeds: ["multisig", sha256(agent_instances)]
+pub fn multisig_pda(agent_instances: &[Pubkey]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"multisig", &owners_hash(agent_instances)], &ID)
+}
+
+#[account]
+pub struct Multisig {
+    pub owners: Vec<Pubkey>,
+    pub threshold: u32,
+    pub data: Vec<u8>,
+    pub bump: u8,
+}
+
+impl Multisig {
+    pub fn size(owner_count: usize, data_len: usize) -> usize {
+        8 +                     // discriminator
+        4 + owner_count * 32 +  // Vec<Pubkey>
+        4 +                     // threshold (u32)
+        4 + data_len +          // Vec<u8>
+        1 // bump
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(agent_instances: Vec<Pubkey>, threshold: u32, data: Vec<u8>)]
+pub struct Create<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = Multisig::size(agent_instances.len(), data.len()),
+        seeds = [b"multisig", owners_hash(&agent_instances).as_ref()],
+        bump,
+    )]
+    pub multisig: Account<'info, Multisig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("Threshold is out of allowed bounds")]
+    WrongThreshold,
+}
@@ -1,6 +1,8 @@
 pub const MAX_AGENT_IDS_PER_SERVICE: usize = 128;
 pub const MAX_AGENT_INSTANCES_PER_SERVICE: usize = 192;
 pub const MAX_MULTISIGS: usize = 300;
+pub const MAX_WHITELISTED_OPERATORS: usize = 128;
+pub const MAX_ROLE_MEMBERS: usize = 16;
 
 pub const STRING_PREFIX_SIZE: usize = 4;
 pub const MAX_NAME_LENGTH: usize = 256;
@@ -13,7 +15,8 @@ pub const BOOL_SIZE: usize = 1;
 pub const U8_SIZE: usize = 1;
 pub const FIXED_SIZE: usize = 32;
 
-pub const REGISTRY_ACCOUNT_SIZE: usize = STRING_PREFIX_SIZE // name prefix
+pub const REGISTRY_ACCOUNT_SIZE: usize = 8 // discriminator
+    + STRING_PREFIX_SIZE // name prefix
     + MAX_NAME_LENGTH   // name
     + STRING_PREFIX_SIZE// symbol prefix
     + MAX_SYMBOL_LENGTH // symbol
@@ -28,4 +31,17 @@ pub const REGISTRY_ACCOUNT_SIZE: usize = STRING_PREFIX_SIZE // name prefix
     + FIXED_SIZE // version fixed size
     + BOOL_SIZE // locked
     + PUBKEY_SIZE // wallet_key
-    + U8_SIZE; // wallet_bump
+    + U8_SIZE // wallet_bump
+    + BOOL_SIZE // permissionless
+    + U64_SIZE // creation_fee
+    + U64_SIZE // accrued_fees
+    + PUBKEY_SIZE // pending_owner
+    + PUBKEY_SIZE // pending_manager
+    + PUBKEY_SIZE // pending_drainer
+    + U8_SIZE // paused
+    + BOOL_SIZE // refunds_open
+    + U64_SIZE; // unbonding_delay
+
+/// Records returned by one `get_config_hash_history` call, bounded by the
+/// 1024 bytes of return data.
+pub const CONFIG_HASH_RECORDS_PER_PAGE: usize = 21;
@@ -104,4 +104,115 @@ pub enum ErrorCode {
 
     #[msg("Missing multisig account")]
     MissingMultisigAccount,
+
+    #[msg("Multisig implementation returned no or invalid multisig address")]
+    InvalidMultisigReturnData,
+
+    #[msg("Multisig account does not match the created multisig")]
+    WrongMultisigAccount,
+
+    #[msg("Signer is not an agent instance of the multisig")]
+    NotMultisigOwner,
+
+    #[msg("Proposal already approved by this agent instance")]
+    ProposalAlreadyApproved,
+
+    #[msg("Proposal already executed")]
+    ProposalAlreadyExecuted,
+
+    #[msg("Not enough approvals to execute the proposal")]
+    NotEnoughApprovals,
+
+    #[msg("Accounts do not match the proposal")]
+    WrongProposalAccounts,
+
+    #[msg("Token account does not hold the service token")]
+    WrongServiceToken,
+
+    #[msg("Bond token accounts are missing")]
+    MissingBondTokenAccounts,
+
+    #[msg("Bond token vault does not match the service bond token")]
+    WrongBondTokenVault,
+
+    #[msg("Token account does not belong to the bond recipient")]
+    WrongBondTokenAccount,
+
+    #[msg("Token account is not the escrow token account of the service")]
+    WrongEscrowTokenAccount,
+
+    #[msg("Operator signature is missing or does not match the registration")]
+    InvalidOperatorSignature,
+
+    #[msg("Agent instance proof of key ownership is missing or wrong")]
+    InvalidInstanceProof,
+
+    #[msg("Operator is not whitelisted for the service")]
+    OperatorNotWhitelisted,
+
+    #[msg("Max whitelisted operators per service reached")]
+    MaxWhitelistedOperatorsReached,
+
+    #[msg("Signer does not hold the required role")]
+    MissingRole,
+
+    #[msg("Max members per role reached")]
+    MaxRoleMembersReached,
+
+    #[msg("Signer is not the pending key")]
+    NotPendingKey,
+
+    #[msg("No pending handover to cancel")]
+    NoPendingHandover,
+
+    #[msg("Timelock operation is not ready for execution")]
+    TimelockNotReady,
+
+    #[msg("Timelock operation was already executed or cancelled")]
+    TimelockOperationDone,
+
+    #[msg("Signer is not the timelock authority")]
+    NotTimelockAuthority,
+
+    #[msg("Service creation is paused")]
+    CreationPaused,
+
+    #[msg("Agent instance registration is paused")]
+    RegistrationPaused,
+
+    #[msg("Service deployment is paused")]
+    DeployPaused,
+
+    #[msg("Slashing is paused")]
+    SlashPaused,
+
+    #[msg("Payouts are paused")]
+    PayoutsPaused,
+
+    #[msg("Service has no agent accounts left to clean up")]
+    NoServiceCleanup,
+
+    #[msg("Agent accounts of the terminated service are not cleaned up yet")]
+    ServiceCleanupPending,
+
+    #[msg("Unbonded funds are still in their unbonding delay")]
+    UnbondingDelayNotExpired,
+
+    #[msg("Operator bonds of the service are missing, repeated or out of order")]
+    WrongOperatorBonds,
+
+    #[msg("Bond token accounts are passed more than once")]
+    DuplicateBondTokenAccounts,
+
+    #[msg("The last admin cannot give up the admin role")]
+    LastAdmin,
+
+    #[msg("Service belongs to another registry")]
+    WrongServiceRegistry,
+
+    #[msg("Service escrow still holds lamports above its rent")]
+    ServiceEscrowNotEmpty,
+
+    #[msg("Service bond token is already set to another mint")]
+    BondTokenAlreadySet,
 }
@@ -1,5 +1,7 @@
 use anchor_lang::prelude::*;
 
+use crate::roles::Role;
+
 #[event]
 pub struct CreateServiceEvent {
     pub service_id: u128,
@@ -14,6 +16,26 @@ pub struct RegisterAgentIdsEvent {
     pub security_deposit: u64,
 }
 
+#[event]
+pub struct ServiceMigrated {
+    pub service_id: u128,
+    pub legacy_service: Pubkey,
+    pub service: Pubkey,
+}
+
+#[event]
+pub struct ServiceBondTokenSet {
+    pub service_id: u128,
+    pub mint: Pubkey,
+}
+
+#[event]
+pub struct ServiceOwnerTransferred {
+    pub service_id: u128,
+    pub previous_owner: Pubkey,
+    pub new_owner: Pubkey,
+}
+
 #[event]
 pub struct UpdateServiceEvent {
     pub service_id: u128,
@@ -25,6 +47,17 @@ pub struct DrainerUpdatedEvent {
     pub new_drainer: Pubkey,
 }
 
+#[event]
+pub struct DrainerProposedEvent {
+    pub drainer: Pubkey,
+    pub pending_drainer: Pubkey,
+}
+
+#[event]
+pub struct DrainerProposalCancelledEvent {
+    pub pending_drainer: Pubkey,
+}
+
 #[event]
 pub struct ActivateRegistrationEvent {
     pub service_id: u128,
@@ -55,11 +88,56 @@ pub struct ServiceTerminated {
     pub service_id: u128,
 }
 
+#[event]
+pub struct ServiceCleanupProgress {
+    pub service_id: u128,
+    pub cursor: u32,
+    pub total: u32,
+}
+
 #[event]
 pub struct OperatorUnbonded {
     pub operator: Pubkey,
     pub service_id: u128,
-    pub refund: u64,
+    /// Added to the pending withdrawal of the operator, not paid out yet
+    pub amount: u64,
+}
+
+#[event]
+pub struct WithdrawalPending {
+    pub operator: Pubkey,
+    pub service_id: u128,
+    pub amount: u64,
+    pub unlock_time: i64,
+}
+
+#[event]
+pub struct UnbondedClaimed {
+    pub operator: Pubkey,
+    pub service_id: u128,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PendingWithdrawalSlashed {
+    pub service_id: u128,
+    pub operator: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct UnbondingDelayUpdated {
+    pub previous_delay: i64,
+    pub new_delay: i64,
+}
+
+#[event]
+pub struct AgentInstanceUnbonded {
+    pub operator: Pubkey,
+    pub service_id: u128,
+    pub agent_instance: Pubkey,
+    /// Added to the pending withdrawal of the operator, not paid out yet
+    pub amount: u64,
 }
 
 #[event]
@@ -86,18 +164,198 @@ pub struct OwnerUpdatedEvent {
     pub new_owner: Pubkey,
 }
 
+#[event]
+pub struct OwnerProposedEvent {
+    pub owner: Pubkey,
+    pub pending_owner: Pubkey,
+}
+
+#[event]
+pub struct OwnerProposalCancelledEvent {
+    pub pending_owner: Pubkey,
+}
+
 #[event]
 pub struct ManagerUpdatedEvent {
     pub new_manager: Pubkey,
 }
 
+#[event]
+pub struct ManagerProposedEvent {
+    pub manager: Pubkey,
+    pub pending_manager: Pubkey,
+}
+
+#[event]
+pub struct ManagerProposalCancelledEvent {
+    pub pending_manager: Pubkey,
+}
+
 #[event]
 pub struct MultisigUpdatedEvent {
     pub service_id: u128,
     pub new_multisig: Pubkey,
 }
 
+#[event]
+pub struct PermissionlessModeSet {
+    pub permissionless: bool,
+    pub creation_fee: u64,
+}
+
 #[event]
 pub struct BaseURIChanged {
     pub new_base_uri: String,
 }
+
+#[event]
+pub struct MultisigProposalCreated {
+    pub multisig: Pubkey,
+    pub proposal_id: u64,
+    pub proposer: Pubkey,
+    pub program_id: Pubkey,
+}
+
+#[event]
+pub struct MultisigProposalApproved {
+    pub multisig: Pubkey,
+    pub proposal_id: u64,
+    pub approver: Pubkey,
+    pub approvals: u32,
+}
+
+#[event]
+pub struct MultisigProposalExecuted {
+    pub multisig: Pubkey,
+    pub proposal_id: u64,
+}
+
+#[event]
+pub struct MultisigMigrated {
+    pub multisig: Pubkey,
+}
+
+#[event]
+pub struct RegistryMigrated {
+    pub registry: Pubkey,
+}
+
+#[event]
+pub struct TimelockOperationQueued {
+    pub timelock: Pubkey,
+    pub operation_id: u64,
+    pub proposer: Pubkey,
+    pub eta: i64,
+}
+
+#[event]
+pub struct TimelockOperationExecuted {
+    pub timelock: Pubkey,
+    pub operation_id: u64,
+}
+
+#[event]
+pub struct TimelockOperationCancelled {
+    pub timelock: Pubkey,
+    pub operation_id: u64,
+    pub guardian: Pubkey,
+}
+
+#[event]
+pub struct TimelockDelayUpdated {
+    pub timelock: Pubkey,
+    pub previous_delay: i64,
+    pub new_delay: i64,
+}
+
+#[event]
+pub struct BondTokenDrained {
+    pub drainer: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct InstanceProofsSet {
+    pub service_id: u128,
+    pub required: bool,
+}
+
+#[event]
+pub struct OperatorsCheckSet {
+    pub service_owner: Pubkey,
+    pub service_id: u128,
+    pub check: bool,
+}
+
+#[event]
+pub struct OperatorsWhitelistUpdated {
+    pub service_owner: Pubkey,
+    pub service_id: u128,
+    pub operators: Vec<Pubkey>,
+    pub statuses: Vec<bool>,
+    pub check: bool,
+}
+
+#[event]
+pub struct SolvencyDeficit {
+    pub deficit: u64,
+    pub insolvent_services: Vec<u128>,
+}
+
+#[event]
+pub struct RoleGranted {
+    pub role: Role,
+    pub account: Pubkey,
+    pub sender: Pubkey,
+}
+
+#[event]
+pub struct RoleRevoked {
+    pub role: Role,
+    pub account: Pubkey,
+    pub sender: Pubkey,
+}
+
+#[event]
+pub struct RoleAdminChanged {
+    pub role: Role,
+    pub previous_admin_role: Role,
+    pub new_admin_role: Role,
+}
+
+#[event]
+pub struct CreationPauseChanged {
+    pub paused: bool,
+    pub sender: Pubkey,
+}
+
+#[event]
+pub struct RegistrationPauseChanged {
+    pub paused: bool,
+    pub sender: Pubkey,
+}
+
+#[event]
+pub struct DeployPauseChanged {
+    pub paused: bool,
+    pub sender: Pubkey,
+}
+
+#[event]
+pub struct SlashPauseChanged {
+    pub paused: bool,
+    pub sender: Pubkey,
+}
+
+#[event]
+pub struct PayoutsPauseChanged {
+    pub paused: bool,
+    pub sender: Pubkey,
+}
+
+#[event]
+pub struct RefundsOpenChanged {
+    pub refunds_open: bool,
+    pub sender: Pubkey,
+}
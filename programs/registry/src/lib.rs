@@ -2,23 +2,37 @@
 use anchor_lang::{
     prelude::*,
     solana_program::{
-        hash::hash,
-        program::invoke_signed,
+        instruction::Instruction,
+        program::{get_return_data, invoke, invoke_signed},
         system_instruction::{self, transfer},
     },
     AccountDeserialize, Discriminator,
 };
+use anchor_spl::{
+    associated_token::{get_associated_token_address, AssociatedToken},
+    token::{
+        self, spl_token::instruction::AuthorityType, Mint, MintTo, SetAuthority, Token,
+        TokenAccount, Transfer,
+    },
+};
 
-mod constants;
+pub mod constants;
 pub mod error;
 pub mod events;
-mod pda;
-mod service_state;
-mod state;
+pub mod metadata;
+pub mod multisig_interface;
+pub mod pause;
+pub mod pda;
+pub mod roles;
+pub mod service_state;
+pub mod signatures;
+pub mod state;
 use constants::*;
 use error::ErrorCode;
 use events::*;
+use pause::PauseGroup;
 use pda::*;
+use roles::Role;
 use service_state::ServiceState;
 use state::*;
 
@@ -45,6 +59,14 @@ pub mod registry {
         let registry = &mut ctx.accounts.registry;
         let registry_wallet = &mut ctx.accounts.registry_wallet;
 
+        // The registry account holds each string at its maximum length
+        if name.len() > MAX_NAME_LENGTH
+            || symbol.len() > MAX_SYMBOL_LENGTH
+            || base_uri.len() > MAX_URI_LENGTH
+        {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+
         registry.name = name;
         registry.symbol = symbol;
         registry.base_uri = base_uri;
@@ -59,6 +81,139 @@ pub mod registry {
 
         registry.wallet_bump = bump_registry_wallet;
 
+        ctx.accounts.roles.set_inner(RegistryRoles::new(
+            registry.key(),
+            registry.owner,
+            manager,
+            drainer,
+        ));
+
+        Ok(())
+    }
+
+    /// Creates the roles account of a registry initialized before it, from its owner,
+    /// manager and drainer. Only the registry owner can call.
+    pub fn initialize_roles(ctx: Context<InitializeRoles>) -> Result<()> {
+        let registry = &ctx.accounts.registry;
+
+        if ctx.accounts.user.key() != registry.owner {
+            return Err(ProgramError::IllegalOwner.into());
+        }
+
+        ctx.accounts.roles.set_inner(RegistryRoles::new(
+            registry.key(),
+            registry.owner,
+            registry.manager,
+            registry.drainer,
+        ));
+
+        Ok(())
+    }
+
+    /// Grows a registry initialized before its latest fields to `REGISTRY_ACCOUNT_SIZE`,
+    /// the fields appended to it reading unset. Anyone can call, paying the rent.
+    pub fn migrate_registry(ctx: Context<MigrateRegistry>) -> Result<()> {
+        let registry_info = ctx.accounts.registry.to_account_info();
+
+        if !registry_info
+            .try_borrow_data()?
+            .starts_with(ServiceRegistry::DISCRIMINATOR)
+        {
+            return Err(anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch.into());
+        }
+        if registry_info.data_len() >= REGISTRY_ACCOUNT_SIZE {
+            return Ok(());
+        }
+
+        ServiceRegistry::grow_account(
+            &registry_info,
+            REGISTRY_ACCOUNT_SIZE,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        emit!(RegistryMigrated {
+            registry: registry_info.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Grants `role` to `account`. Only holders of the admin role of `role` can call.
+    pub fn grant_role(ctx: Context<UpdateRoles>, role: Role, account: Pubkey) -> Result<()> {
+        let roles = &mut ctx.accounts.roles;
+        let sender = ctx.accounts.user.key();
+
+        roles.check_role(roles.admin_role(role), &sender)?;
+
+        if account == Pubkey::default() {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+
+        if roles.grant(role, account)? {
+            emit!(RoleGranted {
+                role,
+                account,
+                sender
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Revokes `role` from `account`. Only holders of the admin role of `role` can call,
+    /// and the last `Admin` holder is kept.
+    pub fn revoke_role(ctx: Context<UpdateRoles>, role: Role, account: Pubkey) -> Result<()> {
+        let roles = &mut ctx.accounts.roles;
+        let sender = ctx.accounts.user.key();
+
+        roles.check_role(roles.admin_role(role), &sender)?;
+        roles.check_keeps_admin(role, &account)?;
+
+        if roles.revoke(role, &account) {
+            emit!(RoleRevoked {
+                role,
+                account,
+                sender
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Gives up `role` held by the signer, unless it is the last `Admin` holder.
+    pub fn renounce_role(ctx: Context<UpdateRoles>, role: Role) -> Result<()> {
+        let roles = &mut ctx.accounts.roles;
+        let sender = ctx.accounts.user.key();
+
+        roles.check_keeps_admin(role, &sender)?;
+
+        if roles.revoke(role, &sender) {
+            emit!(RoleRevoked {
+                role,
+                account: sender,
+                sender
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Makes `admin_role` the role administering `role`. Only `Admin` holders can call.
+    pub fn set_role_admin(ctx: Context<UpdateRoles>, role: Role, admin_role: Role) -> Result<()> {
+        let roles = &mut ctx.accounts.roles;
+
+        roles.check_role(Role::Admin, &ctx.accounts.user.key())?;
+
+        let previous_admin_role = roles.admin_role(role);
+        roles.roles[role as usize].admin_role = admin_role;
+
+        emit!(RoleAdminChanged {
+            role,
+            previous_admin_role,
+            new_admin_role: admin_role,
+        });
+
         Ok(())
     }
 
@@ -70,15 +225,19 @@ pub mod registry {
     ) -> Result<()> {
         let registry = &mut ctx.accounts.registry;
 
+        registry.check_not_paused(PauseGroup::Creation)?;
+
         if registry.locked {
             return Err(ErrorCode::ReentrancyGuard.into());
         }
 
         registry.locked = true;
 
-        // Check for the manager privilege for a service management
-        if ctx.accounts.user.key() != registry.manager {
-            return Err(ProgramError::InvalidAccountOwner.into());
+        // Check for the manager privilege, or the service owner in permissionless mode
+        let user = ctx.accounts.user.key();
+        let roles = &ctx.accounts.roles;
+        if !registry.is_service_authority(roles, &user, &service_owner) {
+            return Err(ErrorCode::MissingRole.into());
         }
 
         // Check for the non-empty service owner address
@@ -86,6 +245,28 @@ pub mod registry {
             return Err(ProgramError::InvalidArgument.into());
         }
 
+        // Every service pays the creation fee, whoever creates it
+        if registry.creation_fee > 0 {
+            let creation_fee = registry.creation_fee;
+            invoke(
+                &transfer(&user, &registry.wallet_key, creation_fee),
+                &[
+                    ctx.accounts.user.to_account_info(),
+                    ctx.accounts.registry_wallet.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+            )?;
+            registry.accrued_fees = registry
+                .accrued_fees
+                .checked_add(creation_fee)
+                .ok_or(ErrorCode::Overflow)?;
+        }
+
+        // The service token goes to the service owner
+        if ctx.accounts.service_owner.key() != service_owner {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+
         // Check for zero config hash
         if config_hash == [0u8; 32] {
             return Err(ErrorCode::ZeroConfigHash.into());
@@ -94,6 +275,7 @@ pub mod registry {
         let service_id = registry.total_supply + 1;
 
         let service = &mut ctx.accounts.service;
+        service.registry = registry.key();
         service.service_id = service_id;
         service.service_owner = service_owner;
         service.security_deposit = 0;
@@ -106,6 +288,31 @@ pub mod registry {
             service.threshold = threshold.unwrap_or_default();
         }
 
+        let service_escrow = &mut ctx.accounts.service_escrow;
+        service_escrow.service_id = service_id;
+        service_escrow.bump = ctx.bumps.service_escrow;
+
+        ServiceRegistry::record_config_hash(
+            ctx.program_id,
+            service_id,
+            config_hash,
+            ctx.bumps.config_hash_history,
+            &service.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.config_hash_history,
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        ServiceRegistry::mint_service_token(
+            &registry.key(),
+            service_id,
+            ctx.bumps.service,
+            &service.to_account_info(),
+            &ctx.accounts.service_mint.to_account_info(),
+            &ctx.accounts.service_token.to_account_info(),
+            &ctx.accounts.token_program.to_account_info(),
+        )?;
+
         emit!(CreateServiceEvent {
             service_id,
             config_hash
@@ -126,15 +333,22 @@ pub mod registry {
         let service = &mut ctx.accounts.service;
         let registry = &mut ctx.accounts.registry;
 
-        // Only the manager can update the service
-        if ctx.accounts.user.key() != registry.manager {
-            return Err(ProgramError::InvalidAccountOwner.into());
+        // Only the manager, or the service owner in permissionless mode, can update it
+        if !registry.is_service_authority(
+            &ctx.accounts.roles,
+            &ctx.accounts.user.key(),
+            &service_owner,
+        ) {
+            return Err(ErrorCode::MissingRole.into());
         }
 
-        // Validate that the provided service owner is the actual owner of the service
-        if service.service_owner != service_owner {
-            return Err(ProgramError::InvalidArgument.into());
-        }
+        // Validate that the provided service owner holds the service token
+        ServiceRegistry::check_service_holder(
+            ctx.program_id,
+            service,
+            &ctx.accounts.service_token,
+            service_owner,
+        )?;
 
         // Check if the service state is PreRegistration, only then can the service be updated
         if service.state != ServiceState::PreRegistration {
@@ -148,10 +362,21 @@ pub mod registry {
 
         ServiceRegistry::validate_threshold(service, threshold)?;
 
-        // Update the service configuration hash
+        // Update the service configuration hash and keep the previous ones on record
         let last_config_hash = service.config_hash;
         if last_config_hash != config_hash {
             service.config_hash = config_hash;
+
+            ServiceRegistry::record_config_hash(
+                ctx.program_id,
+                service.service_id,
+                config_hash,
+                ctx.bumps.config_hash_history,
+                &service.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.config_hash_history,
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
         }
 
         emit!(UpdateServiceEvent {
@@ -162,6 +387,169 @@ pub mod registry {
         Ok(())
     }
 
+    /// Returns, as return data, the config hashes of a service in the order they were
+    /// set, starting at record `start`. At most `CONFIG_HASH_RECORDS_PER_PAGE` records
+    /// fit in one call; `total` tells the caller whether to page further.
+    pub fn get_config_hash_history(
+        ctx: Context<GetConfigHashHistory>,
+        start: u32,
+    ) -> Result<ConfigHashHistoryPage> {
+        let records = &ctx.accounts.config_hash_history.records;
+        let start = (start as usize).min(records.len());
+        let end = (start + CONFIG_HASH_RECORDS_PER_PAGE).min(records.len());
+
+        Ok(ConfigHashHistoryPage {
+            total: records.len() as u32,
+            records: records[start..end].to_vec(),
+        })
+    }
+
+    /// Moves a service created under the legacy `["service", config_hash[..7]]` seeds
+    /// to its `["service", service_id]` address, closing the legacy account.
+    /// `legacy_seed` is only read by the `legacy_service` seeds constraint.
+    ///
+    /// The security deposit and operator bonds of the service move from the registry
+    /// wallet to the service escrow. Remaining accounts: the `OperatorBondAccount` and
+    /// `OperatorAgentInstanceIndex` of each operator of the service, by increasing
+    /// operator key.
+    #[allow(unused_variables)]
+    pub fn migrate_service<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MigrateService<'info>>,
+        legacy_seed: [u8; 7],
+        service_id: u128,
+    ) -> Result<()> {
+        let registry = &ctx.accounts.registry;
+        let legacy_info = ctx.accounts.legacy_service.to_account_info();
+
+        // Check for the manager privilege for a service management
+        ctx.accounts
+            .roles
+            .check_role(Role::ServiceManager, &ctx.accounts.user.key())?;
+
+        let legacy_service = {
+            let data = legacy_info.try_borrow_data()?;
+            if !data.starts_with(ServiceAccount::DISCRIMINATOR) {
+                return Err(anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch.into());
+            }
+            LegacyServiceAccount::deserialize(&mut &data[8..])?
+        };
+
+        require_eq!(legacy_service.service_id, service_id);
+        require_keys_eq!(
+            ctx.accounts.service_owner.key(),
+            legacy_service.service_owner,
+            anchor_lang::error::ErrorCode::ConstraintAddress
+        );
+        require!(
+            service_id > 0 && service_id <= registry.total_supply,
+            ErrorCode::ServiceNotFound
+        );
+
+        let service = &mut ctx.accounts.service;
+        service.set_inner(ServiceAccount {
+            registry: registry.key(),
+            service_id,
+            service_owner: legacy_service.service_owner,
+            security_deposit: legacy_service.security_deposit,
+            multisig: legacy_service.multisig,
+            config_hash: legacy_service.config_hash,
+            threshold: legacy_service.threshold,
+            max_num_agent_instances: legacy_service.max_num_agent_instances,
+            num_agent_instances: legacy_service.num_agent_instances,
+            state: legacy_service.state,
+            require_instance_proofs: false,
+            cleanup_pending: false,
+            cleanup_cursor: 0,
+            legacy_seeds: true,
+        });
+
+        // Legacy services hold their deposit and bonds in the registry wallet
+        let mut owed = legacy_service.security_deposit;
+        let mut num_instances: u32 = 0;
+        let mut previous_operator = Pubkey::default();
+        let mut remaining_accounts = ctx.remaining_accounts.iter();
+        while let Some(operator_bond_info) = remaining_accounts.next() {
+            let operator_bond: Account<OperatorBondAccount> =
+                Account::try_from(operator_bond_info)?;
+            let operator = operator_bond.operator;
+            require_keys_eq!(
+                operator_bond_info.key(),
+                operator_bond_pda(
+                    service.registry_seed(),
+                    service_id,
+                    &operator,
+                    ctx.program_id
+                )
+                .0,
+                ErrorCode::InvalidPda
+            );
+            // Increasing keys count each operator once
+            require!(operator > previous_operator, ErrorCode::WrongOperatorBonds);
+            previous_operator = operator;
+
+            let index_info = next_account_info(&mut remaining_accounts)?;
+            require_keys_eq!(
+                index_info.key(),
+                operator_agent_instance_index_pda(
+                    service.registry_seed(),
+                    service_id,
+                    &operator,
+                    ctx.program_id,
+                )
+                .0,
+                ErrorCode::InvalidPda
+            );
+            let index: Account<OperatorAgentInstanceIndex> = Account::try_from(index_info)?;
+
+            owed = owed
+                .checked_add(operator_bond.bond)
+                .ok_or(ErrorCode::Overflow)?;
+            num_instances = num_instances
+                .checked_add(index.operator_agent_instances.len() as u32)
+                .ok_or(ErrorCode::Overflow)?;
+        }
+        // Every operator with instances in the service is accounted for
+        require_eq!(
+            num_instances,
+            legacy_service.num_agent_instances,
+            ErrorCode::WrongOperatorBonds
+        );
+
+        let service_escrow = &mut ctx.accounts.service_escrow;
+        service_escrow.service_id = service_id;
+        service_escrow.bump = ctx.bumps.service_escrow;
+
+        if owed > 0 {
+            let registry_wallet_info = ctx.accounts.registry_wallet.to_account_info();
+            require!(
+                ServiceRegistry::balance_above_rent(&registry_wallet_info)? >= owed,
+                ErrorCode::InsufficientFunds
+            );
+            **registry_wallet_info.try_borrow_mut_lamports()? -= owed;
+            **service_escrow.to_account_info().try_borrow_mut_lamports()? += owed;
+        }
+
+        ServiceRegistry::mint_service_token(
+            &registry.key(),
+            service_id,
+            ctx.bumps.service,
+            &service.to_account_info(),
+            &ctx.accounts.service_mint.to_account_info(),
+            &ctx.accounts.service_token.to_account_info(),
+            &ctx.accounts.token_program.to_account_info(),
+        )?;
+
+        ServiceRegistry::close_account(&legacy_info, &ctx.accounts.user.to_account_info())?;
+
+        emit!(ServiceMigrated {
+            service_id,
+            legacy_service: legacy_info.key(),
+            service: service.key(),
+        });
+
+        Ok(())
+    }
+
     pub fn register_agent_ids_to_service<'info>(
         ctx: Context<'_, '_, 'info, 'info, RegisterAgentIdsToService<'info>>,
         service_owner: Pubkey,
@@ -175,14 +563,31 @@ pub mod registry {
         let service = &mut ctx.accounts.service;
 
         // Check for the manager privilege for a service management
-        if ctx.accounts.user.key() != registry.manager {
-            return Err(ProgramError::UninitializedAccount.into());
+        let roles = &ctx.accounts.roles;
+        if !registry.is_service_authority(roles, &ctx.accounts.user.key(), &service_owner) {
+            return Err(ErrorCode::MissingRole.into());
+        }
+
+        // The service owner signing proves it holds the service token
+        if !roles.has_role(Role::ServiceManager, &ctx.accounts.user.key()) {
+            let Some(service_token) = &ctx.accounts.service_token else {
+                return Err(ErrorCode::WrongServiceToken.into());
+            };
+            ServiceRegistry::check_service_holder(
+                ctx.program_id,
+                service,
+                service_token,
+                service_owner,
+            )?;
         }
 
         if service_owner != service.service_owner {
             return Err(ProgramError::InvalidAccountOwner.into());
         }
 
+        // The agent ids index of a terminated service is reused once cleaned up
+        require!(!service.cleanup_pending, ErrorCode::ServiceCleanupPending);
+
         let program_id = ctx.program_id;
         let user_account_info = ctx.accounts.user.to_account_info();
         let system_program_account_info = ctx.accounts.system_program.to_account_info();
@@ -199,8 +604,12 @@ pub mod registry {
 
             let agent_param_account_info = next_account_info(&mut remaining_accounts)?;
 
-            let (agent_param_pda, agent_param_bump) =
-                agent_param_pda(service.service_id, agent_id, ctx.program_id);
+            let (agent_param_pda, agent_param_bump) = agent_param_pda(
+                service.registry_seed(),
+                service.service_id,
+                agent_id,
+                ctx.program_id,
+            );
 
             require!(
                 agent_param_pda == agent_param_account_info.key(),
@@ -211,6 +620,7 @@ pub mod registry {
             if agent_param_account_info.data_is_empty() {
                 let agent_param_seeds: &[&[u8]] = &[
                     b"agent_param",
+                    service.registry_seed(),
                     &service.service_id.to_le_bytes(),
                     &agent_id.to_le_bytes(),
                     &[agent_param_bump],
@@ -342,6 +752,8 @@ pub mod registry {
     ) -> Result<()> {
         let registry = &mut ctx.accounts.registry;
 
+        registry.check_not_paused(PauseGroup::Deploy)?;
+
         // Reentrancy guard (same concept as Solidity, using a lock mechanism)
         if registry.locked {
             return Err(ErrorCode::ReentrancyGuard.into());
@@ -354,42 +766,73 @@ pub mod registry {
         let registry_multisig = &ctx.accounts.registry_multisig;
 
         // Check for the manager privilege for a service management
-        if ctx.accounts.user.key() != registry.manager {
-            return Err(ProgramError::InvalidAccountOwner.into());
-        }
+        ctx.accounts
+            .roles
+            .check_role(Role::ServiceManager, &ctx.accounts.user.key())?;
 
-        // Validate that the provided service owner is the actual owner of the service
-        if service.service_owner != service_owner.key() {
-            return Err(ProgramError::InvalidArgument.into());
-        }
+        // Validate that the provided service owner holds the service token
+        ServiceRegistry::check_service_holder(
+            ctx.program_id,
+            service,
+            &ctx.accounts.service_token,
+            service_owner.key(),
+        )?;
 
         require_eq!(service.service_id, service_id);
 
+        // Only a service with all its agent instance slots filled can be deployed
+        require!(
+            service.state == ServiceState::FinishedRegistration,
+            ErrorCode::WrongServiceState
+        );
+
         // Check for whitelisted multisig implementation
         require!(
             registry_multisig.is_authorized(&multisig_implementation),
             ErrorCode::UnauthorizedMultisig
         );
 
-        // Retrieve agent instances
+        // The multisig account comes first, then the agent instances owning it
+        require!(
+            !ctx.remaining_accounts.is_empty(),
+            ErrorCode::MissingMultisigAccount
+        );
+
+        // Retrieve agent instances, the ones registered in the service in registration order
         let agent_instances: Vec<Pubkey> = ctx
             .remaining_accounts
             .iter()
             .skip(1)
             .map(|acc| acc.key())
             .collect();
+        require!(
+            agent_instances == ctx.accounts.agent_instances_index.service_agent_instances,
+            ErrorCode::IncorrectAgentInstances
+        );
 
         let remaining_accounts = ctx.remaining_accounts;
 
-        let multisig_pda = ServiceRegistry::create_multisig(
-            &multisig_implementation, // call this implemenation later instead of create_multisig
-            &agent_instances,
-            service.threshold,
-            &data,
-            &ctx.accounts.user,
-            ctx.program_id,
-            remaining_accounts,
-        )?;
+        // The registry itself acts as the built-in implementation, any other is invoked via CPI
+        let multisig_pda = if multisig_implementation == *ctx.program_id {
+            ServiceRegistry::create_multisig(
+                &agent_instances,
+                service.threshold,
+                &data,
+                &ctx.accounts.user,
+                ctx.program_id,
+                remaining_accounts,
+            )?
+        } else {
+            ServiceRegistry::create_external_multisig(
+                &ctx.accounts.multisig_implementation,
+                &agent_instances,
+                service.threshold,
+                &data,
+                &ctx.accounts.user,
+                &ctx.accounts.system_program,
+                remaining_accounts,
+            )?
+        };
 
         // Update service state
         service.multisig = multisig_pda;
@@ -405,78 +848,221 @@ pub mod registry {
         Ok(())
     }
 
-    pub fn change_drainer(ctx: Context<ChangeDrainer>, new_drainer: Pubkey) -> Result<()> {
+    /// Proposes `new_drainer` as the registry drainer, who takes the drainer role over by
+    /// accepting. Only administrators of the drainer role can call.
+    pub fn propose_drainer(ctx: Context<ChangeDrainer>, new_drainer: Pubkey) -> Result<()> {
         let registry = &mut ctx.accounts.registry;
+        let roles = &ctx.accounts.roles;
 
-        // Only owner can call
-        if ctx.accounts.user.key() != registry.owner {
-            return Err(ProgramError::IllegalOwner.into());
-        }
+        // Only administrators of the drainer role can call
+        roles.check_role(roles.admin_role(Role::Drainer), &ctx.accounts.user.key())?;
 
         // Cannot set zero address
         if new_drainer == Pubkey::default() {
             return Err(ProgramError::InvalidArgument.into());
         }
 
+        registry.pending_drainer = new_drainer;
+
+        emit!(DrainerProposedEvent {
+            drainer: registry.drainer,
+            pending_drainer: new_drainer,
+        });
+
+        Ok(())
+    }
+
+    /// Hands the drainer role over to the pending drainer, who must sign.
+    pub fn accept_drainer(ctx: Context<ChangeDrainer>) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        let new_drainer = ctx.accounts.user.key();
+
+        // Only the pending drainer can accept
+        require!(
+            registry.pending_drainer == new_drainer,
+            ErrorCode::NotPendingKey
+        );
+
+        ctx.accounts
+            .roles
+            .hand_over(&[Role::Drainer], &registry.drainer, new_drainer)?;
         registry.drainer = new_drainer;
+        registry.pending_drainer = Pubkey::default();
 
         emit!(DrainerUpdatedEvent { new_drainer });
 
         Ok(())
     }
 
-    pub fn change_owner(ctx: Context<ChangeOwner>, new_owner: Pubkey) -> Result<()> {
+    /// Withdraws the drainer proposal. Only administrators of the drainer role can call.
+    pub fn cancel_drainer(ctx: Context<ChangeDrainer>) -> Result<()> {
         let registry = &mut ctx.accounts.registry;
+        let roles = &ctx.accounts.roles;
 
-        // Only current owner can call
-        if ctx.accounts.user.key() != registry.owner {
-            return Err(Error::from(ProgramError::IllegalOwner));
-        }
+        roles.check_role(roles.admin_role(Role::Drainer), &ctx.accounts.user.key())?;
+
+        let pending_drainer = registry.pending_drainer;
+        require!(
+            pending_drainer != Pubkey::default(),
+            ErrorCode::NoPendingHandover
+        );
+        registry.pending_drainer = Pubkey::default();
+
+        emit!(DrainerProposalCancelledEvent { pending_drainer });
+
+        Ok(())
+    }
+
+    /// Proposes `new_owner` as the registry owner, who takes the owner roles over by
+    /// accepting. Only administrators of the admin role can call.
+    pub fn propose_owner(ctx: Context<ChangeOwner>, new_owner: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        let roles = &ctx.accounts.roles;
+
+        // Only administrators of the admin role can call
+        roles.check_role(roles.admin_role(Role::Admin), &ctx.accounts.user.key())?;
 
         // Cannot set zero address
         if new_owner == Pubkey::default() {
             return Err(ProgramError::InvalidArgument.into());
         }
 
+        registry.pending_owner = new_owner;
+
+        emit!(OwnerProposedEvent {
+            owner: registry.owner,
+            pending_owner: new_owner,
+        });
+
+        Ok(())
+    }
+
+    /// Hands the owner roles the current owner still holds over to the pending owner,
+    /// who must sign.
+    pub fn accept_owner(ctx: Context<ChangeOwner>) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        let new_owner = ctx.accounts.user.key();
+
+        // Only the pending owner can accept
+        require!(
+            registry.pending_owner == new_owner,
+            ErrorCode::NotPendingKey
+        );
+
         // Update the owner
+        ctx.accounts
+            .roles
+            .hand_over(&Role::OWNER, &registry.owner, new_owner)?;
         registry.owner = new_owner;
+        registry.pending_owner = Pubkey::default();
 
         emit!(OwnerUpdatedEvent { new_owner });
 
         Ok(())
     }
 
-    pub fn change_manager(ctx: Context<ChangeManager>, new_manager: Pubkey) -> Result<()> {
+    /// Withdraws the owner proposal. Only administrators of the admin role can call.
+    pub fn cancel_owner(ctx: Context<ChangeOwner>) -> Result<()> {
         let registry = &mut ctx.accounts.registry;
+        let roles = &ctx.accounts.roles;
 
-        // Only current owner can call
-        if ctx.accounts.user.key() != registry.owner {
-            return Err(Error::from(ProgramError::IllegalOwner));
-        }
+        roles.check_role(roles.admin_role(Role::Admin), &ctx.accounts.user.key())?;
+
+        let pending_owner = registry.pending_owner;
+        require!(
+            pending_owner != Pubkey::default(),
+            ErrorCode::NoPendingHandover
+        );
+        registry.pending_owner = Pubkey::default();
+
+        emit!(OwnerProposalCancelledEvent { pending_owner });
+
+        Ok(())
+    }
+
+    /// Proposes `new_manager` as the registry manager, who takes the manager role over by
+    /// accepting. Only administrators of the manager role can call.
+    pub fn propose_manager(ctx: Context<ChangeManager>, new_manager: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        let roles = &ctx.accounts.roles;
+
+        // Only administrators of the manager role can call
+        roles.check_role(
+            roles.admin_role(Role::ServiceManager),
+            &ctx.accounts.user.key(),
+        )?;
 
         // Cannot set zero address
         if new_manager == Pubkey::default() {
             return Err(ProgramError::InvalidArgument.into());
         }
 
-        // Update the owner
+        registry.pending_manager = new_manager;
+
+        emit!(ManagerProposedEvent {
+            manager: registry.manager,
+            pending_manager: new_manager,
+        });
+
+        Ok(())
+    }
+
+    /// Hands the manager role over to the pending manager, who must sign. A program
+    /// managing services, signing through a PDA, is granted the manager role instead.
+    pub fn accept_manager(ctx: Context<ChangeManager>) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        let new_manager = ctx.accounts.user.key();
+
+        // Only the pending manager can accept
+        require!(
+            registry.pending_manager == new_manager,
+            ErrorCode::NotPendingKey
+        );
+
+        // Update the manager
+        ctx.accounts
+            .roles
+            .hand_over(&[Role::ServiceManager], &registry.manager, new_manager)?;
         registry.manager = new_manager;
+        registry.pending_manager = Pubkey::default();
 
         emit!(ManagerUpdatedEvent { new_manager });
 
         Ok(())
     }
 
-    pub fn set_base_uri(ctx: Context<ChangeManager>, new_base_uri: String) -> Result<()> {
+    /// Withdraws the manager proposal. Only administrators of the manager role can call.
+    pub fn cancel_manager(ctx: Context<ChangeManager>) -> Result<()> {
         let registry = &mut ctx.accounts.registry;
+        let roles = &ctx.accounts.roles;
 
-        // Only current owner can call
-        if ctx.accounts.user.key() != registry.owner {
-            return Err(Error::from(ProgramError::IllegalOwner));
-        }
+        roles.check_role(
+            roles.admin_role(Role::ServiceManager),
+            &ctx.accounts.user.key(),
+        )?;
+
+        let pending_manager = registry.pending_manager;
+        require!(
+            pending_manager != Pubkey::default(),
+            ErrorCode::NoPendingHandover
+        );
+        registry.pending_manager = Pubkey::default();
+
+        emit!(ManagerProposalCancelledEvent { pending_manager });
+
+        Ok(())
+    }
+
+    pub fn set_base_uri(ctx: Context<UpdateRegistry>, new_base_uri: String) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+
+        // Only metadata editors can call
+        ctx.accounts
+            .roles
+            .check_role(Role::MetadataEditor, &ctx.accounts.user.key())?;
 
         // Cannot set zero address
-        if new_base_uri.is_empty() {
+        if new_base_uri.is_empty() || new_base_uri.len() > MAX_URI_LENGTH {
             return Err(ProgramError::InvalidArgument.into());
         }
 
@@ -488,13 +1074,347 @@ pub mod registry {
         Ok(())
     }
 
-    // ! TODO This function is for bypassing mulitisg in tests, shall be remove
-    // ! ONLY ALLOW THIS IN TEST ENV
-    #[cfg(feature = "test-env")]
-    pub fn change_multisig(ctx: Context<ChangeMultiSig>, new_multisig: Pubkey) -> Result<()> {
-        let service = &mut ctx.accounts.service;
+    /// Lets anyone create and configure a service it owns, next to the manager. Every
+    /// created service pays `creation_fee` lamports, whoever creates it. Only `Admin`
+    /// holders can call.
+    pub fn set_permissionless(
+        ctx: Context<UpdateRegistry>,
+        permissionless: bool,
+        creation_fee: u64,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
 
-        // Only current service owner can call
+        // Only administrators can call
+        ctx.accounts
+            .roles
+            .check_role(Role::Admin, &ctx.accounts.user.key())?;
+
+        registry.permissionless = permissionless;
+        registry.creation_fee = creation_fee;
+
+        emit!(PermissionlessModeSet {
+            permissionless,
+            creation_fee,
+        });
+
+        Ok(())
+    }
+
+    /// Freezes or resumes the instructions of `group`. Only pausers can call.
+    pub fn set_paused(ctx: Context<UpdateRegistry>, group: PauseGroup, paused: bool) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        let sender = ctx.accounts.user.key();
+
+        // Only pausers can call
+        ctx.accounts.roles.check_role(Role::Pauser, &sender)?;
+
+        if paused {
+            registry.paused |= group.flag();
+        } else {
+            registry.paused &= !group.flag();
+        }
+
+        match group {
+            PauseGroup::Creation => emit!(CreationPauseChanged { paused, sender }),
+            PauseGroup::Registration => emit!(RegistrationPauseChanged { paused, sender }),
+            PauseGroup::Deploy => emit!(DeployPauseChanged { paused, sender }),
+            PauseGroup::Slash => emit!(SlashPauseChanged { paused, sender }),
+            PauseGroup::Payouts => emit!(PayoutsPauseChanged { paused, sender }),
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the refunds of `terminate` and `claim_unbonded` open while payouts are paused.
+    /// Only pausers can call.
+    pub fn set_refunds_open(ctx: Context<UpdateRegistry>, refunds_open: bool) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        let sender = ctx.accounts.user.key();
+
+        // Only pausers can call
+        ctx.accounts.roles.check_role(Role::Pauser, &sender)?;
+
+        registry.refunds_open = refunds_open;
+
+        emit!(RefundsOpenChanged {
+            refunds_open,
+            sender
+        });
+
+        Ok(())
+    }
+
+    /// Sets the delay in seconds unbonded operators wait before claiming their refunds,
+    /// during which the service multisig can still slash them. Only admins can call.
+    pub fn set_unbonding_delay(ctx: Context<UpdateRegistry>, new_delay: i64) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+
+        // Only admins can call
+        ctx.accounts
+            .roles
+            .check_role(Role::Admin, &ctx.accounts.user.key())?;
+
+        if new_delay < 0 {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+
+        let previous_delay = registry.unbonding_delay;
+        registry.unbonding_delay = new_delay;
+
+        emit!(UnbondingDelayUpdated {
+            previous_delay,
+            new_delay,
+        });
+
+        Ok(())
+    }
+
+    /// Makes the security deposit and operator bonds of a service payable in `bond_mint`
+    /// instead of lamports. Only possible before the service registration is activated,
+    /// while its escrow holds no lamports above rent, and never to another mint.
+    pub fn set_service_bond_token(
+        ctx: Context<SetServiceBondToken>,
+        service_id: u128,
+    ) -> Result<()> {
+        let registry = &ctx.accounts.registry;
+        let service = &ctx.accounts.service;
+
+        // Check for the manager privilege for a service management
+        ctx.accounts
+            .roles
+            .check_role(Role::ServiceManager, &ctx.accounts.user.key())?;
+
+        require_eq!(service.service_id, service_id);
+
+        // Deposits and bonds must all be paid in the same currency
+        require!(
+            service.state == ServiceState::PreRegistration,
+            ErrorCode::WrongServiceState
+        );
+
+        let mint = ctx.accounts.bond_mint.key();
+
+        // Pending withdrawals and any deposit left stay payable in the current currency
+        let service_bond_token = &mut ctx.accounts.service_bond_token;
+        if service_bond_token.mint == Pubkey::default() {
+            let service_escrow = &ctx.accounts.service_escrow;
+            let rent = Rent::get()?.minimum_balance(service_escrow.data_len());
+            require!(
+                service_escrow.lamports() <= rent,
+                ErrorCode::ServiceEscrowNotEmpty
+            );
+        } else {
+            require_keys_eq!(
+                service_bond_token.mint,
+                mint,
+                ErrorCode::BondTokenAlreadySet
+            );
+        }
+
+        service_bond_token.service_id = service_id;
+        service_bond_token.mint = mint;
+
+        let bond_token_vault = &mut ctx.accounts.bond_token_vault;
+        bond_token_vault.registry = registry.key();
+        bond_token_vault.mint = mint;
+        bond_token_vault.bump = ctx.bumps.bond_token_vault;
+
+        emit!(ServiceBondTokenSet { service_id, mint });
+
+        Ok(())
+    }
+
+    /// Hands the service to `new_owner`, moving the service token out of the token
+    /// account of the current owner, who signs.
+    pub fn transfer_service(
+        ctx: Context<TransferService>,
+        service_id: u128,
+        new_owner: Pubkey,
+    ) -> Result<()> {
+        let service = &mut ctx.accounts.service;
+        let service_owner = &ctx.accounts.service_owner;
+
+        // Check for the non-empty new owner address
+        if new_owner == Pubkey::default() {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+
+        require_eq!(service.service_id, service_id);
+
+        // Only the current holder of the service token can transfer the service
+        ServiceRegistry::check_service_holder(
+            ctx.program_id,
+            service,
+            &ctx.accounts.service_token,
+            service_owner.key(),
+        )?;
+
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.service_token.to_account_info(),
+                    to: ctx.accounts.new_service_token.to_account_info(),
+                    authority: service_owner.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+
+        service.service_owner = new_owner;
+
+        emit!(ServiceOwnerTransferred {
+            service_id,
+            previous_owner: service_owner.key(),
+            new_owner,
+        });
+
+        Ok(())
+    }
+
+    /// Requires, or stops requiring, each agent instance registered in the service to
+    /// prove it holds its key by signing `signatures::instance_proof_message`.
+    pub fn set_instance_proofs(
+        ctx: Context<SetInstanceProofs>,
+        service_id: u128,
+        required: bool,
+    ) -> Result<()> {
+        let service = &mut ctx.accounts.service;
+
+        // Check for the manager privilege for a service management
+        ctx.accounts
+            .roles
+            .check_role(Role::ServiceManager, &ctx.accounts.user.key())?;
+
+        require_eq!(service.service_id, service_id);
+
+        // Every instance of a service is registered under the same rule
+        require!(
+            service.state == ServiceState::PreRegistration,
+            ErrorCode::WrongServiceState
+        );
+
+        service.require_instance_proofs = required;
+
+        emit!(InstanceProofsSet {
+            service_id,
+            required
+        });
+
+        Ok(())
+    }
+
+    /// Turns the operator whitelist check of a service on or off. Only the service
+    /// owner, who signs, can change it.
+    pub fn set_operators_check(
+        ctx: Context<UpdateOperatorWhitelist>,
+        service_id: u128,
+        check: bool,
+    ) -> Result<()> {
+        let service_owner = ctx.accounts.service_owner.key();
+
+        require_eq!(ctx.accounts.service.service_id, service_id);
+
+        ServiceRegistry::check_service_holder(
+            ctx.program_id,
+            &mut ctx.accounts.service,
+            &ctx.accounts.service_token,
+            service_owner,
+        )?;
+
+        let operator_whitelist = &mut ctx.accounts.operator_whitelist;
+        operator_whitelist.service_id = service_id;
+        operator_whitelist.check = check;
+
+        emit!(OperatorsCheckSet {
+            service_owner,
+            service_id,
+            check,
+        });
+
+        Ok(())
+    }
+
+    /// Whitelists each of `operators` whose status is true and removes the others from
+    /// the whitelist of a service, then sets its check to `check`. Only the service
+    /// owner, who signs, can change it.
+    pub fn set_operators_statuses(
+        ctx: Context<UpdateOperatorWhitelist>,
+        service_id: u128,
+        operators: Vec<Pubkey>,
+        statuses: Vec<bool>,
+        check: bool,
+    ) -> Result<()> {
+        let service_owner = ctx.accounts.service_owner.key();
+
+        require_eq!(ctx.accounts.service.service_id, service_id);
+
+        ServiceRegistry::check_service_holder(
+            ctx.program_id,
+            &mut ctx.accounts.service,
+            &ctx.accounts.service_token,
+            service_owner,
+        )?;
+
+        if operators.is_empty() || operators.len() != statuses.len() {
+            return Err(ErrorCode::WrongArrayLength.into());
+        }
+
+        let operator_whitelist = &mut ctx.accounts.operator_whitelist;
+        for (operator, status) in operators.iter().zip(&statuses) {
+            if *operator == Pubkey::default() {
+                return Err(ProgramError::InvalidArgument.into());
+            }
+
+            let position = operator_whitelist
+                .operators
+                .iter()
+                .position(|whitelisted| whitelisted == operator);
+            match (position, status) {
+                (None, true) => {
+                    require!(
+                        operator_whitelist.operators.len() < MAX_WHITELISTED_OPERATORS,
+                        ErrorCode::MaxWhitelistedOperatorsReached
+                    );
+                    operator_whitelist.operators.push(*operator);
+                }
+                (Some(position), false) => {
+                    operator_whitelist.operators.swap_remove(position);
+                }
+                _ => {}
+            }
+        }
+        operator_whitelist.service_id = service_id;
+        operator_whitelist.check = check;
+
+        emit!(OperatorsWhitelistUpdated {
+            service_owner,
+            service_id,
+            operators,
+            statuses,
+            check,
+        });
+
+        Ok(())
+    }
+
+    /// Returns, as return data, the metadata URI of a service: the registry base URI
+    /// followed by the config hash encoded as a base16 CIDv1.
+    #[allow(unused_variables)]
+    pub fn token_uri(ctx: Context<TokenUri>, service_id: u128) -> Result<String> {
+        Ok(metadata::token_uri(
+            &ctx.accounts.registry.base_uri,
+            &ctx.accounts.service.config_hash,
+        ))
+    }
+
+    // ! TODO This function is for bypassing mulitisg in tests, shall be remove
+    // ! ONLY ALLOW THIS IN TEST ENV
+    #[cfg(feature = "test-env")]
+    pub fn change_multisig(ctx: Context<ChangeMultiSig>, new_multisig: Pubkey) -> Result<()> {
+        let service = &mut ctx.accounts.service;
+
+        // Only current service owner can call
         if ctx.accounts.user.key() != service.service_owner {
             return Err(Error::from(ProgramError::IllegalOwner));
         }
@@ -518,20 +1438,47 @@ pub mod registry {
         let registry = &mut ctx.accounts.registry;
         let drainer = &ctx.accounts.drainer;
 
+        registry.check_not_paused(PauseGroup::Payouts)?;
+
         // Reentrancy guard (same concept as Solidity, using a lock mechanism)
         if registry.locked {
             return Err(ErrorCode::ReentrancyGuard.into());
         }
         registry.locked = true;
 
-        // Check if the caller is the correct drainer
-        if drainer.key() != registry.drainer {
-            return Err(ProgramError::IllegalOwner.into());
+        // Check if the caller holds the drainer role
+        ctx.accounts
+            .roles
+            .check_role(Role::Drainer, &drainer.key())?;
+
+        // Slashed bond tokens are drained one vault at a time
+        if let Some(vault) = ctx.accounts.bond_token_vault.as_mut() {
+            let amount = vault.slashed_funds;
+            if amount > 0 {
+                vault.slashed_funds = 0;
+                ServiceRegistry::drain_bond_token(
+                    &registry.key(),
+                    vault,
+                    ctx.accounts.vault_token.as_deref(),
+                    ctx.accounts.drainer_token.as_deref(),
+                    ctx.accounts.token_program.as_ref(),
+                    &drainer.key(),
+                    amount,
+                )?;
+            }
+
+            registry.locked = false;
+            return Ok(amount);
         }
 
-        let amount = registry.slashed_funds;
+        // Creation fees accrue to the drainer with the slashed funds
+        let amount = registry
+            .slashed_funds
+            .checked_add(registry.accrued_fees)
+            .ok_or(ErrorCode::Overflow)?;
         if amount > 0 {
             registry.slashed_funds = 0;
+            registry.accrued_fees = 0;
 
             let (registry_wallet_pda, registry_wallet_bump) =
                 registry_wallet_pda(&registry.key(), ctx.program_id);
@@ -583,6 +1530,64 @@ pub mod registry {
         Ok(amount)
     }
 
+    /// Checks that the escrows of a page of services hold their security deposits,
+    /// operator bonds and pending withdrawals, and that the registry wallet holds the
+    /// slashed funds and creation fees. Returns the totals as return data and emits
+    /// `SolvencyDeficit` when anything falls short.
+    ///
+    /// Remaining accounts, per service: the service, its escrow, its `ServiceBondToken`
+    /// address, the escrow token account when that one is set, then `operator_bonds[i]`
+    /// `OperatorBondAccount`s and `pending_withdrawals[i]` `PendingWithdrawal`s of the
+    /// service. Bonds and withdrawals left out are not counted as owed.
+    pub fn audit_solvency<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AuditSolvency<'info>>,
+        operator_bonds: Vec<u8>,
+        pending_withdrawals: Vec<u8>,
+    ) -> Result<SolvencyReport> {
+        require_eq!(
+            operator_bonds.len(),
+            pending_withdrawals.len(),
+            ErrorCode::WrongArrayLength
+        );
+
+        let mut report = SolvencyReport {
+            slashed_funds: ctx.accounts.registry.slashed_funds,
+            accrued_fees: ctx.accounts.registry.accrued_fees,
+            treasury_balance: ServiceRegistry::balance_above_rent(&ctx.accounts.registry_wallet)?,
+            ..Default::default()
+        };
+
+        let mut remaining_accounts = ctx.remaining_accounts.iter();
+        for (bond_count, withdrawal_count) in operator_bonds.into_iter().zip(pending_withdrawals) {
+            ServiceRegistry::audit_service(
+                &mut remaining_accounts,
+                bond_count,
+                withdrawal_count,
+                &ctx.accounts.registry.key(),
+                ctx.program_id,
+                &mut report,
+            )?;
+        }
+
+        let treasury_deficit = report
+            .slashed_funds
+            .saturating_add(report.accrued_fees)
+            .saturating_sub(report.treasury_balance);
+        report.deficit = report
+            .deficit
+            .checked_add(treasury_deficit)
+            .ok_or(ErrorCode::Overflow)?;
+
+        if report.deficit > 0 || !report.insolvent_services.is_empty() {
+            emit!(SolvencyDeficit {
+                deficit: report.deficit,
+                insolvent_services: report.insolvent_services.clone(),
+            });
+        }
+
+        Ok(report)
+    }
+
     pub fn slash<'info>(
         ctx: Context<'_, '_, 'info, 'info, Slash<'info>>,
         service_id: u128,
@@ -592,6 +1597,8 @@ pub mod registry {
         let registry = &mut ctx.accounts.registry;
         let service = &ctx.accounts.service;
 
+        registry.check_not_paused(PauseGroup::Slash)?;
+
         require_eq!(service.service_id, service_id);
 
         require!(
@@ -608,7 +1615,14 @@ pub mod registry {
             ErrorCode::OnlyOwnServiceMultisig
         );
 
+        let token_bonded = ctx.accounts.bond_token.token_bonded(
+            service,
+            ctx.program_id,
+            BondTransfer::Treasury,
+        )?;
+
         let mut remaining_accounts = ctx.remaining_accounts.iter();
+        let mut total_slashed: u64 = 0;
 
         for (i, agent_instance) in agent_instances.iter().enumerate() {
             let amount_to_slash = amounts[i];
@@ -633,8 +1647,12 @@ pub mod registry {
             let mut operator_bond_account: Account<OperatorBondAccount> =
                 Account::try_from(operator_bond_info)?;
 
-            let (operator_bond_pda, _operator_bond_bump) =
-                operator_bond_pda(service_id, &operator.key(), ctx.program_id);
+            let (operator_bond_pda, _operator_bond_bump) = operator_bond_pda(
+                service.registry_seed(),
+                service_id,
+                &operator.key(),
+                ctx.program_id,
+            );
 
             require!(
                 operator_bond_pda == operator_bond_info.key(),
@@ -666,7 +1684,9 @@ pub mod registry {
             data[..8].copy_from_slice(discriminator);
             operator_bond_account.serialize(&mut &mut data[8..])?;
 
-            registry.slashed_funds += slashed_amount;
+            total_slashed = total_slashed
+                .checked_add(slashed_amount)
+                .ok_or(ErrorCode::Overflow)?;
 
             emit!(OperatorSlashed {
                 service_id,
@@ -675,13 +1695,83 @@ pub mod registry {
             });
         }
 
-        Ok(())
+        ctx.accounts.collect(token_bonded, total_slashed)
+    }
+
+    /// Slashes the pending withdrawals of unbonded operators, one remaining account per
+    /// operator. Only the service multisig can call, until the withdrawals are claimed.
+    pub fn slash_pending_withdrawals<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Slash<'info>>,
+        service_id: u128,
+        operators: Vec<Pubkey>,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        let registry = &ctx.accounts.registry;
+        let service = &ctx.accounts.service;
+
+        registry.check_not_paused(PauseGroup::Slash)?;
+
+        require_eq!(service.service_id, service_id);
+        require!(
+            operators.len() == amounts.len() && operators.len() == ctx.remaining_accounts.len(),
+            ErrorCode::WrongArrayLength
+        );
+
+        let token_bonded = ctx.accounts.bond_token.token_bonded(
+            service,
+            ctx.program_id,
+            BondTransfer::Treasury,
+        )?;
+
+        let mut total_slashed: u64 = 0;
+
+        for ((operator, amount), pending_withdrawal_info) in
+            operators.iter().zip(amounts).zip(ctx.remaining_accounts)
+        {
+            let (pending_withdrawal_pda, _bump) = pending_withdrawal_pda(
+                service.registry_seed(),
+                service_id,
+                operator,
+                ctx.program_id,
+            );
+            require!(
+                pending_withdrawal_pda == pending_withdrawal_info.key(),
+                ErrorCode::InvalidPda
+            );
+
+            let mut pending_withdrawal: Account<PendingWithdrawal> =
+                Account::try_from(pending_withdrawal_info)?;
+
+            require!(amount > 0, ErrorCode::InvalidSlashAmount);
+            require!(
+                pending_withdrawal.amount > 0,
+                ErrorCode::IncorrectAgentBondingValue
+            );
+
+            let slashed_amount = std::cmp::min(pending_withdrawal.amount, amount);
+            pending_withdrawal.amount -= slashed_amount;
+            pending_withdrawal
+                .try_serialize(&mut &mut pending_withdrawal_info.try_borrow_mut_data()?[..])?;
+
+            total_slashed = total_slashed
+                .checked_add(slashed_amount)
+                .ok_or(ErrorCode::Overflow)?;
+
+            emit!(PendingWithdrawalSlashed {
+                service_id,
+                operator: *operator,
+                amount: slashed_amount,
+            });
+        }
+
+        ctx.accounts.collect(token_bonded, total_slashed)
     }
 
     pub fn check_service(ctx: Context<CheckService>, service_id: u128) -> Result<()> {
         let service_account = &ctx.accounts.service;
         // Find the Service Account PDA
-        let (service_pda, _bump) = service_pda(&service_account.config_hash, ctx.program_id);
+        let (service_pda, _bump) =
+            service_pda(&service_account.registry, service_id, ctx.program_id);
 
         // Use the service PDA to load the service account
         // Ensure the service account is the expected one
@@ -690,8 +1780,11 @@ pub mod registry {
 
         let service_agent_ids_index = &mut ctx.accounts.service_agent_ids_index;
 
-        let (expected_pda, _bump) =
-            service_agent_ids_index_pda(service_account.service_id, ctx.program_id);
+        let (expected_pda, _bump) = service_agent_ids_index_pda(
+            service_account.registry_seed(),
+            service_account.service_id,
+            ctx.program_id,
+        );
 
         require!(
             service_agent_ids_index.key() == expected_pda,
@@ -714,9 +1807,15 @@ pub mod registry {
         let registry = &ctx.accounts.registry;
         let service = &mut ctx.accounts.service;
 
+        registry.check_not_paused(PauseGroup::Registration)?;
+
         // Check for the manager privilege for a service management
-        if ctx.accounts.user.key() != registry.manager {
-            return Err(ProgramError::InvalidAccountOwner.into());
+        if !registry.is_service_authority(
+            &ctx.accounts.roles,
+            &ctx.accounts.user.key(),
+            &service_owner,
+        ) {
+            return Err(ErrorCode::MissingRole.into());
         }
 
         // Check for the non-empty service owner address
@@ -724,176 +1823,120 @@ pub mod registry {
             return Err(ProgramError::InvalidArgument.into());
         }
 
+        // Validate that the provided service owner holds the service token
+        ServiceRegistry::check_service_holder(
+            ctx.program_id,
+            service,
+            &ctx.accounts.service_token,
+            service_owner,
+        )?;
+
         require_eq!(service.service_id, service_id);
 
         require!(
             service.state == ServiceState::PreRegistration,
             ErrorCode::ServiceMustBeInactive
         );
+        require!(!service.cleanup_pending, ErrorCode::ServiceCleanupPending);
+
+        let bond_token = &ctx.accounts.bond_token;
+        if bond_token.token_bonded(service, ctx.program_id, BondTransfer::Counterparty)? {
+            // Transfer the security deposit from the user token account to the escrow
+            bond_token.deposit(
+                &ctx.accounts.user.to_account_info(),
+                service.security_deposit,
+            )?;
+        } else {
+            // Transfer the security deposit from the user account to the service escrow
+            ServiceRegistry::transfer_bond(
+                &ctx.accounts.user,
+                &ctx.accounts.system_program,
+                &ctx.accounts.service_escrow.to_account_info(),
+                service.security_deposit,
+            )?;
+        }
 
-        // Check that the deposit is the expected amount
-        let user_pre_balance = ctx.accounts.user.lamports();
-        require!(
-            user_pre_balance >= service.security_deposit,
-            ErrorCode::IncorrectRegistrationDepositValue
-        );
-
-        // Transfer the bond from user account to the program's wallet
-        let (registry_wallet_pda, registry_wallet_bump) =
-            registry_wallet_pda(&registry.key(), ctx.program_id);
-
-        require_eq!(
-            registry_wallet_pda,
-            ctx.accounts.registry_wallet.key(),
-            ErrorCode::WrongRegistryWallet
-        );
-
-        require_eq!(
-            registry_wallet_pda,
-            registry.wallet_key,
-            ErrorCode::WrongRegistryWallet
-        );
-
-        require_eq!(
-            registry_wallet_bump,
-            registry.wallet_bump,
-            ErrorCode::WrongRegistryWallet
-        );
+        service.state = ServiceState::ActiveRegistration;
 
-        let transfer_amount = service.security_deposit;
+        emit!(ActivateRegistrationEvent { service_id });
 
-        let transfer_tx = transfer(
-            &ctx.accounts.user.key(),
-            &registry.wallet_key,
-            transfer_amount,
-        );
-
-        // Execute the transfer
-        invoke_signed(
-            &transfer_tx,
-            &[
-                ctx.accounts.user.to_account_info(),
-                ctx.accounts.registry_wallet.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
-            &[&[
-                b"registry_wallet",
-                registry.key().as_ref(),
-                &[registry_wallet_bump],
-            ]],
-        )?;
-
-        // Verify exact amount was transferred
-        let user_post_balance = ctx.accounts.user.lamports();
-
-        let balance_diff = user_pre_balance
-            .checked_sub(user_post_balance)
-            .ok_or(ErrorCode::Overflow)?;
-
-        require!(
-            balance_diff == transfer_amount,
-            ErrorCode::IncorrectRegistrationDepositValue
-        );
-
-        service.state = ServiceState::ActiveRegistration;
-
-        emit!(ActivateRegistrationEvent { service_id });
-
-        Ok(())
-    }
+        Ok(())
+    }
 
+    /// Registers `agent_instances` of `operator`, who signs and pays the bond itself.
     pub fn register_agents<'info>(
         ctx: Context<'_, '_, 'info, 'info, RegisterAgentInstances<'info>>,
         operator: Pubkey,
         agent_instances: Vec<Pubkey>,
         agent_ids: Vec<u32>,
     ) -> Result<()> {
-        let registry = &ctx.accounts.registry;
-
-        // Permissions & State Checks
-        ServiceRegistry::check_access_and_state(
-            &ctx,
-            registry,
-            &ctx.accounts.service.state,
-            &agent_instances,
-            &agent_ids,
-        )?;
-
-        let service = &mut ctx.accounts.service;
-        let mut remaining_accounts = ctx.remaining_accounts.iter();
-
-        // Extract & Validate Agent Params
-        let (agent_params, total_bond) =
-            ServiceRegistry::load_and_validate_agent_params(&mut remaining_accounts, &agent_ids)?;
-
-        // Transfer Bond
-        ServiceRegistry::transfer_bond(
+        ServiceRegistry::register_agent_instances(
+            ctx.accounts,
+            ctx.remaining_accounts,
             ctx.program_id,
-            &ctx.accounts.user,
-            &ctx.accounts.system_program,
-            registry,
-            &ctx.accounts.registry_wallet,
-            total_bond,
-        )?;
-
-        // Validate Operator
-        ServiceRegistry::validate_operator(*ctx.program_id, operator, &mut remaining_accounts)?;
-
-        let program_id = ctx.program_id;
-        let user_account_info = ctx.accounts.user.to_account_info();
-        let agent_instances_account_info = next_account_info(&mut remaining_accounts)?;
-        let system_program_account_info = ctx.accounts.system_program.to_account_info();
-        let operator_agent_instance_index = &mut ctx.accounts.operator_agent_instance_index;
-
-        for (i, agent_id) in agent_ids.iter().enumerate() {
-            let agent_instance = agent_instances[i];
-            let agent_param = &agent_params[i];
+            operator,
+            operator,
+            agent_instances,
+            agent_ids,
+        )
+    }
 
-            ServiceRegistry::register_single_instance(
-                program_id,
-                service,
-                *agent_id,
-                agent_instance,
-                agent_param,
-                operator,
-                &user_account_info,
-                agent_instances_account_info,
-                &system_program_account_info,
-                operator_agent_instance_index,
-                &mut remaining_accounts,
-            )?;
-        }
+    /// Registers `agent_instances` of `operator` on its behalf, the manager paying the
+    /// bond. An earlier instruction of the transaction must be the Ed25519 program
+    /// checking the operator signature of `signatures::registration_message` at the
+    /// current nonce of the operator.
+    pub fn register_agents_with_signature<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RegisterAgentInstancesWithSignature<'info>>,
+        operator: Pubkey,
+        agent_instances: Vec<Pubkey>,
+        agent_ids: Vec<u32>,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.operator.key(),
+            operator,
+            ErrorCode::WrongOperator
+        );
 
-        // Finalize service state if full
-        if service.num_agent_instances == service.max_num_agent_instances {
-            service.state = ServiceState::FinishedRegistration;
-        }
+        let registration = &mut ctx.accounts.registration;
+        let operator_nonce = &mut ctx.accounts.operator_nonce;
 
-        // Extract the operator_bond account from remaining_accounts
-        let operator_bond_account_info = next_account_info(&mut remaining_accounts)?;
+        let message = signatures::registration_message(
+            &registration.registry.key(),
+            registration.service.service_id,
+            &agent_instances,
+            &agent_ids,
+            operator_nonce.nonce,
+        );
+        require!(
+            signatures::is_signed(&registration.instructions, &operator, &message)?,
+            ErrorCode::InvalidOperatorSignature
+        );
+        operator_nonce.nonce = operator_nonce
+            .nonce
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
 
-        // Update operator bond account
-        ServiceRegistry::update_operator_bond(
-            program_id,
+        // The relayer must hold the manager role
+        let manager = registration.user.key();
+        ctx.accounts
+            .roles
+            .check_role(Role::ServiceManager, &manager)?;
+        ServiceRegistry::register_agent_instances(
+            registration,
+            ctx.remaining_accounts,
+            ctx.program_id,
+            manager,
             operator,
-            service.service_id,
-            total_bond,
-            &ctx.accounts.user,
-            operator_bond_account_info,
-            &ctx.accounts.system_program,
-        )?;
-
-        Ok(())
+            agent_instances,
+            agent_ids,
+        )
     }
 
-    pub fn terminate<'info>(
-        ctx: Context<'_, '_, 'info, 'info, TerminateService<'info>>,
-        service_id: u128,
-    ) -> Result<()> {
+    pub fn terminate(ctx: Context<TerminateService>, service_id: u128) -> Result<()> {
         let registry = &mut ctx.accounts.registry;
         let service = &mut ctx.accounts.service;
         let service_owner = &ctx.accounts.service_owner;
-        let service_agent_ids_index = &mut ctx.accounts.service_agent_ids_index;
+        let service_agent_ids_index = &ctx.accounts.service_agent_ids_index;
 
         // Reentrancy guard
         if registry.locked {
@@ -902,14 +1945,17 @@ pub mod registry {
         registry.locked = true;
 
         // Check for the manager privilege for service management
-        if ctx.accounts.user.key() != registry.manager {
-            return Err(ProgramError::InvalidAccountOwner.into());
-        }
+        ctx.accounts
+            .roles
+            .check_role(Role::ServiceManager, &ctx.accounts.user.key())?;
 
-        // Validate that the provided service owner is the actual owner of the service
-        if service.service_owner != service_owner.key() {
-            return Err(ProgramError::InvalidArgument.into());
-        }
+        // Validate that the provided service owner holds the service token
+        ServiceRegistry::check_service_holder(
+            ctx.program_id,
+            service,
+            &ctx.accounts.service_token,
+            service_owner.key(),
+        )?;
 
         require_eq!(service_id, service.service_id);
 
@@ -927,107 +1973,151 @@ pub mod registry {
             service.state = ServiceState::PreRegistration;
         }
 
-        let mut remaining_accounts_iter = ctx.remaining_accounts.iter();
-
-        let (agent_instances_pda, _) =
-            agent_instances_index_pda(service.service_id, ctx.program_id);
+        // The agent PDAs of the service are closed in batches by `cleanup_service`
+        service.cleanup_pending = !service_agent_ids_index.agent_ids.is_empty();
+        service.cleanup_cursor = 0;
 
-        let agent_instances_info = next_account_info(&mut remaining_accounts_iter)?;
-        require!(
-            agent_instances_info.key() == agent_instances_pda,
-            ErrorCode::InvalidPda
-        );
-
-        // Cleanup all agent instance PDAs
-        for param in &service_agent_ids_index.agent_ids {
-            // Close the slot_counter PDA
-            let (slot_counter_pda, _) =
-                service_agent_slot_counter_pda(service.service_id, param.agent_id, ctx.program_id);
-
-            let slot_counter_info = next_account_info(&mut remaining_accounts_iter)?;
-            require!(
-                slot_counter_info.key() == slot_counter_pda,
-                ErrorCode::InvalidPda
-            );
-            ServiceRegistry::close_account(slot_counter_info, &ctx.accounts.user)?;
-
-            // Now close all service_agent_instance PDAs for each agent_instance
-            let agent_instances_account_index: Account<ServiceAgentInstancesIndex> =
-                Account::try_from(agent_instances_info)?;
+        // Refund security deposit, unless payouts are paused
+        let refund = service.security_deposit;
 
-            for agent_instance in agent_instances_account_index.service_agent_instances.iter() {
-                let (service_agent_instance_pda, _) = service_agent_instance_pda(
-                    service.service_id,
-                    param.agent_id,
-                    agent_instance,
-                    ctx.program_id,
-                );
+        if refund > 0 {
+            registry.check_refunds_not_paused()?;
+            service.security_deposit = 0;
 
-                let service_agent_instance_info = next_account_info(&mut remaining_accounts_iter)?;
-                require!(
-                    service_agent_instance_info.key() == service_agent_instance_pda,
-                    ErrorCode::InvalidPda
-                );
-                ServiceRegistry::close_account(service_agent_instance_info, &ctx.accounts.user)?;
+            let bond_token = &ctx.accounts.bond_token;
+            let service_escrow = &ctx.accounts.service_escrow;
+            if bond_token.token_bonded(service, ctx.program_id, BondTransfer::Counterparty)? {
+                bond_token.refund(
+                    service,
+                    service_escrow,
+                    &ctx.accounts.service_owner.key(),
+                    refund,
+                )?;
+            } else {
+                ServiceRegistry::release_escrow(
+                    &service_escrow.to_account_info(),
+                    &ctx.accounts.service_owner,
+                    refund,
+                )?;
             }
+
+            emit!(Refunded {
+                service_owner: ctx.accounts.service_owner.key(),
+                amount: refund,
+            });
         }
 
-        // Close the agent_instances PDA
-        ServiceRegistry::close_account(agent_instances_info, &ctx.accounts.user)?;
+        emit!(ServiceTerminated { service_id });
+        registry.locked = false;
 
-        // Refund security deposit
-        let refund = service.security_deposit;
+        Ok(())
+    }
 
-        let (registry_wallet_pda, registry_wallet_bump) =
-            registry_wallet_pda(&registry.key(), ctx.program_id);
+    /// Closes the next batch of agent PDAs of a terminated service once every operator
+    /// has unbonded, passed as remaining accounts from `ServiceAccount::cleanup_cursor` on: the slot counter of each agent
+    /// id, then the account of each agent instance, in index order. The last batch
+    /// closes the service indexes, after which agent ids can be registered again.
+    pub fn cleanup_service<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CleanupService<'info>>,
+        service_id: u128,
+    ) -> Result<()> {
+        let service = &mut ctx.accounts.service;
 
-        require_eq!(
-            registry_wallet_pda,
-            ctx.accounts.registry_wallet.key(),
-            ErrorCode::WrongRegistryWallet
-        );
+        // Check for the manager privilege for service management
+        ctx.accounts
+            .roles
+            .check_role(Role::ServiceManager, &ctx.accounts.user.key())?;
 
-        require_eq!(
-            registry_wallet_pda,
-            registry.wallet_key,
-            ErrorCode::WrongRegistryWallet
-        );
+        // Validate that the provided service owner holds the service token
+        ServiceRegistry::check_service_holder(
+            ctx.program_id,
+            service,
+            &ctx.accounts.service_token,
+            ctx.accounts.service_owner.key(),
+        )?;
 
-        require_eq!(
-            registry_wallet_bump,
-            registry.wallet_bump,
-            ErrorCode::WrongRegistryWallet
+        require_eq!(service_id, service.service_id);
+        require!(service.cleanup_pending, ErrorCode::NoServiceCleanup);
+        // Unbonding reads the agent bonds and instance accounts closed here
+        require!(
+            service.state != ServiceState::TerminatedBonded,
+            ErrorCode::WrongServiceState
         );
 
-        if refund > 0 {
-            service.security_deposit = 0;
+        // The index only exists once an agent instance has been registered
+        let agent_instances_info = ctx.accounts.agent_instances_index.to_account_info();
+        let agent_instances: Vec<Pubkey> = if agent_instances_info.owner == ctx.program_id {
+            ServiceAgentInstancesIndex::try_deserialize(
+                &mut &agent_instances_info.try_borrow_data()?[..],
+            )?
+            .service_agent_instances
+        } else {
+            Vec::new()
+        };
 
-            let wallet_balance = ctx.accounts.registry_wallet.lamports();
-            require!(wallet_balance >= refund, ErrorCode::InsufficientFunds);
+        let agent_ids = &ctx.accounts.service_agent_ids_index.agent_ids;
+        let total = agent_ids.len() + agent_instances.len();
+        let mut cursor = service.cleanup_cursor as usize;
 
-            **ctx.accounts.registry_wallet.try_borrow_mut_lamports()? -= refund;
-            **ctx.accounts.service_owner.try_borrow_mut_lamports()? += refund;
+        for account_info in ctx.remaining_accounts {
+            require!(cursor < total, ErrorCode::WrongArrayLength);
 
-            emit!(Refunded {
-                service_owner: ctx.accounts.service_owner.key(),
-                amount: refund,
-            });
+            let expected_pda = if cursor < agent_ids.len() {
+                service_agent_slot_counter_pda(
+                    service.registry_seed(),
+                    service_id,
+                    agent_ids[cursor].agent_id,
+                    ctx.program_id,
+                )
+                .0
+            } else {
+                // The instance account records the agent id it is registered for
+                let agent_instance = &agent_instances[cursor - agent_ids.len()];
+                let instance_account =
+                    Account::<ServiceAgentInstanceAccount>::try_from(account_info)?;
+                service_agent_instance_pda(
+                    service.registry_seed(),
+                    service_id,
+                    instance_account.agent_id,
+                    agent_instance,
+                    ctx.program_id,
+                )
+                .0
+            };
+            require_keys_eq!(account_info.key(), expected_pda, ErrorCode::InvalidPda);
+
+            ServiceRegistry::close_account(account_info, &ctx.accounts.user)?;
+            cursor += 1;
         }
 
-        service_agent_ids_index.agent_ids.clear();
-        if service_agent_ids_index.agent_ids.is_empty() {
+        if cursor == total {
+            ServiceRegistry::close_account(&agent_instances_info, &ctx.accounts.user)?;
+            ctx.accounts.service_agent_ids_index.agent_ids.clear();
             ServiceRegistry::close_account(
-                &service_agent_ids_index.to_account_info(),
+                &ctx.accounts.service_agent_ids_index.to_account_info(),
                 &ctx.accounts.user,
             )?;
+            service.cleanup_pending = false;
+            service.cleanup_cursor = 0;
+        } else {
+            service.cleanup_cursor = cursor as u32;
         }
 
-        emit!(ServiceTerminated { service_id });
-        registry.locked = false;
+        emit!(ServiceCleanupProgress {
+            service_id,
+            cursor: cursor as u32,
+            total: total as u32,
+        });
 
         Ok(())
     }
 
+    /// Unbonds the agent instances at the front of the operator index, with two remaining
+    /// accounts each: its `OperatorAgentInstanceAccount`, then the
+    /// `ServiceAgentInstanceAccount` that one references. Each instance refunds the bond of
+    /// its agent id, out of what slashing left of the operator bond, and the operator bond
+    /// closes with the last instance. Refunds add up in the pending withdrawal of the
+    /// operator, claimable once the registry unbonding delay passed since the last batch.
     pub fn unbond<'info>(
         ctx: Context<'_, '_, 'info, 'info, UnbondOperator<'info>>,
         service_id: u128,
@@ -1046,9 +2136,9 @@ pub mod registry {
         require_eq!(service_id, service.service_id);
 
         // Check for the manager privilege for a service management
-        if ctx.accounts.user.key() != registry.manager {
-            return Err(ProgramError::InvalidAccountOwner.into());
-        }
+        ctx.accounts
+            .roles
+            .check_role(Role::ServiceManager, &ctx.accounts.user.key())?;
 
         // Check for the non-empty service owner address
         if operator.key() == Pubkey::default() {
@@ -1067,85 +2157,107 @@ pub mod registry {
         let num_instances = operator_agent_instance_index.operator_agent_instances.len();
         require!(num_instances > 0, ErrorCode::OperatorHasNoInstances);
 
-        // Update service state
-        service.num_agent_instances = service
-            .num_agent_instances
-            .saturating_sub(num_instances as u32);
-        if service.num_agent_instances == 0 {
-            service.state = ServiceState::PreRegistration;
-        }
-
-        // Refund logic
-        let (operator_bond_pda, _operator_bond_bump) =
-            operator_bond_pda(service_id, &operator.key(), ctx.program_id);
-
+        // Two accounts per unbonded instance, at most every instance left
+        let batch = ctx.remaining_accounts.len() / 2;
         require!(
-            operator_bond_pda == operator_bond.key(),
-            ErrorCode::InvalidPda
+            batch > 0 && ctx.remaining_accounts.len().is_multiple_of(2) && batch <= num_instances,
+            ErrorCode::WrongArrayLength
         );
 
-        let refund: u64 = operator_bond.bond;
+        let agent_ids = &ctx.accounts.service_agent_ids_index.agent_ids;
+        let mut amount: u64 = 0;
 
-        let (registry_wallet_pda, registry_wallet_bump) =
-            registry_wallet_pda(&registry.key(), ctx.program_id);
-
-        require_eq!(
-            registry_wallet_pda,
-            ctx.accounts.registry_wallet.key(),
-            ErrorCode::WrongRegistryWallet
-        );
+        for (accounts, operator_agent_instance_pda) in ctx.remaining_accounts.chunks(2).zip(
+            operator_agent_instance_index
+                .operator_agent_instances
+                .iter(),
+        ) {
+            let (operator_agent_instance_info, service_agent_instance_info) =
+                (&accounts[0], &accounts[1]);
 
-        require_eq!(
-            registry_wallet_pda,
-            registry.wallet_key,
-            ErrorCode::WrongRegistryWallet
-        );
+            require!(
+                operator_agent_instance_pda == &operator_agent_instance_info.key(),
+                ErrorCode::InvalidPda
+            );
 
-        require_eq!(
-            registry_wallet_bump,
-            registry.wallet_bump,
-            ErrorCode::WrongRegistryWallet
-        );
+            let operator_agent_instance = OperatorAgentInstanceAccount::try_deserialize(
+                &mut &operator_agent_instance_info.try_borrow_data()?[..],
+            )?;
+            require!(
+                operator_agent_instance.operator == operator.key(),
+                ErrorCode::WrongOperator
+            );
+            require!(
+                operator_agent_instance.service_agent_instance == service_agent_instance_info.key(),
+                ErrorCode::InvalidPda
+            );
+            let service_agent_instance = ServiceAgentInstanceAccount::try_deserialize(
+                &mut &service_agent_instance_info.try_borrow_data()?[..],
+            )?;
 
-        // Only proceed if there's something to refund
-        if refund > 0 {
-            let wallet_balance = ctx.accounts.registry_wallet.lamports();
-            require!(wallet_balance >= refund, ErrorCode::InsufficientFunds);
+            let bond = agent_ids
+                .iter()
+                .find(|param| param.agent_id == service_agent_instance.agent_id)
+                .ok_or(ErrorCode::AgentNotInService)?
+                .bond;
 
-            operator_bond.bond = 0; // wipe the data
+            // Slashing takes from the operator bond as a whole, so later refunds come short
+            let instance_amount = std::cmp::min(bond, operator_bond.bond);
+            operator_bond.bond -= instance_amount;
+            amount = amount
+                .checked_add(instance_amount)
+                .ok_or(ErrorCode::Overflow)?;
 
-            // Transfer lamports back to the operator
-            **operator.to_account_info().try_borrow_mut_lamports()? += refund;
-            **ctx.accounts.registry_wallet.try_borrow_mut_lamports()? -= refund;
+            ServiceRegistry::close_account(operator_agent_instance_info, &ctx.accounts.user)?;
 
-            // msg!("Refunded {} lamports to operator", refund);
+            emit!(AgentInstanceUnbonded {
+                operator: operator.key(),
+                service_id,
+                agent_instance: service_agent_instance.agent_instance,
+                amount: instance_amount,
+            });
         }
 
-        ServiceRegistry::close_account(&operator_bond.to_account_info(), &ctx.accounts.user)?;
-
-        for operator_agent_instance_pda in operator_agent_instance_index
+        operator_agent_instance_index
             .operator_agent_instances
-            .iter()
-        {
-            let operator_agent_instance_info =
-                next_account_info(&mut ctx.remaining_accounts.iter())?;
-
-            require!(
-                operator_agent_instance_pda == &operator_agent_instance_info.key(),
-                ErrorCode::InvalidPda
-            );
+            .drain(..batch);
 
-            ServiceRegistry::close_account(operator_agent_instance_info, &ctx.accounts.user)?;
+        // Update service state
+        service.num_agent_instances = service.num_agent_instances.saturating_sub(batch as u32);
+        if service.num_agent_instances == 0 {
+            service.state = ServiceState::PreRegistration;
         }
 
-        operator_agent_instance_index
+        // The last instance takes what is left of the operator bond along
+        let unbonded = operator_agent_instance_index
             .operator_agent_instances
-            .clear();
+            .is_empty();
+        if unbonded {
+            amount = amount
+                .checked_add(operator_bond.bond)
+                .ok_or(ErrorCode::Overflow)?;
+            operator_bond.bond = 0;
+        }
 
-        if operator_agent_instance_index
-            .operator_agent_instances
-            .is_empty()
-        {
+        // Refunds wait out the unbonding delay, slashable until claimed
+        let unlock_time = Clock::get()?
+            .unix_timestamp
+            .checked_add(registry.unbonding_delay)
+            .ok_or(ErrorCode::Overflow)?;
+        let pending_withdrawal = &mut ctx.accounts.pending_withdrawal;
+        if pending_withdrawal.payer == Pubkey::default() {
+            pending_withdrawal.payer = ctx.accounts.user.key();
+        }
+        pending_withdrawal.service_id = service_id;
+        pending_withdrawal.operator = operator.key();
+        pending_withdrawal.amount = pending_withdrawal
+            .amount
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        pending_withdrawal.unlock_time = unlock_time;
+
+        if unbonded {
+            ServiceRegistry::close_account(&operator_bond.to_account_info(), &ctx.accounts.user)?;
             ServiceRegistry::close_account(
                 &operator_agent_instance_index.to_account_info(),
                 &ctx.accounts.user,
@@ -1156,35 +2268,93 @@ pub mod registry {
         emit!(OperatorUnbonded {
             operator: operator.key(),
             service_id,
-            refund,
+            amount,
+        });
+        emit!(WithdrawalPending {
+            operator: operator.key(),
+            service_id,
+            amount: pending_withdrawal.amount,
+            unlock_time,
         });
 
         registry.locked = false;
         Ok(())
     }
 
-    pub fn change_multisig_permission(
-        ctx: Context<ChangeMultisigPermission>,
-        multisig: Pubkey,
-        permission: bool,
-    ) -> Result<()> {
-        let registry = &mut ctx.accounts.registry;
+    /// Pays out the pending withdrawal of an operator once its unbonding delay passed.
+    /// The operator claims it itself, or the manager on its behalf.
+    pub fn claim_unbonded(ctx: Context<ClaimUnbonded>, service_id: u128) -> Result<()> {
+        let registry = &ctx.accounts.registry;
+        let operator = &ctx.accounts.operator;
+        let pending_withdrawal = &ctx.accounts.pending_withdrawal;
 
-        if ctx.accounts.user.key() != registry.owner {
-            return Err(ProgramError::IllegalOwner.into());
-        }
+        registry.check_refunds_not_paused()?;
 
-        if multisig == Pubkey::default() {
-            return Err(ProgramError::InvalidArgument.into());
+        // Check for the operator itself, or the manager privilege for a service management
+        let user = ctx.accounts.user.key();
+        if user != operator.key() {
+            ctx.accounts.roles.check_role(Role::ServiceManager, &user)?;
         }
 
-        let registry_multisig = &mut ctx.accounts.registry_multisig;
+        require!(
+            Clock::get()?.unix_timestamp >= pending_withdrawal.unlock_time,
+            ErrorCode::UnbondingDelayNotExpired
+        );
 
-        if permission && registry_multisig.authorized_multisigs.len() >= MAX_MULTISIGS {
-            return Err(ErrorCode::MaxMultiSigsReached.into());
+        let amount = pending_withdrawal.amount;
+        if amount > 0 {
+            let bond_token = &ctx.accounts.bond_token;
+            let service_escrow = &ctx.accounts.service_escrow;
+            if bond_token.token_bonded(
+                &ctx.accounts.service,
+                ctx.program_id,
+                BondTransfer::Counterparty,
+            )? {
+                bond_token.refund(
+                    &ctx.accounts.service,
+                    service_escrow,
+                    &operator.key(),
+                    amount,
+                )?;
+            } else {
+                // Transfer lamports back to the operator
+                ServiceRegistry::release_escrow(
+                    &service_escrow.to_account_info(),
+                    operator,
+                    amount,
+                )?;
+            }
         }
 
-        if permission {
+        emit!(UnbondedClaimed {
+            operator: operator.key(),
+            service_id,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn change_multisig_permission(
+        ctx: Context<ChangeMultisigPermission>,
+        multisig: Pubkey,
+        permission: bool,
+    ) -> Result<()> {
+        ctx.accounts
+            .roles
+            .check_role(Role::MultisigWhitelister, &ctx.accounts.user.key())?;
+
+        if multisig == Pubkey::default() {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+
+        let registry_multisig = &mut ctx.accounts.registry_multisig;
+
+        if permission && registry_multisig.authorized_multisigs.len() >= MAX_MULTISIGS {
+            return Err(ErrorCode::MaxMultiSigsReached.into());
+        }
+
+        if permission {
             if !registry_multisig.authorized_multisigs.contains(&multisig) {
                 registry_multisig.authorized_multisigs.push(multisig);
             }
@@ -1197,6 +2367,395 @@ pub mod registry {
         Ok(())
     }
 
+    pub fn propose_multisig_transaction(
+        ctx: Context<ProposeMultisigTransaction>,
+        target_program: Pubkey,
+        accounts: Vec<ProposalAccountMeta>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let multisig = &mut ctx.accounts.multisig;
+        let proposer = ctx.accounts.proposer.key();
+
+        // Only agent instances of the multisig can propose
+        require!(multisig.is_owner(&proposer), ErrorCode::NotMultisigOwner);
+
+        let proposal_id = multisig.proposal_count;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.multisig = multisig.key();
+        proposal.proposal_id = proposal_id;
+        proposal.proposer = proposer;
+        proposal.program_id = target_program;
+        proposal.accounts = accounts;
+        proposal.data = data;
+        // Proposing counts as the first approval
+        proposal.approvals = vec![proposer];
+        proposal.executed = false;
+
+        multisig.proposal_count = proposal_id.checked_add(1).ok_or(ErrorCode::Overflow)?;
+
+        emit!(MultisigProposalCreated {
+            multisig: multisig.key(),
+            proposal_id,
+            proposer,
+            program_id: target_program,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_multisig_transaction(ctx: Context<ApproveMultisigTransaction>) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+        let proposal = &mut ctx.accounts.proposal;
+        let approver = ctx.accounts.approver.key();
+
+        require!(multisig.is_owner(&approver), ErrorCode::NotMultisigOwner);
+        require!(!proposal.executed, ErrorCode::ProposalAlreadyExecuted);
+        require!(
+            !proposal.approvals.contains(&approver),
+            ErrorCode::ProposalAlreadyApproved
+        );
+
+        proposal.approvals.push(approver);
+
+        emit!(MultisigProposalApproved {
+            multisig: multisig.key(),
+            proposal_id: proposal.proposal_id,
+            approver,
+            approvals: proposal.approvals.len() as u32,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_multisig_transaction<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteMultisigTransaction<'info>>,
+    ) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+        let proposal = &mut ctx.accounts.proposal;
+
+        require!(!proposal.executed, ErrorCode::ProposalAlreadyExecuted);
+        require!(
+            proposal.approvals.len() >= multisig.threshold as usize,
+            ErrorCode::NotEnoughApprovals
+        );
+
+        let (multisig_pda, multisig_bump) = multisig_pda(&multisig.agent_instances, ctx.program_id);
+        require_keys_eq!(multisig_pda, multisig.key(), ErrorCode::InvalidPda);
+
+        // Remaining accounts: the target program, then every proposal account in order
+        let mut remaining_accounts = ctx.remaining_accounts.iter();
+
+        let target_program_info = next_account_info(&mut remaining_accounts)?;
+        require_keys_eq!(
+            target_program_info.key(),
+            proposal.program_id,
+            ErrorCode::WrongProposalAccounts
+        );
+
+        let mut account_infos = vec![target_program_info.clone()];
+        for meta in &proposal.accounts {
+            let account_info = next_account_info(&mut remaining_accounts)?;
+            require_keys_eq!(
+                account_info.key(),
+                meta.pubkey,
+                ErrorCode::WrongProposalAccounts
+            );
+            account_infos.push(account_info.clone());
+        }
+
+        // Mark as executed before the CPI so the proposal cannot be replayed
+        proposal.executed = true;
+
+        let ix = Instruction {
+            program_id: proposal.program_id,
+            accounts: proposal
+                .accounts
+                .iter()
+                .map(|meta| AccountMeta {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: proposal.data.clone(),
+        };
+
+        let seed = multisig_seed(&multisig.agent_instances);
+        invoke_signed(
+            &ix,
+            &account_infos,
+            &[&[b"multisig", seed.as_ref(), &[multisig_bump]]],
+        )?;
+
+        emit!(MultisigProposalExecuted {
+            multisig: multisig.key(),
+            proposal_id: proposal.proposal_id,
+        });
+
+        Ok(())
+    }
+
+    /// Grows a built-in multisig deployed before proposals to the current layout, with no
+    /// proposal made yet. Anyone can call, paying the extra rent; multisigs already in
+    /// the current layout are left untouched.
+    pub fn migrate_multisig(ctx: Context<MigrateMultisig>) -> Result<()> {
+        let multisig_info = ctx.accounts.multisig.to_account_info();
+
+        let legacy = {
+            let data = multisig_info.try_borrow_data()?;
+            require!(
+                data.starts_with(MultisigAccount::DISCRIMINATOR),
+                ErrorCode::WrongMultisigAccount
+            );
+            LegacyMultisigAccount::deserialize(&mut &data[8..])?
+        };
+
+        let space = MultisigAccount::size(legacy.agent_instances.len(), legacy.data.len());
+        if multisig_info.data_len() >= space {
+            return Ok(());
+        }
+
+        // The proposal count appended to the legacy fields reads zero
+        ServiceRegistry::grow_account(
+            &multisig_info,
+            space,
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
+
+        emit!(MultisigMigrated {
+            multisig: multisig_info.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Creates the timelock of the registry, delaying the operations it executes by
+    /// `delay` seconds. Only admins can call.
+    ///
+    /// The timelock authority becomes the only holder of the `Role::TIMELOCKED` roles
+    /// and `Admin` administers every role again, so that neither admin actions nor role
+    /// changes take effect without going through the queue.
+    pub fn initialize_timelock(ctx: Context<InitializeTimelock>, delay: i64) -> Result<()> {
+        let roles = &mut ctx.accounts.roles;
+        let sender = ctx.accounts.user.key();
+
+        roles.check_role(Role::Admin, &sender)?;
+
+        if delay < 0 {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+
+        let registry = ctx.accounts.registry.key();
+        let (authority, authority_bump) = timelock_authority_pda(&registry, ctx.program_id);
+
+        for role in Role::ALL {
+            let previous_admin_role = roles.admin_role(role);
+            if previous_admin_role != Role::Admin {
+                roles.roles[role as usize].admin_role = Role::Admin;
+                emit!(RoleAdminChanged {
+                    role,
+                    previous_admin_role,
+                    new_admin_role: Role::Admin,
+                });
+            }
+        }
+        for role in Role::TIMELOCKED {
+            let members = std::mem::take(&mut roles.roles[role as usize].members);
+            for account in members.into_iter().filter(|member| *member != authority) {
+                emit!(RoleRevoked {
+                    role,
+                    account,
+                    sender
+                });
+            }
+            roles.grant(role, authority)?;
+            emit!(RoleGranted {
+                role,
+                account: authority,
+                sender
+            });
+        }
+
+        let timelock = &mut ctx.accounts.timelock;
+        timelock.registry = registry;
+        timelock.delay = delay;
+        timelock.operation_count = 0;
+        timelock.authority_bump = authority_bump;
+
+        emit!(TimelockDelayUpdated {
+            timelock: timelock.key(),
+            previous_delay: 0,
+            new_delay: delay,
+        });
+
+        Ok(())
+    }
+
+    /// Sets the delay of the timelock. Only the timelock authority can call, through
+    /// an operation executed after the current delay.
+    pub fn set_timelock_delay(ctx: Context<SetTimelockDelay>, new_delay: i64) -> Result<()> {
+        let timelock = &mut ctx.accounts.timelock;
+
+        let (authority, _) = timelock_authority_pda(&timelock.registry, ctx.program_id);
+        require_keys_eq!(
+            ctx.accounts.user.key(),
+            authority,
+            ErrorCode::NotTimelockAuthority
+        );
+
+        if new_delay < 0 {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+
+        let previous_delay = timelock.delay;
+        timelock.delay = new_delay;
+
+        emit!(TimelockDelayUpdated {
+            timelock: timelock.key(),
+            previous_delay,
+            new_delay,
+        });
+
+        Ok(())
+    }
+
+    /// Queues the registry instruction with `accounts` and `data`, signed by the timelock
+    /// authority once executed. Only proposers can call.
+    pub fn queue_timelock_operation(
+        ctx: Context<QueueTimelockOperation>,
+        accounts: Vec<ProposalAccountMeta>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let timelock = &mut ctx.accounts.timelock;
+        let proposer = ctx.accounts.user.key();
+
+        ctx.accounts.roles.check_role(Role::Proposer, &proposer)?;
+
+        let operation_id = timelock.operation_count;
+        let eta = Clock::get()?
+            .unix_timestamp
+            .checked_add(timelock.delay)
+            .ok_or(ErrorCode::Overflow)?;
+
+        let operation = &mut ctx.accounts.operation;
+        operation.timelock = timelock.key();
+        operation.operation_id = operation_id;
+        operation.proposer = proposer;
+        operation.accounts = accounts;
+        operation.data = data;
+        operation.eta = eta;
+        operation.executed = false;
+        operation.cancelled = false;
+
+        timelock.operation_count = operation_id.checked_add(1).ok_or(ErrorCode::Overflow)?;
+
+        emit!(TimelockOperationQueued {
+            timelock: timelock.key(),
+            operation_id,
+            proposer,
+            eta,
+        });
+
+        Ok(())
+    }
+
+    /// Executes a queued operation once its ETA is reached. Anyone can call.
+    pub fn execute_timelock_operation<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteTimelockOperation<'info>>,
+    ) -> Result<()> {
+        let timelock = &ctx.accounts.timelock;
+        let operation = &mut ctx.accounts.operation;
+
+        require!(
+            !operation.executed && !operation.cancelled,
+            ErrorCode::TimelockOperationDone
+        );
+        require!(
+            Clock::get()?.unix_timestamp >= operation.eta,
+            ErrorCode::TimelockNotReady
+        );
+
+        // Remaining accounts: the registry program, then every operation account in order
+        let mut remaining_accounts = ctx.remaining_accounts.iter();
+
+        let program_info = next_account_info(&mut remaining_accounts)?;
+        require_keys_eq!(
+            program_info.key(),
+            *ctx.program_id,
+            ErrorCode::WrongProposalAccounts
+        );
+
+        let mut account_infos = vec![program_info.clone()];
+        for meta in &operation.accounts {
+            let account_info = next_account_info(&mut remaining_accounts)?;
+            require_keys_eq!(
+                account_info.key(),
+                meta.pubkey,
+                ErrorCode::WrongProposalAccounts
+            );
+            account_infos.push(account_info.clone());
+        }
+
+        // Mark as executed before the CPI so the operation cannot be replayed
+        operation.executed = true;
+
+        let ix = Instruction {
+            program_id: *ctx.program_id,
+            accounts: operation
+                .accounts
+                .iter()
+                .map(|meta| AccountMeta {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: operation.data.clone(),
+        };
+
+        invoke_signed(
+            &ix,
+            &account_infos,
+            &[&[
+                b"timelock_authority",
+                timelock.registry.as_ref(),
+                &[timelock.authority_bump],
+            ]],
+        )?;
+
+        emit!(TimelockOperationExecuted {
+            timelock: timelock.key(),
+            operation_id: operation.operation_id,
+        });
+
+        Ok(())
+    }
+
+    /// Cancels a queued operation. Only guardians can call.
+    pub fn cancel_timelock_operation(ctx: Context<CancelTimelockOperation>) -> Result<()> {
+        let operation = &mut ctx.accounts.operation;
+        let guardian = ctx.accounts.user.key();
+
+        ctx.accounts.roles.check_role(Role::Guardian, &guardian)?;
+
+        require!(
+            !operation.executed && !operation.cancelled,
+            ErrorCode::TimelockOperationDone
+        );
+
+        operation.cancelled = true;
+
+        emit!(TimelockOperationCancelled {
+            timelock: operation.timelock,
+            operation_id: operation.operation_id,
+            guardian,
+        });
+
+        Ok(())
+    }
+
     pub fn dummy_include_agent_param_account(
         _ctx: Context<DummyContextForAgentParam>,
     ) -> Result<()> {
@@ -1294,14 +2853,14 @@ impl ServiceRegistry {
         Ok(())
     }
 
-    fn check_access_and_state<'info>(
-        ctx: &Context<'_, '_, 'info, 'info, RegisterAgentInstances<'info>>,
-        registry: &ServiceRegistry,
+    fn check_access_and_state(
+        user: &Pubkey,
+        authority: &Pubkey,
         service_state: &ServiceState,
         agent_instances: &[Pubkey],
         agent_ids: &[u32],
     ) -> Result<()> {
-        if ctx.accounts.user.key() != registry.manager {
+        if user != authority {
             return Err(ProgramError::InvalidAccountOwner.into());
         }
 
@@ -1318,6 +2877,158 @@ impl ServiceRegistry {
         Ok(())
     }
 
+    /// Body of `register_agents`, with `authority` the signer allowed to register
+    /// `operator` and pay its bond.
+    fn register_agent_instances<'info>(
+        accounts: &mut RegisterAgentInstances<'info>,
+        remaining_accounts: &'info [AccountInfo<'info>],
+        program_id: &Pubkey,
+        authority: Pubkey,
+        operator: Pubkey,
+        agent_instances: Vec<Pubkey>,
+        agent_ids: Vec<u32>,
+    ) -> Result<()> {
+        let registry = &accounts.registry;
+
+        registry.check_not_paused(PauseGroup::Registration)?;
+
+        // Permissions & State Checks
+        Self::check_access_and_state(
+            &accounts.user.key(),
+            &authority,
+            &accounts.service.state,
+            &agent_instances,
+            &agent_ids,
+        )?;
+
+        Self::check_operator_whitelist(program_id, &accounts.operator_whitelist, &operator)?;
+
+        if accounts.service.require_instance_proofs {
+            Self::check_instance_proofs(
+                &accounts.instructions,
+                &accounts.registry.key(),
+                accounts.service.service_id,
+                &operator,
+                &agent_instances,
+            )?;
+        }
+
+        let service = &mut accounts.service;
+        let mut remaining_accounts = remaining_accounts.iter();
+
+        // Extract & Validate Agent Params
+        let (agent_params, total_bond) =
+            Self::load_and_validate_agent_params(&mut remaining_accounts, &agent_ids)?;
+
+        // Transfer Bond
+        let bond_token = &accounts.bond_token;
+        if bond_token.token_bonded(service, program_id, BondTransfer::Counterparty)? {
+            bond_token.deposit(&accounts.user.to_account_info(), total_bond)?;
+        } else {
+            Self::transfer_bond(
+                &accounts.user,
+                &accounts.system_program,
+                &accounts.service_escrow.to_account_info(),
+                total_bond,
+            )?;
+        }
+
+        // Validate Operator
+        Self::validate_operator(*program_id, operator, &mut remaining_accounts)?;
+
+        let user_account_info = accounts.user.to_account_info();
+        let agent_instances_account_info = next_account_info(&mut remaining_accounts)?;
+        let system_program_account_info = accounts.system_program.to_account_info();
+        let operator_agent_instance_index = &mut accounts.operator_agent_instance_index;
+
+        for (i, agent_id) in agent_ids.iter().enumerate() {
+            let agent_instance = agent_instances[i];
+            let agent_param = &agent_params[i];
+
+            Self::register_single_instance(
+                program_id,
+                service,
+                *agent_id,
+                agent_instance,
+                agent_param,
+                operator,
+                &user_account_info,
+                agent_instances_account_info,
+                &system_program_account_info,
+                operator_agent_instance_index,
+                &mut remaining_accounts,
+            )?;
+        }
+
+        // Finalize service state if full
+        if service.num_agent_instances == service.max_num_agent_instances {
+            service.state = ServiceState::FinishedRegistration;
+        }
+
+        // Extract the operator_bond account from remaining_accounts
+        let operator_bond_account_info = next_account_info(&mut remaining_accounts)?;
+
+        // Update operator bond account
+        Self::update_operator_bond(
+            program_id,
+            operator,
+            service.registry_seed(),
+            service.service_id,
+            total_bond,
+            &accounts.user,
+            operator_bond_account_info,
+            &accounts.system_program,
+        )?;
+
+        Ok(())
+    }
+
+    /// Requires each agent instance to have signed `signatures::instance_proof_message`
+    /// in an earlier Ed25519 program instruction of the transaction.
+    fn check_instance_proofs(
+        instructions: &AccountInfo,
+        registry: &Pubkey,
+        service_id: u128,
+        operator: &Pubkey,
+        agent_instances: &[Pubkey],
+    ) -> Result<()> {
+        let message = signatures::instance_proof_message(registry, service_id, operator);
+        for agent_instance in agent_instances {
+            require!(
+                signatures::is_signed(instructions, agent_instance, &message)?,
+                ErrorCode::InvalidInstanceProof
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Requires `operator` to be on the operator whitelist of the service while its
+    /// check is set.
+    fn check_operator_whitelist(
+        program_id: &Pubkey,
+        operator_whitelist: &AccountInfo,
+        operator: &Pubkey,
+    ) -> Result<()> {
+        if operator_whitelist.data_is_empty() {
+            return Ok(());
+        }
+
+        require_keys_eq!(
+            *operator_whitelist.owner,
+            *program_id,
+            ErrorCode::InvalidPda
+        );
+        let operator_whitelist =
+            OperatorWhitelist::try_deserialize(&mut &operator_whitelist.data.borrow()[..])?;
+        require!(
+            !operator_whitelist.check || operator_whitelist.operators.contains(operator),
+            ErrorCode::OperatorNotWhitelisted
+        );
+
+        Ok(())
+    }
+
     fn load_and_validate_agent_params<'info>(
         remaining_accounts: &mut std::slice::Iter<AccountInfo<'info>>,
         agent_ids: &Vec<u32>,
@@ -1340,11 +3051,9 @@ impl ServiceRegistry {
     }
 
     fn transfer_bond<'info>(
-        program_id: &Pubkey,
         user: &Signer<'info>,
         system_program: &Program<'info, System>,
-        registry: &Account<'_, ServiceRegistry>,
-        registry_wallet: &AccountInfo<'info>,
+        service_escrow: &AccountInfo<'info>,
         transfer_amount: u64,
     ) -> Result<()> {
         let user_pre_balance = user.lamports();
@@ -1354,41 +3063,15 @@ impl ServiceRegistry {
             ErrorCode::IncorrectRegistrationDepositValue
         );
 
-        let (registry_wallet_pda, registry_wallet_bump) =
-            registry_wallet_pda(&registry.key(), program_id);
-
-        require_eq!(
-            registry_wallet_pda,
-            registry_wallet.key(),
-            ErrorCode::WrongRegistryWallet
-        );
+        let transfer_tx = transfer(&user.key(), &service_escrow.key(), transfer_amount);
 
-        require_eq!(
-            registry_wallet_pda,
-            registry.wallet_key,
-            ErrorCode::WrongRegistryWallet
-        );
-
-        require_eq!(
-            registry_wallet_bump,
-            registry.wallet_bump,
-            ErrorCode::WrongRegistryWallet
-        );
-
-        let transfer_tx = transfer(&user.key(), &registry_wallet.key(), transfer_amount);
-
-        invoke_signed(
+        invoke(
             &transfer_tx,
             &[
                 user.to_account_info(),
-                registry_wallet.clone(),
+                service_escrow.clone(),
                 system_program.to_account_info(),
             ],
-            &[&[
-                b"registry_wallet",
-                registry.key().as_ref(),
-                &[registry_wallet_bump],
-            ]],
         )?;
 
         let user_post_balance = user.lamports();
@@ -1405,6 +3088,214 @@ impl ServiceRegistry {
         Ok(())
     }
 
+    fn balance_above_rent(account: &AccountInfo) -> Result<u64> {
+        let rent = Rent::get()?.minimum_balance(account.data_len());
+        Ok(account.lamports().saturating_sub(rent))
+    }
+
+    /// Adds one service of an `audit_solvency` page to `report`.
+    fn audit_service<'info>(
+        remaining_accounts: &mut std::slice::Iter<'info, AccountInfo<'info>>,
+        bond_count: u8,
+        withdrawal_count: u8,
+        registry: &Pubkey,
+        program_id: &Pubkey,
+        report: &mut SolvencyReport,
+    ) -> Result<()> {
+        let service_info = next_account_info(remaining_accounts)?;
+        let service: Account<ServiceAccount> = Account::try_from(service_info)?;
+        let service_id = service.service_id;
+        require_keys_eq!(
+            service_info.key(),
+            service_pda(registry, service_id, program_id).0,
+            ErrorCode::InvalidPda
+        );
+        require_keys_eq!(service.registry, *registry, ErrorCode::WrongServiceRegistry);
+        let registry_seed = service.registry_seed();
+
+        let service_escrow_info = next_account_info(remaining_accounts)?;
+        require_keys_eq!(
+            service_escrow_info.key(),
+            service_escrow_pda(registry_seed, service_id, program_id).0,
+            ErrorCode::InvalidPda
+        );
+
+        // Token-bonded services owe tokens, held by the escrow token account
+        let bond_token_info = next_account_info(remaining_accounts)?;
+        require_keys_eq!(
+            bond_token_info.key(),
+            service_bond_token_pda(registry_seed, service_id, program_id).0,
+            ErrorCode::InvalidPda
+        );
+        let escrow_token_amount = if bond_token_info.data_is_empty() {
+            None
+        } else {
+            let bond_token: Account<ServiceBondToken> = Account::try_from(bond_token_info)?;
+            let escrow_token_info = next_account_info(remaining_accounts)?;
+            require_keys_eq!(
+                escrow_token_info.key(),
+                escrow_token_address(registry_seed, service_id, &bond_token.mint, program_id),
+                ErrorCode::WrongEscrowTokenAccount
+            );
+            let escrow_token: Account<TokenAccount> = Account::try_from(escrow_token_info)?;
+            Some(escrow_token.amount)
+        };
+
+        let mut bonds: u64 = 0;
+        for _ in 0..bond_count {
+            let operator_bond_info = next_account_info(remaining_accounts)?;
+            let operator_bond: Account<OperatorBondAccount> =
+                Account::try_from(operator_bond_info)?;
+            require_keys_eq!(
+                operator_bond_info.key(),
+                operator_bond_pda(
+                    registry_seed,
+                    service_id,
+                    &operator_bond.operator,
+                    program_id
+                )
+                .0,
+                ErrorCode::InvalidPda
+            );
+            bonds = bonds
+                .checked_add(operator_bond.bond)
+                .ok_or(ErrorCode::Overflow)?;
+        }
+
+        // Unbonded amounts stay in the escrow until claimed
+        let mut pending: u64 = 0;
+        for _ in 0..withdrawal_count {
+            let pending_withdrawal_info = next_account_info(remaining_accounts)?;
+            let pending_withdrawal: Account<PendingWithdrawal> =
+                Account::try_from(pending_withdrawal_info)?;
+            require_keys_eq!(
+                pending_withdrawal_info.key(),
+                pending_withdrawal_pda(
+                    registry_seed,
+                    service_id,
+                    &pending_withdrawal.operator,
+                    program_id
+                )
+                .0,
+                ErrorCode::InvalidPda
+            );
+            pending = pending
+                .checked_add(pending_withdrawal.amount)
+                .ok_or(ErrorCode::Overflow)?;
+        }
+
+        let owed = service
+            .security_deposit
+            .checked_add(bonds)
+            .and_then(|owed| owed.checked_add(pending))
+            .ok_or(ErrorCode::Overflow)?;
+        report.services += 1;
+        report.operator_bonds += bond_count as u32;
+
+        match escrow_token_amount {
+            Some(held) => {
+                if held < owed {
+                    report.insolvent_services.push(service_id);
+                }
+            }
+            None => {
+                let held = Self::balance_above_rent(service_escrow_info)?;
+                report.security_deposits = report
+                    .security_deposits
+                    .checked_add(service.security_deposit)
+                    .ok_or(ErrorCode::Overflow)?;
+                report.bonds = report.bonds.checked_add(bonds).ok_or(ErrorCode::Overflow)?;
+                report.pending_withdrawals = report
+                    .pending_withdrawals
+                    .checked_add(pending)
+                    .ok_or(ErrorCode::Overflow)?;
+                report.escrow_balance = report
+                    .escrow_balance
+                    .checked_add(held)
+                    .ok_or(ErrorCode::Overflow)?;
+                if held < owed {
+                    report.deficit = report
+                        .deficit
+                        .checked_add(owed - held)
+                        .ok_or(ErrorCode::Overflow)?;
+                    report.insolvent_services.push(service_id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves `amount` lamports out of a service escrow, never touching its rent.
+    fn release_escrow<'info>(
+        service_escrow: &AccountInfo<'info>,
+        to: &AccountInfo<'info>,
+        amount: u64,
+    ) -> Result<()> {
+        let rent = Rent::get()?.minimum_balance(service_escrow.data_len());
+        let available = service_escrow.lamports().saturating_sub(rent);
+        require!(available >= amount, ErrorCode::InsufficientFunds);
+
+        **service_escrow.try_borrow_mut_lamports()? -= amount;
+        **to.try_borrow_mut_lamports()? += amount;
+
+        Ok(())
+    }
+
+    fn drain_bond_token<'info>(
+        registry: &Pubkey,
+        vault: &Account<'info, BondTokenVault>,
+        vault_token: Option<&Account<'info, TokenAccount>>,
+        drainer_token: Option<&Account<'info, TokenAccount>>,
+        token_program: Option<&Program<'info, Token>>,
+        drainer: &Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let (Some(vault_token), Some(drainer_token), Some(token_program)) =
+            (vault_token, drainer_token, token_program)
+        else {
+            return Err(ErrorCode::MissingBondTokenAccounts.into());
+        };
+
+        require_keys_eq!(vault.registry, *registry, ErrorCode::WrongBondTokenVault);
+        require_keys_eq!(
+            vault_token.key(),
+            get_associated_token_address(&vault.key(), &vault.mint),
+            ErrorCode::WrongBondTokenVault
+        );
+        require_keys_eq!(
+            drainer_token.owner,
+            *drainer,
+            ErrorCode::WrongBondTokenAccount
+        );
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                Transfer {
+                    from: vault_token.to_account_info(),
+                    to: drainer_token.to_account_info(),
+                    authority: vault.to_account_info(),
+                },
+                &[&[
+                    b"bond_token_vault",
+                    vault.registry.as_ref(),
+                    vault.mint.as_ref(),
+                    &[vault.bump],
+                ]],
+            ),
+            amount,
+        )?;
+
+        emit!(BondTokenDrained {
+            drainer: *drainer,
+            mint: vault.mint,
+            amount,
+        });
+
+        Ok(())
+    }
+
     fn validate_operator<'info>(
         program_id: Pubkey,
         operator: Pubkey,
@@ -1449,7 +3340,7 @@ impl ServiceRegistry {
 
         // 1. Global agent_instances
         let (agent_instances_pda, agent_instances_bump) =
-            agent_instances_index_pda(service.service_id, program_id);
+            agent_instances_index_pda(service.registry_seed(), service_id, program_id);
         require!(
             agent_instances_pda == agent_instances_account_info_index.key(),
             ErrorCode::InvalidPda
@@ -1475,6 +3366,7 @@ impl ServiceRegistry {
                 ],
                 &[&[
                     b"agent_instances_index",
+                    service.registry_seed(),
                     &service_id.to_le_bytes(),
                     &[agent_instances_bump],
                 ]],
@@ -1506,8 +3398,12 @@ impl ServiceRegistry {
         agent_instances_index.serialize(&mut &mut data[8..])?;
 
         //  2. Slot counter
-        let (slot_counter_pda, slot_counter_bump) =
-            service_agent_slot_counter_pda(service.service_id, agent_id, program_id);
+        let (slot_counter_pda, slot_counter_bump) = service_agent_slot_counter_pda(
+            service.registry_seed(),
+            service_id,
+            agent_id,
+            program_id,
+        );
 
         let slot_counter_info = next_account_info(remaining_accounts)?;
         require!(
@@ -1532,6 +3428,7 @@ impl ServiceRegistry {
                     ],
                     &[&[
                         b"service_agent_slot",
+                        service.registry_seed(),
                         &service_id.to_le_bytes(),
                         &agent_id.to_le_bytes(),
                         &[slot_counter_bump],
@@ -1558,8 +3455,13 @@ impl ServiceRegistry {
         slot_counter.serialize(&mut &mut data[8..])?;
 
         //  3. service_agent_instance
-        let (service_agent_instance_pda, service_agent_instance_bump) =
-            service_agent_instance_pda(service.service_id, agent_id, &agent_instance, program_id);
+        let (service_agent_instance_pda, service_agent_instance_bump) = service_agent_instance_pda(
+            service.registry_seed(),
+            service_id,
+            agent_id,
+            &agent_instance,
+            program_id,
+        );
 
         let service_agent_instance_account_info = next_account_info(remaining_accounts)?;
         require!(
@@ -1586,6 +3488,7 @@ impl ServiceRegistry {
             ],
             &[&[
                 b"service_agent_instance_account",
+                service.registry_seed(),
                 &service_id.to_le_bytes(),
                 &agent_id.to_le_bytes(),
                 &agent_instance.to_bytes(),
@@ -1664,7 +3567,12 @@ impl ServiceRegistry {
 
         //  Push in operator_agent_instance_index
         let (operator_agent_instance_index_pda, _operator_agent_instance_index_bump) =
-            operator_agent_instance_index_pda(service.service_id, &operator, program_id);
+            operator_agent_instance_index_pda(
+                service.registry_seed(),
+                service.service_id,
+                &operator,
+                program_id,
+            );
 
         require!(
             operator_agent_instance_index_pda == operator_agent_instance_index.key(),
@@ -1691,9 +3599,11 @@ impl ServiceRegistry {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn update_operator_bond<'info>(
         program_id: &Pubkey,
         operator: Pubkey,
+        registry_seed: &[u8],
         service_id: u128,
         total_bond: u64,
         user_account_info: &AccountInfo<'info>,
@@ -1701,7 +3611,7 @@ impl ServiceRegistry {
         system_program_account_info: &AccountInfo<'info>,
     ) -> Result<()> {
         let (operator_bond_pda, operator_bond_bump) =
-            operator_bond_pda(service_id, &operator.key(), program_id);
+            operator_bond_pda(registry_seed, service_id, &operator.key(), program_id);
 
         require!(
             operator_bond_pda == operator_bond_account_info.key(),
@@ -1724,6 +3634,7 @@ impl ServiceRegistry {
                 ],
                 &[&[
                     b"operator_bond",
+                    registry_seed,
                     &service_id.to_le_bytes(),
                     &operator.to_bytes(),
                     &[operator_bond_bump],
@@ -1753,6 +3664,198 @@ impl ServiceRegistry {
         Ok(())
     }
 
+    /// Appends `config_hash` to the service config hash history, creating the
+    /// history account on first use and growing it by one record otherwise.
+    #[allow(clippy::too_many_arguments)]
+    fn record_config_hash<'info>(
+        program_id: &Pubkey,
+        service_id: u128,
+        config_hash: [u8; 32],
+        history_bump: u8,
+        service_account_info: &AccountInfo<'info>,
+        user_account_info: &AccountInfo<'info>,
+        history_account_info: &AccountInfo<'info>,
+        system_program_account_info: &AccountInfo<'info>,
+    ) -> Result<()> {
+        let is_new = history_account_info.data_is_empty();
+        let mut records = if is_new {
+            Vec::new()
+        } else {
+            ConfigHashHistory::try_deserialize(&mut &history_account_info.try_borrow_data()?[..])?
+                .records
+        };
+
+        let clock = Clock::get()?;
+        records.push(ConfigHashRecord {
+            config_hash,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        let space = ConfigHashHistory::size(records.len());
+        let rent = Rent::get()?.minimum_balance(space);
+
+        if is_new {
+            invoke_signed(
+                &system_instruction::create_account(
+                    &user_account_info.key(),
+                    &history_account_info.key(),
+                    rent,
+                    space as u64,
+                    program_id,
+                ),
+                &[
+                    user_account_info.clone(),
+                    history_account_info.clone(),
+                    system_program_account_info.clone(),
+                ],
+                &[&[
+                    b"config_hash_history",
+                    service_account_info.key.as_ref(),
+                    &[history_bump],
+                ]],
+            )?;
+        } else {
+            ServiceRegistry::grow_account(
+                history_account_info,
+                space,
+                user_account_info,
+                system_program_account_info,
+            )?;
+        }
+
+        let history = ConfigHashHistory {
+            service_id,
+            records,
+        };
+        let mut data = history_account_info.try_borrow_mut_data()?;
+        data[..8].copy_from_slice(ConfigHashHistory::DISCRIMINATOR);
+        history.serialize(&mut &mut data[8..])?;
+
+        Ok(())
+    }
+
+    /// Mints the single service token to `service_token` and revokes the mint
+    /// authority, so that the supply stays at one.
+    fn mint_service_token<'info>(
+        registry: &Pubkey,
+        service_id: u128,
+        service_bump: u8,
+        service_account_info: &AccountInfo<'info>,
+        service_mint_account_info: &AccountInfo<'info>,
+        service_token_account_info: &AccountInfo<'info>,
+        token_program_account_info: &AccountInfo<'info>,
+    ) -> Result<()> {
+        let service_id_bytes = service_id.to_le_bytes();
+        let service_seeds: &[&[u8]] = &[
+            b"service",
+            registry.as_ref(),
+            &service_id_bytes,
+            &[service_bump],
+        ];
+
+        token::mint_to(
+            CpiContext::new_with_signer(
+                token_program_account_info.clone(),
+                MintTo {
+                    mint: service_mint_account_info.clone(),
+                    to: service_token_account_info.clone(),
+                    authority: service_account_info.clone(),
+                },
+                &[service_seeds],
+            ),
+            1,
+        )?;
+
+        token::set_authority(
+            CpiContext::new_with_signer(
+                token_program_account_info.clone(),
+                SetAuthority {
+                    current_authority: service_account_info.clone(),
+                    account_or_mint: service_mint_account_info.clone(),
+                },
+                &[service_seeds],
+            ),
+            AuthorityType::MintTokens,
+            None,
+        )
+    }
+
+    /// Whether `user` may manage a service of `service_owner`: holders of the manager
+    /// role always, the service owner itself in permissionless mode.
+    fn is_service_authority(
+        &self,
+        roles: &RegistryRoles,
+        user: &Pubkey,
+        service_owner: &Pubkey,
+    ) -> bool {
+        roles.has_role(Role::ServiceManager, user) || (self.permissionless && user == service_owner)
+    }
+
+    pub fn is_paused(&self, group: PauseGroup) -> bool {
+        self.paused & group.flag() != 0
+    }
+
+    /// Fails with the error of `group` while it is paused.
+    fn check_not_paused(&self, group: PauseGroup) -> Result<()> {
+        if self.is_paused(group) {
+            return Err(group.error().into());
+        }
+        Ok(())
+    }
+
+    /// Refunds of `terminate` and `claim_unbonded` are payouts, kept open by
+    /// `refunds_open`.
+    fn check_refunds_not_paused(&self) -> Result<()> {
+        if self.refunds_open {
+            return Ok(());
+        }
+        self.check_not_paused(PauseGroup::Payouts)
+    }
+
+    /// Checks that `service_token` holds the service token and belongs to
+    /// `service_owner`, and records that holder as the service owner.
+    fn check_service_holder(
+        program_id: &Pubkey,
+        service: &mut ServiceAccount,
+        service_token: &TokenAccount,
+        service_owner: Pubkey,
+    ) -> Result<()> {
+        let (service_mint, _bump) =
+            service_mint_pda(service.registry_seed(), service.service_id, program_id);
+        require!(
+            service_token.mint == service_mint && service_token.amount == 1,
+            ErrorCode::WrongServiceToken
+        );
+
+        if service_token.owner != service_owner {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+
+        service.service_owner = service_owner;
+        Ok(())
+    }
+
+    /// Reallocates a program account to `space` bytes, `payer` topping its lamports up
+    /// to the rent exemption of the new size.
+    fn grow_account<'info>(
+        account: &AccountInfo<'info>,
+        space: usize,
+        payer: &AccountInfo<'info>,
+        system_program: &AccountInfo<'info>,
+    ) -> Result<()> {
+        let rent = Rent::get()?.minimum_balance(space);
+        let top_up = rent.saturating_sub(account.lamports());
+        if top_up > 0 {
+            invoke(
+                &transfer(&payer.key(), &account.key(), top_up),
+                &[payer.clone(), account.clone(), system_program.clone()],
+            )?;
+        }
+        account.realloc(space, true)?;
+        Ok(())
+    }
+
     fn close_account<'info>(
         account: &AccountInfo<'info>,
         refund_to: &AccountInfo<'info>,
@@ -1767,7 +3870,6 @@ impl ServiceRegistry {
     }
 
     pub fn create_multisig<'info>(
-        _multisig_implementation: &Pubkey,
         agent_instances: &[Pubkey],
         threshold: u32,
         data: &[u8],
@@ -1775,19 +3877,13 @@ impl ServiceRegistry {
         program_id: &Pubkey,
         remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<Pubkey> {
-        // TODO! here we normally call multisig_implementation but we mimic a IMultisig(multisigImplementation).create
         require!(
             threshold > 0 && threshold as usize <= agent_instances.len(),
             ErrorCode::WrongThreshold
         );
 
         // Prepare seed data based on agent instances
-        let mut seed_data = vec![];
-        for agent in agent_instances {
-            seed_data.extend_from_slice(agent.as_ref());
-        }
-
-        let hash = hash(&seed_data);
+        let hash = multisig_seed(agent_instances);
         msg!(&hex::encode(hash));
         let seeds: &[&[u8]] = &[b"multisig", hash.as_ref()];
 
@@ -1830,14 +3926,69 @@ impl ServiceRegistry {
             agent_instances: agent_instances.to_vec(),
             threshold,
             data: data.to_vec(),
+            proposal_count: 0,
         };
 
-        multisig_account_info.try_borrow_mut_data()?;
-        multisig_account_data.serialize(&mut *multisig_account_info.data.borrow_mut())?;
+        let mut data = multisig_account_info.try_borrow_mut_data()?;
+        let discriminator =
+            &anchor_lang::solana_program::hash::hash("account:MultisigAccount".as_bytes())
+                .to_bytes()[..8];
+        data[..8].copy_from_slice(discriminator);
+        multisig_account_data.serialize(&mut &mut data[8..])?;
 
         // Return the multisig PDA (which is the address of the newly created account)
         Ok(multisig_pda)
     }
+
+    pub fn create_external_multisig<'info>(
+        multisig_implementation: &AccountInfo<'info>,
+        agent_instances: &[Pubkey],
+        threshold: u32,
+        data: &[u8],
+        payer: &Signer<'info>,
+        system_program: &Program<'info, System>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<Pubkey> {
+        let multisig_account_info = remaining_accounts
+            .first()
+            .ok_or(ErrorCode::MissingMultisigAccount)?;
+
+        let ix = multisig_interface::create_instruction(
+            &multisig_implementation.key(),
+            &payer.key(),
+            &multisig_account_info.key(),
+            &multisig_interface::CreateMultisigArgs {
+                agent_instances: agent_instances.to_vec(),
+                threshold,
+                data: data.to_vec(),
+            },
+        )?;
+
+        // Signer privileges of the payer are extended to the implementation
+        invoke(
+            &ix,
+            &[
+                payer.to_account_info(),
+                multisig_account_info.clone(),
+                system_program.to_account_info(),
+                multisig_implementation.clone(),
+            ],
+        )?;
+
+        let multisig = multisig_interface::parse_return_data(
+            &multisig_implementation.key(),
+            get_return_data(),
+        )?;
+
+        // The implementation must have created the account we were given
+        require_keys_eq!(
+            multisig,
+            multisig_account_info.key(),
+            ErrorCode::WrongMultisigAccount
+        );
+
+        Ok(multisig)
+    }
 }
 
 #[derive(Accounts)]
@@ -1855,6 +4006,15 @@ pub struct Initialize<'info> {
         )]
     pub registry_wallet: AccountInfo<'info>,
 
+    #[account(
+        init,
+        payer = user,
+        space = RegistryRoles::LEN,
+        seeds = [b"roles", registry.key().as_ref()],
+        bump
+    )]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
     #[account(mut)]
     pub user: Signer<'info>,
 
@@ -1862,83 +4022,631 @@ pub struct Initialize<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(config_hash: [u8; 32])]
-pub struct CreateService<'info> {
-    #[account(mut)]
+pub struct InitializeRoles<'info> {
     pub registry: Account<'info, ServiceRegistry>,
 
     #[account(
         init,
         payer = user,
-        space = 8 + std::mem::size_of::<ServiceAccount>(),
-        seeds = [b"service", &config_hash[..7]],
-        bump,
+        space = RegistryRoles::LEN,
+        seeds = [b"roles", registry.key().as_ref()],
+        bump
     )]
-    pub service: Account<'info, ServiceAccount>,
+    pub roles: Box<Account<'info, RegistryRoles>>,
 
-    #[account(mut, address = registry.manager)]
+    #[account(mut)]
     pub user: Signer<'info>,
 
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateService<'info> {
-    #[account(mut)]
-    pub registry: Account<'info, ServiceRegistry>,
+pub struct MigrateRegistry<'info> {
+    /// CHECK: Registry, possibly too small to deserialize before it is grown
+    #[account(mut, owner = crate::ID)]
+    pub registry: UncheckedAccount<'info>,
 
     #[account(mut)]
-    pub service: Account<'info, ServiceAccount>,
-
-    #[account(address = registry.manager)]
     pub user: Signer<'info>,
 
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct RegisterAgentIdsToService<'info> {
-    #[account(mut)]
+pub struct UpdateRoles<'info> {
     pub registry: Account<'info, ServiceRegistry>,
 
+    #[account(mut, seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateService<'info> {
     #[account(mut)]
+    pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(
+        init,
+        payer = user,
+        space = ServiceAccount::LEN,
+        seeds = [b"service", registry.key().as_ref(), &(registry.total_supply + 1).to_le_bytes()[..]],
+        bump,
+    )]
     pub service: Account<'info, ServiceAccount>,
 
+    /// CHECK: Created by the instruction, seeds checked
+    #[account(mut, seeds = [b"config_hash_history", service.key().as_ref()], bump)]
+    pub config_hash_history: AccountInfo<'info>,
+
+    /// Escrow of the security deposit and operator bonds of the service
     #[account(
-        init_if_needed,
+        init,
         payer = user,
-        space = 8 + (MAX_AGENT_IDS_PER_SERVICE * AgentParamAccount::LEN) + 8, // 8 bytes for Vec metadata + data for MAX_AGENT_IDS_PER_SERVICE u32 agent IDs + 8 bytes Vec overhead
-        seeds = [b"service_agent_ids_index", &service.service_id.to_le_bytes()[..]],
+        space = ServiceEscrow::LEN,
+        seeds = [b"service_escrow", registry.key().as_ref(), &(registry.total_supply + 1).to_le_bytes()[..]],
         bump,
     )]
-    pub service_agent_ids_index: Account<'info, ServiceAgentIdsIndex>,
+    pub service_escrow: Box<Account<'info, ServiceEscrow>>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"service_mint", registry.key().as_ref(), &(registry.total_supply + 1).to_le_bytes()[..]],
+        bump,
+        mint::decimals = 0,
+        mint::authority = service,
+    )]
+    pub service_mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: Receives the service token, checked against the service_owner argument
+    pub service_owner: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = user,
+        associated_token::mint = service_mint,
+        associated_token::authority = service_owner,
+    )]
+    pub service_token: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Registry wallet receiving the creation fee
+    #[account(mut, address = registry.wallet_key)]
+    pub registry_wallet: AccountInfo<'info>,
 
-    #[account(mut, address = registry.manager)]
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The manager, or in permissionless mode the service owner
+    #[account(mut)]
     pub user: Signer<'info>,
 
     pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
 #[derive(Accounts)]
-pub struct ChangeDrainer<'info> {
+pub struct UpdateService<'info> {
     #[account(mut)]
     pub registry: Account<'info, ServiceRegistry>,
-    #[account(address = registry.owner)]
-    pub user: Signer<'info>,
-}
 
-#[derive(Accounts)]
-pub struct ChangeOwner<'info> {
+    #[account(mut, has_one = registry @ ErrorCode::WrongServiceRegistry)]
+    pub service: Account<'info, ServiceAccount>,
+
+    /// CHECK: Grown by the instruction, or created for services that predate it
+    #[account(mut, seeds = [b"config_hash_history", service.key().as_ref()], bump)]
+    pub config_hash_history: AccountInfo<'info>,
+
+    /// Token account of the service owner holding the service token
+    pub service_token: Account<'info, TokenAccount>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The manager, or in permissionless mode the service owner
     #[account(mut)]
-    pub registry: Account<'info, ServiceRegistry>,
     pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ChangeManager<'info> {
-    #[account(mut)]
+pub struct GetConfigHashHistory<'info> {
+    pub service: Account<'info, ServiceAccount>,
+
+    #[account(seeds = [b"config_hash_history", service.key().as_ref()], bump)]
+    pub config_hash_history: Account<'info, ConfigHashHistory>,
+}
+
+#[derive(Accounts)]
+#[instruction(legacy_seed: [u8; 7], service_id: u128)]
+pub struct MigrateService<'info> {
+    pub registry: Account<'info, ServiceRegistry>,
+
+    /// CHECK: Service read in its legacy layout and closed by the instruction
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"service", &legacy_seed[..]],
+        bump,
+    )]
+    pub legacy_service: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = user,
+        space = ServiceAccount::LEN,
+        seeds = [b"service", registry.key().as_ref(), &service_id.to_le_bytes()[..]],
+        bump,
+    )]
+    pub service: Account<'info, ServiceAccount>,
+
+    #[account(
+        init,
+        payer = user,
+        space = ServiceEscrow::LEN,
+        seeds = [b"service_escrow", &service_id.to_le_bytes()[..]],
+        bump,
+    )]
+    pub service_escrow: Box<Account<'info, ServiceEscrow>>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"service_mint", &service_id.to_le_bytes()[..]],
+        bump,
+        mint::decimals = 0,
+        mint::authority = service,
+    )]
+    pub service_mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: Receives the service token, checked against the legacy service owner
+    pub service_owner: AccountInfo<'info>,
+
+    /// CHECK: Registry wallet holding the deposit and bonds of legacy services
+    #[account(mut, address = registry.wallet_key)]
+    pub registry_wallet: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = user,
+        associated_token::mint = service_mint,
+        associated_token::authority = service_owner,
+    )]
+    pub service_token: Box<Account<'info, TokenAccount>>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterAgentIdsToService<'info> {
+    #[account(mut)]
+    pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(mut, has_one = registry @ ErrorCode::WrongServiceRegistry)]
+    pub service: Account<'info, ServiceAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + (MAX_AGENT_IDS_PER_SERVICE * AgentParamAccount::LEN) + 8, // 8 bytes for Vec metadata + data for MAX_AGENT_IDS_PER_SERVICE u32 agent IDs + 8 bytes Vec overhead
+        seeds = [b"service_agent_ids_index", service.registry_seed(), &service.service_id.to_le_bytes()[..]],
+        bump,
+    )]
+    pub service_agent_ids_index: Account<'info, ServiceAgentIdsIndex>,
+
+    /// Token account of the service owner holding the service token, only read when
+    /// the service owner signs
+    pub service_token: Option<Account<'info, TokenAccount>>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The manager, or in permissionless mode the service owner
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeDrainer<'info> {
+    #[account(mut)]
+    pub registry: Account<'info, ServiceRegistry>,
+    /// Roles of the registry, checked against the signer
+    #[account(mut, seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// Administrator proposing or cancelling, or the pending key accepting
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeOwner<'info> {
+    #[account(mut)]
+    pub registry: Account<'info, ServiceRegistry>,
+    /// Roles of the registry, checked against the signer
+    #[account(mut, seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// Administrator proposing or cancelling, or the pending key accepting
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeManager<'info> {
+    #[account(mut)]
+    pub registry: Account<'info, ServiceRegistry>,
+    /// Roles of the registry, checked against the signer
+    #[account(mut, seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// Administrator proposing or cancelling, or the pending key accepting
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRegistry<'info> {
+    #[account(mut)]
+    pub registry: Account<'info, ServiceRegistry>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    pub user: Signer<'info>,
+}
+
+/// Accounts a bond token amount moves through besides the service escrow.
+#[derive(Clone, Copy)]
+enum BondTransfer {
+    /// Deposits from and refunds to `counterparty_token`
+    Counterparty,
+    /// Slashes into the treasury vault
+    Treasury,
+}
+
+/// Accounts moving the amounts of a service bonded in a bond token. Only
+/// `service_bond_token` is needed for services bonded in lamports, and the treasury
+/// accounts only by the slashing instructions.
+#[derive(Accounts)]
+pub struct BondToken<'info> {
+    /// CHECK: Bond token setting of the service, empty for lamport bonds, checked by
+    /// `token_bonded`
+    pub service_bond_token: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub bond_token_vault: Option<Box<Account<'info, BondTokenVault>>>,
+
+    #[account(mut)]
+    pub vault_token: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// Token account of the service escrow holding the deposit and bonds
+    #[account(mut)]
+    pub escrow_token: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// Token account paying or receiving the amount moved by the instruction
+    #[account(mut)]
+    pub counterparty_token: Option<Box<Account<'info, TokenAccount>>>,
+
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+impl<'info> BondToken<'info> {
+    /// Whether the service is bonded in a bond token, checking the escrow and
+    /// treasury accounts when it is, and that the accounts `transfer` moves the amount
+    /// through are all passed, each once.
+    fn token_bonded(
+        &self,
+        service: &ServiceAccount,
+        program_id: &Pubkey,
+        transfer: BondTransfer,
+    ) -> Result<bool> {
+        let registry_seed = service.registry_seed();
+        let (service_bond_token_pda, _bump) =
+            service_bond_token_pda(registry_seed, service.service_id, program_id);
+        require_keys_eq!(
+            self.service_bond_token.key(),
+            service_bond_token_pda,
+            ErrorCode::InvalidPda
+        );
+
+        if self.service_bond_token.data_is_empty() {
+            return Ok(false);
+        }
+
+        require_keys_eq!(
+            *self.service_bond_token.owner,
+            *program_id,
+            ErrorCode::InvalidPda
+        );
+        let service_bond_token =
+            ServiceBondToken::try_deserialize(&mut &self.service_bond_token.data.borrow()[..])?;
+        let Some(escrow_token) = &self.escrow_token else {
+            return Err(ErrorCode::MissingBondTokenAccounts.into());
+        };
+
+        require_keys_eq!(
+            escrow_token.key(),
+            escrow_token_address(
+                registry_seed,
+                service.service_id,
+                &service_bond_token.mint,
+                program_id
+            ),
+            ErrorCode::WrongEscrowTokenAccount
+        );
+
+        match (&self.bond_token_vault, &self.vault_token) {
+            (Some(vault), Some(vault_token)) => {
+                require!(
+                    vault.registry == service.registry && vault.mint == service_bond_token.mint,
+                    ErrorCode::WrongBondTokenVault
+                );
+                require_keys_eq!(
+                    vault_token.key(),
+                    get_associated_token_address(&vault.key(), &vault.mint),
+                    ErrorCode::WrongBondTokenVault
+                );
+            }
+            (None, None) => {}
+            _ => return Err(ErrorCode::MissingBondTokenAccounts.into()),
+        }
+
+        let complete = self.token_program.is_some()
+            && match transfer {
+                BondTransfer::Counterparty => self.counterparty_token.is_some(),
+                BondTransfer::Treasury => self.vault_token.is_some(),
+            };
+        require!(complete, ErrorCode::MissingBondTokenAccounts);
+
+        let token_accounts: Vec<Pubkey> = [
+            Some(escrow_token),
+            self.vault_token.as_ref(),
+            self.counterparty_token.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|token_account| token_account.key())
+        .collect();
+        for (position, token_account) in token_accounts.iter().enumerate() {
+            require!(
+                !token_accounts[position + 1..].contains(token_account),
+                ErrorCode::DuplicateBondTokenAccounts
+            );
+        }
+
+        Ok(true)
+    }
+
+    /// Moves `amount` from the counterparty token account, signed by `authority`, to
+    /// the service escrow.
+    fn deposit(&self, authority: &AccountInfo<'info>, amount: u64) -> Result<()> {
+        let (Some(escrow_token), Some(counterparty_token), Some(token_program)) = (
+            &self.escrow_token,
+            &self.counterparty_token,
+            &self.token_program,
+        ) else {
+            return Err(ErrorCode::MissingBondTokenAccounts.into());
+        };
+
+        token::transfer(
+            CpiContext::new(
+                token_program.to_account_info(),
+                Transfer {
+                    from: counterparty_token.to_account_info(),
+                    to: escrow_token.to_account_info(),
+                    authority: authority.clone(),
+                },
+            ),
+            amount,
+        )
+    }
+
+    /// Moves `amount` from the service escrow to the counterparty token account, which
+    /// must belong to `recipient`.
+    fn refund(
+        &self,
+        service: &ServiceAccount,
+        service_escrow: &Account<'info, ServiceEscrow>,
+        recipient: &Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let Some(counterparty_token) = &self.counterparty_token else {
+            return Err(ErrorCode::MissingBondTokenAccounts.into());
+        };
+
+        require_keys_eq!(
+            counterparty_token.owner,
+            *recipient,
+            ErrorCode::WrongBondTokenAccount
+        );
+
+        self.release(service, service_escrow, counterparty_token, amount)
+    }
+
+    /// Moves `amount` slashed from the service escrow to the treasury of the bond token.
+    fn slash(
+        &mut self,
+        service: &ServiceAccount,
+        service_escrow: &Account<'info, ServiceEscrow>,
+        amount: u64,
+    ) -> Result<()> {
+        // `token_bonded` checked the vault accounts come together
+        let Some(vault_token) = &self.vault_token else {
+            return Err(ErrorCode::MissingBondTokenAccounts.into());
+        };
+        self.release(service, service_escrow, vault_token, amount)?;
+
+        let Some(vault) = self.bond_token_vault.as_mut() else {
+            return Err(ErrorCode::MissingBondTokenAccounts.into());
+        };
+        vault.slashed_funds += amount;
+
+        Ok(())
+    }
+
+    fn release(
+        &self,
+        service: &ServiceAccount,
+        service_escrow: &Account<'info, ServiceEscrow>,
+        to: &Account<'info, TokenAccount>,
+        amount: u64,
+    ) -> Result<()> {
+        let (Some(escrow_token), Some(token_program)) = (&self.escrow_token, &self.token_program)
+        else {
+            return Err(ErrorCode::MissingBondTokenAccounts.into());
+        };
+
+        token::transfer(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                Transfer {
+                    from: escrow_token.to_account_info(),
+                    to: to.to_account_info(),
+                    authority: service_escrow.to_account_info(),
+                },
+                &[&[
+                    b"service_escrow",
+                    service.registry_seed(),
+                    &service_escrow.service_id.to_le_bytes(),
+                    &[service_escrow.bump],
+                ]],
+            ),
+            amount,
+        )
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: u128)]
+pub struct SetServiceBondToken<'info> {
     pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(
+        seeds = [b"service", registry.key().as_ref(), &service_id.to_le_bytes()[..]],
+        bump,
+        has_one = registry @ ErrorCode::WrongServiceRegistry,
+    )]
+    pub service: Account<'info, ServiceAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = ServiceBondToken::LEN,
+        seeds = [b"service_bond_token", service.registry_seed(), &service_id.to_le_bytes()[..]],
+        bump,
+    )]
+    pub service_bond_token: Account<'info, ServiceBondToken>,
+
+    pub bond_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = BondTokenVault::LEN,
+        seeds = [b"bond_token_vault", registry.key().as_ref(), bond_mint.key().as_ref()],
+        bump,
+    )]
+    pub bond_token_vault: Box<Account<'info, BondTokenVault>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = bond_mint,
+        associated_token::authority = bond_token_vault,
+    )]
+    pub vault_token: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Escrow of the service, only the authority of its escrow token account
+    #[account(seeds = [b"service_escrow", service.registry_seed(), &service_id.to_le_bytes()[..]], bump)]
+    pub service_escrow: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = bond_mint,
+        associated_token::authority = service_escrow,
+    )]
+    pub escrow_token: Box<Account<'info, TokenAccount>>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    #[account(mut)]
     pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: u128, new_owner: Pubkey)]
+pub struct TransferService<'info> {
+    pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(
+        mut,
+        seeds = [b"service", registry.key().as_ref(), &service_id.to_le_bytes()[..]],
+        bump,
+        has_one = registry @ ErrorCode::WrongServiceRegistry,
+    )]
+    pub service: Account<'info, ServiceAccount>,
+
+    #[account(mut)]
+    pub service_owner: Signer<'info>,
+
+    /// Token account of the service owner holding the service token
+    #[account(mut)]
+    pub service_token: Box<Account<'info, TokenAccount>>,
+
+    #[account(seeds = [b"service_mint", service.registry_seed(), &service_id.to_le_bytes()[..]], bump)]
+    pub service_mint: Box<Account<'info, Mint>>,
+
+    /// CHECK: Receives the service token
+    #[account(address = new_owner)]
+    pub new_service_owner: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = service_owner,
+        associated_token::mint = service_mint,
+        associated_token::authority = new_service_owner,
+    )]
+    pub new_service_token: Box<Account<'info, TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: u128)]
+pub struct TokenUri<'info> {
+    pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(
+        seeds = [b"service", registry.key().as_ref(), &service_id.to_le_bytes()[..]],
+        bump,
+        has_one = registry @ ErrorCode::WrongServiceRegistry,
+    )]
+    pub service: Account<'info, ServiceAccount>,
 }
 
 #[derive(Accounts)]
@@ -1961,67 +4669,169 @@ pub struct ActivateRegistration<'info> {
     #[account(mut)]
     pub registry: Account<'info, ServiceRegistry>,
 
+    #[account(mut, has_one = registry @ ErrorCode::WrongServiceRegistry)]
+    pub service: Account<'info, ServiceAccount>,
+
+    /// Token account of the service owner holding the service token
+    pub service_token: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"service_escrow", service.registry_seed(), &service.service_id.to_le_bytes()[..]],
+        bump = service_escrow.bump,
+    )]
+    pub service_escrow: Account<'info, ServiceEscrow>,
+
+    pub bond_token: BondToken<'info>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The manager, or in permissionless mode the service owner
     #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(operator: Pubkey)]
+pub struct RegisterAgentInstances<'info> {
+    #[account(mut)]
+    pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(mut, has_one = registry @ ErrorCode::WrongServiceRegistry)]
     pub service: Account<'info, ServiceAccount>,
 
-    /// CHECK: PDA wallet owned by the program
-    #[account(mut, address = registry.wallet_key)]
-    pub registry_wallet: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"service_escrow", service.registry_seed(), &service.service_id.to_le_bytes()[..]],
+        bump = service_escrow.bump,
+    )]
+    pub service_escrow: Account<'info, ServiceEscrow>,
+
+    pub bond_token: BondToken<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + (MAX_AGENT_INSTANCES_PER_SERVICE * PUBKEY_SIZE) + 8, // 8 bytes for Vec metadata + data for MAX_AGENT_INSTANCES_PER_SERVICE PUBKEY_SIZE operator_agent_instance_pda PDA + 8 bytes Vec overhead
+        seeds = [b"operator_agent_instance_index", service.registry_seed(), &service.service_id.to_le_bytes()[..], &operator.to_bytes()[..]],
+        bump,
+    )]
+    pub operator_agent_instance_index: Account<'info, OperatorAgentInstanceIndex>,
+
+    /// CHECK: Operator whitelist of the service, empty if it never had one, checked by
+    /// `check_operator_whitelist`
+    #[account(seeds = [b"operator_whitelist", service.registry_seed(), &service.service_id.to_le_bytes()[..]], bump)]
+    pub operator_whitelist: AccountInfo<'info>,
+
+    /// The operator itself, or the manager relaying its signature
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: the instructions sysvar, read for the Ed25519 signature checks
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterAgentInstancesWithSignature<'info> {
+    pub registration: RegisterAgentInstances<'info>,
+
+    /// CHECK: the operator whose signature is checked, equal to the `operator` argument
+    pub operator: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = registration.user,
+        space = OperatorNonce::LEN,
+        seeds = [b"operator_nonce", operator.key().as_ref()],
+        bump,
+    )]
+    pub operator_nonce: Account<'info, OperatorNonce>,
 
-    #[account(mut, address = registry.manager)]
-    pub user: Signer<'info>,
+    /// Roles of the registry, checked against the relayer
+    #[account(seeds = [b"roles", registration.registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
 
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(operator: Pubkey)]
-pub struct RegisterAgentInstances<'info> {
+pub struct TerminateService<'info> {
     #[account(mut)]
     pub registry: Account<'info, ServiceRegistry>,
 
-    #[account(mut)]
+    #[account(mut, has_one = registry @ ErrorCode::WrongServiceRegistry)]
     pub service: Account<'info, ServiceAccount>,
 
-    /// CHECK: PDA wallet owned by the program
-    #[account(mut, address = registry.wallet_key)]
-    pub registry_wallet: AccountInfo<'info>,
-
     #[account(
-        init_if_needed,
-        payer = user,
-        space = 8 + (MAX_AGENT_INSTANCES_PER_SERVICE * PUBKEY_SIZE) + 8, // 8 bytes for Vec metadata + data for MAX_AGENT_INSTANCES_PER_SERVICE PUBKEY_SIZE operator_agent_instance_pda PDA + 8 bytes Vec overhead
-        seeds = [b"operator_agent_instance_index", &service.service_id.to_le_bytes()[..], &operator.to_bytes()[..]],
+        seeds = [b"service_agent_ids_index", service.registry_seed(), &service.service_id.to_le_bytes()[..]],
         bump,
     )]
-    pub operator_agent_instance_index: Account<'info, OperatorAgentInstanceIndex>,
+    pub service_agent_ids_index: Account<'info, ServiceAgentIdsIndex>,
 
-    #[account(mut, address = registry.manager)]
-    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"service_escrow", service.registry_seed(), &service.service_id.to_le_bytes()[..]],
+        bump = service_escrow.bump,
+    )]
+    pub service_escrow: Account<'info, ServiceEscrow>,
 
-    pub system_program: Program<'info, System>,
+    /// CHECK: service_owner, checked against service_token
+    #[account(mut)]
+    pub service_owner: AccountInfo<'info>,
+
+    /// Token account of the service owner holding the service token
+    pub service_token: Box<Account<'info, TokenAccount>>,
+
+    pub bond_token: BondToken<'info>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct TerminateService<'info> {
-    #[account(mut)]
+pub struct CleanupService<'info> {
     pub registry: Account<'info, ServiceRegistry>,
 
-    #[account(mut)]
+    #[account(mut, has_one = registry @ ErrorCode::WrongServiceRegistry)]
     pub service: Account<'info, ServiceAccount>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [b"service_agent_ids_index", service.registry_seed(), &service.service_id.to_le_bytes()[..]],
+        bump,
+    )]
     pub service_agent_ids_index: Account<'info, ServiceAgentIdsIndex>,
 
-    /// CHECK: PDA wallet owned by the program
-    #[account(mut, address = registry.wallet_key)]
-    pub registry_wallet: AccountInfo<'info>,
+    /// CHECK: Read when owned by the registry, created by the first registration
+    #[account(
+        mut,
+        seeds = [b"agent_instances_index", service.registry_seed(), &service.service_id.to_le_bytes()[..]],
+        bump,
+    )]
+    pub agent_instances_index: UncheckedAccount<'info>,
 
-    /// CHECK: service_owner
-    #[account(mut, address = service.service_owner)]
+    /// CHECK: service_owner, checked against service_token
     pub service_owner: AccountInfo<'info>,
 
-    #[account(mut, address = registry.manager)]
+    /// Token account of the service owner holding the service token
+    pub service_token: Box<Account<'info, TokenAccount>>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    #[account(mut)]
     pub user: Signer<'info>,
 }
 
@@ -2029,34 +4839,123 @@ pub struct TerminateService<'info> {
 pub struct UnbondOperator<'info> {
     pub registry: Account<'info, ServiceRegistry>,
 
-    #[account(mut)]
+    #[account(mut, has_one = registry @ ErrorCode::WrongServiceRegistry)]
     pub service: Account<'info, ServiceAccount>,
 
-    #[account(mut)]
+    /// Bonds of the agent ids, refunded per unbonded instance
+    #[account(
+        seeds = [b"service_agent_ids_index", service.registry_seed(), &service.service_id.to_le_bytes()[..]],
+        bump,
+    )]
+    pub service_agent_ids_index: Account<'info, ServiceAgentIdsIndex>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"operator_agent_instance_index",
+            service.registry_seed(),
+            &service.service_id.to_le_bytes()[..],
+            operator.key().as_ref(),
+        ],
+        bump,
+    )]
     pub operator_agent_instance_index: Account<'info, OperatorAgentInstanceIndex>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [
+            b"operator_bond",
+            service.registry_seed(),
+            &service.service_id.to_le_bytes()[..],
+            operator.key().as_ref(),
+        ],
+        bump,
+    )]
     pub operator_bond: Account<'info, OperatorBondAccount>,
 
     /// CHECK: operator
     #[account(mut)]
     pub operator: AccountInfo<'info>,
 
-    /// CHECK: PDA wallet owned by the program
-    #[account(mut, address = registry.wallet_key)]
-    pub registry_wallet: AccountInfo<'info>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = PendingWithdrawal::LEN,
+        seeds = [
+            b"pending_withdrawal",
+            service.registry_seed(),
+            &service.service_id.to_le_bytes()[..],
+            operator.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub pending_withdrawal: Account<'info, PendingWithdrawal>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
 
-    #[account(mut, address = registry.manager)]
+    #[account(mut)]
     pub user: Signer<'info>,
 
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction(service_id: u128)]
+pub struct ClaimUnbonded<'info> {
+    pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(
+        seeds = [b"service", registry.key().as_ref(), &service_id.to_le_bytes()[..]],
+        bump,
+        has_one = registry @ ErrorCode::WrongServiceRegistry,
+    )]
+    pub service: Account<'info, ServiceAccount>,
+
+    #[account(
+        mut,
+        close = payer,
+        seeds = [b"pending_withdrawal", service.registry_seed(), &service_id.to_le_bytes()[..], operator.key().as_ref()],
+        bump,
+    )]
+    pub pending_withdrawal: Account<'info, PendingWithdrawal>,
+
+    /// CHECK: Paid the rent of the pending withdrawal, refunded to it
+    #[account(mut, address = pending_withdrawal.payer)]
+    pub payer: AccountInfo<'info>,
+
+    /// CHECK: operator
+    #[account(mut)]
+    pub operator: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"service_escrow", service.registry_seed(), &service_id.to_le_bytes()[..]],
+        bump = service_escrow.bump,
+    )]
+    pub service_escrow: Account<'info, ServiceEscrow>,
+
+    pub bond_token: BondToken<'info>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The operator, or the manager claiming for it
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct Drain<'info> {
     #[account(mut)]
     pub registry: Account<'info, ServiceRegistry>,
 
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
     #[account(mut)]
     pub drainer: Signer<'info>,
 
@@ -2065,6 +4964,18 @@ pub struct Drain<'info> {
     pub registry_wallet: AccountInfo<'info>,
 
     pub system_program: Program<'info, System>,
+
+    /// Vault to drain the slashed bond tokens of, instead of the slashed lamports
+    #[account(mut)]
+    pub bond_token_vault: Option<Box<Account<'info, BondTokenVault>>>,
+
+    #[account(mut)]
+    pub vault_token: Option<Box<Account<'info, TokenAccount>>>,
+
+    #[account(mut)]
+    pub drainer_token: Option<Box<Account<'info, TokenAccount>>>,
+
+    pub token_program: Option<Program<'info, Token>>,
 }
 
 #[derive(Accounts)]
@@ -2072,32 +4983,138 @@ pub struct Slash<'info> {
     #[account(mut)]
     pub registry: Account<'info, ServiceRegistry>,
 
-    #[account(mut)]
+    #[account(mut, has_one = registry @ ErrorCode::WrongServiceRegistry)]
     pub service: Account<'info, ServiceAccount>,
 
     /// CHECK: The wallet where slashed funds are accumulated.
     #[account(mut, address = registry.wallet_key)]
     pub registry_wallet: AccountInfo<'info>,
 
+    #[account(
+        mut,
+        seeds = [b"service_escrow", service.registry_seed(), &service.service_id.to_le_bytes()[..]],
+        bump = service_escrow.bump,
+    )]
+    pub service_escrow: Account<'info, ServiceEscrow>,
+
+    pub bond_token: BondToken<'info>,
+
     #[account(address = service.multisig)]
     pub user: Signer<'info>,
 }
 
+impl<'info> Slash<'info> {
+    /// Moves `amount` slashed from the service escrow to the treasury until drained.
+    fn collect(&mut self, token_bonded: bool, amount: u64) -> Result<()> {
+        if token_bonded {
+            self.bond_token
+                .slash(&self.service, &self.service_escrow, amount)
+        } else {
+            ServiceRegistry::release_escrow(
+                &self.service_escrow.to_account_info(),
+                &self.registry_wallet,
+                amount,
+            )?;
+            self.registry.slashed_funds += amount;
+            Ok(())
+        }
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: u128)]
+pub struct SetInstanceProofs<'info> {
+    pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(
+        mut,
+        seeds = [b"service", registry.key().as_ref(), &service_id.to_le_bytes()[..]],
+        bump,
+        has_one = registry @ ErrorCode::WrongServiceRegistry,
+    )]
+    pub service: Account<'info, ServiceAccount>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    pub user: Signer<'info>,
+}
+
 #[derive(Accounts)]
-pub struct Deploy<'info> {
+#[instruction(service_id: u128)]
+pub struct UpdateOperatorWhitelist<'info> {
+    pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(
+        mut,
+        seeds = [b"service", registry.key().as_ref(), &service_id.to_le_bytes()[..]],
+        bump,
+        has_one = registry @ ErrorCode::WrongServiceRegistry,
+    )]
+    pub service: Account<'info, ServiceAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = service_owner,
+        space = OperatorWhitelist::LEN,
+        seeds = [b"operator_whitelist", service.registry_seed(), &service_id.to_le_bytes()[..]],
+        bump,
+    )]
+    pub operator_whitelist: Account<'info, OperatorWhitelist>,
+
     #[account(mut)]
+    pub service_owner: Signer<'info>,
+
+    /// Token account of the service owner holding the service token
+    pub service_token: Account<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AuditSolvency<'info> {
     pub registry: Account<'info, ServiceRegistry>,
 
+    /// CHECK: The wallet where slashed funds are accumulated.
+    #[account(address = registry.wallet_key)]
+    pub registry_wallet: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: u128, implementation: Pubkey)]
+pub struct Deploy<'info> {
     #[account(mut)]
+    pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(mut, has_one = registry @ ErrorCode::WrongServiceRegistry)]
     pub service: Account<'info, ServiceAccount>,
 
-    /// CHECK: service_owner
-    #[account(address = service.service_owner)]
+    /// CHECK: service_owner, checked against service_token
     pub service_owner: AccountInfo<'info>,
 
-    #[account(mut)]
+    /// Token account of the service owner holding the service token
+    pub service_token: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"registry_multisig", registry.key().as_ref()],
+        bump
+    )]
     pub registry_multisig: Account<'info, RegistryMultisig>,
 
+    /// CHECK: whitelisted multisig implementation, checked against registry_multisig
+    #[account(executable, address = implementation)]
+    pub multisig_implementation: AccountInfo<'info>,
+
+    /// Agent instances registered in the service, the owners of its multisig
+    #[account(seeds = [b"agent_instances_index", service.registry_seed(), &service_id.to_le_bytes()[..]], bump)]
+    pub agent_instances_index: Box<Account<'info, ServiceAgentInstancesIndex>>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
     #[account(mut)]
     pub user: Signer<'info>,
     pub system_program: Program<'info, System>,
@@ -2117,12 +5134,167 @@ pub struct ChangeMultisigPermission<'info> {
     )]
     pub registry_multisig: Account<'info, RegistryMultisig>,
 
-    #[account(mut, address = registry.owner)]
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(target_program: Pubkey, accounts: Vec<ProposalAccountMeta>, data: Vec<u8>)]
+pub struct ProposeMultisigTransaction<'info> {
+    #[account(mut)]
+    pub multisig: Account<'info, MultisigAccount>,
+
+    #[account(
+        init,
+        payer = proposer,
+        space = MultisigProposal::size(multisig.agent_instances.len(), accounts.len(), data.len()),
+        seeds = [b"multisig_proposal", multisig.key().as_ref(), &multisig.proposal_count.to_le_bytes()],
+        bump,
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveMultisigTransaction<'info> {
+    pub multisig: Account<'info, MultisigAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"multisig_proposal", multisig.key().as_ref(), &proposal.proposal_id.to_le_bytes()],
+        bump,
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+
+    pub approver: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteMultisigTransaction<'info> {
+    pub multisig: Account<'info, MultisigAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"multisig_proposal", multisig.key().as_ref(), &proposal.proposal_id.to_le_bytes()],
+        bump,
+    )]
+    pub proposal: Account<'info, MultisigProposal>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateMultisig<'info> {
+    /// CHECK: Built-in multisig, read in its legacy layout by the instruction
+    #[account(mut, owner = crate::ID)]
+    pub multisig: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTimelock<'info> {
+    pub registry: Account<'info, ServiceRegistry>,
+
+    #[account(
+        init,
+        payer = user,
+        space = Timelock::LEN,
+        seeds = [b"timelock", registry.key().as_ref()],
+        bump,
+    )]
+    pub timelock: Account<'info, Timelock>,
+
+    /// Roles of the registry, checked against the signer and handed to the timelock
+    #[account(mut, seeds = [b"roles", registry.key().as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetTimelockDelay<'info> {
+    #[account(mut, seeds = [b"timelock", timelock.registry.as_ref()], bump)]
+    pub timelock: Account<'info, Timelock>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(accounts: Vec<ProposalAccountMeta>, data: Vec<u8>)]
+pub struct QueueTimelockOperation<'info> {
+    #[account(mut, seeds = [b"timelock", timelock.registry.as_ref()], bump)]
+    pub timelock: Account<'info, Timelock>,
+
+    #[account(
+        init,
+        payer = user,
+        space = TimelockOperation::size(accounts.len(), data.len()),
+        seeds = [b"timelock_operation", timelock.key().as_ref(), &timelock.operation_count.to_le_bytes()],
+        bump,
+    )]
+    pub operation: Account<'info, TimelockOperation>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", timelock.registry.as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    #[account(mut)]
     pub user: Signer<'info>,
 
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct ExecuteTimelockOperation<'info> {
+    #[account(seeds = [b"timelock", timelock.registry.as_ref()], bump)]
+    pub timelock: Account<'info, Timelock>,
+
+    #[account(
+        mut,
+        seeds = [b"timelock_operation", timelock.key().as_ref(), &operation.operation_id.to_le_bytes()],
+        bump,
+    )]
+    pub operation: Account<'info, TimelockOperation>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelTimelockOperation<'info> {
+    #[account(seeds = [b"timelock", timelock.registry.as_ref()], bump)]
+    pub timelock: Account<'info, Timelock>,
+
+    #[account(
+        mut,
+        seeds = [b"timelock_operation", timelock.key().as_ref(), &operation.operation_id.to_le_bytes()],
+        bump,
+    )]
+    pub operation: Account<'info, TimelockOperation>,
+
+    /// Roles of the registry, checked against the signer
+    #[account(seeds = [b"roles", timelock.registry.as_ref()], bump)]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    pub user: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct DummyContextForAgentParam<'info> {
     pub agent_param_account: Account<'info, AgentParamAccount>,
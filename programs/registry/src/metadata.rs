@@ -0,0 +1,9 @@
+/// Multibase `f` (base16), CIDv1, dag-pb codec and a sha2-256 multihash of 32 bytes,
+/// as prefixed by the Solidity registry.
+pub const CID_PREFIX: &str = "f01701220";
+
+/// Service metadata URI: the registry base URI followed by the config hash as a
+/// base16 CIDv1.
+pub fn token_uri(base_uri: &str, config_hash: &[u8; 32]) -> String {
+    format!("{base_uri}{CID_PREFIX}{}", hex::encode(config_hash))
+}
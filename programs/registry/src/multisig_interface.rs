@@ -0,0 +1,73 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{hash::hash, instruction::Instruction},
+};
+
+use crate::error::ErrorCode;
+
+/// Sighash preimage of the `create` instruction every multisig implementation must expose.
+/// The instruction discriminator is `sha256("global:create")[..8]`, as generated by Anchor.
+pub const CREATE_SIGHASH_PREIMAGE: &[u8] = b"global:create";
+
+/// Borsh-encoded arguments following the `create` discriminator.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct CreateMultisigArgs {
+    pub agent_instances: Vec<Pubkey>,
+    pub threshold: u32,
+    pub data: Vec<u8>,
+}
+
+pub fn create_discriminator() -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(CREATE_SIGHASH_PREIMAGE).to_bytes()[..8]);
+    discriminator
+}
+
+/// Builds the `create` instruction of a multisig implementation.
+///
+/// Accounts expected by the implementation:
+/// 0. `[writable, signer]` payer funding the multisig account
+/// 1. `[writable]` multisig account created by the implementation
+/// 2. `[]` system program
+///
+/// The implementation must set the 32 bytes of the created multisig address as return data.
+pub fn create_instruction(
+    implementation: &Pubkey,
+    payer: &Pubkey,
+    multisig: &Pubkey,
+    args: &CreateMultisigArgs,
+) -> Result<Instruction> {
+    let mut data = create_discriminator().to_vec();
+    args.serialize(&mut data)?;
+
+    Ok(Instruction {
+        program_id: *implementation,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*multisig, false),
+            AccountMeta::new_readonly(anchor_lang::system_program::ID, false),
+        ],
+        data,
+    })
+}
+
+/// Decodes the multisig address returned by `implementation`.
+pub fn parse_return_data(
+    implementation: &Pubkey,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+) -> Result<Pubkey> {
+    let (program_id, data) = return_data.ok_or(ErrorCode::InvalidMultisigReturnData)?;
+
+    require_keys_eq!(
+        program_id,
+        *implementation,
+        ErrorCode::InvalidMultisigReturnData
+    );
+
+    let bytes: [u8; 32] = data
+        .as_slice()
+        .try_into()
+        .map_err(|_| ErrorCode::InvalidMultisigReturnData)?;
+
+    Ok(Pubkey::new_from_array(bytes))
+}
@@ -0,0 +1,39 @@
+#![allow(unexpected_cfgs)]
+use anchor_lang::prelude::*;
+
+use crate::error::ErrorCode;
+
+/// Instructions `Pauser` holders freeze together, each flagged by one bit of
+/// `ServiceRegistry::paused`.
+#[repr(u8)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseGroup {
+    /// `create`
+    Creation,
+    /// `activate_registration`, `register_agents` and `register_agents_with_signature`
+    Registration,
+    /// `deploy`
+    Deploy,
+    /// `slash`
+    Slash,
+    /// `drain`, and the refunds of `terminate` and `claim_unbonded` unless refunds are
+    /// kept open
+    Payouts,
+}
+
+impl PauseGroup {
+    pub fn flag(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// Error of the instructions of the group while it is paused.
+    pub fn error(self) -> ErrorCode {
+        match self {
+            PauseGroup::Creation => ErrorCode::CreationPaused,
+            PauseGroup::Registration => ErrorCode::RegistrationPaused,
+            PauseGroup::Deploy => ErrorCode::DeployPaused,
+            PauseGroup::Slash => ErrorCode::SlashPaused,
+            PauseGroup::Payouts => ErrorCode::PayoutsPaused,
+        }
+    }
+}
@@ -1,13 +1,55 @@
-use anchor_lang::prelude::*;
+//! Accounts of a service are seeded with its `registry_seed`, the key of its registry
+//! or nothing for legacy services, see `ServiceAccount::registry_seed`.
+
+use anchor_lang::{prelude::*, solana_program::hash::hash};
+use anchor_spl::associated_token::get_associated_token_address;
 
 pub fn registry_wallet_pda(registry: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
     Pubkey::find_program_address(&[b"registry_wallet", &registry.to_bytes()], program_id)
 }
 
-pub fn agent_param_pda(service_id: u128, agent_id: u32, program_id: &Pubkey) -> (Pubkey, u8) {
+pub fn registry_multisig_pda(registry: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"registry_multisig", &registry.to_bytes()], program_id)
+}
+
+pub fn registry_roles_pda(registry: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"roles", &registry.to_bytes()], program_id)
+}
+
+pub fn timelock_pda(registry: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"timelock", &registry.to_bytes()], program_id)
+}
+
+/// Signer of the operations executed by the timelock of `registry`.
+pub fn timelock_authority_pda(registry: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"timelock_authority", &registry.to_bytes()], program_id)
+}
+
+pub fn timelock_operation_pda(
+    timelock: &Pubkey,
+    operation_id: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"timelock_operation",
+            &timelock.to_bytes(),
+            &operation_id.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+pub fn agent_param_pda(
+    registry_seed: &[u8],
+    service_id: u128,
+    agent_id: u32,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
     Pubkey::find_program_address(
         &[
             b"agent_param",
+            registry_seed,
             &service_id.to_le_bytes(),
             &agent_id.to_le_bytes(),
         ],
@@ -15,18 +57,131 @@ pub fn agent_param_pda(service_id: u128, agent_id: u32, program_id: &Pubkey) ->
     )
 }
 
-pub fn service_pda(config_hash: &[u8], program_id: &Pubkey) -> (Pubkey, u8) {
-    Pubkey::find_program_address(&[b"service", &config_hash[..7]], program_id)
+pub fn service_pda(registry: &Pubkey, service_id: u128, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"service", &registry.to_bytes(), &service_id.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Address of a service created before services were keyed by id, `legacy_seed`
+/// being the first 7 bytes of its config hash at creation.
+pub fn legacy_service_pda(legacy_seed: &[u8], program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"service", &legacy_seed[..7]], program_id)
+}
+
+pub fn service_mint_pda(
+    registry_seed: &[u8],
+    service_id: u128,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"service_mint", registry_seed, &service_id.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Associated token account of `owner` for the service token.
+pub fn service_token_address(
+    registry_seed: &[u8],
+    service_id: u128,
+    owner: &Pubkey,
+    program_id: &Pubkey,
+) -> Pubkey {
+    get_associated_token_address(
+        owner,
+        &service_mint_pda(registry_seed, service_id, program_id).0,
+    )
+}
+
+pub fn service_escrow_pda(
+    registry_seed: &[u8],
+    service_id: u128,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"service_escrow", registry_seed, &service_id.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Token account holding the deposit and bonds of a service bonded in `mint`, owned
+/// by its service escrow.
+pub fn escrow_token_address(
+    registry_seed: &[u8],
+    service_id: u128,
+    mint: &Pubkey,
+    program_id: &Pubkey,
+) -> Pubkey {
+    get_associated_token_address(
+        &service_escrow_pda(registry_seed, service_id, program_id).0,
+        mint,
+    )
+}
+
+pub fn service_bond_token_pda(
+    registry_seed: &[u8],
+    service_id: u128,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"service_bond_token",
+            registry_seed,
+            &service_id.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+pub fn operator_whitelist_pda(
+    registry_seed: &[u8],
+    service_id: u128,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"operator_whitelist",
+            registry_seed,
+            &service_id.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+pub fn bond_token_vault_pda(registry: &Pubkey, mint: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"bond_token_vault", &registry.to_bytes(), &mint.to_bytes()],
+        program_id,
+    )
+}
+
+/// Token account holding the slashed funds paid in `mint`, owned by its bond token vault.
+pub fn vault_token_address(registry: &Pubkey, mint: &Pubkey, program_id: &Pubkey) -> Pubkey {
+    get_associated_token_address(&bond_token_vault_pda(registry, mint, program_id).0, mint)
+}
+
+pub fn config_hash_history_pda(service: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config_hash_history", &service.to_bytes()], program_id)
 }
 
-pub fn service_agent_ids_index_pda(service_id: u128, program_id: &Pubkey) -> (Pubkey, u8) {
+pub fn service_agent_ids_index_pda(
+    registry_seed: &[u8],
+    service_id: u128,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
     Pubkey::find_program_address(
-        &[b"service_agent_ids_index", &service_id.to_le_bytes()],
+        &[
+            b"service_agent_ids_index",
+            registry_seed,
+            &service_id.to_le_bytes(),
+        ],
         program_id,
     )
 }
 
 pub fn service_agent_slot_counter_pda(
+    registry_seed: &[u8],
     service_id: u128,
     agent_id: u32,
     program_id: &Pubkey,
@@ -34,6 +189,7 @@ pub fn service_agent_slot_counter_pda(
     Pubkey::find_program_address(
         &[
             b"service_agent_slot",
+            registry_seed,
             &service_id.to_le_bytes(),
             &agent_id.to_le_bytes(),
         ],
@@ -41,9 +197,17 @@ pub fn service_agent_slot_counter_pda(
     )
 }
 
-pub fn agent_instances_index_pda(service_id: u128, program_id: &Pubkey) -> (Pubkey, u8) {
+pub fn agent_instances_index_pda(
+    registry_seed: &[u8],
+    service_id: u128,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
     Pubkey::find_program_address(
-        &[b"agent_instances_index", &service_id.to_le_bytes()],
+        &[
+            b"agent_instances_index",
+            registry_seed,
+            &service_id.to_le_bytes(),
+        ],
         program_id,
     )
 }
@@ -60,6 +224,7 @@ pub fn operator_as_agent_pda(operator: &Pubkey, program_id: &Pubkey) -> (Pubkey,
 }
 
 pub fn service_agent_instance_pda(
+    registry_seed: &[u8],
     service_id: u128,
     agent_id: u32,
     agent_instance: &Pubkey,
@@ -68,6 +233,7 @@ pub fn service_agent_instance_pda(
     Pubkey::find_program_address(
         &[
             b"service_agent_instance_account",
+            registry_seed,
             &service_id.to_le_bytes(),
             &agent_id.to_le_bytes(),
             &agent_instance.to_bytes(),
@@ -92,6 +258,7 @@ pub fn operator_agent_instance_pda(
 }
 
 pub fn operator_agent_instance_index_pda(
+    registry_seed: &[u8],
     service_id: u128,
     operator: &Pubkey,
     program_id: &Pubkey,
@@ -99,6 +266,7 @@ pub fn operator_agent_instance_index_pda(
     Pubkey::find_program_address(
         &[
             b"operator_agent_instance_index",
+            registry_seed,
             &service_id.to_le_bytes(),
             &operator.to_bytes(),
         ],
@@ -106,13 +274,67 @@ pub fn operator_agent_instance_index_pda(
     )
 }
 
-pub fn operator_bond_pda(service_id: u128, operator: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+pub fn operator_nonce_pda(operator: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"operator_nonce", &operator.to_bytes()], program_id)
+}
+
+pub fn operator_bond_pda(
+    registry_seed: &[u8],
+    service_id: u128,
+    operator: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
     Pubkey::find_program_address(
         &[
             b"operator_bond",
+            registry_seed,
+            &service_id.to_le_bytes(),
+            &operator.to_bytes(),
+        ],
+        program_id,
+    )
+}
+
+pub fn pending_withdrawal_pda(
+    registry_seed: &[u8],
+    service_id: u128,
+    operator: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"pending_withdrawal",
+            registry_seed,
             &service_id.to_le_bytes(),
             &operator.to_bytes(),
         ],
         program_id,
     )
 }
+
+pub fn multisig_seed(agent_instances: &[Pubkey]) -> [u8; 32] {
+    let mut seed_data = vec![];
+    for agent in agent_instances {
+        seed_data.extend_from_slice(agent.as_ref());
+    }
+    hash(&seed_data).to_bytes()
+}
+
+pub fn multisig_pda(agent_instances: &[Pubkey], program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"multisig", &multisig_seed(agent_instances)], program_id)
+}
+
+pub fn multisig_proposal_pda(
+    multisig: &Pubkey,
+    proposal_id: u64,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"multisig_proposal",
+            &multisig.to_bytes(),
+            &proposal_id.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
@@ -0,0 +1,54 @@
+#![allow(unexpected_cfgs)]
+use anchor_lang::prelude::*;
+
+/// Privileges checked by the registry against `RegistryRoles`. Each role is
+/// administered by another role, `Admin` by default, whose holders grant and revoke it.
+#[repr(u8)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// Administers every role by default, and switches permissionless mode
+    Admin,
+    /// Creates, configures, deploys and terminates services, relays registrations
+    ServiceManager,
+    /// Drains the slashed funds and creation fees
+    Drainer,
+    /// Freezes and resumes groups of instructions, see `PauseGroup`
+    Pauser,
+    /// Whitelists multisig implementations services deploy through
+    MultisigWhitelister,
+    /// Sets the base URI of the service metadata
+    MetadataEditor,
+    /// Queues timelock operations
+    Proposer,
+    /// Cancels queued timelock operations
+    Guardian,
+}
+
+impl Role {
+    pub const ALL: [Role; 8] = [
+        Role::Admin,
+        Role::ServiceManager,
+        Role::Drainer,
+        Role::Pauser,
+        Role::MultisigWhitelister,
+        Role::MetadataEditor,
+        Role::Proposer,
+        Role::Guardian,
+    ];
+
+    /// Roles the registry owner holds at initialization. `accept_owner` hands over
+    /// those the owner still holds
+    pub const OWNER: [Role; 6] = [
+        Role::Admin,
+        Role::Pauser,
+        Role::MultisigWhitelister,
+        Role::MetadataEditor,
+        Role::Proposer,
+        Role::Guardian,
+    ];
+
+    /// Roles `initialize_timelock` moves to the timelock authority alone, so that the
+    /// actions they allow only run through queued operations
+    pub const TIMELOCKED: [Role; 3] =
+        [Role::Admin, Role::MultisigWhitelister, Role::MetadataEditor];
+}
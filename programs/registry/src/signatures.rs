@@ -0,0 +1,111 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        ed25519_program,
+        hash::hashv,
+        sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+    },
+};
+
+/// Layout of an Ed25519 program instruction checking one signature: signature count and
+/// padding, the seven offsets, then the public key, the signature and the message.
+const OFFSETS_START: usize = 2;
+const PUBKEY_START: usize = OFFSETS_START + 14;
+const SIGNATURE_START: usize = PUBKEY_START + 32;
+const MESSAGE_START: usize = SIGNATURE_START + 64;
+const MESSAGE_SIZE: usize = 32;
+/// Instruction index telling the Ed25519 program to read from its own data.
+const THIS_INSTRUCTION: u16 = u16::MAX;
+
+/// Message an operator signs to let the manager register `agent_instances` for
+/// `agent_ids` on its behalf. `nonce` is the current value of its `OperatorNonce`.
+pub fn registration_message(
+    registry: &Pubkey,
+    service_id: u128,
+    agent_instances: &[Pubkey],
+    agent_ids: &[u32],
+    nonce: u64,
+) -> [u8; MESSAGE_SIZE] {
+    let service_id = service_id.to_le_bytes();
+    let count = (agent_instances.len() as u32).to_le_bytes();
+    let agent_ids: Vec<[u8; 4]> = agent_ids.iter().map(|id| id.to_le_bytes()).collect();
+    let nonce = nonce.to_le_bytes();
+
+    let mut fields: Vec<&[u8]> = vec![b"register_agents", registry.as_ref(), &service_id, &count];
+    fields.extend(
+        agent_instances
+            .iter()
+            .map(|agent_instance| agent_instance.as_ref()),
+    );
+    fields.extend(agent_ids.iter().map(|agent_id| agent_id.as_slice()));
+    fields.push(&nonce);
+
+    hashv(&fields).to_bytes()
+}
+
+/// Message an agent instance signs to prove it holds its key when `operator` registers
+/// it in a service requiring instance proofs.
+pub fn instance_proof_message(
+    registry: &Pubkey,
+    service_id: u128,
+    operator: &Pubkey,
+) -> [u8; MESSAGE_SIZE] {
+    hashv(&[
+        b"agent_instance",
+        registry.as_ref(),
+        &service_id.to_le_bytes(),
+        operator.as_ref(),
+    ])
+    .to_bytes()
+}
+
+/// Data of the Ed25519 program instruction checking `signature` of `signer` over
+/// `message`, all held in the instruction itself.
+pub fn ed25519_instruction_data(
+    signer: &Pubkey,
+    signature: &[u8; 64],
+    message: &[u8; MESSAGE_SIZE],
+) -> Vec<u8> {
+    let mut data = vec![1, 0];
+    for offset in [
+        SIGNATURE_START as u16,
+        THIS_INSTRUCTION,
+        PUBKEY_START as u16,
+        THIS_INSTRUCTION,
+        MESSAGE_START as u16,
+        MESSAGE_SIZE as u16,
+        THIS_INSTRUCTION,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+    data
+}
+
+/// Whether an instruction of the transaction before the current one is the Ed25519
+/// program checking a signature of `signer` over `message`. The transaction only gets
+/// here if every such check passed.
+pub fn is_signed(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8; MESSAGE_SIZE],
+) -> Result<bool> {
+    let current = load_current_index_checked(instructions)? as usize;
+    let expected = ed25519_instruction_data(signer, &[0; 64], message);
+
+    for index in 0..current {
+        let instruction = load_instruction_at_checked(index, instructions)?;
+        let data = &instruction.data;
+        if instruction.program_id == ed25519_program::ID
+            && data.len() == expected.len()
+            && data[..SIGNATURE_START] == expected[..SIGNATURE_START]
+            && data[MESSAGE_START..] == expected[MESSAGE_START..]
+        {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
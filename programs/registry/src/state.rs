@@ -1,36 +1,316 @@
 #![allow(unexpected_cfgs)]
 use anchor_lang::{prelude::*, Discriminator};
 
-use crate::{constants::*, service_state::ServiceState};
+use crate::{constants::*, error::ErrorCode, roles::Role, service_state::ServiceState};
 
+/// In `permissionless` mode anyone can create and configure a service it owns. Every
+/// service pays `creation_fee` into the registry wallet, whoever creates it; the fees are
+/// counted in `accrued_fees` until drained with the slashed funds. A new owner, manager
+/// or drainer is first proposed as pending and takes over once accepting. `paused` holds
+/// one `PauseGroup::flag` per frozen group of instructions, `refunds_open` lets refunds
+/// through paused payouts. `unbonding_delay` is how many seconds unbonded amounts wait in
+/// a `PendingWithdrawal` before `claim_unbonded` pays them out. Registries initialized
+/// before these fields are grown to `REGISTRY_ACCOUNT_SIZE` by `migrate_registry` and
+/// read them unset.
 #[account]
 pub struct ServiceRegistry {
-    pub name: String,       // 4 bytes (length prefix) + max_len
-    pub symbol: String,     // 4 bytes + max_len
-    pub base_uri: String,   // 4 bytes + max_len
-    pub owner: Pubkey,      // 32 bytes
-    pub manager: Pubkey,    // 32 bytes
-    pub drainer: Pubkey,    // 32 bytes
-    pub slashed_funds: u64, // 8 bytes
-    pub total_supply: u128, // 16 bytes
-    pub version: String,    // 4 bytes + FIXED_SIZE
-    pub locked: bool,       // 1 byte
-    pub wallet_key: Pubkey, // 32 bytes
-    pub wallet_bump: u8,    // 1 byte
-}
-
-/// PDA seeds: ["service", config_hash[..7]]
+    pub name: String,            // 4 bytes (length prefix) + max_len
+    pub symbol: String,          // 4 bytes + max_len
+    pub base_uri: String,        // 4 bytes + max_len
+    pub owner: Pubkey,           // 32 bytes
+    pub manager: Pubkey,         // 32 bytes
+    pub drainer: Pubkey,         // 32 bytes
+    pub slashed_funds: u64,      // 8 bytes
+    pub total_supply: u128,      // 16 bytes
+    pub version: String,         // 4 bytes + FIXED_SIZE
+    pub locked: bool,            // 1 byte
+    pub wallet_key: Pubkey,      // 32 bytes
+    pub wallet_bump: u8,         // 1 byte
+    pub permissionless: bool,    // 1 byte
+    pub creation_fee: u64,       // 8 bytes
+    pub accrued_fees: u64,       // 8 bytes
+    pub pending_owner: Pubkey,   // 32 bytes
+    pub pending_manager: Pubkey, // 32 bytes
+    pub pending_drainer: Pubkey, // 32 bytes
+    pub paused: u8,              // 1 byte
+    pub refunds_open: bool,      // 1 byte
+    pub unbonding_delay: i64,    // 8 bytes
+}
+
+/// PDA seeds: ["roles", registry]
+///
+/// Holders of each `Role`, indexed by the role, and the role administering it. Every
+/// privileged instruction checks its signer here; `owner`, `manager` and `drainer` of
+/// the registry are the holders `accept_owner`, `accept_manager` and `accept_drainer`
+/// hand their roles over from.
+#[account]
+pub struct RegistryRoles {
+    pub registry: Pubkey,
+    pub roles: Vec<RoleMembers>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct RoleMembers {
+    pub admin_role: Role,
+    pub members: Vec<Pubkey>,
+}
+
+impl RoleMembers {
+    pub const LEN: usize = U8_SIZE + 4 + MAX_ROLE_MEMBERS * PUBKEY_SIZE;
+}
+
+impl RegistryRoles {
+    pub const LEN: usize = 8 + PUBKEY_SIZE + 4 + Role::ALL.len() * RoleMembers::LEN;
+
+    /// Roles of a new registry: `owner` holds every role but those of the manager and
+    /// the drainer, and `Admin` administers them all.
+    pub fn new(registry: Pubkey, owner: Pubkey, manager: Pubkey, drainer: Pubkey) -> Self {
+        let roles = Role::ALL
+            .iter()
+            .map(|role| RoleMembers {
+                admin_role: Role::Admin,
+                members: vec![match role {
+                    Role::ServiceManager => manager,
+                    Role::Drainer => drainer,
+                    _ => owner,
+                }],
+            })
+            .collect();
+
+        Self { registry, roles }
+    }
+
+    pub fn has_role(&self, role: Role, key: &Pubkey) -> bool {
+        self.roles[role as usize].members.contains(key)
+    }
+
+    pub fn check_role(&self, role: Role, key: &Pubkey) -> Result<()> {
+        require!(self.has_role(role, key), ErrorCode::MissingRole);
+        Ok(())
+    }
+
+    pub fn admin_role(&self, role: Role) -> Role {
+        self.roles[role as usize].admin_role
+    }
+
+    /// Adds `key` to the holders of `role`, returning whether it was missing.
+    pub fn grant(&mut self, role: Role, key: Pubkey) -> Result<bool> {
+        let members = &mut self.roles[role as usize].members;
+        if members.contains(&key) {
+            return Ok(false);
+        }
+        require!(
+            members.len() < MAX_ROLE_MEMBERS,
+            ErrorCode::MaxRoleMembersReached
+        );
+        members.push(key);
+        Ok(true)
+    }
+
+    /// Fails when `key` is the last holder of `Admin` and `role` is `Admin`: without an
+    /// admin no role could be granted again.
+    pub fn check_keeps_admin(&self, role: Role, key: &Pubkey) -> Result<()> {
+        let admins = &self.roles[Role::Admin as usize].members;
+        require!(
+            role != Role::Admin || admins.len() != 1 || admins[0] != *key,
+            ErrorCode::LastAdmin
+        );
+        Ok(())
+    }
+
+    /// Removes `key` from the holders of `role`, returning whether it held it.
+    pub fn revoke(&mut self, role: Role, key: &Pubkey) -> bool {
+        let members = &mut self.roles[role as usize].members;
+        match members.iter().position(|member| member == key) {
+            Some(position) => {
+                members.swap_remove(position);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves each of `roles` that `from` still holds over to `to`. Roles `from` gave up,
+    /// e.g. to a timelock, stay with their current members.
+    pub fn hand_over(&mut self, roles: &[Role], from: &Pubkey, to: Pubkey) -> Result<()> {
+        for role in roles {
+            if self.revoke(*role, from) {
+                self.grant(*role, to)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// PDA seeds: ["service", service_id]
+///
+/// `service_owner` mirrors the holder of the service token, the single token of the
+/// `["service_mint", service_id]` mint, and is refreshed whenever the holder is checked.
+/// `require_instance_proofs` makes every agent instance prove it holds its key when
+/// registered. `cleanup_pending` is set by `terminate` until `cleanup_service` has closed
+/// the agent PDAs, `cleanup_cursor` of them so far. `registry` is the registry the
+/// service was created in, the only one instructions on the service accept.
+/// `legacy_seeds` marks a service moved by `migrate_service`, whose accounts stay at
+/// the seeds without the registry key, see [`ServiceAccount::registry_seed`].
 #[account]
 pub struct ServiceAccount {
-    pub service_id: u128,             // 16 bytes
-    pub service_owner: Pubkey,        // 32 bytes
-    pub security_deposit: u64,        // 8 bytes
-    pub multisig: Pubkey,             // 32 bytes
-    pub config_hash: [u8; 32],        // 32 bytes
-    pub threshold: u32,               // 4 bytes
-    pub max_num_agent_instances: u32, // 4 bytes
-    pub num_agent_instances: u32,     // 4 bytes
-    pub state: ServiceState,          // 1 byte
+    pub registry: Pubkey,              // 32 bytes
+    pub service_id: u128,              // 16 bytes
+    pub service_owner: Pubkey,         // 32 bytes
+    pub security_deposit: u64,         // 8 bytes
+    pub multisig: Pubkey,              // 32 bytes
+    pub config_hash: [u8; 32],         // 32 bytes
+    pub threshold: u32,                // 4 bytes
+    pub max_num_agent_instances: u32,  // 4 bytes
+    pub num_agent_instances: u32,      // 4 bytes
+    pub state: ServiceState,           // 1 byte
+    pub require_instance_proofs: bool, // 1 byte
+    pub cleanup_pending: bool,         // 1 byte
+    pub cleanup_cursor: u32,           // 4 bytes
+    pub legacy_seeds: bool,            // 1 byte
+}
+
+impl ServiceAccount {
+    pub const LEN: usize = 8 // discriminator
+        + PUBKEY_SIZE // registry
+        + U128_SIZE // service_id
+        + PUBKEY_SIZE // service_owner
+        + U64_SIZE // security_deposit
+        + PUBKEY_SIZE // multisig
+        + FIXED_SIZE // config_hash
+        + 4 // threshold
+        + 4 // max_num_agent_instances
+        + 4 // num_agent_instances
+        + U8_SIZE // state
+        + BOOL_SIZE // require_instance_proofs
+        + BOOL_SIZE // cleanup_pending
+        + 4 // cleanup_cursor
+        + BOOL_SIZE; // legacy_seeds
+
+    /// Registry part of the seeds of the accounts of the service, empty for legacy
+    /// services whose accounts were created before services were keyed by registry.
+    pub fn registry_seed(&self) -> &[u8] {
+        if self.legacy_seeds {
+            &[]
+        } else {
+            self.registry.as_ref()
+        }
+    }
+}
+
+/// Layout of the services created at `["service", config_hash[..7]]`, before the
+/// service id PDAs, read by `migrate_service`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct LegacyServiceAccount {
+    pub service_id: u128,
+    pub service_owner: Pubkey,
+    pub security_deposit: u64,
+    pub multisig: Pubkey,
+    pub config_hash: [u8; 32],
+    pub threshold: u32,
+    pub max_num_agent_instances: u32,
+    pub num_agent_instances: u32,
+    pub state: ServiceState,
+}
+
+/// PDA seeds: ["config_hash_history", service]
+#[account]
+pub struct ConfigHashHistory {
+    pub service_id: u128,
+    pub records: Vec<ConfigHashRecord>,
+}
+
+impl ConfigHashHistory {
+    pub fn size(record_count: usize) -> usize {
+        8 +                                         // discriminator
+        U128_SIZE +                                 // service_id
+        4 + record_count * ConfigHashRecord::LEN // Vec<ConfigHashRecord>
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigHashRecord {
+    pub config_hash: [u8; 32],
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+impl ConfigHashRecord {
+    pub const LEN: usize = FIXED_SIZE + U64_SIZE + U64_SIZE;
+}
+
+/// One page of a `ConfigHashHistory`, as returned by `get_config_hash_history`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigHashHistoryPage {
+    pub total: u32,
+    pub records: Vec<ConfigHashRecord>,
+}
+
+/// Outcome of one `audit_solvency` page, in lamports unless noted otherwise.
+///
+/// Token-bonded services are left out of the totals and only listed in
+/// `insolvent_services` when their escrow token account holds less than they owe.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct SolvencyReport {
+    pub services: u32,
+    pub operator_bonds: u32,
+    pub security_deposits: u64,
+    pub bonds: u64,
+    /// Unbonded amounts not claimed yet, still held by the escrows
+    pub pending_withdrawals: u64,
+    /// Escrow lamports of the audited services above their rent
+    pub escrow_balance: u64,
+    pub slashed_funds: u64,
+    pub accrued_fees: u64,
+    /// Registry wallet lamports above its rent
+    pub treasury_balance: u64,
+    /// Shortfall of the escrows and of the registry wallet, summed
+    pub deficit: u64,
+    pub insolvent_services: Vec<u128>,
+}
+
+/// PDA seeds: ["service_escrow", service_id]
+///
+/// Holds the security deposit and operator bonds of the service in lamports, above
+/// its rent. Token-bonded services hold them in its associated token account instead.
+#[account]
+pub struct ServiceEscrow {
+    pub service_id: u128,
+    pub bump: u8,
+}
+
+impl ServiceEscrow {
+    pub const LEN: usize = 8 + U128_SIZE + U8_SIZE;
+}
+
+/// PDA seeds: ["service_bond_token", service_id]
+///
+/// Present only for services whose security deposit and operator bonds are paid in
+/// `mint` instead of lamports.
+#[account]
+pub struct ServiceBondToken {
+    pub service_id: u128,
+    pub mint: Pubkey,
+}
+
+impl ServiceBondToken {
+    pub const LEN: usize = 8 + U128_SIZE + PUBKEY_SIZE;
+}
+
+/// PDA seeds: ["bond_token_vault", registry, mint]
+///
+/// Treasury of the slashed funds paid in `mint`, held by its associated token account
+/// for `mint`, the vault token account.
+#[account]
+pub struct BondTokenVault {
+    pub registry: Pubkey,
+    pub mint: Pubkey,
+    pub slashed_funds: u64,
+    pub bump: u8,
+}
+
+impl BondTokenVault {
+    pub const LEN: usize = 8 + PUBKEY_SIZE + PUBKEY_SIZE + U64_SIZE + U8_SIZE;
 }
 
 /// PDA seeds: ["agent_param", service_id, agent_id]
@@ -108,6 +388,55 @@ impl OperatorBondAccount {
     pub const LEN: usize = 8 + U128_SIZE + PUBKEY_SIZE + U64_SIZE;
 }
 
+/// PDA seeds: ["pending_withdrawal", service_id, operator]
+///
+/// Refunds of the unbonded instances of an operator, paid out by `claim_unbonded` from
+/// `unlock_time` on and slashable by the service multisig until then. Each `unbond`
+/// batch adds to `amount` and restarts the delay for all of it, so the multisig has a
+/// full delay to slash the latest refunds and those before them. `payer` paid its rent
+/// in the first batch and gets it back when the withdrawal is claimed.
+#[account]
+pub struct PendingWithdrawal {
+    pub service_id: u128,
+    pub operator: Pubkey,
+    pub amount: u64,
+    pub unlock_time: i64,
+    pub payer: Pubkey,
+}
+
+impl PendingWithdrawal {
+    pub const LEN: usize = 8 + U128_SIZE + PUBKEY_SIZE + U64_SIZE + U64_SIZE + PUBKEY_SIZE;
+}
+
+/// PDA seeds: ["operator_nonce", operator]
+///
+/// Signed into every registration the manager relays for `operator`, and bumped by
+/// each one so a signature is never used twice.
+#[account]
+pub struct OperatorNonce {
+    pub nonce: u64,
+}
+
+impl OperatorNonce {
+    pub const LEN: usize = 8 + U64_SIZE;
+}
+
+/// PDA seeds: ["operator_whitelist", service_id]
+///
+/// Operators the service owner lets register agent instances in the service. Only
+/// enforced while `check` is set; a service without one accepts every operator.
+#[account]
+pub struct OperatorWhitelist {
+    pub service_id: u128,
+    pub check: bool,
+    pub operators: Vec<Pubkey>,
+}
+
+impl OperatorWhitelist {
+    pub const LEN: usize =
+        8 + U128_SIZE + BOOL_SIZE + 4 + (MAX_WHITELISTED_OPERATORS * PUBKEY_SIZE);
+}
+
 #[account]
 pub struct RegistryMultisig {
     pub authorized_multisigs: Vec<Pubkey>,
@@ -119,11 +448,13 @@ impl RegistryMultisig {
     }
 }
 
+/// PDA seeds: ["multisig", sha256(agent_instances)]
 #[account]
 pub struct MultisigAccount {
     pub agent_instances: Vec<Pubkey>,
     pub threshold: u32,
     pub data: Vec<u8>,
+    pub proposal_count: u64,
 }
 
 impl MultisigAccount {
@@ -131,6 +462,104 @@ impl MultisigAccount {
         8 +                     // discriminator
         4 + agent_count * 32 +  // Vec<Pubkey>
         4 +                     // threshold (u32)
-        4 + data_len // Vec<u8>
+        4 + data_len +          // Vec<u8>
+        U64_SIZE // proposal_count
+    }
+
+    pub fn is_owner(&self, key: &Pubkey) -> bool {
+        self.agent_instances.contains(key)
+    }
+}
+
+/// Layout of the built-in multisigs deployed before proposals, without
+/// `proposal_count`, read by `migrate_multisig`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct LegacyMultisigAccount {
+    pub agent_instances: Vec<Pubkey>,
+    pub threshold: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct ProposalAccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl ProposalAccountMeta {
+    pub const LEN: usize = PUBKEY_SIZE + BOOL_SIZE + BOOL_SIZE;
+}
+
+/// PDA seeds: ["multisig_proposal", multisig, proposal_id]
+#[account]
+pub struct MultisigProposal {
+    pub multisig: Pubkey,
+    pub proposal_id: u64,
+    pub proposer: Pubkey,
+    pub program_id: Pubkey,
+    pub accounts: Vec<ProposalAccountMeta>,
+    pub data: Vec<u8>,
+    pub approvals: Vec<Pubkey>,
+    pub executed: bool,
+}
+
+impl MultisigProposal {
+    pub fn size(owner_count: usize, account_count: usize, data_len: usize) -> usize {
+        8 +                                             // discriminator
+        PUBKEY_SIZE +                                   // multisig
+        U64_SIZE +                                      // proposal_id
+        PUBKEY_SIZE +                                   // proposer
+        PUBKEY_SIZE +                                   // program_id
+        4 + account_count * ProposalAccountMeta::LEN +  // Vec<ProposalAccountMeta>
+        4 + data_len +                                  // Vec<u8>
+        4 + owner_count * PUBKEY_SIZE +                 // Vec<Pubkey> approvals
+        BOOL_SIZE // executed
+    }
+}
+
+/// PDA seeds: ["timelock", registry]
+///
+/// Delays the admin actions of a registry granting its roles to the timelock authority,
+/// the PDA ["timelock_authority", registry]: `Proposer` holders queue registry
+/// instructions it signs, executable `delay` seconds later unless a `Guardian`
+/// holder cancels them.
+#[account]
+pub struct Timelock {
+    pub registry: Pubkey,
+    pub delay: i64,
+    pub operation_count: u64,
+    pub authority_bump: u8,
+}
+
+impl Timelock {
+    pub const LEN: usize = 8 + PUBKEY_SIZE + U64_SIZE + U64_SIZE + U8_SIZE;
+}
+
+/// PDA seeds: ["timelock_operation", timelock, operation_id]
+#[account]
+pub struct TimelockOperation {
+    pub timelock: Pubkey,
+    pub operation_id: u64,
+    pub proposer: Pubkey,
+    pub accounts: Vec<ProposalAccountMeta>,
+    pub data: Vec<u8>,
+    /// Unix timestamp from which the operation can be executed
+    pub eta: i64,
+    pub executed: bool,
+    pub cancelled: bool,
+}
+
+impl TimelockOperation {
+    pub fn size(account_count: usize, data_len: usize) -> usize {
+        8 +                                             // discriminator
+        PUBKEY_SIZE +                                   // timelock
+        U64_SIZE +                                      // operation_id
+        PUBKEY_SIZE +                                   // proposer
+        4 + account_count * ProposalAccountMeta::LEN +  // Vec<ProposalAccountMeta>
+        4 + data_len +                                  // Vec<u8>
+        U64_SIZE +                                      // eta
+        BOOL_SIZE +                                     // executed
+        BOOL_SIZE // cancelled
     }
 }
@@ -0,0 +1,224 @@
+//! Serialized accounts at their largest against the space allocated for them.
+//!
+//! The allocations are computed field by field, not from `size_of`, which pads
+//! differently on the host and on SBF. A field added without growing its account's
+//! size fails here.
+
+mod common;
+
+use anchor_lang::{prelude::*, AccountSerialize};
+use common::*;
+use registry::{
+    constants::*,
+    roles::Role,
+    service_state::ServiceState,
+    state::{
+        AgentParamAccount, BondTokenVault, OperatorAgentInstanceAccount, OperatorBondAccount,
+        OperatorNonce, OperatorWhitelist, PendingWithdrawal, RegistryRoles, RoleMembers,
+        ServiceAccount, ServiceAgentInstanceAccount, ServiceAgentInstancesIndex, ServiceBondToken,
+        ServiceEscrow, ServiceRegistry, Timelock,
+    },
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::{instruction::InstructionError, signature::Signer, transaction::TransactionError};
+
+fn serialized_len<T: AccountSerialize>(account: &T) -> usize {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data.len()
+}
+
+fn max_registry() -> ServiceRegistry {
+    ServiceRegistry {
+        name: "n".repeat(MAX_NAME_LENGTH),
+        symbol: "s".repeat(MAX_SYMBOL_LENGTH),
+        base_uri: "u".repeat(MAX_URI_LENGTH),
+        owner: Pubkey::new_unique(),
+        manager: Pubkey::new_unique(),
+        drainer: Pubkey::new_unique(),
+        slashed_funds: u64::MAX,
+        total_supply: u128::MAX,
+        version: "v".repeat(FIXED_SIZE),
+        locked: true,
+        wallet_key: Pubkey::new_unique(),
+        wallet_bump: u8::MAX,
+        permissionless: true,
+        creation_fee: u64::MAX,
+        accrued_fees: u64::MAX,
+        pending_owner: Pubkey::new_unique(),
+        pending_manager: Pubkey::new_unique(),
+        pending_drainer: Pubkey::new_unique(),
+        paused: u8::MAX,
+        refunds_open: true,
+        unbonding_delay: i64::MAX,
+    }
+}
+
+fn full_keys(count: usize) -> Vec<Pubkey> {
+    (0..count).map(|_| Pubkey::new_unique()).collect()
+}
+
+#[test]
+fn registry_and_service_fit_their_space() {
+    assert_eq!(serialized_len(&max_registry()), REGISTRY_ACCOUNT_SIZE);
+
+    let service = ServiceAccount {
+        registry: Pubkey::new_unique(),
+        service_id: u128::MAX,
+        service_owner: Pubkey::new_unique(),
+        security_deposit: u64::MAX,
+        multisig: Pubkey::new_unique(),
+        config_hash: [u8::MAX; 32],
+        threshold: u32::MAX,
+        max_num_agent_instances: u32::MAX,
+        num_agent_instances: u32::MAX,
+        state: ServiceState::TerminatedBonded,
+        require_instance_proofs: true,
+        cleanup_pending: true,
+        cleanup_cursor: u32::MAX,
+        legacy_seeds: true,
+    };
+    assert_eq!(serialized_len(&service), ServiceAccount::LEN);
+}
+
+#[test]
+fn fixed_size_accounts_fit_their_space() {
+    let roles = RegistryRoles {
+        registry: Pubkey::new_unique(),
+        roles: Role::ALL
+            .iter()
+            .map(|role| RoleMembers {
+                admin_role: *role,
+                members: full_keys(MAX_ROLE_MEMBERS),
+            })
+            .collect(),
+    };
+    assert_eq!(serialized_len(&roles), RegistryRoles::LEN);
+
+    let whitelist = OperatorWhitelist {
+        service_id: u128::MAX,
+        check: true,
+        operators: full_keys(MAX_WHITELISTED_OPERATORS),
+    };
+    assert_eq!(serialized_len(&whitelist), OperatorWhitelist::LEN);
+
+    let instances = ServiceAgentInstancesIndex {
+        service_agent_instances: full_keys(MAX_AGENT_INSTANCES_PER_SERVICE),
+    };
+    assert_eq!(serialized_len(&instances), ServiceAgentInstancesIndex::LEN);
+
+    let escrow = ServiceEscrow {
+        service_id: u128::MAX,
+        bump: u8::MAX,
+    };
+    assert_eq!(serialized_len(&escrow), ServiceEscrow::LEN);
+
+    let bond_token = ServiceBondToken {
+        service_id: u128::MAX,
+        mint: Pubkey::new_unique(),
+    };
+    assert_eq!(serialized_len(&bond_token), ServiceBondToken::LEN);
+
+    let vault = BondTokenVault {
+        registry: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        slashed_funds: u64::MAX,
+        bump: u8::MAX,
+    };
+    assert_eq!(serialized_len(&vault), BondTokenVault::LEN);
+
+    let agent_param = AgentParamAccount {
+        agent_id: u32::MAX,
+        slots: u32::MAX,
+        bond: u64::MAX,
+    };
+    assert_eq!(serialized_len(&agent_param), AgentParamAccount::LEN);
+
+    let agent_instance = ServiceAgentInstanceAccount {
+        service_id: u128::MAX,
+        agent_id: u32::MAX,
+        agent_instance: Pubkey::new_unique(),
+    };
+    assert_eq!(
+        serialized_len(&agent_instance),
+        ServiceAgentInstanceAccount::LEN
+    );
+
+    let operator_instance = OperatorAgentInstanceAccount {
+        operator: Pubkey::new_unique(),
+        service_agent_instance: Pubkey::new_unique(),
+    };
+    assert_eq!(
+        serialized_len(&operator_instance),
+        OperatorAgentInstanceAccount::LEN
+    );
+
+    let bond = OperatorBondAccount {
+        service_id: u128::MAX,
+        operator: Pubkey::new_unique(),
+        bond: u64::MAX,
+    };
+    assert_eq!(serialized_len(&bond), OperatorBondAccount::LEN);
+
+    let pending = PendingWithdrawal {
+        service_id: u128::MAX,
+        operator: Pubkey::new_unique(),
+        amount: u64::MAX,
+        unlock_time: i64::MAX,
+        payer: Pubkey::new_unique(),
+    };
+    assert_eq!(serialized_len(&pending), PendingWithdrawal::LEN);
+
+    let nonce = OperatorNonce { nonce: u64::MAX };
+    assert_eq!(serialized_len(&nonce), OperatorNonce::LEN);
+
+    let timelock = Timelock {
+        registry: Pubkey::new_unique(),
+        delay: i64::MAX,
+        operation_count: u64::MAX,
+        authority_bump: u8::MAX,
+    };
+    assert_eq!(serialized_len(&timelock), Timelock::LEN);
+}
+
+#[tokio::test]
+async fn migrates_registry_to_its_current_size() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let registry_key = env.registry.pubkey();
+
+    // Registries initialized before the permissionless fields hold 1030 bytes
+    let mut account = env.raw_account(&registry_key).await.unwrap();
+    account.data.truncate(1030);
+    env.ctx.set_account(&registry_key, &account.into());
+
+    let ix = env.client.migrate_registry(&owner.pubkey());
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let account = env.raw_account(&registry_key).await.unwrap();
+    assert_eq!(account.data.len(), REGISTRY_ACCOUNT_SIZE);
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+    assert!(account.lamports >= rent.minimum_balance(REGISTRY_ACCOUNT_SIZE));
+
+    // A base URI of the maximum length now fits, a longer one is rejected
+    let ix_uri = env
+        .client
+        .set_base_uri(&owner.pubkey(), "u".repeat(MAX_URI_LENGTH));
+    env.send(&[ix_uri], &[&owner]).await.unwrap();
+    let registry: ServiceRegistry = env.account(&registry_key).await;
+    assert_eq!(registry.base_uri.len(), MAX_URI_LENGTH);
+    let ix_uri = env
+        .client
+        .set_base_uri(&owner.pubkey(), "u".repeat(MAX_URI_LENGTH + 1));
+    assert!(matches!(
+        env.send(&[ix_uri], &[&owner]).await,
+        Err(BanksClientError::TransactionError(
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        ))
+    ));
+
+    // Migrating again leaves the registry as it is
+    let ix = env.client.migrate_registry(&env.manager.pubkey());
+    env.send_as_manager(ix).await.unwrap();
+    let account = env.raw_account(&registry_key).await.unwrap();
+    assert_eq!(account.data.len(), REGISTRY_ACCOUNT_SIZE);
+}
@@ -0,0 +1,438 @@
+mod common;
+
+use anchor_lang::{prelude::*, solana_program::program_pack::Pack};
+use anchor_spl::{
+    associated_token::{
+        get_associated_token_address,
+        spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    token::spl_token,
+};
+use common::*;
+use registry::{
+    error::ErrorCode,
+    service_state::ServiceState,
+    state::{BondTokenVault, ServiceAccount, ServiceBondToken, SolvencyReport},
+    AgentParams,
+};
+use registry_client::{AuditedService, SlashTarget};
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+};
+
+const BOND: u64 = 1_000;
+
+async fn token_amount(env: &mut TestEnv, token_account: &Pubkey) -> u64 {
+    let account = env.raw_account(token_account).await.unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+/// Creates a bond token minted by the registry owner.
+async fn create_mint(env: &mut TestEnv) -> Pubkey {
+    let owner = env.owner.insecure_clone();
+    let mint = Keypair::new();
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+    let create = anchor_lang::solana_program::system_instruction::create_account(
+        &owner.pubkey(),
+        &mint.pubkey(),
+        rent.minimum_balance(spl_token::state::Mint::LEN),
+        spl_token::state::Mint::LEN as u64,
+        &spl_token::ID,
+    );
+    let initialize = spl_token::instruction::initialize_mint(
+        &spl_token::ID,
+        &mint.pubkey(),
+        &owner.pubkey(),
+        None,
+        0,
+    )
+    .unwrap();
+    env.send(&[create, initialize], &[&owner, &mint])
+        .await
+        .unwrap();
+    mint.pubkey()
+}
+
+/// Creates the token account of `holder` and mints `amount` bond tokens into it.
+async fn mint_to(env: &mut TestEnv, mint: &Pubkey, holder: &Pubkey, amount: u64) -> Pubkey {
+    let owner = env.owner.insecure_clone();
+    let token_account = get_associated_token_address(holder, mint);
+    let create =
+        create_associated_token_account_idempotent(&owner.pubkey(), holder, mint, &spl_token::ID);
+    let mint_to = spl_token::instruction::mint_to(
+        &spl_token::ID,
+        mint,
+        &token_account,
+        &owner.pubkey(),
+        &[],
+        amount,
+    )
+    .unwrap();
+    env.send(&[create, mint_to], &[&owner]).await.unwrap();
+    token_account
+}
+
+#[tokio::test]
+async fn runs_lifecycle_with_bond_token() {
+    let mut env = setup().await;
+    let mint = create_mint(&mut env).await;
+    let manager = env.manager.pubkey();
+    let manager_token = mint_to(&mut env, &mint, &manager, 10 * BOND).await;
+    let vault_token = env.client.vault_token(&mint);
+    let wallet = env.registry_wallet();
+    let wallet_rent = env.balance(&wallet).await;
+
+    let agent_ids = vec![1u32, 2];
+    let agent_params: Vec<AgentParams> = agent_ids
+        .iter()
+        .map(|agent_id| AgentParams {
+            slots: 1,
+            bond: BOND * *agent_id as u64,
+        })
+        .collect();
+    let security_deposit = 2 * BOND;
+    let total_bond = 3 * BOND;
+
+    let (service_id, service) = env.create_service([120u8; 32]).await;
+    env.register_agent_ids(service_id, service, &agent_ids, &agent_params, 2)
+        .await;
+
+    let ix = env
+        .client
+        .set_service_bond_token(&env.manager.pubkey(), service_id, &mint);
+    env.send_as_manager(ix).await.unwrap();
+    let bond_token: ServiceBondToken = env
+        .account(&service_bond_token_pda(env.client.registry_seed(), service_id, &registry::ID).0)
+        .await;
+    assert_eq!(bond_token.mint, mint);
+
+    // the security deposit is paid in bond tokens, into the service escrow
+    let escrow_token = env.client.escrow_token(service_id, &mint);
+    let ix = env.client.activate_registration(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &env.service_owner.pubkey(),
+        Some(&mint),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(
+        token_amount(&mut env, &escrow_token).await,
+        security_deposit
+    );
+    assert_eq!(token_amount(&mut env, &vault_token).await, 0);
+    assert_eq!(env.balance(&wallet).await, wallet_rent);
+
+    // so are the operator bonds
+    let operator = Keypair::new();
+    let operator_token = mint_to(&mut env, &mint, &operator.pubkey(), 0).await;
+    let agent_instance_keys: Vec<Keypair> = agent_ids.iter().map(|_| Keypair::new()).collect();
+    let agent_instances: Vec<Pubkey> = agent_instance_keys
+        .iter()
+        .map(|agent_instance| agent_instance.pubkey())
+        .collect();
+    let ixs = env
+        .signed_registration(
+            service_id,
+            service,
+            &operator,
+            &agent_ids,
+            &agent_instances,
+            Some(&mint),
+        )
+        .await;
+    let manager_key = env.manager.insecure_clone();
+    env.send(&ixs, &[&manager_key]).await.unwrap();
+    assert_eq!(
+        token_amount(&mut env, &escrow_token).await,
+        security_deposit + total_bond
+    );
+    assert_eq!(
+        token_amount(&mut env, &manager_token).await,
+        10 * BOND - security_deposit - total_bond
+    );
+
+    // token-bonded services are audited against their escrow token account only
+    let ix = env.client.audit_solvency(&[AuditedService {
+        service_id,
+        bond_mint: Some(mint),
+        operators: vec![operator.pubkey()],
+        pending_withdrawals: vec![],
+    }]);
+    let report: SolvencyReport = env.view(ix).await;
+    assert_eq!(report.services, 1);
+    assert_eq!(report.operator_bonds, 1);
+    assert_eq!(report.security_deposits + report.bonds, 0);
+    assert!(report.insolvent_services.is_empty());
+
+    // slashed bond tokens move to the treasury vault until drained
+    let registered = RegisteredService {
+        service_id,
+        service,
+        agent_ids: agent_ids.clone(),
+        operator: operator.insecure_clone(),
+        agent_instance_keys,
+        agent_instances: agent_instances.clone(),
+    };
+    let multisig = env.deploy_builtin(&registered).await;
+    for agent_instance in &agent_instances {
+        env.transfer(agent_instance, LAMPORTS_PER_SOL).await;
+    }
+    let slash_amount = BOND / 2;
+    let slash = env.client.slash(
+        &multisig,
+        &service,
+        service_id,
+        &[SlashTarget {
+            agent_instance: agent_instances[0],
+            operator: operator.pubkey(),
+            amount: slash_amount,
+        }],
+        Some(&mint),
+    );
+    env.execute_through_multisig(&registered, multisig, &slash)
+        .await
+        .unwrap();
+    let vault: BondTokenVault = env
+        .account(&bond_token_vault_pda(&env.registry.pubkey(), &mint, &registry::ID).0)
+        .await;
+    assert_eq!(vault.slashed_funds, slash_amount);
+    assert_eq!(token_amount(&mut env, &vault_token).await, slash_amount);
+    assert_eq!(
+        token_amount(&mut env, &escrow_token).await,
+        security_deposit + total_bond - slash_amount
+    );
+    let registry_account: registry::state::ServiceRegistry =
+        env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry_account.slashed_funds, 0);
+
+    // terminate refunds the security deposit to the service owner token account
+    let service_owner = env.service_owner.pubkey();
+    let owner_token = mint_to(&mut env, &mint, &service_owner, 0).await;
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &env.service_owner.pubkey(),
+        Some(&mint),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(token_amount(&mut env, &owner_token).await, security_deposit);
+
+    // unbond refunds what is left of the operator bond
+    let ix = env.client.unbond(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &operator.pubkey(),
+        &registered.registered_instances(),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let ix = env.client.claim_unbonded(
+        &env.manager.pubkey(),
+        service_id,
+        &operator.pubkey(),
+        &env.manager.pubkey(),
+        Some(&mint),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(
+        token_amount(&mut env, &operator_token).await,
+        total_bond - slash_amount
+    );
+    assert!(env
+        .raw_account(&env.operator_bond(service_id, &operator.pubkey()))
+        .await
+        .is_none());
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.state, ServiceState::PreRegistration);
+
+    // drain sends the slashed bond tokens to the drainer
+    let drainer = env.drainer.insecure_clone();
+    let drainer_token = mint_to(&mut env, &mint, &drainer.pubkey(), 0).await;
+    let ix = env.client.drain(&drainer.pubkey(), Some(&mint));
+    env.send(&[ix], &[&drainer]).await.unwrap();
+    assert_eq!(token_amount(&mut env, &drainer_token).await, slash_amount);
+    assert_eq!(token_amount(&mut env, &vault_token).await, 0);
+    let vault: BondTokenVault = env
+        .account(&bond_token_vault_pda(&env.registry.pubkey(), &mint, &registry::ID).0)
+        .await;
+    assert_eq!(vault.slashed_funds, 0);
+    assert_eq!(token_amount(&mut env, &escrow_token).await, 0);
+}
+
+#[tokio::test]
+async fn bond_token_errors() {
+    let mut env = setup().await;
+    let mint = create_mint(&mut env).await;
+    let manager = env.manager.pubkey();
+    mint_to(&mut env, &mint, &manager, 10 * BOND).await;
+
+    let service = env.register_service([121u8; 32], 1, 1, 0).await;
+
+    // The bond token is fixed once the registration is active
+    let ix = env
+        .client
+        .set_service_bond_token(&env.manager.pubkey(), service.service_id, &mint);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongServiceState);
+
+    let (service_id, service) = env.create_service([122u8; 32]).await;
+    env.register_agent_ids(
+        service_id,
+        service,
+        &[1],
+        &[AgentParams {
+            slots: 1,
+            bond: BOND,
+        }],
+        1,
+    )
+    .await;
+    let ix = env
+        .client
+        .set_service_bond_token(&env.manager.pubkey(), service_id, &mint);
+    env.send_as_manager(ix).await.unwrap();
+
+    // Token-bonded services need the vault accounts
+    let ix = env.client.activate_registration(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::MissingBondTokenAccounts,
+    );
+
+    // Escrow and treasury accounts of another service bonded in another token
+    let other_mint = create_mint(&mut env).await;
+    let (other_id, _) = env.create_service([123u8; 32]).await;
+    let ix = env
+        .client
+        .set_service_bond_token(&env.manager.pubkey(), other_id, &other_mint);
+    env.send_as_manager(ix).await.unwrap();
+    let activate = env.client.activate_registration(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &env.service_owner.pubkey(),
+        Some(&mint),
+    );
+    // bond token accounts: setting, vault, vault token, escrow token, counterparty
+    let mut ix = activate.clone();
+    ix.accounts[7].pubkey = env.client.escrow_token(other_id, &other_mint);
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::WrongEscrowTokenAccount,
+    );
+    let mut ix = activate.clone();
+    ix.accounts[5].pubkey =
+        bond_token_vault_pda(&env.registry.pubkey(), &other_mint, &registry::ID).0;
+    ix.accounts[6].pubkey = env.client.vault_token(&other_mint);
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::WrongBondTokenVault,
+    );
+
+    // The deposit needs a counterparty token account besides the escrow
+    let mut ix = activate.clone();
+    ix.accounts[8].pubkey = env.client.escrow_token(service_id, &mint);
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::DuplicateBondTokenAccounts,
+    );
+    let mut ix = activate.clone();
+    ix.accounts[8] = AccountMeta::new_readonly(registry::ID, false);
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::MissingBondTokenAccounts,
+    );
+
+    // Refunds only go to token accounts of the service owner
+    env.send_as_manager(activate).await.unwrap();
+    let mut ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &env.service_owner.pubkey(),
+        Some(&mint),
+    );
+    // counterparty token account of the bond token accounts
+    ix.accounts[10].pubkey = get_associated_token_address(&env.manager.pubkey(), &mint);
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::WrongBondTokenAccount,
+    );
+}
+
+#[tokio::test]
+async fn bond_token_stays_while_funds_are_held() {
+    let mut env = setup().await;
+    let mint = create_mint(&mut env).await;
+    let other_mint = create_mint(&mut env).await;
+    let manager = env.manager.pubkey();
+
+    // Unbonded back to pre-registration, with the bond still pending in lamports
+    let service = env.register_service([124u8; 32], 1, 1, 1).await;
+    let operator = service.operator.pubkey();
+    let ixs = [
+        env.client.terminate(
+            &manager,
+            &service.service,
+            service.service_id,
+            &env.service_owner.pubkey(),
+            None,
+        ),
+        env.client.unbond(
+            &manager,
+            &service.service,
+            service.service_id,
+            &operator,
+            &service.registered_instances(),
+        ),
+    ];
+    let manager_key = env.manager.insecure_clone();
+    env.send(&ixs, &[&manager_key]).await.unwrap();
+    let stored: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(stored.state, ServiceState::PreRegistration);
+
+    let set_bond_token = |env: &TestEnv, mint: &Pubkey| {
+        env.client
+            .set_service_bond_token(&manager, service.service_id, mint)
+    };
+    let ix = set_bond_token(&env, &mint);
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::ServiceEscrowNotEmpty,
+    );
+
+    // Once paid out, the escrow takes another currency, but only one mint
+    let ix = env
+        .client
+        .claim_unbonded(&manager, service.service_id, &operator, &manager, None);
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(env.balance(&operator).await, LAMPORTS_PER_SOL);
+    let ix = set_bond_token(&env, &mint);
+    env.send_as_manager(ix).await.unwrap();
+    let ix = set_bond_token(&env, &other_mint);
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::BondTokenAlreadySet,
+    );
+    let bond_token: ServiceBondToken = env
+        .account(
+            &service_bond_token_pda(
+                env.client.registry_seed(),
+                service.service_id,
+                &registry::ID,
+            )
+            .0,
+        )
+        .await;
+    assert_eq!(bond_token.mint, mint);
+}
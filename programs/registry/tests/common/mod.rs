@@ -0,0 +1,560 @@
+//! Shared setup for the native program tests.
+//!
+//! The programs run through `processor!`, compiled for the host and called in
+//! process, not as the SBF build that is deployed. Compute budgets, stack and heap
+//! limits, and the SBF layout of account structs are not exercised here, the
+//! TypeScript suite in `tests/` runs against the deployed build.
+
+#![allow(dead_code)]
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        entrypoint::ProgramResult, instruction::Instruction, program::set_return_data,
+    },
+    AccountDeserialize, AccountSerialize,
+};
+use registry::{
+    state::{MultisigAccount, OperatorNonce, ServiceRegistry},
+    AgentParams,
+};
+use registry_client::{RegistryClient, SlashTarget};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account as SolanaAccount,
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+pub use registry_client::pda::*;
+
+/// Multisig implementation that succeeds without returning a multisig address.
+pub const SILENT_MULTISIG_ID: Pubkey = Pubkey::new_from_array([7u8; 32]);
+/// Multisig implementation that returns an address it did not create.
+pub const FORGED_MULTISIG_ID: Pubkey = Pubkey::new_from_array([8u8; 32]);
+
+// Anchor entrypoints tie the accounts slice to the 'info lifetime, the test runtime does not
+fn registry_entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    registry::entry(program_id, accounts, data)
+}
+
+fn multisig_entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    multisig::entry(program_id, accounts, data)
+}
+
+fn silent_multisig_entry(_: &Pubkey, _: &[AccountInfo], _: &[u8]) -> ProgramResult {
+    Ok(())
+}
+
+fn forged_multisig_entry(_: &Pubkey, _: &[AccountInfo], _: &[u8]) -> ProgramResult {
+    set_return_data(&Pubkey::new_unique().to_bytes());
+    Ok(())
+}
+
+pub struct TestEnv {
+    pub ctx: ProgramTestContext,
+    pub client: RegistryClient,
+    pub registry: Keypair,
+    pub owner: Keypair,
+    pub manager: Keypair,
+    pub drainer: Keypair,
+    pub service_owner: Keypair,
+}
+
+pub struct RegisteredService {
+    pub service_id: u128,
+    pub service: Pubkey,
+    pub agent_ids: Vec<u32>,
+    pub operator: Keypair,
+    pub agent_instance_keys: Vec<Keypair>,
+    pub agent_instances: Vec<Pubkey>,
+}
+
+impl RegisteredService {
+    /// Agent instances with the agent id each one is registered for.
+    pub fn registered_instances(&self) -> Vec<(Pubkey, u32)> {
+        self.agent_instances
+            .iter()
+            .copied()
+            .zip(self.agent_ids.iter().copied())
+            .collect()
+    }
+}
+
+pub fn fund(program_test: &mut ProgramTest, key: &Pubkey, lamports: u64) {
+    program_test.add_account(
+        *key,
+        SolanaAccount {
+            lamports,
+            ..SolanaAccount::default()
+        },
+    );
+}
+
+pub async fn setup() -> TestEnv {
+    let mut program_test = ProgramTest::new("registry", registry::ID, processor!(registry_entry));
+    program_test.add_program("multisig", multisig::ID, processor!(multisig_entry));
+    program_test.add_program(
+        "silent_multisig",
+        SILENT_MULTISIG_ID,
+        processor!(silent_multisig_entry),
+    );
+    program_test.add_program(
+        "forged_multisig",
+        FORGED_MULTISIG_ID,
+        processor!(forged_multisig_entry),
+    );
+
+    let owner = Keypair::new();
+    let manager = Keypair::new();
+    let drainer = Keypair::new();
+    let service_owner = Keypair::new();
+    for key in [&owner, &manager, &drainer, &service_owner] {
+        fund(&mut program_test, &key.pubkey(), 1_000 * LAMPORTS_PER_SOL);
+    }
+
+    let ctx = program_test.start_with_context().await;
+    let registry = Keypair::new();
+    let mut env = TestEnv {
+        ctx,
+        client: RegistryClient::new(registry.pubkey()),
+        registry,
+        owner,
+        manager,
+        drainer,
+        service_owner,
+    };
+
+    let ix = env.client.initialize(
+        &env.owner.pubkey(),
+        "test_token".into(),
+        "AUTO".into(),
+        "base_uri".into(),
+        &env.manager.pubkey(),
+        &env.drainer.pubkey(),
+    );
+    let owner = env.owner.insecure_clone();
+    let registry = env.registry.insecure_clone();
+    env.send(&[ix], &[&owner, &registry]).await.unwrap();
+
+    env
+}
+
+impl TestEnv {
+    pub fn registry_wallet(&self) -> Pubkey {
+        self.client.registry_wallet()
+    }
+
+    pub fn operator_bond(&self, service_id: u128, operator: &Pubkey) -> Pubkey {
+        operator_bond_pda(
+            self.client.registry_seed(),
+            service_id,
+            operator,
+            &registry::ID,
+        )
+        .0
+    }
+
+    pub async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> std::result::Result<(), BanksClientError> {
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signers[0].pubkey()),
+            signers,
+            blockhash,
+        );
+        self.ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// Sends `ix` signed by the registry manager.
+    pub async fn send_as_manager(
+        &mut self,
+        ix: Instruction,
+    ) -> std::result::Result<(), BanksClientError> {
+        let manager = self.manager.insecure_clone();
+        self.send(&[ix], &[&manager]).await
+    }
+
+    /// Simulates `ix` as the manager and decodes its return data.
+    pub async fn view<T: AnchorDeserialize>(&mut self, ix: Instruction) -> T {
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.manager.pubkey()),
+            &[&self.manager],
+            blockhash,
+        );
+        let simulation = self
+            .ctx
+            .banks_client
+            .simulate_transaction(tx)
+            .await
+            .unwrap();
+        simulation.result.unwrap().unwrap();
+        let return_data = simulation
+            .simulation_details
+            .and_then(|details| details.return_data)
+            .expect("no return data");
+        T::try_from_slice(&return_data.data).unwrap()
+    }
+
+    pub async fn account<T: AccountDeserialize>(&mut self, key: &Pubkey) -> T {
+        let account = self
+            .ctx
+            .banks_client
+            .get_account(*key)
+            .await
+            .unwrap()
+            .expect("account not found");
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn balance(&mut self, key: &Pubkey) -> u64 {
+        self.ctx.banks_client.get_balance(*key).await.unwrap()
+    }
+
+    pub async fn raw_account(&mut self, key: &Pubkey) -> Option<SolanaAccount> {
+        self.ctx.banks_client.get_account(*key).await.unwrap()
+    }
+
+    /// Overwrites a program account, for states no instruction sequence can reach.
+    pub async fn set_account<T: AccountDeserialize + AccountSerialize>(
+        &mut self,
+        key: &Pubkey,
+        update: impl FnOnce(&mut T),
+    ) {
+        let mut state: T = self.account(key).await;
+        update(&mut state);
+
+        let mut account = self.raw_account(key).await.unwrap();
+        let mut data = Vec::with_capacity(account.data.len());
+        state.try_serialize(&mut data).unwrap();
+        account.data[..data.len()].copy_from_slice(&data);
+        self.ctx.set_account(key, &account.into());
+    }
+
+    pub async fn set_registry(&mut self, update: impl FnOnce(&mut ServiceRegistry)) {
+        let key = self.registry.pubkey();
+        self.set_account(&key, update).await;
+    }
+
+    pub async fn whitelist_multisig(&mut self, implementation: Pubkey, permission: bool) {
+        let ix = self.client.change_multisig_permission(
+            &self.owner.pubkey(),
+            &implementation,
+            permission,
+        );
+        let owner = self.owner.insecure_clone();
+        self.send(&[ix], &[&owner]).await.unwrap();
+    }
+
+    pub async fn next_service_id(&mut self) -> u128 {
+        let registry: ServiceRegistry = self.account(&self.registry.pubkey()).await;
+        registry.total_supply + 1
+    }
+
+    pub async fn create_service(&mut self, config_hash: [u8; 32]) -> (u128, Pubkey) {
+        let service_id = self.next_service_id().await;
+        let ix = self.client.create(
+            &self.manager.pubkey(),
+            service_id,
+            config_hash,
+            &self.service_owner.pubkey(),
+            None,
+        );
+        self.send_as_manager(ix).await.unwrap();
+
+        (service_id, self.client.service(service_id))
+    }
+
+    pub async fn register_agent_ids(
+        &mut self,
+        service_id: u128,
+        service: Pubkey,
+        agent_ids: &[u32],
+        agent_params: &[AgentParams],
+        threshold: u32,
+    ) {
+        let ix = self.client.register_agent_ids_to_service(
+            &self.manager.pubkey(),
+            &service,
+            service_id,
+            &self.service_owner.pubkey(),
+            agent_ids,
+            agent_params,
+            Some(threshold),
+        );
+        self.send_as_manager(ix).await.unwrap();
+    }
+
+    pub async fn activate_registration(&mut self, service_id: u128, service: Pubkey) {
+        let ix = self.client.activate_registration(
+            &self.manager.pubkey(),
+            &service,
+            service_id,
+            &self.service_owner.pubkey(),
+            None,
+        );
+        self.send_as_manager(ix).await.unwrap();
+    }
+
+    /// Current registration nonce of `operator`, zero before its first signed one.
+    pub async fn operator_nonce(&mut self, operator: &Pubkey) -> u64 {
+        match self
+            .raw_account(&operator_nonce_pda(operator, &registry::ID).0)
+            .await
+        {
+            Some(account) => {
+                OperatorNonce::try_deserialize(&mut account.data.as_slice())
+                    .unwrap()
+                    .nonce
+            }
+            None => 0,
+        }
+    }
+
+    /// Registration paid by the manager, signed by `operator` at its current nonce.
+    pub async fn signed_registration(
+        &mut self,
+        service_id: u128,
+        service: Pubkey,
+        operator: &Keypair,
+        agent_ids: &[u32],
+        agent_instances: &[Pubkey],
+        bond_mint: Option<&Pubkey>,
+    ) -> [Instruction; 2] {
+        let nonce = self.operator_nonce(&operator.pubkey()).await;
+        let message =
+            self.client
+                .registration_message(service_id, agent_instances, agent_ids, nonce);
+        let signature = operator.sign_message(&message);
+        self.client.register_agents_with_signature(
+            &self.manager.pubkey(),
+            &service,
+            service_id,
+            &operator.pubkey(),
+            agent_instances,
+            agent_ids,
+            bond_mint,
+            signature.as_ref().try_into().unwrap(),
+            nonce,
+        )
+    }
+
+    pub async fn register_agents(
+        &mut self,
+        service_id: u128,
+        service: Pubkey,
+        operator: &Keypair,
+        agent_ids: &[u32],
+        agent_instances: &[Pubkey],
+    ) {
+        let ixs = self
+            .signed_registration(
+                service_id,
+                service,
+                operator,
+                agent_ids,
+                agent_instances,
+                None,
+            )
+            .await;
+        let manager = self.manager.insecure_clone();
+        self.send(&ixs, &[&manager]).await.unwrap();
+    }
+
+    /// Creates a service with `agent_ids_count` agent ids of one slot each and fills
+    /// `agents_to_register` of them with instances of a single operator.
+    pub async fn register_service(
+        &mut self,
+        config_hash: [u8; 32],
+        agent_ids_count: u32,
+        threshold: u32,
+        agents_to_register: usize,
+    ) -> RegisteredService {
+        let agent_ids: Vec<u32> = (1..=agent_ids_count).collect();
+        let agent_params: Vec<AgentParams> = agent_ids
+            .iter()
+            .map(|agent_id| AgentParams {
+                slots: 1,
+                bond: LAMPORTS_PER_SOL * *agent_id as u64,
+            })
+            .collect();
+
+        let (service_id, service) = self.create_service(config_hash).await;
+        self.register_agent_ids(service_id, service, &agent_ids, &agent_params, threshold)
+            .await;
+        self.activate_registration(service_id, service).await;
+
+        let operator = Keypair::new();
+        let agent_instance_keys: Vec<Keypair> =
+            (0..agents_to_register).map(|_| Keypair::new()).collect();
+        let agent_instances: Vec<Pubkey> = agent_instance_keys
+            .iter()
+            .map(|agent_instance| agent_instance.pubkey())
+            .collect();
+        if agents_to_register > 0 {
+            self.register_agents(
+                service_id,
+                service,
+                &operator,
+                &agent_ids[..agents_to_register],
+                &agent_instances,
+            )
+            .await;
+        }
+
+        RegisteredService {
+            service_id,
+            service,
+            agent_ids,
+            operator,
+            agent_instance_keys,
+            agent_instances,
+        }
+    }
+
+    /// Closes every agent PDA of a terminated service in a single cleanup.
+    pub async fn cleanup(&mut self, service: &RegisteredService) {
+        let agent_instances = service.registered_instances();
+        let ix = self.client.cleanup_service(
+            &self.manager.pubkey(),
+            &service.service,
+            service.service_id,
+            &self.service_owner.pubkey(),
+            &service.agent_ids,
+            &agent_instances,
+            0..service.agent_ids.len() + agent_instances.len(),
+        );
+        self.send_as_manager(ix).await.unwrap();
+    }
+
+    pub fn deploy_ix(
+        &self,
+        service: &RegisteredService,
+        implementation: Pubkey,
+        multisig: Pubkey,
+        data: Vec<u8>,
+    ) -> Instruction {
+        self.client.deploy(
+            &self.manager.pubkey(),
+            &service.service,
+            service.service_id,
+            &self.service_owner.pubkey(),
+            &implementation,
+            &multisig,
+            &service.agent_instances,
+            data,
+        )
+    }
+
+    /// Deploys the service through the registry built-in multisig and returns its address.
+    pub async fn deploy_builtin(&mut self, service: &RegisteredService) -> Pubkey {
+        self.whitelist_multisig(registry::ID, true).await;
+        let multisig = self.client.builtin_multisig(&service.agent_instances);
+        let ix = self.deploy_ix(service, registry::ID, multisig, vec![]);
+        self.send_as_manager(ix).await.unwrap();
+        multisig
+    }
+
+    pub async fn transfer(&mut self, to: &Pubkey, lamports: u64) {
+        let ix = solana_sdk::system_instruction::transfer(&self.owner.pubkey(), to, lamports);
+        let owner = self.owner.insecure_clone();
+        self.send(&[ix], &[&owner]).await.unwrap();
+    }
+
+    pub fn slash_ix(
+        &self,
+        service: &RegisteredService,
+        signer: Pubkey,
+        agent_instances: &[Pubkey],
+        amounts: &[u64],
+    ) -> Instruction {
+        let targets: Vec<SlashTarget> = agent_instances
+            .iter()
+            .zip(amounts)
+            .map(|(agent_instance, amount)| SlashTarget {
+                agent_instance: *agent_instance,
+                operator: service.operator.pubkey(),
+                amount: *amount,
+            })
+            .collect();
+
+        self.client.slash(
+            &signer,
+            &service.service,
+            service.service_id,
+            &targets,
+            None,
+        )
+    }
+
+    /// Runs `target` through a built-in multisig proposal approved by the first
+    /// `threshold` agent instances of `service`, which must hold lamports for the proposal.
+    pub async fn execute_through_multisig(
+        &mut self,
+        service: &RegisteredService,
+        multisig: Pubkey,
+        target: &Instruction,
+    ) -> std::result::Result<(), BanksClientError> {
+        let multisig_account: MultisigAccount = self.account(&multisig).await;
+        let proposal_id = multisig_account.proposal_count;
+        let agents: Vec<Keypair> = service
+            .agent_instance_keys
+            .iter()
+            .take(multisig_account.threshold as usize)
+            .map(|key| key.insecure_clone())
+            .collect();
+
+        let ix = self.client.propose_multisig_transaction(
+            &agents[0].pubkey(),
+            &multisig,
+            proposal_id,
+            target,
+        );
+        self.send(&[ix], &[&agents[0]]).await?;
+        for agent in &agents[1..] {
+            let ix =
+                self.client
+                    .approve_multisig_transaction(&agent.pubkey(), &multisig, proposal_id);
+            self.send(&[ix], &[agent]).await?;
+        }
+
+        let ix = self.client.execute_multisig_transaction(
+            &self.manager.pubkey(),
+            &multisig,
+            proposal_id,
+            target,
+        );
+        self.send_as_manager(ix).await
+    }
+}
+
+/// Extracts the custom program error code of a failed transaction.
+pub fn error_code(err: BanksClientError) -> u32 {
+    use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+
+    match err.unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => code,
+        other => panic!("unexpected transaction error: {other:?}"),
+    }
+}
+
+pub fn anchor_error(code: registry::error::ErrorCode) -> u32 {
+    u32::from(code)
+}
+
+pub fn assert_error(
+    result: std::result::Result<(), BanksClientError>,
+    code: registry::error::ErrorCode,
+) {
+    let err = result.expect_err("transaction should have failed");
+    assert_eq!(error_code(err), anchor_error(code), "expected {code:?}");
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use anchor_lang::prelude::*;
+use common::*;
+use registry::{
+    constants::CONFIG_HASH_RECORDS_PER_PAGE,
+    state::{ConfigHashHistory, ConfigHashHistoryPage},
+    AgentParams,
+};
+use solana_sdk::{account::AccountSharedData, signature::Signer};
+
+/// A service with one agent id, so that it can be updated.
+async fn create_service(env: &mut TestEnv, config_hash: [u8; 32]) -> (u128, Pubkey) {
+    let (service_id, service) = env.create_service(config_hash).await;
+    env.register_agent_ids(
+        service_id,
+        service,
+        &[1],
+        &[AgentParams { slots: 1, bond: 1 }],
+        1,
+    )
+    .await;
+    (service_id, service)
+}
+
+async fn update(env: &mut TestEnv, service_id: u128, service: &Pubkey, config_hash: [u8; 32]) {
+    let ix = env.client.update(
+        &env.manager.pubkey(),
+        service,
+        service_id,
+        config_hash,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+}
+
+#[tokio::test]
+async fn records_each_config_hash_in_order() {
+    let mut env = setup().await;
+    let (service_id, service) = create_service(&mut env, [80u8; 32]).await;
+
+    env.ctx.warp_to_slot(100).unwrap();
+    update(&mut env, service_id, &service, [81u8; 32]).await;
+    // Same hash, nothing to record
+    update(&mut env, service_id, &service, [81u8; 32]).await;
+    env.ctx.warp_to_slot(200).unwrap();
+    update(&mut env, service_id, &service, [82u8; 32]).await;
+
+    let history: ConfigHashHistory = env
+        .account(&config_hash_history_pda(&service, &registry::ID).0)
+        .await;
+    assert_eq!(history.service_id, service_id);
+    let hashes: Vec<[u8; 32]> = history.records.iter().map(|r| r.config_hash).collect();
+    assert_eq!(hashes, vec![[80u8; 32], [81u8; 32], [82u8; 32]]);
+    assert!(history.records[0].slot < 100);
+    assert!((100..200).contains(&history.records[1].slot));
+    assert!(history.records[2].slot >= 200);
+    assert!(history.records[1].timestamp <= history.records[2].timestamp);
+
+    let ix = env.client.get_config_hash_history(&service, 0);
+    let page: ConfigHashHistoryPage = env.view(ix).await;
+    assert_eq!(page.total, 3);
+    assert_eq!(page.records, history.records);
+}
+
+#[tokio::test]
+async fn pages_through_long_histories() {
+    let mut env = setup().await;
+    let (service_id, service) = create_service(&mut env, [1u8; 32]).await;
+    let updates = CONFIG_HASH_RECORDS_PER_PAGE as u8 + 4;
+    for hash in 2..=updates {
+        update(&mut env, service_id, &service, [hash; 32]).await;
+    }
+
+    let ix = env.client.get_config_hash_history(&service, 0);
+    let first: ConfigHashHistoryPage = env.view(ix).await;
+    assert_eq!(first.total, updates as u32);
+    assert_eq!(first.records.len(), CONFIG_HASH_RECORDS_PER_PAGE);
+    assert_eq!(first.records[0].config_hash, [1u8; 32]);
+
+    let ix = env
+        .client
+        .get_config_hash_history(&service, CONFIG_HASH_RECORDS_PER_PAGE as u32);
+    let second: ConfigHashHistoryPage = env.view(ix).await;
+    let hashes: Vec<[u8; 32]> = second.records.iter().map(|r| r.config_hash).collect();
+    assert_eq!(
+        hashes,
+        (22..=updates).map(|hash| [hash; 32]).collect::<Vec<_>>()
+    );
+
+    let ix = env.client.get_config_hash_history(&service, u32::MAX);
+    let past_end: ConfigHashHistoryPage = env.view(ix).await;
+    assert_eq!(past_end.total, updates as u32);
+    assert!(past_end.records.is_empty());
+}
+
+#[tokio::test]
+async fn update_starts_the_history_of_older_services() {
+    let mut env = setup().await;
+    let (service_id, service) = create_service(&mut env, [83u8; 32]).await;
+    // A service created before the history existed
+    let history_key = config_hash_history_pda(&service, &registry::ID).0;
+    env.ctx
+        .set_account(&history_key, &AccountSharedData::default());
+    assert!(env.raw_account(&history_key).await.is_none());
+
+    update(&mut env, service_id, &service, [84u8; 32]).await;
+
+    let history: ConfigHashHistory = env.account(&history_key).await;
+    assert_eq!(history.service_id, service_id);
+    assert_eq!(history.records.len(), 1);
+    assert_eq!(history.records[0].config_hash, [84u8; 32]);
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use common::*;
+use registry::{error::ErrorCode, service_state::ServiceState, state::ServiceAccount};
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn deploys_through_whitelisted_implementation() {
+    let mut env = setup().await;
+    env.whitelist_multisig(multisig::ID, true).await;
+
+    let service = env.register_service([17u8; 32], 3, 3, 3).await;
+    let (multisig_pda, bump) = multisig::multisig_pda(&service.agent_instances);
+
+    let ix = env.deploy_ix(&service, multisig::ID, multisig_pda, vec![1, 2, 3]);
+    let manager = env.manager.insecure_clone();
+    env.send(&[ix], &[&manager]).await.unwrap();
+
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.state, ServiceState::Deployed);
+    assert_eq!(service_account.multisig, multisig_pda);
+
+    // The multisig belongs to the implementation, not to the registry
+    let raw = env.raw_account(&multisig_pda).await.unwrap();
+    assert_eq!(raw.owner, multisig::ID);
+
+    let multisig_account: multisig::Multisig = env.account(&multisig_pda).await;
+    assert_eq!(multisig_account.owners, service.agent_instances);
+    assert_eq!(multisig_account.threshold, service_account.threshold);
+    assert_eq!(multisig_account.data, vec![1, 2, 3]);
+    assert_eq!(multisig_account.bump, bump);
+}
+
+#[tokio::test]
+async fn deploys_once_registration_finished() {
+    let mut env = setup().await;
+    env.whitelist_multisig(registry::ID, true).await;
+
+    // Enough instances for the threshold, but one slot is still open
+    let partial = env.register_service([19u8; 32], 4, 3, 3).await;
+    let ix = env.deploy_ix(
+        &partial,
+        registry::ID,
+        env.client.builtin_multisig(&partial.agent_instances),
+        vec![],
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongServiceState);
+    let service_account: ServiceAccount = env.account(&partial.service).await;
+    assert_eq!(service_account.state, ServiceState::ActiveRegistration);
+
+    // A deployed service is not deployed a second time
+    let service = env.register_service([20u8; 32], 2, 2, 2).await;
+    env.deploy_builtin(&service).await;
+    let ix = env.deploy_ix(
+        &service,
+        registry::ID,
+        env.client.builtin_multisig(&service.agent_instances),
+        vec![],
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongServiceState);
+}
+
+#[tokio::test]
+async fn deploys_through_builtin_implementation() {
+    let mut env = setup().await;
+    env.whitelist_multisig(registry::ID, true).await;
+
+    let service = env.register_service([18u8; 32], 2, 2, 2).await;
+    let multisig_pda = env.client.builtin_multisig(&service.agent_instances);
+
+    let ix = env.deploy_ix(&service, registry::ID, multisig_pda, vec![]);
+    let manager = env.manager.insecure_clone();
+    env.send(&[ix], &[&manager]).await.unwrap();
+
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.multisig, multisig_pda);
+    assert_eq!(
+        env.raw_account(&multisig_pda).await.unwrap().owner,
+        registry::ID
+    );
+}
+
+#[tokio::test]
+async fn rejects_implementation_not_whitelisted() {
+    let mut env = setup().await;
+    env.whitelist_multisig(registry::ID, true).await;
+
+    let service = env.register_service([19u8; 32], 1, 1, 1).await;
+    let (multisig_pda, _) = multisig::multisig_pda(&service.agent_instances);
+
+    let ix = env.deploy_ix(&service, multisig::ID, multisig_pda, vec![]);
+    let manager = env.manager.insecure_clone();
+    let err = env.send(&[ix], &[&manager]).await.unwrap_err();
+    assert_eq!(
+        error_code(err),
+        anchor_error(ErrorCode::UnauthorizedMultisig)
+    );
+}
+
+#[tokio::test]
+async fn rejects_multisig_account_not_created_by_implementation() {
+    let mut env = setup().await;
+    env.whitelist_multisig(multisig::ID, true).await;
+
+    let service = env.register_service([20u8; 32], 1, 1, 1).await;
+    let stray = Keypair::new();
+
+    let ix = env.deploy_ix(&service, multisig::ID, stray.pubkey(), vec![]);
+    let manager = env.manager.insecure_clone();
+    // The implementation only creates the multisig at its own PDA
+    let err = env.send(&[ix], &[&manager]).await.unwrap_err();
+    assert_eq!(
+        error_code(err),
+        u32::from(anchor_lang::error::ErrorCode::ConstraintSeeds)
+    );
+
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.state, ServiceState::FinishedRegistration);
+}
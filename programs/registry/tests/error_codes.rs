@@ -0,0 +1,549 @@
+//! One failing call per reachable `ErrorCode` variant.
+//!
+//! The multisig proposal errors are covered in `multisig_proposals.rs`,
+//! `UnauthorizedMultisig` in `deploy_multisig.rs` and `ServiceNotFound` in
+//! `service_pda.rs`. The following variants cannot be produced by any sequence of
+//! instructions and have no test:
+//! - `NotEnoughSpace`, `ServiceAgentDoesNotExist`,
+//!   `AgentParamDoesNotExist`, `InvalidAgentParamPda`: never returned by the program.
+//! - `InvalidServiceAgentPda`: `validate_threshold` rejects every update that would
+//!   leave the agent ids index empty.
+//! - `AgentNotInService`: agent params with zero slots are closed, not stored.
+//! - `WrongOperator`: the operator-as-agent PDA is never created, and operator bonds
+//!   are derived from their operator.
+//! - `IncorrectAgentInstances`, `MaxAgentInstancesPerServiceReached`: slot counters
+//!   and the service agent instances index fill up first.
+//! - `OperatorHasNoInstances`: the operator index is closed when it is emptied.
+//! - `AccountNotWritable`, `OnlyOwnServiceMultisig`: the `mut` and `address` account
+//!   constraints fail first.
+//! - `Overflow`: lamport balances and proposal counters cannot wrap.
+
+mod common;
+
+use anchor_lang::{prelude::*, solana_program::native_token::LAMPORTS_PER_SOL};
+use common::*;
+use registry::{error::ErrorCode, state::MultisigAccount, AgentParams};
+use solana_sdk::signature::{Keypair, Signer};
+
+fn params(bonds: &[u64]) -> Vec<AgentParams> {
+    bonds
+        .iter()
+        .map(|bond| AgentParams {
+            slots: 1,
+            bond: *bond,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn create_and_update_errors() {
+    let mut env = setup().await;
+
+    let ix = env.client.create(
+        &env.manager.pubkey(),
+        1,
+        [0u8; 32],
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::ZeroConfigHash);
+
+    let service = env.register_service([40u8; 32], 1, 1, 0).await;
+    let ix = env.client.update(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        [41u8; 32],
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongServiceState);
+
+    env.set_registry(|registry| registry.locked = true).await;
+    let ix = env.client.create(
+        &env.manager.pubkey(),
+        2,
+        [42u8; 32],
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::ReentrancyGuard);
+}
+
+#[tokio::test]
+async fn register_agent_ids_errors() {
+    let mut env = setup().await;
+    let (service_id, service) = env.create_service([43u8; 32]).await;
+    let manager = env.manager.pubkey();
+    let service_owner = env.service_owner.pubkey();
+
+    let cases: Vec<(Vec<u32>, Vec<AgentParams>, u32, ErrorCode)> = vec![
+        (vec![], vec![], 1, ErrorCode::WrongArrayLength),
+        (vec![1, 2], params(&[1]), 1, ErrorCode::WrongArrayLength),
+        (vec![2, 1], params(&[1, 1]), 2, ErrorCode::WrongAgentId),
+        (vec![1, 1], params(&[1, 1]), 2, ErrorCode::WrongAgentId),
+        (
+            vec![1],
+            vec![AgentParams { slots: 1, bond: 0 }],
+            1,
+            ErrorCode::ZeroValue,
+        ),
+        (
+            vec![1, 2, 3],
+            params(&[1, 1, 1]),
+            2,
+            ErrorCode::WrongThreshold,
+        ),
+        (
+            vec![1, 2, 3],
+            params(&[1, 1, 1]),
+            4,
+            ErrorCode::WrongThreshold2,
+        ),
+    ];
+    for (agent_ids, agent_params, threshold, code) in cases {
+        let ix = env.client.register_agent_ids_to_service(
+            &manager,
+            &service,
+            service_id,
+            &service_owner,
+            &agent_ids,
+            &agent_params,
+            Some(threshold),
+        );
+        assert_error(env.send_as_manager(ix).await, code);
+    }
+
+    // Agent param accounts out of order
+    let mut ix = env.client.register_agent_ids_to_service(
+        &manager,
+        &service,
+        service_id,
+        &service_owner,
+        &[1, 2],
+        &params(&[1, 1]),
+        Some(2),
+    );
+    let len = ix.accounts.len();
+    ix.accounts.swap(len - 1, len - 2);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::InvalidPda);
+}
+
+#[tokio::test]
+async fn max_agent_ids_per_service() {
+    let mut env = setup().await;
+    let (service_id, service) = env.create_service([44u8; 32]).await;
+    let max = registry::constants::MAX_AGENT_IDS_PER_SERVICE as u32;
+
+    let mut registered = 0;
+    for chunk in (1..=max).collect::<Vec<u32>>().chunks(16) {
+        registered += chunk.len() as u32;
+        env.register_agent_ids(
+            service_id,
+            service,
+            chunk,
+            &params(&vec![1; chunk.len()]),
+            registered,
+        )
+        .await;
+    }
+
+    let ix = env.client.register_agent_ids_to_service(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &env.service_owner.pubkey(),
+        &[max + 1],
+        &params(&[1]),
+        Some(max + 1),
+    );
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::MaxAgentIdPerServiceReached,
+    );
+}
+
+#[tokio::test]
+async fn activate_registration_errors() {
+    let mut env = setup().await;
+    let manager_balance = env.balance(&env.manager.pubkey()).await;
+
+    // Security deposit above what the manager holds
+    let (service_id, service) = env.create_service([45u8; 32]).await;
+    env.register_agent_ids(service_id, service, &[1], &params(&[manager_balance]), 1)
+        .await;
+    let ix = env.client.activate_registration(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::IncorrectRegistrationDepositValue,
+    );
+
+    let service = env.register_service([46u8; 32], 1, 1, 0).await;
+    let ix = env.client.activate_registration(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::ServiceMustBeInactive,
+    );
+}
+
+#[tokio::test]
+async fn register_agents_errors() {
+    let mut env = setup().await;
+    let service = env.register_service([48u8; 32], 2, 2, 1).await;
+    let manager = env.manager.insecure_clone();
+    let operator = service.operator.insecure_clone();
+
+    let ixs = env
+        .signed_registration(
+            service.service_id,
+            service.service,
+            &operator,
+            &[2, 2],
+            &service.agent_instances,
+            None,
+        )
+        .await;
+    assert_error(
+        env.send(&ixs, &[&manager]).await,
+        ErrorCode::WrongArrayLength,
+    );
+
+    let ixs = env
+        .signed_registration(
+            service.service_id,
+            service.service,
+            &Keypair::new(),
+            &[1],
+            &[Keypair::new().pubkey()],
+            None,
+        )
+        .await;
+    assert_error(
+        env.send(&ixs, &[&manager]).await,
+        ErrorCode::AgentInstancesSlotsFilled,
+    );
+
+    // An operator cannot register the same instance for a second agent id
+    let ixs = env
+        .signed_registration(
+            service.service_id,
+            service.service,
+            &operator,
+            &[2],
+            &service.agent_instances,
+            None,
+        )
+        .await;
+    assert_error(
+        env.send(&ixs, &[&manager]).await,
+        ErrorCode::AccountAgentIdInstanceOperatorExists,
+    );
+
+    let agent_instance = Keypair::new().pubkey();
+    let (service_id, service) = env.create_service([49u8; 32]).await;
+    env.register_agent_ids(
+        service_id,
+        service,
+        &[1],
+        &[AgentParams { slots: 2, bond: 1 }],
+        2,
+    )
+    .await;
+    env.activate_registration(service_id, service).await;
+    let ixs = env
+        .signed_registration(
+            service_id,
+            service,
+            &Keypair::new(),
+            &[1, 1],
+            &[agent_instance, agent_instance],
+            None,
+        )
+        .await;
+    assert_error(
+        env.send(&ixs, &[&manager]).await,
+        ErrorCode::AccountServiceAgentIdInstanceExists,
+    );
+
+    // The operator registering itself signs and pays its bond
+    env.transfer(&operator.pubkey(), LAMPORTS_PER_SOL).await;
+    let mut ix = env.client.register_agents(
+        &operator.pubkey(),
+        &service,
+        service_id,
+        &[agent_instance],
+        &[1],
+        None,
+    );
+    // operator check account, right after the agent param
+    ix.accounts[15].pubkey = Keypair::new().pubkey();
+    assert_error(env.send(&[ix], &[&operator]).await, ErrorCode::InvalidPda);
+}
+
+#[tokio::test]
+async fn deploy_errors() {
+    let mut env = setup().await;
+    env.whitelist_multisig(multisig::ID, true).await;
+    env.whitelist_multisig(SILENT_MULTISIG_ID, true).await;
+
+    let service = env.register_service([51u8; 32], 1, 1, 1).await;
+    let multisig_pda = multisig::multisig_pda(&service.agent_instances).0;
+
+    let mut ix = env.deploy_ix(&service, multisig::ID, multisig_pda, vec![]);
+    ix.accounts.truncate(ix.accounts.len() - 2);
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::MissingMultisigAccount,
+    );
+
+    env.whitelist_multisig(FORGED_MULTISIG_ID, true).await;
+    let ix = env.deploy_ix(&service, FORGED_MULTISIG_ID, multisig_pda, vec![]);
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::WrongMultisigAccount,
+    );
+
+    let ix = env.deploy_ix(&service, SILENT_MULTISIG_ID, multisig_pda, vec![]);
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::InvalidMultisigReturnData,
+    );
+
+    // The built-in implementation checks the threshold against the instances it is given
+    env.whitelist_multisig(registry::ID, true).await;
+    // The multisig owners are the agent instances registered in the service
+    let mut ix = env.deploy_ix(
+        &service,
+        registry::ID,
+        env.client.builtin_multisig(&[]),
+        vec![],
+    );
+    ix.accounts.pop();
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::IncorrectAgentInstances,
+    );
+    let stranger = Keypair::new().pubkey();
+    let mut ix = env.deploy_ix(
+        &service,
+        registry::ID,
+        env.client.builtin_multisig(&[stranger]),
+        vec![],
+    );
+    let last = ix.accounts.len() - 1;
+    ix.accounts[last].pubkey = stranger;
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::IncorrectAgentInstances,
+    );
+}
+
+#[tokio::test]
+async fn max_multisig_implementations() {
+    let mut env = setup().await;
+    let implementations: Vec<Pubkey> = (0..registry::constants::MAX_MULTISIGS)
+        .map(|_| Keypair::new().pubkey())
+        .collect();
+
+    let owner = env.owner.insecure_clone();
+    for chunk in implementations.chunks(20) {
+        let ixs: Vec<_> = chunk
+            .iter()
+            .map(|implementation| {
+                env.client
+                    .change_multisig_permission(&owner.pubkey(), implementation, true)
+            })
+            .collect();
+        env.send(&ixs, &[&owner]).await.unwrap();
+    }
+
+    let ix = env
+        .client
+        .change_multisig_permission(&owner.pubkey(), &Keypair::new().pubkey(), true);
+    assert_error(
+        env.send(&[ix], &[&owner]).await,
+        ErrorCode::MaxMultiSigsReached,
+    );
+}
+
+#[tokio::test]
+async fn slash_errors() {
+    let mut env = setup().await;
+    let service = env.register_service([52u8; 32], 2, 2, 2).await;
+    let first = &service.agent_instances[..1];
+
+    let multisig = env.deploy_builtin(&service).await;
+    for agent_instance in &service.agent_instances {
+        env.transfer(agent_instance, LAMPORTS_PER_SOL).await;
+    }
+
+    let ix = env.slash_ix(&service, multisig, first, &[0]);
+    assert_error(
+        env.execute_through_multisig(&service, multisig, &ix).await,
+        ErrorCode::InvalidSlashAmount,
+    );
+
+    // Operator agent instance of the second instance given for the first one
+    let mut ix = env.slash_ix(&service, multisig, &service.agent_instances, &[1, 1]);
+    ix.accounts[11].pubkey = ix.accounts[13].pubkey;
+    assert_error(
+        env.execute_through_multisig(&service, multisig, &ix).await,
+        ErrorCode::InvalidPda,
+    );
+
+    // Slashing more than the bond empties it, after which nothing is left to slash
+    let ix = env.slash_ix(&service, multisig, first, &[u64::MAX]);
+    env.execute_through_multisig(&service, multisig, &ix)
+        .await
+        .unwrap();
+    let slash = env.slash_ix(&service, multisig, first, &[1]);
+    assert_error(
+        env.execute_through_multisig(&service, multisig, &slash)
+            .await,
+        ErrorCode::IncorrectAgentBondingValue,
+    );
+
+    // The proposal binds the exact accounts it was created with
+    let multisig_account: MultisigAccount = env.account(&multisig).await;
+    let proposal_id = multisig_account.proposal_count;
+    let agent = service.agent_instance_keys[0].insecure_clone();
+    let ix =
+        env.client
+            .propose_multisig_transaction(&agent.pubkey(), &multisig, proposal_id, &slash);
+    env.send(&[ix], &[&agent]).await.unwrap();
+    let approver = service.agent_instance_keys[1].insecure_clone();
+    let ix = env
+        .client
+        .approve_multisig_transaction(&approver.pubkey(), &multisig, proposal_id);
+    env.send(&[ix], &[&approver]).await.unwrap();
+    let mut other = slash.clone();
+    other.accounts[0].pubkey = Keypair::new().pubkey();
+    let ix = env.client.execute_multisig_transaction(
+        &env.manager.pubkey(),
+        &multisig,
+        proposal_id,
+        &other,
+    );
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::WrongProposalAccounts,
+    );
+    // The multisig outlives the deployment
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_error(
+        env.execute_through_multisig(&service, multisig, &slash)
+            .await,
+        ErrorCode::WrongServiceState,
+    );
+}
+
+#[tokio::test]
+async fn terminate_and_unbond_errors() {
+    let mut env = setup().await;
+    let service = env.register_service([53u8; 32], 1, 1, 1).await;
+
+    let unbond = env.client.unbond(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &service.operator.pubkey(),
+        &service.registered_instances(),
+    );
+    assert_error(
+        env.send_as_manager(unbond.clone()).await,
+        ErrorCode::WrongServiceState,
+    );
+
+    let terminate = env.client.terminate(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(terminate.clone()).await.unwrap();
+    let (service_id, service_key) = env.create_service([54u8; 32]).await;
+    env.register_agent_ids(service_id, service_key, &[1], &params(&[1]), 1)
+        .await;
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service_key,
+        service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongServiceState);
+
+    let mut wrong_instance = unbond.clone();
+    let len = wrong_instance.accounts.len();
+    wrong_instance.accounts[len - 1].pubkey = Keypair::new().pubkey();
+    assert_error(
+        env.send_as_manager(wrong_instance).await,
+        ErrorCode::InvalidPda,
+    );
+
+    // Agent accounts are only cleaned up once every operator unbonded
+    let cleanup = env.client.cleanup_service(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        &service.agent_ids,
+        &service.registered_instances(),
+        0..1,
+    );
+    assert_error(
+        env.send_as_manager(cleanup.clone()).await,
+        ErrorCode::WrongServiceState,
+    );
+    env.send_as_manager(unbond).await.unwrap();
+
+    let mut wrong_counter = cleanup;
+    let len = wrong_counter.accounts.len();
+    wrong_counter.accounts[len - 1].pubkey = Keypair::new().pubkey();
+    assert_error(
+        env.send_as_manager(wrong_counter).await,
+        ErrorCode::InvalidPda,
+    );
+}
+
+#[tokio::test]
+async fn drain_errors() {
+    let mut env = setup().await;
+    env.set_registry(|registry| registry.slashed_funds = 1_000 * LAMPORTS_PER_SOL)
+        .await;
+
+    let drainer = env.drainer.insecure_clone();
+    let ix = env.client.drain(&drainer.pubkey(), None);
+    assert_error(
+        env.send(std::slice::from_ref(&ix), &[&drainer]).await,
+        ErrorCode::InsufficientFunds,
+    );
+
+    // Wallet bump no longer matching the registry wallet
+    env.set_registry(|registry| registry.wallet_bump = registry.wallet_bump.wrapping_add(1))
+        .await;
+    assert_error(
+        env.send(&[ix], &[&drainer]).await,
+        ErrorCode::WrongRegistryWallet,
+    );
+}
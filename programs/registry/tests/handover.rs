@@ -0,0 +1,100 @@
+mod common;
+
+use common::*;
+use registry::{
+    error::ErrorCode,
+    roles::Role,
+    state::{RegistryRoles, ServiceRegistry},
+};
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+#[tokio::test]
+async fn owner_takes_over_once_accepting() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let new_owner = Keypair::new();
+    let stranger = Keypair::new();
+    env.transfer(&new_owner.pubkey(), LAMPORTS_PER_SOL).await;
+    env.transfer(&stranger.pubkey(), LAMPORTS_PER_SOL).await;
+
+    let ix = env.client.cancel_owner(&owner.pubkey());
+    assert_error(
+        env.send(&[ix], &[&owner]).await,
+        ErrorCode::NoPendingHandover,
+    );
+    let ix = env
+        .client
+        .propose_owner(&stranger.pubkey(), &stranger.pubkey());
+    assert_error(env.send(&[ix], &[&stranger]).await, ErrorCode::MissingRole);
+
+    // A proposal leaves the owner in place until cancelled
+    let ix = env
+        .client
+        .propose_owner(&owner.pubkey(), &new_owner.pubkey());
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.owner, owner.pubkey());
+    assert_eq!(registry.pending_owner, new_owner.pubkey());
+
+    let ix = env.client.accept_owner(&stranger.pubkey());
+    assert_error(
+        env.send(&[ix], &[&stranger]).await,
+        ErrorCode::NotPendingKey,
+    );
+    let ix = env.client.cancel_owner(&owner.pubkey());
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let ix = env.client.accept_owner(&new_owner.pubkey());
+    assert_error(
+        env.send(&[ix], &[&new_owner]).await,
+        ErrorCode::NotPendingKey,
+    );
+
+    let ixs = [
+        env.client
+            .propose_owner(&owner.pubkey(), &new_owner.pubkey()),
+        env.client.accept_owner(&new_owner.pubkey()),
+    ];
+    env.send(&ixs, &[&owner, &new_owner]).await.unwrap();
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.owner, new_owner.pubkey());
+    assert_eq!(registry.pending_owner, Pubkey::default());
+    let roles: RegistryRoles = env.account(&env.client.roles()).await;
+    for role in Role::OWNER {
+        assert!(roles.has_role(role, &new_owner.pubkey()));
+        assert!(!roles.has_role(role, &owner.pubkey()));
+    }
+
+    let ix = env.client.propose_owner(&owner.pubkey(), &owner.pubkey());
+    assert_error(env.send(&[ix], &[&owner]).await, ErrorCode::MissingRole);
+}
+
+#[tokio::test]
+async fn drainer_takes_over_once_accepting() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let drainer = env.drainer.insecure_clone();
+    let new_drainer = Keypair::new();
+    env.transfer(&new_drainer.pubkey(), LAMPORTS_PER_SOL).await;
+
+    let ix = env
+        .client
+        .propose_drainer(&owner.pubkey(), &new_drainer.pubkey());
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let ix = env.client.drain(&drainer.pubkey(), None);
+    env.send(&[ix], &[&drainer]).await.unwrap();
+
+    let ix = env.client.accept_drainer(&new_drainer.pubkey());
+    env.send(&[ix], &[&new_drainer]).await.unwrap();
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.drainer, new_drainer.pubkey());
+    assert_eq!(registry.pending_drainer, Pubkey::default());
+
+    let ix = env.client.drain(&drainer.pubkey(), None);
+    assert_error(env.send(&[ix], &[&drainer]).await, ErrorCode::MissingRole);
+    let ix = env.client.drain(&new_drainer.pubkey(), None);
+    env.send(&[ix], &[&new_drainer]).await.unwrap();
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL},
+};
+use common::*;
+use registry::{error::ErrorCode, state::ServiceAccount};
+use solana_sdk::signature::{Keypair, Signer};
+
+/// Proof of `agent_instance` holding its key for a registration by `operator`.
+fn proof(
+    env: &TestEnv,
+    agent_instance: &Keypair,
+    service_id: u128,
+    operator: &Pubkey,
+) -> Instruction {
+    let message = env.client.instance_proof_message(service_id, operator);
+    env.client.instance_proof(
+        &agent_instance.pubkey(),
+        agent_instance
+            .sign_message(&message)
+            .as_ref()
+            .try_into()
+            .unwrap(),
+        service_id,
+        operator,
+    )
+}
+
+#[tokio::test]
+async fn registration_requires_instance_proofs() {
+    let mut env = setup().await;
+    let (service_id, service) = env.create_service([160u8; 32]).await;
+    env.register_agent_ids(
+        service_id,
+        service,
+        &[1],
+        &[registry::AgentParams {
+            slots: 2,
+            bond: LAMPORTS_PER_SOL,
+        }],
+        2,
+    )
+    .await;
+    let ix = env
+        .client
+        .set_instance_proofs(&env.manager.pubkey(), service_id, true);
+    env.send_as_manager(ix).await.unwrap();
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert!(service_account.require_instance_proofs);
+    env.activate_registration(service_id, service).await;
+
+    let operator = Keypair::new();
+    env.transfer(&operator.pubkey(), 3 * LAMPORTS_PER_SOL).await;
+    let agent_instance_keys = [Keypair::new(), Keypair::new()];
+    let agent_instances: Vec<Pubkey> = agent_instance_keys.iter().map(|k| k.pubkey()).collect();
+    let register = env.client.register_agents(
+        &operator.pubkey(),
+        &service,
+        service_id,
+        &agent_instances,
+        &[1, 1],
+        None,
+    );
+
+    // Instances without a proof
+    assert_error(
+        env.send(std::slice::from_ref(&register), &[&operator])
+            .await,
+        ErrorCode::InvalidInstanceProof,
+    );
+    let first = proof(
+        &env,
+        &agent_instance_keys[0],
+        service_id,
+        &operator.pubkey(),
+    );
+    assert_error(
+        env.send(&[first.clone(), register.clone()], &[&operator])
+            .await,
+        ErrorCode::InvalidInstanceProof,
+    );
+
+    // A proof given for another operator
+    let other = proof(
+        &env,
+        &agent_instance_keys[1],
+        service_id,
+        &Keypair::new().pubkey(),
+    );
+    assert_error(
+        env.send(&[first.clone(), other, register.clone()], &[&operator])
+            .await,
+        ErrorCode::InvalidInstanceProof,
+    );
+
+    // Proofs sit next to the operator signature when the manager registers
+    let second = proof(
+        &env,
+        &agent_instance_keys[1],
+        service_id,
+        &operator.pubkey(),
+    );
+    let signed = env
+        .signed_registration(
+            service_id,
+            service,
+            &operator,
+            &[1, 1],
+            &agent_instances,
+            None,
+        )
+        .await;
+    let manager = env.manager.insecure_clone();
+    env.send(
+        &[second, first, signed[0].clone(), signed[1].clone()],
+        &[&manager],
+    )
+    .await
+    .unwrap();
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.num_agent_instances, 2);
+}
+
+#[tokio::test]
+async fn instance_proofs_are_set_by_the_manager_before_registration() {
+    let mut env = setup().await;
+    let (service_id, _) = env.create_service([161u8; 32]).await;
+
+    let owner = env.owner.insecure_clone();
+    let ix = env
+        .client
+        .set_instance_proofs(&owner.pubkey(), service_id, true);
+    assert_error(env.send(&[ix], &[&owner]).await, ErrorCode::MissingRole);
+
+    let service = env.register_service([162u8; 32], 1, 1, 0).await;
+    let ix = env
+        .client
+        .set_instance_proofs(&env.manager.pubkey(), service.service_id, true);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongServiceState);
+}
@@ -0,0 +1,301 @@
+mod common;
+
+use anchor_lang::{prelude::*, solana_program::native_token::LAMPORTS_PER_SOL};
+use common::*;
+use registry::{
+    service_state::ServiceState,
+    state::{
+        OperatorAgentInstanceAccount, OperatorAgentInstanceIndex, OperatorBondAccount,
+        ServiceAccount, ServiceAgentIdsIndex, ServiceAgentInstancesIndex, ServiceRegistry,
+    },
+    AgentParams,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn initialize_sets_roles_and_wallet() {
+    let mut env = setup().await;
+
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.owner, env.owner.pubkey());
+    assert_eq!(registry.manager, env.manager.pubkey());
+    assert_eq!(registry.drainer, env.drainer.pubkey());
+    assert_eq!(registry.name, "test_token");
+    assert_eq!(registry.symbol, "AUTO");
+    assert_eq!(registry.base_uri, "base_uri");
+    assert_eq!(registry.total_supply, 0);
+    assert_eq!(registry.slashed_funds, 0);
+    assert!(!registry.locked);
+    assert_eq!(registry.wallet_key, env.registry_wallet());
+
+    let wallet = env.raw_account(&env.registry_wallet()).await.unwrap();
+    assert_eq!(wallet.owner, registry::ID);
+}
+
+#[tokio::test]
+async fn runs_full_service_lifecycle() {
+    let mut env = setup().await;
+    let config_hash = [1u8; 32];
+    let agent_ids = vec![1u32, 2, 3];
+    let agent_params: Vec<AgentParams> = agent_ids
+        .iter()
+        .map(|agent_id| AgentParams {
+            slots: 1,
+            bond: LAMPORTS_PER_SOL * *agent_id as u64,
+        })
+        .collect();
+    let security_deposit = 3 * LAMPORTS_PER_SOL;
+    let total_bond = 6 * LAMPORTS_PER_SOL;
+    let wallet = env.registry_wallet();
+    let wallet_rent = env.balance(&wallet).await;
+
+    // create
+    let (service_id, service) = env.create_service(config_hash).await;
+    assert_eq!(service_id, 1);
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.service_owner, env.service_owner.pubkey());
+    assert_eq!(service_account.config_hash, config_hash);
+    assert_eq!(service_account.state, ServiceState::PreRegistration);
+    let registry_account: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry_account.total_supply, 1);
+    let escrow = env.client.service_escrow(service_id);
+    let escrow_rent = env.balance(&escrow).await;
+
+    // register_agent_ids_to_service
+    env.register_agent_ids(service_id, service, &agent_ids, &agent_params, 3)
+        .await;
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.security_deposit, security_deposit);
+    assert_eq!(service_account.max_num_agent_instances, 3);
+    assert_eq!(service_account.threshold, 3);
+    let index: ServiceAgentIdsIndex = env
+        .account(
+            &service_agent_ids_index_pda(env.client.registry_seed(), service_id, &registry::ID).0,
+        )
+        .await;
+    let indexed: Vec<u32> = index.agent_ids.iter().map(|param| param.agent_id).collect();
+    assert_eq!(indexed, agent_ids);
+
+    // activate_registration moves the security deposit into the service escrow
+    env.activate_registration(service_id, service).await;
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.state, ServiceState::ActiveRegistration);
+    assert_eq!(env.balance(&escrow).await, escrow_rent + security_deposit);
+    assert_eq!(env.balance(&wallet).await, wallet_rent);
+
+    // register_agents moves the operator bond into the service escrow
+    let operator = Keypair::new();
+    let agent_instance_keys: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+    let agent_instances: Vec<Pubkey> = agent_instance_keys.iter().map(|k| k.pubkey()).collect();
+    env.register_agents(service_id, service, &operator, &agent_ids, &agent_instances)
+        .await;
+    assert_eq!(
+        env.balance(&escrow).await,
+        escrow_rent + security_deposit + total_bond
+    );
+
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.state, ServiceState::FinishedRegistration);
+    assert_eq!(service_account.num_agent_instances, 3);
+    let bond: OperatorBondAccount = env
+        .account(&env.operator_bond(service_id, &operator.pubkey()))
+        .await;
+    assert_eq!(bond.bond, total_bond);
+    assert_eq!(bond.operator, operator.pubkey());
+    let instances_index: ServiceAgentInstancesIndex = env
+        .account(
+            &agent_instances_index_pda(env.client.registry_seed(), service_id, &registry::ID).0,
+        )
+        .await;
+    assert_eq!(instances_index.service_agent_instances, agent_instances);
+    let operator_instance: OperatorAgentInstanceAccount = env
+        .account(
+            &operator_agent_instance_pda(&agent_instances[0], &operator.pubkey(), &registry::ID).0,
+        )
+        .await;
+    assert_eq!(operator_instance.operator, operator.pubkey());
+
+    // deploy
+    let registered = RegisteredService {
+        service_id,
+        service,
+        agent_ids: agent_ids.clone(),
+        operator: operator.insecure_clone(),
+        agent_instance_keys,
+        agent_instances: agent_instances.clone(),
+    };
+    let multisig = env.deploy_builtin(&registered).await;
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.state, ServiceState::Deployed);
+    assert_eq!(service_account.multisig, multisig);
+
+    // slash, signed by the service multisig
+    for agent_instance in &agent_instances {
+        env.transfer(agent_instance, LAMPORTS_PER_SOL).await;
+    }
+    let slash_amount = LAMPORTS_PER_SOL / 2;
+    let slash = env.slash_ix(
+        &registered,
+        multisig,
+        &agent_instances[..1],
+        &[slash_amount],
+    );
+    env.execute_through_multisig(&registered, multisig, &slash)
+        .await
+        .unwrap();
+    let bond: OperatorBondAccount = env
+        .account(&env.operator_bond(service_id, &operator.pubkey()))
+        .await;
+    assert_eq!(bond.bond, total_bond - slash_amount);
+    let registry_account: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry_account.slashed_funds, slash_amount);
+    // Slashed funds move to the registry wallet until drained
+    assert_eq!(env.balance(&wallet).await, wallet_rent + slash_amount);
+    assert_eq!(
+        env.balance(&escrow).await,
+        escrow_rent + security_deposit + total_bond - slash_amount
+    );
+
+    // terminate refunds the security deposit to the service owner
+    let owner_before = env.balance(&env.service_owner.pubkey()).await;
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(
+        env.balance(&env.service_owner.pubkey()).await,
+        owner_before + security_deposit
+    );
+    assert_eq!(
+        env.balance(&escrow).await,
+        escrow_rent + total_bond - slash_amount
+    );
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.state, ServiceState::TerminatedBonded);
+    assert_eq!(service_account.security_deposit, 0);
+    assert!(service_account.cleanup_pending);
+
+    // unbond refunds what is left of the operator bond
+    let operator_index: OperatorAgentInstanceIndex = env
+        .account(
+            &operator_agent_instance_index_pda(
+                env.client.registry_seed(),
+                service_id,
+                &operator.pubkey(),
+                &registry::ID,
+            )
+            .0,
+        )
+        .await;
+    assert_eq!(operator_index.operator_agent_instances.len(), 3);
+    let registered: Vec<(Pubkey, u32)> = agent_instances
+        .iter()
+        .copied()
+        .zip(agent_ids.iter().copied())
+        .collect();
+    let ix = env.client.unbond(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &operator.pubkey(),
+        &registered,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let ix = env.client.claim_unbonded(
+        &env.manager.pubkey(),
+        service_id,
+        &operator.pubkey(),
+        &env.manager.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(
+        env.balance(&operator.pubkey()).await,
+        total_bond - slash_amount
+    );
+    assert_eq!(env.balance(&wallet).await, wallet_rent + slash_amount);
+    assert_eq!(env.balance(&escrow).await, escrow_rent);
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.state, ServiceState::PreRegistration);
+    assert_eq!(service_account.num_agent_instances, 0);
+    assert!(env
+        .raw_account(&env.operator_bond(service_id, &operator.pubkey()))
+        .await
+        .is_none());
+    for agent_instance in &agent_instances {
+        let pda = operator_agent_instance_pda(agent_instance, &operator.pubkey(), &registry::ID).0;
+        assert!(env.raw_account(&pda).await.is_none());
+    }
+
+    // cleanup_service closes the agent PDAs and the service indexes
+    let ix = env.client.cleanup_service(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &env.service_owner.pubkey(),
+        &agent_ids,
+        &registered,
+        0..agent_ids.len() + registered.len(),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert!(!service_account.cleanup_pending);
+    for closed in [
+        service_agent_ids_index_pda(env.client.registry_seed(), service_id, &registry::ID).0,
+        agent_instances_index_pda(env.client.registry_seed(), service_id, &registry::ID).0,
+        service_agent_slot_counter_pda(env.client.registry_seed(), service_id, 1, &registry::ID).0,
+        service_agent_instance_pda(
+            env.client.registry_seed(),
+            service_id,
+            1,
+            &agent_instances[0],
+            &registry::ID,
+        )
+        .0,
+    ] {
+        assert!(env.raw_account(&closed).await.is_none());
+    }
+
+    // drain sends the slashed funds to the drainer
+    let drainer = env.drainer.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let drainer_before = env.balance(&drainer.pubkey()).await;
+    let ix = env.client.drain(&drainer.pubkey(), None);
+    env.send(&[ix], &[&manager, &drainer]).await.unwrap();
+    assert_eq!(
+        env.balance(&drainer.pubkey()).await,
+        drainer_before + slash_amount
+    );
+    assert_eq!(env.balance(&wallet).await, wallet_rent);
+    let registry_account: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry_account.slashed_funds, 0);
+    assert!(!registry_account.locked);
+}
+
+#[tokio::test]
+async fn terminate_before_registration_returns_to_pre_registration() {
+    let mut env = setup().await;
+    let service = env.register_service([2u8; 32], 2, 2, 0).await;
+    let escrow = env.client.service_escrow(service.service_id);
+    let escrow_before = env.balance(&escrow).await;
+
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.state, ServiceState::PreRegistration);
+    assert_eq!(
+        env.balance(&escrow).await,
+        escrow_before - 2 * LAMPORTS_PER_SOL
+    );
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use anchor_lang::{prelude::*, solana_program::native_token::LAMPORTS_PER_SOL};
+use common::*;
+use registry::{
+    error::ErrorCode,
+    state::{
+        LegacyMultisigAccount, MultisigAccount, MultisigProposal, OperatorBondAccount,
+        ServiceRegistry,
+    },
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+async fn deployed_service(env: &mut TestEnv, config_hash: [u8; 32]) -> (RegisteredService, Pubkey) {
+    let service = env.register_service(config_hash, 3, 3, 3).await;
+    let multisig = env.deploy_builtin(&service).await;
+    for agent_instance in &service.agent_instances {
+        env.transfer(agent_instance, LAMPORTS_PER_SOL).await;
+    }
+    (service, multisig)
+}
+
+#[tokio::test]
+async fn slashes_through_multisig_once_threshold_is_reached() {
+    let mut env = setup().await;
+    let (service, multisig) = deployed_service(&mut env, [30u8; 32]).await;
+    let agents: Vec<Keypair> = service
+        .agent_instance_keys
+        .iter()
+        .map(|key| key.insecure_clone())
+        .collect();
+
+    let multisig_account: MultisigAccount = env.account(&multisig).await;
+    assert_eq!(multisig_account.threshold, 3);
+    assert_eq!(multisig_account.proposal_count, 0);
+
+    let slash_amount = LAMPORTS_PER_SOL / 10;
+    let slash = env.slash_ix(
+        &service,
+        multisig,
+        &service.agent_instances[..1],
+        &[slash_amount],
+    );
+
+    env.send(
+        &[env
+            .client
+            .propose_multisig_transaction(&agents[0].pubkey(), &multisig, 0, &slash)],
+        &[&agents[0]],
+    )
+    .await
+    .unwrap();
+    env.send(
+        &[env
+            .client
+            .approve_multisig_transaction(&agents[1].pubkey(), &multisig, 0)],
+        &[&agents[1]],
+    )
+    .await
+    .unwrap();
+
+    // Two approvals out of three
+    let manager = env.manager.insecure_clone();
+    let err = env
+        .send(
+            &[env
+                .client
+                .execute_multisig_transaction(&manager.pubkey(), &multisig, 0, &slash)],
+            &[&manager],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(error_code(err), anchor_error(ErrorCode::NotEnoughApprovals));
+
+    env.send(
+        &[env
+            .client
+            .approve_multisig_transaction(&agents[2].pubkey(), &multisig, 0)],
+        &[&agents[2]],
+    )
+    .await
+    .unwrap();
+
+    let bond_before: OperatorBondAccount = env
+        .account(&env.operator_bond(service.service_id, &service.operator.pubkey()))
+        .await;
+
+    env.send(
+        &[env
+            .client
+            .execute_multisig_transaction(&manager.pubkey(), &multisig, 0, &slash)],
+        &[&manager],
+    )
+    .await
+    .unwrap();
+
+    let bond_after: OperatorBondAccount = env
+        .account(&env.operator_bond(service.service_id, &service.operator.pubkey()))
+        .await;
+    assert_eq!(bond_before.bond - bond_after.bond, slash_amount);
+
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.slashed_funds, slash_amount);
+
+    let proposal: MultisigProposal = env
+        .account(&multisig_proposal_pda(&multisig, 0, &registry::ID).0)
+        .await;
+    assert!(proposal.executed);
+    assert_eq!(proposal.approvals.len(), 3);
+
+    // Executed proposals cannot be replayed
+    let err = env
+        .send(
+            &[env
+                .client
+                .execute_multisig_transaction(&manager.pubkey(), &multisig, 0, &slash)],
+            &[&manager],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error_code(err),
+        anchor_error(ErrorCode::ProposalAlreadyExecuted)
+    );
+}
+
+#[tokio::test]
+async fn rejects_non_owner_and_duplicate_approvals() {
+    let mut env = setup().await;
+    let (service, multisig) = deployed_service(&mut env, [31u8; 32]).await;
+    let agent = service.agent_instance_keys[0].insecure_clone();
+
+    let slash = env.slash_ix(&service, multisig, &service.agent_instances[..1], &[1]);
+
+    let outsider = Keypair::new();
+    env.transfer(&outsider.pubkey(), LAMPORTS_PER_SOL).await;
+    let err = env
+        .send(
+            &[env
+                .client
+                .propose_multisig_transaction(&outsider.pubkey(), &multisig, 0, &slash)],
+            &[&outsider],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(error_code(err), anchor_error(ErrorCode::NotMultisigOwner));
+
+    env.send(
+        &[env
+            .client
+            .propose_multisig_transaction(&agent.pubkey(), &multisig, 0, &slash)],
+        &[&agent],
+    )
+    .await
+    .unwrap();
+
+    let err = env
+        .send(
+            &[env
+                .client
+                .approve_multisig_transaction(&agent.pubkey(), &multisig, 0)],
+            &[&agent],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error_code(err),
+        anchor_error(ErrorCode::ProposalAlreadyApproved)
+    );
+
+    let err = env
+        .send(
+            &[env
+                .client
+                .approve_multisig_transaction(&outsider.pubkey(), &multisig, 0)],
+            &[&outsider],
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(error_code(err), anchor_error(ErrorCode::NotMultisigOwner));
+
+    let multisig_account: MultisigAccount = env.account(&multisig).await;
+    assert_eq!(multisig_account.proposal_count, 1);
+}
+
+#[tokio::test]
+async fn migrates_multisig_deployed_before_proposals() {
+    let mut env = setup().await;
+    let (service, multisig) = deployed_service(&mut env, [36u8; 32]).await;
+
+    // Rewrite the multisig in its layout without the proposal count
+    let current: MultisigAccount = env.account(&multisig).await;
+    let mut data = MultisigAccount::DISCRIMINATOR.to_vec();
+    LegacyMultisigAccount {
+        agent_instances: current.agent_instances.clone(),
+        threshold: current.threshold,
+        data: current.data.clone(),
+    }
+    .serialize(&mut data)
+    .unwrap();
+    let mut account = env.raw_account(&multisig).await.unwrap();
+    account.data = data;
+    env.ctx.set_account(&multisig, &account.into());
+
+    let agent = service.agent_instance_keys[0].insecure_clone();
+    let target = env.slash_ix(&service, multisig, &service.agent_instances[..1], &[1]);
+    let propose = env
+        .client
+        .propose_multisig_transaction(&agent.pubkey(), &multisig, 0, &target);
+    assert!(env
+        .send(std::slice::from_ref(&propose), &[&agent])
+        .await
+        .is_err());
+
+    let ix = env
+        .client
+        .migrate_multisig(&env.manager.pubkey(), &multisig);
+    env.send_as_manager(ix.clone()).await.unwrap();
+    let migrated: MultisigAccount = env.account(&multisig).await;
+    assert_eq!(migrated.agent_instances, current.agent_instances);
+    assert_eq!(migrated.threshold, current.threshold);
+    assert_eq!(migrated.proposal_count, 0);
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+    let raw = env.raw_account(&multisig).await.unwrap();
+    assert!(raw.lamports >= rent.minimum_balance(raw.data.len()));
+
+    // Migrating again changes nothing, and proposals work on the migrated multisig
+    env.send_as_manager(ix).await.unwrap();
+    env.send(&[propose], &[&agent]).await.unwrap();
+    let migrated: MultisigAccount = env.account(&multisig).await;
+    assert_eq!(migrated.proposal_count, 1);
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+use common::*;
+use registry::{error::ErrorCode, signatures, state::OperatorBondAccount};
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+#[tokio::test]
+async fn operator_registers_itself_paying_its_bond() {
+    let mut env = setup().await;
+    let service = env.register_service([150u8; 32], 2, 2, 0).await;
+    let escrow = env.client.service_escrow(service.service_id);
+    let escrow_before = env.balance(&escrow).await;
+    let operator = Keypair::new();
+    env.transfer(&operator.pubkey(), 2 * LAMPORTS_PER_SOL).await;
+    let agent_instance = Keypair::new().pubkey();
+
+    // The manager cannot register an operator without its signature
+    let mut ix = env.client.register_agents(
+        &operator.pubkey(),
+        &service.service,
+        service.service_id,
+        &[agent_instance],
+        &[1],
+        None,
+    );
+    // payer of the registration, right before the system program
+    ix.accounts[11].pubkey = env.manager.pubkey();
+    assert_eq!(
+        env.send_as_manager(ix).await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidAccountOwner)
+    );
+
+    let ix = env.client.register_agents(
+        &operator.pubkey(),
+        &service.service,
+        service.service_id,
+        &[agent_instance],
+        &[1],
+        None,
+    );
+    env.send(&[ix], &[&operator]).await.unwrap();
+
+    let bond: OperatorBondAccount = env
+        .account(&env.operator_bond(service.service_id, &operator.pubkey()))
+        .await;
+    assert_eq!(bond.bond, LAMPORTS_PER_SOL);
+    assert_eq!(env.balance(&escrow).await, escrow_before + LAMPORTS_PER_SOL);
+    assert_eq!(env.operator_nonce(&operator.pubkey()).await, 0);
+}
+
+#[tokio::test]
+async fn manager_registers_with_operator_signature() {
+    let mut env = setup().await;
+    let service = env.register_service([151u8; 32], 2, 2, 0).await;
+    let manager = env.manager.insecure_clone();
+    let operator = Keypair::new();
+    let agent_instances = [Keypair::new().pubkey()];
+    let register = env
+        .signed_registration(
+            service.service_id,
+            service.service,
+            &operator,
+            &[1],
+            &agent_instances,
+            None,
+        )
+        .await;
+
+    // Without the Ed25519 program check
+    assert_error(
+        env.send(&register[1..], &[&manager]).await,
+        ErrorCode::InvalidOperatorSignature,
+    );
+
+    // Signed by someone else
+    let impostor = Keypair::new();
+    let message = env
+        .client
+        .registration_message(service.service_id, &agent_instances, &[1], 0);
+    let mut ixs = register.clone();
+    ixs[0].data = signatures::ed25519_instruction_data(
+        &impostor.pubkey(),
+        impostor.sign_message(&message).as_ref().try_into().unwrap(),
+        &message,
+    );
+    assert_error(
+        env.send(&ixs, &[&manager]).await,
+        ErrorCode::InvalidOperatorSignature,
+    );
+
+    // Signed for another agent instance
+    let other = env
+        .signed_registration(
+            service.service_id,
+            service.service,
+            &operator,
+            &[1],
+            &[Keypair::new().pubkey()],
+            None,
+        )
+        .await;
+    let ixs = [other[0].clone(), register[1].clone()];
+    assert_error(
+        env.send(&ixs, &[&manager]).await,
+        ErrorCode::InvalidOperatorSignature,
+    );
+
+    env.send(&register, &[&manager]).await.unwrap();
+    assert_eq!(env.operator_nonce(&operator.pubkey()).await, 1);
+    let bond: OperatorBondAccount = env
+        .account(&env.operator_bond(service.service_id, &operator.pubkey()))
+        .await;
+    assert_eq!(bond.operator, operator.pubkey());
+
+    // A signature is only good once
+    assert_error(
+        env.send(&register, &[&manager]).await,
+        ErrorCode::InvalidOperatorSignature,
+    );
+    let next = env
+        .signed_registration(
+            service.service_id,
+            service.service,
+            &operator,
+            &[2],
+            &[Keypair::new().pubkey()],
+            None,
+        )
+        .await;
+    env.send(&next, &[&manager]).await.unwrap();
+    assert_eq!(env.operator_nonce(&operator.pubkey()).await, 2);
+}
@@ -0,0 +1,215 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use common::*;
+use registry::{constants::MAX_WHITELISTED_OPERATORS, error::ErrorCode, state::OperatorWhitelist};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+fn assert_invalid_argument(result: std::result::Result<(), BanksClientError>) {
+    assert!(matches!(
+        result,
+        Err(BanksClientError::TransactionError(
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn only_whitelisted_operators_register_while_checked() {
+    let mut env = setup().await;
+    let service = env.register_service([160u8; 32], 3, 3, 0).await;
+    let service_id = service.service_id;
+    let service_owner = env.service_owner.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let whitelisted = Keypair::new();
+    let other = Keypair::new();
+
+    let ix = env.client.set_operators_statuses(
+        &service_owner.pubkey(),
+        service_id,
+        &[whitelisted.pubkey()],
+        &[true],
+        true,
+    );
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+
+    let whitelist: OperatorWhitelist = env
+        .account(&env.client.operator_whitelist(service_id))
+        .await;
+    assert_eq!(whitelist.service_id, service_id);
+    assert!(whitelist.check);
+    assert_eq!(whitelist.operators, vec![whitelisted.pubkey()]);
+
+    let register = env
+        .signed_registration(
+            service_id,
+            service.service,
+            &other,
+            &[1],
+            &[Keypair::new().pubkey()],
+            None,
+        )
+        .await;
+    assert_error(
+        env.send(&register, &[&manager]).await,
+        ErrorCode::OperatorNotWhitelisted,
+    );
+    env.register_agents(
+        service_id,
+        service.service,
+        &whitelisted,
+        &[1],
+        &[Keypair::new().pubkey()],
+    )
+    .await;
+
+    // Removed operators are rejected again
+    let ix = env.client.set_operators_statuses(
+        &service_owner.pubkey(),
+        service_id,
+        &[whitelisted.pubkey(), other.pubkey()],
+        &[false, false],
+        true,
+    );
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+    let whitelist: OperatorWhitelist = env
+        .account(&env.client.operator_whitelist(service_id))
+        .await;
+    assert!(whitelist.operators.is_empty());
+    let register = env
+        .signed_registration(
+            service_id,
+            service.service,
+            &whitelisted,
+            &[2],
+            &[Keypair::new().pubkey()],
+            None,
+        )
+        .await;
+    assert_error(
+        env.send(&register, &[&manager]).await,
+        ErrorCode::OperatorNotWhitelisted,
+    );
+
+    // Any operator registers once the check is off
+    let ix = env
+        .client
+        .set_operators_check(&service_owner.pubkey(), service_id, false);
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+    env.send(&register, &[&manager]).await.unwrap();
+    env.register_agents(
+        service_id,
+        service.service,
+        &other,
+        &[3],
+        &[Keypair::new().pubkey()],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn only_the_service_owner_updates_the_whitelist() {
+    let mut env = setup().await;
+    let service = env.register_service([161u8; 32], 1, 1, 0).await;
+    let service_id = service.service_id;
+    let service_owner = env.service_owner.insecure_clone();
+    let operators = [Keypair::new().pubkey()];
+
+    // Someone else signing with the token account of the service owner
+    let stranger = env.manager.insecure_clone();
+    let mut ix = env.client.set_operators_statuses(
+        &stranger.pubkey(),
+        service_id,
+        &operators,
+        &[true],
+        true,
+    );
+    // service token account, after the registry, service, whitelist and signer
+    ix.accounts[4].pubkey = env
+        .client
+        .service_token(service_id, &service_owner.pubkey());
+    assert_invalid_argument(env.send(&[ix], &[&stranger]).await);
+
+    let ix = env.client.set_operators_statuses(
+        &service_owner.pubkey(),
+        service_id,
+        &operators,
+        &[true, false],
+        true,
+    );
+    assert_error(
+        env.send(&[ix], &[&service_owner]).await,
+        ErrorCode::WrongArrayLength,
+    );
+
+    let ix = env.client.set_operators_statuses(
+        &service_owner.pubkey(),
+        service_id,
+        &[Pubkey::default()],
+        &[true],
+        true,
+    );
+    assert_invalid_argument(env.send(&[ix], &[&service_owner]).await);
+
+    // A service without a whitelist accepts every operator
+    env.register_agents(
+        service_id,
+        service.service,
+        &Keypair::new(),
+        &[1],
+        &[Keypair::new().pubkey()],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn whitelists_hold_a_bounded_number_of_operators() {
+    let mut env = setup().await;
+    let service = env.register_service([162u8; 32], 1, 1, 0).await;
+    let service_id = service.service_id;
+    let service_owner = env.service_owner.insecure_clone();
+
+    let ix = env.client.set_operators_statuses(
+        &service_owner.pubkey(),
+        service_id,
+        &[Keypair::new().pubkey()],
+        &[true],
+        true,
+    );
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+    // Filling the whitelist through instructions takes dozens of transactions
+    let whitelist_key = env.client.operator_whitelist(service_id);
+    env.set_account(&whitelist_key, |whitelist: &mut OperatorWhitelist| {
+        whitelist
+            .operators
+            .resize_with(MAX_WHITELISTED_OPERATORS, Pubkey::new_unique)
+    })
+    .await;
+
+    // Operators already whitelisted and removals still go through
+    let whitelist: OperatorWhitelist = env.account(&whitelist_key).await;
+    let ix = env.client.set_operators_statuses(
+        &service_owner.pubkey(),
+        service_id,
+        &whitelist.operators[..2],
+        &[true, false],
+        true,
+    );
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+    let ix = env.client.set_operators_statuses(
+        &service_owner.pubkey(),
+        service_id,
+        &[Keypair::new().pubkey(), Keypair::new().pubkey()],
+        &[true, true],
+        true,
+    );
+    assert_error(
+        env.send(&[ix], &[&service_owner]).await,
+        ErrorCode::MaxWhitelistedOperatorsReached,
+    );
+}
@@ -0,0 +1,290 @@
+mod common;
+
+use common::*;
+use registry::{
+    error::ErrorCode,
+    service_state::ServiceState,
+    state::{OperatorAgentInstanceIndex, OperatorBondAccount, ServiceAccount},
+};
+use solana_sdk::{
+    instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+#[tokio::test]
+async fn instances_unbond_in_batches() {
+    let mut env = setup().await;
+    // Agent ids 1, 2 and 3 bond 1, 2 and 3 SOL
+    let service = env.register_service([220u8; 32], 3, 3, 3).await;
+    let multisig = env.deploy_builtin(&service).await;
+    for agent_instance in &service.agent_instances {
+        env.transfer(agent_instance, LAMPORTS_PER_SOL).await;
+    }
+    let slash_amount = 5 * LAMPORTS_PER_SOL / 2;
+    let ix = env.slash_ix(
+        &service,
+        multisig,
+        &service.agent_instances[..1],
+        &[slash_amount],
+    );
+    env.execute_through_multisig(&service, multisig, &ix)
+        .await
+        .unwrap();
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+
+    let operator = service.operator.pubkey();
+    let instances = service.registered_instances();
+    let bond_key = env.operator_bond(service.service_id, &operator);
+    let index_key = operator_agent_instance_index_pda(
+        env.client.registry_seed(),
+        service.service_id,
+        &operator,
+        &registry::ID,
+    )
+    .0;
+    let unbond = |env: &TestEnv, agent_instances: &[(Pubkey, u32)]| {
+        env.client.unbond(
+            &env.manager.pubkey(),
+            &service.service,
+            service.service_id,
+            &operator,
+            agent_instances,
+        )
+    };
+    let claim = |env: &TestEnv| {
+        env.client.claim_unbonded(
+            &env.manager.pubkey(),
+            service.service_id,
+            &operator,
+            &env.manager.pubkey(),
+            None,
+        )
+    };
+
+    // Instances unbond from the front of the operator index
+    let ix = unbond(&env, &instances[1..2]);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::InvalidPda);
+    let ix = unbond(&env, &[]);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongArrayLength);
+
+    // Each instance refunds the bond of its agent id
+    let ix = unbond(&env, &instances[..1]);
+    env.send_as_manager(ix).await.unwrap();
+    env.send_as_manager(claim(&env)).await.unwrap();
+    assert_eq!(env.balance(&operator).await, LAMPORTS_PER_SOL);
+    let bond: OperatorBondAccount = env.account(&bond_key).await;
+    assert_eq!(
+        bond.bond,
+        6 * LAMPORTS_PER_SOL - slash_amount - LAMPORTS_PER_SOL
+    );
+    let index: OperatorAgentInstanceIndex = env.account(&index_key).await;
+    assert_eq!(index.operator_agent_instances.len(), 2);
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.state, ServiceState::TerminatedBonded);
+    assert_eq!(service_account.num_agent_instances, 2);
+
+    // The slashed amount comes off the last refunds
+    let ix = unbond(&env, &instances[1..]);
+    env.send_as_manager(ix).await.unwrap();
+    env.send_as_manager(claim(&env)).await.unwrap();
+    assert_eq!(
+        env.balance(&operator).await,
+        6 * LAMPORTS_PER_SOL - slash_amount
+    );
+    assert!(env.raw_account(&bond_key).await.is_none());
+    assert!(env.raw_account(&index_key).await.is_none());
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.state, ServiceState::PreRegistration);
+    assert_eq!(service_account.num_agent_instances, 0);
+}
+
+/// Terminated service with agent ids 1 and 2 registered by one operator, and agent id 3
+/// by another.
+async fn two_operator_service(
+    env: &mut TestEnv,
+    config_hash: [u8; 32],
+) -> (RegisteredService, Keypair, Pubkey) {
+    let service = env.register_service(config_hash, 3, 3, 2).await;
+    let other = Keypair::new();
+    let other_instance = Pubkey::new_unique();
+    env.register_agents(
+        service.service_id,
+        service.service,
+        &other,
+        &[3],
+        &[other_instance],
+    )
+    .await;
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    (service, other, other_instance)
+}
+
+/// Unbonds `victim` instances on behalf of `attacker`, as the service manager passes its
+/// caller for the operator.
+fn unbond_as(
+    env: &TestEnv,
+    service: &RegisteredService,
+    victim: &Pubkey,
+    attacker: &Pubkey,
+    agent_instances: &[(Pubkey, u32)],
+) -> Instruction {
+    let service_id = service.service_id;
+    let mut ix = env.client.unbond(
+        &env.manager.pubkey(),
+        &service.service,
+        service_id,
+        victim,
+        agent_instances,
+    );
+    let swaps = [
+        (*victim, *attacker),
+        (
+            env.operator_bond(service_id, victim),
+            env.operator_bond(service_id, attacker),
+        ),
+        (
+            pending_withdrawal_pda(
+                env.client.registry_seed(),
+                service_id,
+                victim,
+                &registry::ID,
+            )
+            .0,
+            pending_withdrawal_pda(
+                env.client.registry_seed(),
+                service_id,
+                attacker,
+                &registry::ID,
+            )
+            .0,
+        ),
+    ];
+    for meta in &mut ix.accounts {
+        if let Some((_, to)) = swaps.iter().find(|(from, _)| *from == meta.pubkey) {
+            meta.pubkey = *to;
+        }
+    }
+    ix
+}
+
+#[tokio::test]
+async fn operator_cannot_unbond_with_another_index() {
+    let mut env = setup().await;
+    let (service, other, _) = two_operator_service(&mut env, [221u8; 32]).await;
+    let operator = service.operator.pubkey();
+    let index_key = operator_agent_instance_index_pda(
+        env.client.registry_seed(),
+        service.service_id,
+        &operator,
+        &registry::ID,
+    )
+    .0;
+
+    // The other operator passes the index and instances of the first one
+    let ix = unbond_as(
+        &env,
+        &service,
+        &operator,
+        &other.pubkey(),
+        &service.registered_instances(),
+    );
+    let err = env.send_as_manager(ix).await.unwrap_err();
+    assert_eq!(
+        error_code(err),
+        u32::from(anchor_lang::error::ErrorCode::ConstraintSeeds)
+    );
+
+    let index: OperatorAgentInstanceIndex = env.account(&index_key).await;
+    assert_eq!(index.operator_agent_instances.len(), 2);
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.num_agent_instances, 3);
+    let bond: OperatorBondAccount = env
+        .account(&env.operator_bond(service.service_id, &operator))
+        .await;
+    assert_eq!(bond.bond, 3 * LAMPORTS_PER_SOL);
+}
+
+#[tokio::test]
+async fn final_batch_needs_own_index() {
+    let mut env = setup().await;
+    let (service, other, other_instance) = two_operator_service(&mut env, [222u8; 32]).await;
+    let operator = service.operator.pubkey();
+    let instances = service.registered_instances();
+    let other_bond = env.operator_bond(service.service_id, &other.pubkey());
+
+    // A partial batch of the first operator leaves one instance in its index
+    let ix = env.client.unbond(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &operator,
+        &instances[..1],
+    );
+    env.send_as_manager(ix).await.unwrap();
+
+    // The final batch of that index does not close the bond of another operator
+    let ix = unbond_as(&env, &service, &operator, &other.pubkey(), &instances[1..]);
+    let err = env.send_as_manager(ix).await.unwrap_err();
+    assert_eq!(
+        error_code(err),
+        u32::from(anchor_lang::error::ErrorCode::ConstraintSeeds)
+    );
+    let bond: OperatorBondAccount = env.account(&other_bond).await;
+    assert_eq!(bond.bond, 3 * LAMPORTS_PER_SOL);
+    assert!(env
+        .raw_account(
+            &pending_withdrawal_pda(
+                env.client.registry_seed(),
+                service.service_id,
+                &other.pubkey(),
+                &registry::ID
+            )
+            .0
+        )
+        .await
+        .is_none());
+
+    // Each operator closes its own bond with its final batch
+    let ix = env.client.unbond(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &operator,
+        &instances[1..],
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert!(env
+        .raw_account(&env.operator_bond(service.service_id, &operator))
+        .await
+        .is_none());
+    let bond: OperatorBondAccount = env.account(&other_bond).await;
+    assert_eq!(bond.bond, 3 * LAMPORTS_PER_SOL);
+
+    let ix = env.client.unbond(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &other.pubkey(),
+        &[(other_instance, 3)],
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert!(env.raw_account(&other_bond).await.is_none());
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.state, ServiceState::PreRegistration);
+}
@@ -0,0 +1,323 @@
+mod common;
+
+use common::*;
+use registry::{
+    error::ErrorCode,
+    pause::PauseGroup,
+    service_state::ServiceState,
+    state::{ServiceAccount, ServiceRegistry},
+};
+use registry_client::RegistryClient;
+use solana_sdk::{
+    instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+};
+
+#[tokio::test]
+async fn paused_groups_reject_their_instructions() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let deployed = env.register_service([199u8; 32], 1, 1, 1).await;
+    let multisig = env.deploy_builtin(&deployed).await;
+    env.transfer(&deployed.agent_instances[0], LAMPORTS_PER_SOL)
+        .await;
+    let service = env.register_service([200u8; 32], 1, 1, 1).await;
+
+    let ix = env
+        .client
+        .set_paused(&manager.pubkey(), PauseGroup::Creation, true);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::MissingRole);
+
+    let ixs = [
+        env.client
+            .set_paused(&owner.pubkey(), PauseGroup::Creation, true),
+        env.client
+            .set_paused(&owner.pubkey(), PauseGroup::Registration, true),
+        env.client
+            .set_paused(&owner.pubkey(), PauseGroup::Deploy, true),
+        env.client
+            .set_paused(&owner.pubkey(), PauseGroup::Slash, true),
+    ];
+    env.send(&ixs, &[&owner]).await.unwrap();
+
+    let service_id = env.next_service_id().await;
+    let ix = env.client.create(
+        &manager.pubkey(),
+        service_id,
+        [201u8; 32],
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::CreationPaused);
+
+    let ix = env.deploy_ix(
+        &service,
+        registry::ID,
+        env.client.builtin_multisig(&service.agent_instances),
+        vec![],
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::DeployPaused);
+    let ix = env.slash_ix(&deployed, multisig, &deployed.agent_instances, &[1]);
+    assert_error(
+        env.execute_through_multisig(&deployed, multisig, &ix).await,
+        ErrorCode::SlashPaused,
+    );
+
+    // Resuming a group leaves the others paused
+    let ix = env
+        .client
+        .set_paused(&owner.pubkey(), PauseGroup::Creation, false);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let (service_id, service_pda) = env.create_service([202u8; 32]).await;
+    env.register_agent_ids(
+        service_id,
+        service_pda,
+        &[1],
+        &[registry::AgentParams { slots: 1, bond: 1 }],
+        1,
+    )
+    .await;
+    let ix = env.client.activate_registration(
+        &manager.pubkey(),
+        &service_pda,
+        service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::RegistrationPaused);
+
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert!(!registry.is_paused(PauseGroup::Creation));
+    assert!(registry.is_paused(PauseGroup::Registration));
+    assert!(registry.is_paused(PauseGroup::Slash));
+    assert!(!registry.is_paused(PauseGroup::Payouts));
+}
+
+#[tokio::test]
+async fn refunds_stay_open_while_payouts_are_paused() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let drainer = env.drainer.insecure_clone();
+    let service = env.register_service([203u8; 32], 1, 1, 1).await;
+
+    let ix = env
+        .client
+        .set_paused(&owner.pubkey(), PauseGroup::Payouts, true);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    let terminate = env.client.terminate(
+        &manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(
+        env.send_as_manager(terminate.clone()).await,
+        ErrorCode::PayoutsPaused,
+    );
+    let ix = env.client.drain(&drainer.pubkey(), None);
+    assert_error(env.send(&[ix], &[&drainer]).await, ErrorCode::PayoutsPaused);
+
+    let ix = env.client.set_refunds_open(&owner.pubkey(), true);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    env.send_as_manager(terminate).await.unwrap();
+    let stored: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(stored.state, ServiceState::TerminatedBonded);
+    let ix = env.client.unbond(
+        &manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &service.operator.pubkey(),
+        &service.registered_instances(),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let ix = env.client.claim_unbonded(
+        &manager.pubkey(),
+        service.service_id,
+        &service.operator.pubkey(),
+        &manager.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+
+    let ix = env.client.drain(&drainer.pubkey(), None);
+    assert_error(env.send(&[ix], &[&drainer]).await, ErrorCode::PayoutsPaused);
+}
+
+#[tokio::test]
+async fn paused_payouts_only_hold_back_refunds() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let service = env.register_service([205u8; 32], 1, 1, 1).await;
+
+    let ix = env
+        .client
+        .set_paused(&owner.pubkey(), PauseGroup::Payouts, true);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    // Without a deposit to refund, the service terminates while payouts are paused
+    env.set_account(&service.service, |service: &mut ServiceAccount| {
+        service.security_deposit = 0;
+    })
+    .await;
+    let ix = env.client.terminate(
+        &manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let stored: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(stored.state, ServiceState::TerminatedBonded);
+
+    // Unbonding only queues the refund, its claim is the payout
+    let ix = env.client.unbond(
+        &manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &service.operator.pubkey(),
+        &service.registered_instances(),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let ix = env.client.claim_unbonded(
+        &manager.pubkey(),
+        service.service_id,
+        &service.operator.pubkey(),
+        &manager.pubkey(),
+        None,
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::PayoutsPaused);
+}
+
+#[tokio::test]
+async fn services_only_accept_their_registry() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let service = env.register_service([204u8; 32], 1, 1, 1).await;
+    let stored: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(stored.registry, env.registry.pubkey());
+
+    let ix = env
+        .client
+        .set_paused(&owner.pubkey(), PauseGroup::Payouts, true);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    // A registry of its own makes the attacker the manager of that registry only
+    let attacker = Keypair::new();
+    let fake_registry = Keypair::new();
+    env.transfer(&attacker.pubkey(), 10 * LAMPORTS_PER_SOL)
+        .await;
+    let fake = RegistryClient::new(fake_registry.pubkey());
+    let ix = fake.initialize(
+        &attacker.pubkey(),
+        "fake".into(),
+        "FAKE".into(),
+        "fake_uri".into(),
+        &attacker.pubkey(),
+        &attacker.pubkey(),
+    );
+    env.send(&[ix], &[&attacker, &fake_registry]).await.unwrap();
+
+    // The fake registry with the accounts of the real service fails their seeds
+    let registry_accounts = [
+        (env.client.registry, fake.registry),
+        (env.client.roles(), fake.roles()),
+        (env.client.registry_wallet(), fake.registry_wallet()),
+    ];
+    let with_fake_registry = |mut ix: Instruction| {
+        for meta in &mut ix.accounts {
+            if let Some((_, fake_key)) = registry_accounts
+                .iter()
+                .find(|(key, _)| *key == meta.pubkey)
+            {
+                meta.pubkey = *fake_key;
+            }
+        }
+        ix
+    };
+    let ix = env
+        .client
+        .set_instance_proofs(&attacker.pubkey(), service.service_id, true);
+    let err = env
+        .send(&[with_fake_registry(ix)], &[&attacker])
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error_code(err),
+        u32::from(anchor_lang::error::ErrorCode::ConstraintSeeds)
+    );
+
+    // Instructions taking any service check its registry
+    let ixs = [
+        env.client.terminate(
+            &attacker.pubkey(),
+            &service.service,
+            service.service_id,
+            &env.service_owner.pubkey(),
+            None,
+        ),
+        env.client.unbond(
+            &attacker.pubkey(),
+            &service.service,
+            service.service_id,
+            &service.operator.pubkey(),
+            &service.registered_instances(),
+        ),
+    ];
+    for ix in ixs {
+        assert_error(
+            env.send(&[with_fake_registry(ix)], &[&attacker]).await,
+            ErrorCode::WrongServiceRegistry,
+        );
+    }
+
+    // Nor does the fake registry pay out a withdrawal the real one holds back
+    let ix = env.client.set_refunds_open(&owner.pubkey(), true);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let ix = env.client.terminate(
+        &manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let ix = env.client.unbond(
+        &manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &service.operator.pubkey(),
+        &service.registered_instances(),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let ix = env.client.set_refunds_open(&owner.pubkey(), false);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    let ix = with_fake_registry(env.client.claim_unbonded(
+        &attacker.pubkey(),
+        service.service_id,
+        &service.operator.pubkey(),
+        &manager.pubkey(),
+        None,
+    ));
+    let err = env.send(&[ix], &[&attacker]).await.unwrap_err();
+    assert_eq!(
+        error_code(err),
+        u32::from(anchor_lang::error::ErrorCode::ConstraintSeeds)
+    );
+    let ix = env.client.claim_unbonded(
+        &manager.pubkey(),
+        service.service_id,
+        &service.operator.pubkey(),
+        &manager.pubkey(),
+        None,
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::PayoutsPaused);
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use common::*;
+use registry::{
+    error::ErrorCode,
+    service_state::ServiceState,
+    state::{ServiceAccount, ServiceRegistry},
+    AgentParams,
+};
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+};
+
+const CREATION_FEE: u64 = LAMPORTS_PER_SOL / 10;
+
+/// Funded signer creating its own service, and the id and address it creates.
+async fn owner_creates(env: &mut TestEnv, owner: &Keypair) -> (u128, Pubkey) {
+    let service_id = env.next_service_id().await;
+    let ix = env.client.create(
+        &owner.pubkey(),
+        service_id,
+        [170u8; 32],
+        &owner.pubkey(),
+        None,
+    );
+    env.send(&[ix], &[owner]).await.unwrap();
+    (service_id, env.client.service(service_id))
+}
+
+#[tokio::test]
+async fn service_owner_creates_and_configures_its_service() {
+    let mut env = setup().await;
+    let owner = Keypair::new();
+    env.transfer(&owner.pubkey(), 10 * LAMPORTS_PER_SOL).await;
+
+    // Only the manager creates services until the registry owner allows it
+    let service_id = env.next_service_id().await;
+    let create = env.client.create(
+        &owner.pubkey(),
+        service_id,
+        [170u8; 32],
+        &owner.pubkey(),
+        None,
+    );
+    assert_error(env.send(&[create], &[&owner]).await, ErrorCode::MissingRole);
+
+    let ix = env
+        .client
+        .set_permissionless(&owner.pubkey(), true, CREATION_FEE);
+    assert_error(env.send(&[ix], &[&owner]).await, ErrorCode::MissingRole);
+    let registry_owner = env.owner.insecure_clone();
+    let ix = env
+        .client
+        .set_permissionless(&registry_owner.pubkey(), true, CREATION_FEE);
+    env.send(&[ix], &[&registry_owner]).await.unwrap();
+
+    // Services are only created for the signer itself
+    let ix = env.client.create(
+        &owner.pubkey(),
+        service_id,
+        [170u8; 32],
+        &Keypair::new().pubkey(),
+        None,
+    );
+    assert_error(env.send(&[ix], &[&owner]).await, ErrorCode::MissingRole);
+
+    let wallet = env.registry_wallet();
+    let wallet_before = env.balance(&wallet).await;
+    let (service_id, service) = owner_creates(&mut env, &owner).await;
+    assert_eq!(env.balance(&wallet).await, wallet_before + CREATION_FEE);
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.accrued_fees, CREATION_FEE);
+
+    let ix = env.client.register_agent_ids_to_service(
+        &owner.pubkey(),
+        &service,
+        service_id,
+        &owner.pubkey(),
+        &[1],
+        &[AgentParams {
+            slots: 1,
+            bond: LAMPORTS_PER_SOL,
+        }],
+        Some(1),
+    );
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let ix = env.client.update(
+        &owner.pubkey(),
+        &service,
+        service_id,
+        [171u8; 32],
+        &owner.pubkey(),
+        None,
+    );
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let ix = env.client.activate_registration(
+        &owner.pubkey(),
+        &service,
+        service_id,
+        &owner.pubkey(),
+        None,
+    );
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    let account: ServiceAccount = env.account(&service).await;
+    assert_eq!(account.config_hash, [171u8; 32]);
+    assert_eq!(account.security_deposit, LAMPORTS_PER_SOL);
+    assert_eq!(account.state, ServiceState::ActiveRegistration);
+
+    // Services the manager creates pay the fee as well
+    let manager_wallet_before = env.balance(&wallet).await;
+    env.create_service([172u8; 32]).await;
+    assert_eq!(
+        env.balance(&wallet).await,
+        manager_wallet_before + CREATION_FEE
+    );
+}
+
+#[tokio::test]
+async fn previous_owner_cannot_configure_the_service() {
+    let mut env = setup().await;
+    let registry_owner = env.owner.insecure_clone();
+    let ix = env
+        .client
+        .set_permissionless(&registry_owner.pubkey(), true, 0);
+    env.send(&[ix], &[&registry_owner]).await.unwrap();
+
+    let owner = Keypair::new();
+    env.transfer(&owner.pubkey(), 10 * LAMPORTS_PER_SOL).await;
+    let (service_id, service) = owner_creates(&mut env, &owner).await;
+    let new_owner = Keypair::new().pubkey();
+    let ix = env
+        .client
+        .transfer_service(&owner.pubkey(), service_id, &new_owner);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    let ix = env.client.register_agent_ids_to_service(
+        &owner.pubkey(),
+        &service,
+        service_id,
+        &owner.pubkey(),
+        &[1],
+        &[AgentParams {
+            slots: 1,
+            bond: LAMPORTS_PER_SOL,
+        }],
+        Some(1),
+    );
+    assert_error(
+        env.send(&[ix], &[&owner]).await,
+        ErrorCode::WrongServiceToken,
+    );
+    let ix = env.client.activate_registration(
+        &owner.pubkey(),
+        &service,
+        service_id,
+        &owner.pubkey(),
+        None,
+    );
+    assert_error(
+        env.send(&[ix], &[&owner]).await,
+        ErrorCode::WrongServiceToken,
+    );
+}
+
+#[tokio::test]
+async fn creation_fees_are_drained_with_slashed_funds() {
+    let mut env = setup().await;
+    let registry_owner = env.owner.insecure_clone();
+    let ix = env
+        .client
+        .set_permissionless(&registry_owner.pubkey(), true, CREATION_FEE);
+    env.send(&[ix], &[&registry_owner]).await.unwrap();
+    let owner = Keypair::new();
+    env.transfer(&owner.pubkey(), 10 * LAMPORTS_PER_SOL).await;
+    owner_creates(&mut env, &owner).await;
+    owner_creates(&mut env, &owner).await;
+
+    let drainer = env.drainer.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let drainer_before = env.balance(&drainer.pubkey()).await;
+    let ix = env.client.drain(&drainer.pubkey(), None);
+    env.send(&[ix], &[&manager, &drainer]).await.unwrap();
+
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.accrued_fees, 0);
+    assert_eq!(
+        env.balance(&drainer.pubkey()).await,
+        drainer_before + 2 * CREATION_FEE
+    );
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use common::*;
+use registry::{
+    constants::MAX_ROLE_MEMBERS,
+    error::ErrorCode,
+    roles::Role,
+    state::{RegistryRoles, ServiceRegistry},
+};
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+};
+
+async fn roles(env: &mut TestEnv) -> RegistryRoles {
+    let key = env.client.roles();
+    env.account(&key).await
+}
+
+#[tokio::test]
+async fn roles_are_granted_by_their_admin_role() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let editor = Keypair::new();
+    env.transfer(&editor.pubkey(), LAMPORTS_PER_SOL).await;
+
+    let roles_account = roles(&mut env).await;
+    assert!(roles_account.has_role(Role::Admin, &owner.pubkey()));
+    assert!(roles_account.has_role(Role::MetadataEditor, &owner.pubkey()));
+    assert!(roles_account.has_role(Role::ServiceManager, &env.manager.pubkey()));
+    assert!(roles_account.has_role(Role::Drainer, &env.drainer.pubkey()));
+
+    let ix = env
+        .client
+        .set_base_uri(&editor.pubkey(), "editor_uri".into());
+    assert_error(env.send(&[ix], &[&editor]).await, ErrorCode::MissingRole);
+    let ix = env
+        .client
+        .grant_role(&editor.pubkey(), Role::MetadataEditor, &editor.pubkey());
+    assert_error(env.send(&[ix], &[&editor]).await, ErrorCode::MissingRole);
+
+    let ix = env
+        .client
+        .grant_role(&owner.pubkey(), Role::MetadataEditor, &editor.pubkey());
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let ix = env
+        .client
+        .set_base_uri(&editor.pubkey(), "editor_uri".into());
+    env.send(&[ix], &[&editor]).await.unwrap();
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.base_uri, "editor_uri");
+
+    // Editors administer their own role once it is made its admin role
+    let ix =
+        env.client
+            .set_role_admin(&editor.pubkey(), Role::MetadataEditor, Role::MetadataEditor);
+    assert_error(env.send(&[ix], &[&editor]).await, ErrorCode::MissingRole);
+    let ix = env
+        .client
+        .set_role_admin(&owner.pubkey(), Role::MetadataEditor, Role::MetadataEditor);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let ix = env
+        .client
+        .revoke_role(&editor.pubkey(), Role::MetadataEditor, &owner.pubkey());
+    env.send(&[ix], &[&editor]).await.unwrap();
+    let ix = env.client.set_base_uri(&owner.pubkey(), "owner_uri".into());
+    assert_error(env.send(&[ix], &[&owner]).await, ErrorCode::MissingRole);
+
+    let ix = env
+        .client
+        .renounce_role(&editor.pubkey(), Role::MetadataEditor);
+    env.send(&[ix], &[&editor]).await.unwrap();
+    let roles_account = roles(&mut env).await;
+    assert!(roles_account.roles[Role::MetadataEditor as usize]
+        .members
+        .is_empty());
+    assert_eq!(
+        roles_account.admin_role(Role::MetadataEditor),
+        Role::MetadataEditor
+    );
+}
+
+#[tokio::test]
+async fn every_manager_role_holder_manages_services() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let second_manager = Keypair::new();
+    env.transfer(&second_manager.pubkey(), 10 * LAMPORTS_PER_SOL)
+        .await;
+
+    let ix = env.client.grant_role(
+        &owner.pubkey(),
+        Role::ServiceManager,
+        &second_manager.pubkey(),
+    );
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let service_id = env.next_service_id().await;
+    let ix = env.client.create(
+        &second_manager.pubkey(),
+        service_id,
+        [190u8; 32],
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send(&[ix], &[&second_manager]).await.unwrap();
+
+    // Changing the manager hands its role over, leaving the other holders
+    let new_manager = Keypair::new();
+    env.transfer(&new_manager.pubkey(), LAMPORTS_PER_SOL).await;
+    let ixs = [
+        env.client
+            .propose_manager(&owner.pubkey(), &new_manager.pubkey()),
+        env.client.accept_manager(&new_manager.pubkey()),
+    ];
+    env.send(&ixs, &[&owner, &new_manager]).await.unwrap();
+    let roles_account = roles(&mut env).await;
+    assert!(!roles_account.has_role(Role::ServiceManager, &manager.pubkey()));
+    assert!(roles_account.has_role(Role::ServiceManager, &new_manager.pubkey()));
+    assert!(roles_account.has_role(Role::ServiceManager, &second_manager.pubkey()));
+
+    let service_id = env.next_service_id().await;
+    let ix = env.client.create(
+        &manager.pubkey(),
+        service_id,
+        [191u8; 32],
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(env.send(&[ix], &[&manager]).await, ErrorCode::MissingRole);
+
+    // The drainer role alone drains
+    let ix = env.client.drain(&manager.pubkey(), None);
+    assert_error(env.send(&[ix], &[&manager]).await, ErrorCode::MissingRole);
+    let drainer = env.drainer.insecure_clone();
+    let ix = env.client.drain(&drainer.pubkey(), None);
+    env.send(&[ix], &[&drainer]).await.unwrap();
+}
+
+#[tokio::test]
+async fn roles_hold_a_bounded_number_of_members() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+
+    // The owner already holds the metadata editor role
+    let ixs: Vec<_> = (1..MAX_ROLE_MEMBERS)
+        .map(|_| {
+            env.client.grant_role(
+                &owner.pubkey(),
+                Role::MetadataEditor,
+                &Keypair::new().pubkey(),
+            )
+        })
+        .collect();
+    for chunk in ixs.chunks(5) {
+        env.send(chunk, &[&owner]).await.unwrap();
+    }
+    let roles_account = roles(&mut env).await;
+    assert_eq!(
+        roles_account.roles[Role::MetadataEditor as usize]
+            .members
+            .len(),
+        MAX_ROLE_MEMBERS
+    );
+
+    let ix = env.client.grant_role(
+        &owner.pubkey(),
+        Role::MetadataEditor,
+        &Keypair::new().pubkey(),
+    );
+    assert_error(
+        env.send(&[ix], &[&owner]).await,
+        ErrorCode::MaxRoleMembersReached,
+    );
+}
+
+#[tokio::test]
+async fn the_last_admin_keeps_its_role() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+
+    let ix = env.client.renounce_role(&owner.pubkey(), Role::Admin);
+    assert_error(env.send(&[ix], &[&owner]).await, ErrorCode::LastAdmin);
+    let ix = env
+        .client
+        .revoke_role(&owner.pubkey(), Role::Admin, &owner.pubkey());
+    assert_error(env.send(&[ix], &[&owner]).await, ErrorCode::LastAdmin);
+
+    // With a second admin either one can leave, down to the last
+    let admin = Keypair::new();
+    env.transfer(&admin.pubkey(), LAMPORTS_PER_SOL).await;
+    let ix = env
+        .client
+        .grant_role(&owner.pubkey(), Role::Admin, &admin.pubkey());
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let ix = env.client.renounce_role(&owner.pubkey(), Role::Admin);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let ix = env
+        .client
+        .revoke_role(&admin.pubkey(), Role::Admin, &admin.pubkey());
+    assert_error(env.send(&[ix], &[&admin]).await, ErrorCode::LastAdmin);
+
+    let roles_account = roles(&mut env).await;
+    assert_eq!(
+        roles_account.roles[Role::Admin as usize].members,
+        vec![admin.pubkey()]
+    );
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::*;
+use registry::{error::ErrorCode, service_state::ServiceState, state::ServiceAccount, AgentParams};
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn cleanup_resumes_across_batches() {
+    let mut env = setup().await;
+    let service = env.register_service([210u8; 32], 3, 3, 3).await;
+    let agent_instances = service.registered_instances();
+    let total = service.agent_ids.len() + agent_instances.len();
+    let cleanup = |env: &TestEnv, range| {
+        env.client.cleanup_service(
+            &env.manager.pubkey(),
+            &service.service,
+            service.service_id,
+            &env.service_owner.pubkey(),
+            &service.agent_ids,
+            &agent_instances,
+            range,
+        )
+    };
+
+    let ix = cleanup(&env, 0..2);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::NoServiceCleanup);
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let ix = cleanup(&env, 0..2);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongServiceState);
+    let ix = env.client.unbond(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &service.operator.pubkey(),
+        &agent_instances,
+    );
+    env.send_as_manager(ix).await.unwrap();
+
+    // Registration stays closed until every agent account is gone
+    let ix = env.client.register_agent_ids_to_service(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        &[4],
+        &[AgentParams { slots: 1, bond: 1 }],
+        Some(1),
+    );
+    assert_error(
+        env.send_as_manager(ix).await,
+        ErrorCode::ServiceCleanupPending,
+    );
+
+    let ix = cleanup(&env, 0..2);
+    env.send_as_manager(ix).await.unwrap();
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert!(service_account.cleanup_pending);
+    assert_eq!(service_account.cleanup_cursor, 2);
+    let slot_counter = service_agent_slot_counter_pda(
+        env.client.registry_seed(),
+        service.service_id,
+        1,
+        &registry::ID,
+    )
+    .0;
+    assert!(env.raw_account(&slot_counter).await.is_none());
+
+    // Batches pick up at the cursor, and stop at the last agent account
+    let ix = cleanup(&env, 0..2);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::InvalidPda);
+    let mut ix = cleanup(&env, 2..total);
+    ix.accounts.push(ix.accounts[ix.accounts.len() - 1].clone());
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongArrayLength);
+    let ix = cleanup(&env, 2..4);
+    env.send_as_manager(ix).await.unwrap();
+    let ix = cleanup(&env, 4..total);
+    env.send_as_manager(ix).await.unwrap();
+
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert!(!service_account.cleanup_pending);
+    assert_eq!(service_account.cleanup_cursor, 0);
+    for (agent_instance, agent_id) in &agent_instances {
+        let pda = service_agent_instance_pda(
+            env.client.registry_seed(),
+            service.service_id,
+            *agent_id,
+            agent_instance,
+            &registry::ID,
+        )
+        .0;
+        assert!(env.raw_account(&pda).await.is_none());
+    }
+    for closed in [
+        service_agent_ids_index_pda(
+            env.client.registry_seed(),
+            service.service_id,
+            &registry::ID,
+        )
+        .0,
+        agent_instances_index_pda(
+            env.client.registry_seed(),
+            service.service_id,
+            &registry::ID,
+        )
+        .0,
+    ] {
+        assert!(env.raw_account(&closed).await.is_none());
+    }
+}
+
+#[tokio::test]
+async fn terminate_takes_own_agent_ids_index() {
+    let mut env = setup().await;
+    let service = env.register_service([211u8; 32], 3, 3, 3).await;
+    let other = env.register_service([212u8; 32], 1, 1, 0).await;
+
+    // An index with fewer agent ids would leave agent accounts out of the cleanup
+    let mut ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    let index = service_agent_ids_index_pda(
+        env.client.registry_seed(),
+        service.service_id,
+        &registry::ID,
+    )
+    .0;
+    let other_index =
+        service_agent_ids_index_pda(env.client.registry_seed(), other.service_id, &registry::ID).0;
+    for meta in &mut ix.accounts {
+        if meta.pubkey == index {
+            meta.pubkey = other_index;
+        }
+    }
+    let err = env.send_as_manager(ix).await.unwrap_err();
+    assert_eq!(
+        error_code(err),
+        u32::from(anchor_lang::error::ErrorCode::ConstraintSeeds)
+    );
+
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.state, ServiceState::FinishedRegistration);
+    assert!(!service_account.cleanup_pending);
+}
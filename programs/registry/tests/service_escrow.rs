@@ -0,0 +1,85 @@
+mod common;
+
+use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+use common::*;
+use registry::{
+    error::ErrorCode,
+    state::{ServiceAccount, ServiceEscrow},
+};
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn create_opens_an_escrow_per_service() {
+    let mut env = setup().await;
+    let (first_id, _) = env.create_service([130u8; 32]).await;
+    let (second_id, _) = env.create_service([131u8; 32]).await;
+
+    let first = env.client.service_escrow(first_id);
+    let second = env.client.service_escrow(second_id);
+    assert_ne!(first, second);
+
+    let escrow: ServiceEscrow = env.account(&first).await;
+    assert_eq!(escrow.service_id, first_id);
+    assert_eq!(
+        escrow.bump,
+        service_escrow_pda(env.client.registry_seed(), first_id, &registry::ID).1
+    );
+}
+
+#[tokio::test]
+async fn deposits_of_one_service_never_refund_another() {
+    let mut env = setup().await;
+    let wallet = env.registry_wallet();
+    let wallet_before = env.balance(&wallet).await;
+
+    let first = env.register_service([132u8; 32], 1, 1, 1).await;
+    let second = env.register_service([133u8; 32], 2, 2, 1).await;
+    let first_escrow = env.client.service_escrow(first.service_id);
+    let second_escrow = env.client.service_escrow(second.service_id);
+    let second_before = env.balance(&second_escrow).await;
+    assert_eq!(env.balance(&wallet).await, wallet_before);
+
+    // A security deposit drifting above everything the first escrow holds
+    env.set_account(&first.service, |service: &mut ServiceAccount| {
+        service.security_deposit += 2 * LAMPORTS_PER_SOL
+    })
+    .await;
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &first.service,
+        first.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::InsufficientFunds);
+    assert_eq!(env.balance(&second_escrow).await, second_before);
+
+    // The other service still gets back exactly what it deposited
+    let escrow_rent = env.balance(&first_escrow).await - 2 * LAMPORTS_PER_SOL;
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &second.service,
+        second.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let ix = env.client.unbond(
+        &env.manager.pubkey(),
+        &second.service,
+        second.service_id,
+        &second.operator.pubkey(),
+        &second.registered_instances(),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let ix = env.client.claim_unbonded(
+        &env.manager.pubkey(),
+        second.service_id,
+        &second.operator.pubkey(),
+        &env.manager.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(env.balance(&second_escrow).await, escrow_rent);
+    assert_eq!(env.balance(&wallet).await, wallet_before);
+}
@@ -0,0 +1,405 @@
+mod common;
+
+use anchor_lang::{prelude::*, Discriminator};
+use common::*;
+use registry::{
+    error::ErrorCode,
+    service_state::ServiceState,
+    state::{LegacyServiceAccount, OperatorAgentInstanceAccount, ServiceAccount, ServiceEscrow},
+    AgentParams,
+};
+use registry_client::RegistryClient;
+use solana_sdk::{
+    account::{Account as SolanaAccount, AccountSharedData},
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+};
+
+/// Writes a service at its pre-migration `["service", config_hash[..7]]` address.
+async fn write_legacy_service(env: &mut TestEnv, service: LegacyServiceAccount) -> Pubkey {
+    let legacy_service = legacy_service_pda(&service.config_hash[..7], &registry::ID).0;
+    // `8 + size_of` of the legacy layout on SBF, where u128 aligns to 8 bytes
+    let space = 8 + 136;
+    let mut data = ServiceAccount::DISCRIMINATOR.to_vec();
+    service.serialize(&mut data).unwrap();
+    data.resize(space, 0);
+
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+    let account = SolanaAccount {
+        lamports: rent.minimum_balance(space),
+        data,
+        owner: registry::ID,
+        ..SolanaAccount::default()
+    };
+    env.ctx.set_account(&legacy_service, &account.into());
+    legacy_service
+}
+
+fn legacy_seed(config_hash: &[u8; 32]) -> [u8; 7] {
+    config_hash[..7].try_into().unwrap()
+}
+
+#[tokio::test]
+async fn services_sharing_a_config_hash_prefix_coexist() {
+    let mut env = setup().await;
+    let mut second_hash = [70u8; 32];
+    second_hash[31] = 71;
+
+    let (first_id, first) = env.create_service([70u8; 32]).await;
+    let (second_id, second) = env.create_service(second_hash).await;
+
+    assert_eq!(first, env.client.service(first_id));
+    assert_eq!(second, env.client.service(second_id));
+    let service: ServiceAccount = env.account(&second).await;
+    assert_eq!(service.config_hash, second_hash);
+}
+
+#[tokio::test]
+async fn registries_number_their_services_apart() {
+    let mut env = setup().await;
+    let (service_id, service) = env.create_service([78u8; 32]).await;
+    assert_eq!(service_id, 1);
+
+    let other_registry = Keypair::new();
+    let other = RegistryClient::new(other_registry.pubkey());
+    let owner = env.owner.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let ix = other.initialize(
+        &owner.pubkey(),
+        "other".into(),
+        "OTHER".into(),
+        "other_uri".into(),
+        &manager.pubkey(),
+        &env.drainer.pubkey(),
+    );
+    env.send(&[ix], &[&owner, &other_registry]).await.unwrap();
+
+    // The second registry issues its own service 1, next to the first one
+    let ix = other.create(
+        &manager.pubkey(),
+        1,
+        [79u8; 32],
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let other_service = other.service(1);
+    assert_ne!(other_service, service);
+    assert_ne!(other.service_escrow(1), env.client.service_escrow(1));
+    let first: ServiceAccount = env.account(&service).await;
+    let second: ServiceAccount = env.account(&other_service).await;
+    assert_eq!(
+        (first.service_id, first.registry),
+        (1, env.registry.pubkey())
+    );
+    assert_eq!(
+        (second.service_id, second.registry),
+        (1, other_registry.pubkey())
+    );
+    assert_eq!(second.config_hash, [79u8; 32]);
+    let owner_token = other.service_token(1, &env.service_owner.pubkey());
+    assert!(env.raw_account(&owner_token).await.is_some());
+}
+
+#[tokio::test]
+async fn update_keeps_the_service_address() {
+    let mut env = setup().await;
+    let (service_id, service) = env.create_service([72u8; 32]).await;
+    env.register_agent_ids(
+        service_id,
+        service,
+        &[1],
+        &[AgentParams { slots: 1, bond: 1 }],
+        1,
+    )
+    .await;
+
+    let ix = env.client.update(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        [73u8; 32],
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+
+    let ix = env.client.check_service(&service, service_id);
+    env.send_as_manager(ix).await.unwrap();
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.config_hash, [73u8; 32]);
+}
+
+#[tokio::test]
+async fn migrates_legacy_service() {
+    let mut env = setup().await;
+    env.set_registry(|registry| registry.total_supply = 1).await;
+
+    let config_hash = [74u8; 32];
+    let legacy = LegacyServiceAccount {
+        service_id: 1,
+        service_owner: env.service_owner.pubkey(),
+        security_deposit: 42,
+        multisig: Pubkey::new_unique(),
+        config_hash,
+        threshold: 2,
+        max_num_agent_instances: 3,
+        num_agent_instances: 0,
+        state: ServiceState::ActiveRegistration,
+    };
+    let legacy_service = write_legacy_service(&mut env, legacy).await;
+    let wallet = env.registry_wallet();
+    env.transfer(&wallet, 42).await;
+    let wallet_before = env.balance(&wallet).await;
+
+    let ix = env.client.migrate_service(
+        &env.manager.pubkey(),
+        legacy_seed(&config_hash),
+        1,
+        &env.service_owner.pubkey(),
+        &[],
+    );
+    env.send_as_manager(ix).await.unwrap();
+
+    assert!(env.raw_account(&legacy_service).await.is_none());
+    let service: ServiceAccount = env.account(&env.client.service(1)).await;
+    assert_eq!(service.service_id, 1);
+    assert_eq!(service.service_owner, env.service_owner.pubkey());
+    assert_eq!(service.security_deposit, 42);
+    assert_eq!(service.config_hash, config_hash);
+    assert_eq!(service.threshold, 2);
+    assert_eq!(service.max_num_agent_instances, 3);
+    assert_eq!(service.num_agent_instances, 0);
+    assert_eq!(service.state, ServiceState::ActiveRegistration);
+    // The other accounts of the service keep the seeds without the registry key
+    assert!(service.legacy_seeds);
+    let legacy = env.client.with_legacy_seeds();
+    // The security deposit moves from the registry wallet to the escrow
+    assert_eq!(env.balance(&wallet).await, wallet_before - 42);
+    let escrow = legacy.service_escrow(1);
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+    assert_eq!(
+        env.balance(&escrow).await,
+        rent.minimum_balance(ServiceEscrow::LEN) + 42
+    );
+    // The service token is minted to the legacy owner
+    let owner_token = legacy.service_token(1, &env.service_owner.pubkey());
+    assert!(env.raw_account(&owner_token).await.is_some());
+
+    // New services continue after the migrated one, keyed by the registry
+    let (service_id, service) = env.create_service([75u8; 32]).await;
+    assert_eq!(service_id, 2);
+    let service: ServiceAccount = env.account(&service).await;
+    assert!(!service.legacy_seeds);
+}
+
+#[tokio::test]
+async fn rejects_unknown_legacy_service() {
+    let mut env = setup().await;
+    let config_hash = [76u8; 32];
+    let legacy = LegacyServiceAccount {
+        service_id: 1,
+        service_owner: env.service_owner.pubkey(),
+        security_deposit: 0,
+        multisig: Pubkey::default(),
+        config_hash,
+        threshold: 0,
+        max_num_agent_instances: 0,
+        num_agent_instances: 0,
+        state: ServiceState::PreRegistration,
+    };
+    write_legacy_service(&mut env, legacy).await;
+
+    // The registry never issued service id 1
+    let ix = env.client.migrate_service(
+        &env.manager.pubkey(),
+        legacy_seed(&config_hash),
+        1,
+        &env.service_owner.pubkey(),
+        &[],
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::ServiceNotFound);
+
+    // Only the manager can migrate
+    env.set_registry(|registry| registry.total_supply = 1).await;
+    let owner = env.owner.insecure_clone();
+    let ix = env.client.migrate_service(
+        &owner.pubkey(),
+        legacy_seed(&config_hash),
+        1,
+        &env.service_owner.pubkey(),
+        &[],
+    );
+    assert!(env.send(&[ix], &[&owner]).await.is_err());
+}
+
+#[tokio::test]
+async fn migrated_legacy_service_refunds_from_its_escrow() {
+    let mut env = setup().await;
+    let config_hash = [77u8; 32];
+    // Agent ids 1 and 2 bond 1 and 2 SOL
+    let registered = env.register_service(config_hash, 2, 2, 2).await;
+    let service_id = registered.service_id;
+    let service_owner = env.service_owner.pubkey();
+    let operator = registered.operator.pubkey();
+    let current: ServiceAccount = env.account(&registered.service).await;
+
+    // Before escrows, the deposit and bonds sat in the registry wallet
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+    let escrow = env.client.service_escrow(service_id);
+    let held = env.balance(&escrow).await - rent.minimum_balance(ServiceEscrow::LEN);
+    assert_eq!(held, current.security_deposit + 3 * LAMPORTS_PER_SOL);
+    let wallet = env.registry_wallet();
+    let mut wallet_account = env.raw_account(&wallet).await.unwrap();
+    wallet_account.lamports += held;
+    env.ctx.set_account(&wallet, &wallet_account.into());
+    for key in [
+        registered.service,
+        escrow,
+        service_mint_pda(env.client.registry_seed(), service_id, &registry::ID).0,
+        env.client.service_token(service_id, &service_owner),
+    ] {
+        env.ctx.set_account(&key, &AccountSharedData::default());
+    }
+    // Its agent accounts sat at the seeds without the registry key
+    let agent_accounts = |registry_seed: &[u8]| {
+        let mut keys = vec![
+            service_agent_ids_index_pda(registry_seed, service_id, &registry::ID).0,
+            agent_instances_index_pda(registry_seed, service_id, &registry::ID).0,
+            operator_agent_instance_index_pda(registry_seed, service_id, &operator, &registry::ID)
+                .0,
+            operator_bond_pda(registry_seed, service_id, &operator, &registry::ID).0,
+        ];
+        for agent_id in &registered.agent_ids {
+            keys.push(agent_param_pda(registry_seed, service_id, *agent_id, &registry::ID).0);
+            keys.push(
+                service_agent_slot_counter_pda(registry_seed, service_id, *agent_id, &registry::ID)
+                    .0,
+            );
+        }
+        for (instance, agent_id) in registered.registered_instances() {
+            keys.push(
+                service_agent_instance_pda(
+                    registry_seed,
+                    service_id,
+                    agent_id,
+                    &instance,
+                    &registry::ID,
+                )
+                .0,
+            );
+        }
+        keys
+    };
+    let legacy = env.client.with_legacy_seeds();
+    for (key, legacy_key) in agent_accounts(env.client.registry_seed())
+        .into_iter()
+        .zip(agent_accounts(legacy.registry_seed()))
+    {
+        let account = env.raw_account(&key).await.unwrap();
+        env.ctx.set_account(&legacy_key, &account.into());
+        env.ctx.set_account(&key, &AccountSharedData::default());
+    }
+    for (instance, agent_id) in registered.registered_instances() {
+        let service_agent_instance = service_agent_instance_pda(
+            legacy.registry_seed(),
+            service_id,
+            agent_id,
+            &instance,
+            &registry::ID,
+        )
+        .0;
+        let key = operator_agent_instance_pda(&instance, &operator, &registry::ID).0;
+        env.set_account(&key, |account: &mut OperatorAgentInstanceAccount| {
+            account.service_agent_instance = service_agent_instance
+        })
+        .await;
+    }
+    write_legacy_service(
+        &mut env,
+        LegacyServiceAccount {
+            service_id,
+            service_owner,
+            security_deposit: current.security_deposit,
+            multisig: current.multisig,
+            config_hash,
+            threshold: current.threshold,
+            max_num_agent_instances: current.max_num_agent_instances,
+            num_agent_instances: current.num_agent_instances,
+            state: current.state,
+        },
+    )
+    .await;
+    let wallet_before = env.balance(&wallet).await;
+
+    // Every operator of the service is passed, once
+    let migrate = |operators: &[Pubkey]| {
+        env.client.migrate_service(
+            &env.manager.pubkey(),
+            legacy_seed(&config_hash),
+            service_id,
+            &service_owner,
+            operators,
+        )
+    };
+    let missing = migrate(&[]);
+    let repeated = migrate(&[operator, operator]);
+    let ix = migrate(&[operator]);
+    assert_error(
+        env.send_as_manager(missing).await,
+        ErrorCode::WrongOperatorBonds,
+    );
+    assert_error(
+        env.send_as_manager(repeated).await,
+        ErrorCode::WrongOperatorBonds,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(env.balance(&wallet).await, wallet_before - held);
+    assert!(env.raw_account(&escrow).await.is_none());
+    let escrow = legacy.service_escrow(service_id);
+    assert_eq!(
+        env.balance(&escrow).await,
+        rent.minimum_balance(ServiceEscrow::LEN) + held
+    );
+
+    // Terminating and unbonding pay out of the escrow
+    let owner_before = env.balance(&service_owner).await;
+    let ix = legacy.terminate(
+        &env.manager.pubkey(),
+        &registered.service,
+        service_id,
+        &service_owner,
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(
+        env.balance(&service_owner).await,
+        owner_before + current.security_deposit
+    );
+
+    let operator_before = env.balance(&operator).await;
+    let ix = legacy.unbond(
+        &env.manager.pubkey(),
+        &registered.service,
+        service_id,
+        &operator,
+        &registered.registered_instances(),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let ix = legacy.claim_unbonded(
+        &env.manager.pubkey(),
+        service_id,
+        &operator,
+        &env.manager.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(
+        env.balance(&operator).await,
+        operator_before + 3 * LAMPORTS_PER_SOL
+    );
+    assert_eq!(
+        env.balance(&escrow).await,
+        rent.minimum_balance(ServiceEscrow::LEN)
+    );
+}
@@ -0,0 +1,507 @@
+//! Random operation sequences run against a reference model of one service and
+//! against the program, comparing both after every step.
+
+mod common;
+
+use anchor_lang::{prelude::*, solana_program::native_token::LAMPORTS_PER_SOL};
+use common::*;
+use proptest::{collection::vec, prelude::*};
+use registry::{
+    service_state::ServiceState,
+    state::{OperatorBondAccount, ServiceAccount, ServiceRegistry},
+    AgentParams,
+};
+use registry_client::SlashTarget;
+use solana_sdk::signature::{Keypair, Signer};
+
+const AGENT_IDS: [u32; 2] = [1, 2];
+const SLOTS: [u32; 2] = [1, 3];
+const BONDS: [u64; 2] = [LAMPORTS_PER_SOL, 5 * LAMPORTS_PER_SOL / 2];
+const MAX_NUM_AGENT_INSTANCES: u32 = 4;
+const THRESHOLD: u32 = 3;
+const OPERATORS: usize = 2;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Activate,
+    Register { agent: usize, operator: usize },
+    Deploy,
+    Slash { operator: usize, amount: u64 },
+    Terminate,
+    Unbond { operator: usize },
+    Drain,
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        2 => Just(Op::Activate),
+        5 => (0..AGENT_IDS.len(), 0..OPERATORS)
+            .prop_map(|(agent, operator)| Op::Register { agent, operator }),
+        2 => Just(Op::Deploy),
+        3 => (0..OPERATORS, 0..3 * LAMPORTS_PER_SOL)
+            .prop_map(|(operator, amount)| Op::Slash { operator, amount }),
+        2 => Just(Op::Terminate),
+        3 => (0..OPERATORS).prop_map(|operator| Op::Unbond { operator }),
+        1 => Just(Op::Drain),
+    ]
+}
+
+/// What the program is expected to hold for the service and its registry.
+#[derive(Clone, Debug, PartialEq)]
+struct ServiceModel {
+    state: ServiceState,
+    security_deposit: u64,
+    num_agent_instances: u32,
+    bonds: [Option<u64>; OPERATORS],
+    slashed_funds: u64,
+    /// Lamports held by the service escrow above its rent
+    escrow: u64,
+    /// Lamports held by the registry wallet above its rent
+    wallet: u64,
+    // Not read back from the program, only needed to predict outcomes
+    operator_instances: [u32; OPERATORS],
+    slots_filled: [u32; 2],
+    agent_ids_indexed: bool,
+}
+
+impl ServiceModel {
+    /// The service right after `create` and `register_agent_ids_to_service`.
+    fn new() -> Self {
+        Self {
+            state: ServiceState::PreRegistration,
+            security_deposit: BONDS[1],
+            num_agent_instances: 0,
+            bonds: [None; OPERATORS],
+            slashed_funds: 0,
+            escrow: 0,
+            wallet: 0,
+            operator_instances: [0; OPERATORS],
+            slots_filled: [0; 2],
+            agent_ids_indexed: true,
+        }
+    }
+
+    /// Returns the model after `op`, or `None` if the program must reject it.
+    fn apply(&self, op: &Op) -> Option<Self> {
+        let mut next = self.clone();
+        match *op {
+            Op::Activate => {
+                if self.state != ServiceState::PreRegistration {
+                    return None;
+                }
+                next.state = ServiceState::ActiveRegistration;
+                next.escrow += self.security_deposit;
+            }
+            Op::Register { agent, operator } => {
+                if self.state != ServiceState::ActiveRegistration
+                    || self.slots_filled[agent] == SLOTS[agent]
+                {
+                    return None;
+                }
+                next.slots_filled[agent] += 1;
+                next.num_agent_instances += 1;
+                next.operator_instances[operator] += 1;
+                next.bonds[operator] = Some(self.bonds[operator].unwrap_or(0) + BONDS[agent]);
+                next.escrow += BONDS[agent];
+                if next.num_agent_instances == MAX_NUM_AGENT_INSTANCES {
+                    next.state = ServiceState::FinishedRegistration;
+                }
+            }
+            Op::Deploy => {
+                if self.state != ServiceState::FinishedRegistration {
+                    return None;
+                }
+                next.state = ServiceState::Deployed;
+            }
+            Op::Slash { operator, amount } => {
+                let bond = self.bonds[operator]?;
+                if self.state != ServiceState::Deployed || amount == 0 || bond == 0 {
+                    return None;
+                }
+                let slashed = bond.min(amount);
+                next.bonds[operator] = Some(bond - slashed);
+                next.slashed_funds += slashed;
+                next.escrow -= slashed;
+                next.wallet += slashed;
+            }
+            Op::Terminate => {
+                if matches!(
+                    self.state,
+                    ServiceState::PreRegistration | ServiceState::TerminatedBonded
+                ) || !self.agent_ids_indexed
+                {
+                    return None;
+                }
+                next.state = if self.num_agent_instances > 0 {
+                    ServiceState::TerminatedBonded
+                } else {
+                    ServiceState::PreRegistration
+                };
+                next.escrow -= self.security_deposit;
+                next.security_deposit = 0;
+                next.slots_filled = [0; 2];
+                // cleanup closes the agent ids index, which terminate needs to run again
+                next.agent_ids_indexed = false;
+            }
+            Op::Unbond { operator } => {
+                let bond = self.bonds[operator]?;
+                if self.state != ServiceState::TerminatedBonded {
+                    return None;
+                }
+                next.num_agent_instances -= self.operator_instances[operator];
+                next.operator_instances[operator] = 0;
+                next.bonds[operator] = None;
+                next.escrow -= bond;
+                if next.num_agent_instances == 0 {
+                    next.state = ServiceState::PreRegistration;
+                }
+            }
+            Op::Drain => {
+                next.wallet -= self.slashed_funds;
+                next.slashed_funds = 0;
+            }
+        }
+        Some(next)
+    }
+
+    fn observable(&self) -> Observed {
+        Observed {
+            state: self.state.clone(),
+            security_deposit: self.security_deposit,
+            num_agent_instances: self.num_agent_instances,
+            bonds: self.bonds,
+            slashed_funds: self.slashed_funds,
+            escrow: self.escrow,
+            wallet: self.wallet,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Observed {
+    state: ServiceState,
+    security_deposit: u64,
+    num_agent_instances: u32,
+    bonds: [Option<u64>; OPERATORS],
+    slashed_funds: u64,
+    escrow: u64,
+    wallet: u64,
+}
+
+struct Harness {
+    env: TestEnv,
+    service_id: u128,
+    service: Pubkey,
+    escrow_rent: u64,
+    wallet_rent: u64,
+    operators: Vec<Keypair>,
+    /// Per operator, its agent instances in `OperatorAgentInstanceIndex` order with the
+    /// agent id each one is registered for
+    operator_instances: Vec<Vec<(Pubkey, u32)>>,
+    /// Agent instances in `ServiceAgentInstancesIndex` order
+    service_instances: Vec<Keypair>,
+    /// Agent id each of `service_instances` is registered for
+    service_instance_agents: Vec<u32>,
+    /// Last deployment and its multisig
+    deployed: Option<(RegisteredService, Pubkey)>,
+}
+
+impl Harness {
+    async fn new() -> Self {
+        let mut env = setup().await;
+        env.whitelist_multisig(registry::ID, true).await;
+        let wallet_rent = env.balance(&env.registry_wallet()).await;
+
+        let (service_id, service) = env.create_service([60u8; 32]).await;
+        let agent_params: Vec<AgentParams> = SLOTS
+            .iter()
+            .zip(BONDS)
+            .map(|(slots, bond)| AgentParams {
+                slots: *slots,
+                bond,
+            })
+            .collect();
+        env.register_agent_ids(service_id, service, &AGENT_IDS, &agent_params, THRESHOLD)
+            .await;
+        let escrow_rent = env.balance(&env.client.service_escrow(service_id)).await;
+
+        // Funded so that any refund keeps them rent exempt
+        let operators: Vec<Keypair> = (0..OPERATORS).map(|_| Keypair::new()).collect();
+        for operator in &operators {
+            env.transfer(&operator.pubkey(), LAMPORTS_PER_SOL).await;
+        }
+
+        Self {
+            env,
+            service_id,
+            service,
+            escrow_rent,
+            wallet_rent,
+            operators,
+            operator_instances: vec![Vec::new(); OPERATORS],
+            service_instances: Vec::new(),
+            service_instance_agents: Vec::new(),
+            deployed: None,
+        }
+    }
+
+    /// Sends `op`, returning whether the program accepted it, or `None` if `op`
+    /// cannot be expressed in the current state.
+    async fn run(&mut self, op: &Op) -> Option<bool> {
+        let manager = self.env.manager.pubkey();
+        let service_owner = self.env.service_owner.pubkey();
+        let (service, service_id) = (self.service, self.service_id);
+
+        let result = match *op {
+            Op::Activate => {
+                let ix = self.env.client.activate_registration(
+                    &manager,
+                    &service,
+                    service_id,
+                    &service_owner,
+                    None,
+                );
+                self.env.send_as_manager(ix).await
+            }
+            Op::Register { agent, operator } => {
+                let agent_instance = Keypair::new();
+                let operator_key = self.operators[operator].insecure_clone();
+                let ixs = self
+                    .env
+                    .signed_registration(
+                        service_id,
+                        service,
+                        &operator_key,
+                        &[AGENT_IDS[agent]],
+                        &[agent_instance.pubkey()],
+                        None,
+                    )
+                    .await;
+                let manager_key = self.env.manager.insecure_clone();
+                let result = self.env.send(&ixs, &[&manager_key]).await;
+                if result.is_ok() {
+                    self.operator_instances[operator]
+                        .push((agent_instance.pubkey(), AGENT_IDS[agent]));
+                    self.service_instances.push(agent_instance);
+                    self.service_instance_agents.push(AGENT_IDS[agent]);
+                }
+                result
+            }
+            Op::Deploy => {
+                let registered = RegisteredService {
+                    service_id,
+                    service,
+                    agent_ids: AGENT_IDS.to_vec(),
+                    operator: Keypair::new(),
+                    agent_instance_keys: self
+                        .service_instances
+                        .iter()
+                        .map(|key| key.insecure_clone())
+                        .collect(),
+                    agent_instances: self.service_instances.iter().map(|k| k.pubkey()).collect(),
+                };
+                let multisig = self
+                    .env
+                    .client
+                    .builtin_multisig(&registered.agent_instances);
+                let ix = self
+                    .env
+                    .deploy_ix(&registered, registry::ID, multisig, vec![]);
+                let result = self.env.send_as_manager(ix).await;
+                if result.is_ok() {
+                    // Agent instances pay for the proposals they create
+                    for agent_instance in &registered.agent_instances {
+                        self.env.transfer(agent_instance, LAMPORTS_PER_SOL).await;
+                    }
+                    self.deployed = Some((registered, multisig));
+                }
+                result
+            }
+            Op::Slash { operator, amount } => {
+                let (agent_instance, _) = *self.operator_instances[operator].first()?;
+                let (registered, multisig) = self.deployed.as_ref()?;
+                let ix = self.env.client.slash(
+                    multisig,
+                    &service,
+                    service_id,
+                    &[SlashTarget {
+                        agent_instance,
+                        operator: self.operators[operator].pubkey(),
+                        amount,
+                    }],
+                    None,
+                );
+                self.env
+                    .execute_through_multisig(registered, *multisig, &ix)
+                    .await
+            }
+            Op::Terminate => {
+                let ix =
+                    self.env
+                        .client
+                        .terminate(&manager, &service, service_id, &service_owner, None);
+                let result = self.env.send_as_manager(ix).await;
+                if result.is_ok() {
+                    self.cleanup_unbonded().await;
+                }
+                result
+            }
+            Op::Unbond { operator } => {
+                let ix = self.env.client.unbond(
+                    &manager,
+                    &service,
+                    service_id,
+                    &self.operators[operator].pubkey(),
+                    &self.operator_instances[operator],
+                );
+                let result = self.env.send_as_manager(ix).await;
+                if result.is_ok() {
+                    // Without an unbonding delay the refund is claimable right away
+                    let ix = self.env.client.claim_unbonded(
+                        &manager,
+                        service_id,
+                        &self.operators[operator].pubkey(),
+                        &manager,
+                        None,
+                    );
+                    self.env.send_as_manager(ix).await.unwrap();
+                    self.operator_instances[operator].clear();
+                    self.cleanup_unbonded().await;
+                }
+                result
+            }
+            Op::Drain => {
+                let manager = self.env.manager.insecure_clone();
+                let drainer = self.env.drainer.insecure_clone();
+                let ix = self.env.client.drain(&drainer.pubkey(), None);
+                self.env.send(&[ix], &[&manager, &drainer]).await
+            }
+        };
+        Some(result.is_ok())
+    }
+
+    /// Closes the agent PDAs once every operator unbonded from a terminated service, as
+    /// the model does not track them.
+    async fn cleanup_unbonded(&mut self) {
+        let service: ServiceAccount = self.env.account(&self.service).await;
+        if !service.cleanup_pending || service.state != ServiceState::PreRegistration {
+            return;
+        }
+        let agent_instances: Vec<(Pubkey, u32)> = self
+            .service_instances
+            .iter()
+            .map(|key| key.pubkey())
+            .zip(self.service_instance_agents.iter().copied())
+            .collect();
+        let ix = self.env.client.cleanup_service(
+            &self.env.manager.pubkey(),
+            &self.service,
+            self.service_id,
+            &self.env.service_owner.pubkey(),
+            &AGENT_IDS,
+            &agent_instances,
+            0..AGENT_IDS.len() + agent_instances.len(),
+        );
+        self.env.send_as_manager(ix).await.unwrap();
+        self.service_instances.clear();
+        self.service_instance_agents.clear();
+    }
+
+    async fn observe(&mut self) -> Observed {
+        let service: ServiceAccount = self.env.account(&self.service).await;
+        let registry: ServiceRegistry = self.env.account(&self.env.registry.pubkey()).await;
+        assert!(!registry.locked);
+
+        let mut bonds = [None; OPERATORS];
+        for (bond, operator) in bonds.iter_mut().zip(&self.operators) {
+            let key = self.env.operator_bond(self.service_id, &operator.pubkey());
+            if self.env.raw_account(&key).await.is_some() {
+                let account: OperatorBondAccount = self.env.account(&key).await;
+                *bond = Some(account.bond);
+            }
+        }
+
+        let escrow_key = self.env.client.service_escrow(self.service_id);
+        let escrow = self.env.balance(&escrow_key).await - self.escrow_rent;
+        let wallet = self.env.balance(&self.env.registry_wallet()).await - self.wallet_rent;
+        Observed {
+            state: service.state,
+            security_deposit: service.security_deposit,
+            num_agent_instances: service.num_agent_instances,
+            bonds,
+            slashed_funds: registry.slashed_funds,
+            escrow,
+            wallet,
+        }
+    }
+}
+
+async fn check_sequence(ops: Vec<Op>) {
+    let mut harness = Harness::new().await;
+    let mut model = ServiceModel::new();
+    assert_eq!(harness.observe().await, model.observable());
+
+    for (step, op) in ops.iter().enumerate() {
+        let Some(accepted) = harness.run(op).await else {
+            continue;
+        };
+        let expected = model.apply(op);
+        assert_eq!(
+            accepted,
+            expected.is_some(),
+            "step {step}: {op:?} from {model:?}"
+        );
+        if let Some(next) = expected {
+            model = next;
+        }
+        assert_eq!(
+            harness.observe().await,
+            model.observable(),
+            "step {step}: after {op:?}"
+        );
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 16,
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn service_state_matches_model(ops in vec(op(), 1..32)) {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(check_sequence(ops));
+    }
+}
+
+#[tokio::test]
+async fn full_cycle_matches_model() {
+    let register = |agent, operator| Op::Register { agent, operator };
+    let ops = vec![
+        Op::Activate,
+        register(0, 0),
+        register(1, 1),
+        register(1, 1),
+        // Enough instances for the threshold, but registration is not finished
+        Op::Deploy,
+        register(1, 0),
+        Op::Deploy,
+        Op::Slash {
+            operator: 1,
+            amount: LAMPORTS_PER_SOL,
+        },
+        Op::Terminate,
+        Op::Unbond { operator: 0 },
+        Op::Unbond { operator: 1 },
+        Op::Drain,
+        Op::Activate,
+        register(0, 1),
+        Op::Terminate,
+    ];
+    check_sequence(ops).await;
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use anchor_lang::{prelude::*, solana_program::program_pack::Pack};
+use anchor_spl::{
+    associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    token::spl_token::{self, state::Mint},
+};
+use common::*;
+use registry::{error::ErrorCode, service_state::ServiceState, state::ServiceAccount, AgentParams};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    instruction::InstructionError,
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+async fn token_amount(env: &mut TestEnv, token_account: &Pubkey) -> u64 {
+    let account = env.raw_account(token_account).await.unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+/// Moves the service token from `from` to the associated token account of `to`.
+async fn transfer_service_token(env: &mut TestEnv, service_id: u128, from: &Keypair, to: &Pubkey) {
+    let manager = env.manager.insecure_clone();
+    let create = create_associated_token_account_idempotent(
+        &manager.pubkey(),
+        to,
+        &service_mint_pda(env.client.registry_seed(), service_id, &registry::ID).0,
+        &spl_token::ID,
+    );
+    let transfer = spl_token::instruction::transfer(
+        &spl_token::ID,
+        &env.client.service_token(service_id, &from.pubkey()),
+        &env.client.service_token(service_id, to),
+        &from.pubkey(),
+        &[],
+        1,
+    )
+    .unwrap();
+    env.send(&[create, transfer], &[&manager, from])
+        .await
+        .unwrap();
+}
+
+fn assert_invalid_argument(result: std::result::Result<(), BanksClientError>) {
+    assert!(matches!(
+        result,
+        Err(BanksClientError::TransactionError(
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn create_mints_a_single_service_token() {
+    let mut env = setup().await;
+    let (service_id, _) = env.create_service([100u8; 32]).await;
+
+    let owner_token = env
+        .client
+        .service_token(service_id, &env.service_owner.pubkey());
+    assert_eq!(token_amount(&mut env, &owner_token).await, 1);
+
+    let mint = env
+        .raw_account(&service_mint_pda(env.client.registry_seed(), service_id, &registry::ID).0)
+        .await
+        .unwrap();
+    let mint = Mint::unpack(&mint.data).unwrap();
+    assert_eq!(mint.supply, 1);
+    assert_eq!(mint.decimals, 0);
+    assert!(mint.mint_authority.is_none());
+    assert!(mint.freeze_authority.is_none());
+}
+
+#[tokio::test]
+async fn the_token_holder_owns_the_service() {
+    let mut env = setup().await;
+    let (service_id, service) = env.create_service([101u8; 32]).await;
+    env.register_agent_ids(
+        service_id,
+        service,
+        &[1],
+        &[AgentParams {
+            slots: 1,
+            bond: LAMPORTS_PER_SOL,
+        }],
+        1,
+    )
+    .await;
+
+    let previous_owner = env.service_owner.insecure_clone();
+    let new_owner = Pubkey::new_unique();
+    transfer_service_token(&mut env, service_id, &previous_owner, &new_owner).await;
+
+    // The previous owner no longer holds the token
+    let ix = env.client.update(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        [102u8; 32],
+        &previous_owner.pubkey(),
+        None,
+    );
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongServiceToken);
+
+    let ix = env.client.activate_registration(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &new_owner,
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let service_account: ServiceAccount = env.account(&service).await;
+    assert_eq!(service_account.service_owner, new_owner);
+    assert_eq!(service_account.state, ServiceState::ActiveRegistration);
+
+    // The security deposit is refunded to the new owner
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &new_owner,
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(env.balance(&new_owner).await, LAMPORTS_PER_SOL);
+}
+
+#[tokio::test]
+async fn rejects_tokens_of_other_services() {
+    let mut env = setup().await;
+    let (service_id, service) = env.create_service([103u8; 32]).await;
+    let (other_id, _) = env.create_service([104u8; 32]).await;
+
+    let mut ix = env.client.activate_registration(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    ix.accounts[2].pubkey = env
+        .client
+        .service_token(other_id, &env.service_owner.pubkey());
+    assert_error(env.send_as_manager(ix).await, ErrorCode::WrongServiceToken);
+
+    // The token account must belong to the given owner
+    let mut ix = env.client.activate_registration(
+        &env.manager.pubkey(),
+        &service,
+        service_id,
+        &Pubkey::new_unique(),
+        None,
+    );
+    ix.accounts[2].pubkey = env
+        .client
+        .service_token(service_id, &env.service_owner.pubkey());
+    assert_invalid_argument(env.send_as_manager(ix).await);
+}
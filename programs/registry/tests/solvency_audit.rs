@@ -0,0 +1,171 @@
+mod common;
+
+use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+use common::*;
+use registry::{
+    error::ErrorCode,
+    state::{OperatorBondAccount, PendingWithdrawal, ServiceAccount, SolvencyReport},
+};
+use registry_client::AuditedService;
+use solana_sdk::signature::Signer;
+
+fn audited(service: &RegisteredService) -> AuditedService {
+    AuditedService {
+        service_id: service.service_id,
+        bond_mint: None,
+        operators: vec![service.operator.pubkey()],
+        pending_withdrawals: vec![],
+    }
+}
+
+#[tokio::test]
+async fn audit_matches_escrows_and_treasury() {
+    let mut env = setup().await;
+    let first = env.register_service([140u8; 32], 1, 1, 1).await;
+    let second = env.register_service([141u8; 32], 2, 2, 1).await;
+
+    let mut security_deposits = 0;
+    let mut bonds = 0;
+    for service in [&first, &second] {
+        let account: ServiceAccount = env.account(&service.service).await;
+        let bond: OperatorBondAccount = env
+            .account(&env.operator_bond(service.service_id, &service.operator.pubkey()))
+            .await;
+        security_deposits += account.security_deposit;
+        bonds += bond.bond;
+    }
+
+    let ix = env
+        .client
+        .audit_solvency(&[audited(&first), audited(&second)]);
+    let report: SolvencyReport = env.view(ix).await;
+    assert_eq!(
+        report,
+        SolvencyReport {
+            services: 2,
+            operator_bonds: 2,
+            security_deposits,
+            bonds,
+            pending_withdrawals: 0,
+            escrow_balance: security_deposits + bonds,
+            slashed_funds: 0,
+            accrued_fees: 0,
+            treasury_balance: 0,
+            deficit: 0,
+            insolvent_services: vec![],
+        }
+    );
+}
+
+#[tokio::test]
+async fn audit_reports_deficits() {
+    let mut env = setup().await;
+    let first = env.register_service([142u8; 32], 1, 1, 1).await;
+    let second = env.register_service([143u8; 32], 2, 2, 1).await;
+
+    // A deposit above what the first escrow holds, and slashed funds the wallet lacks
+    env.set_account(&first.service, |service: &mut ServiceAccount| {
+        service.security_deposit += LAMPORTS_PER_SOL
+    })
+    .await;
+    env.set_registry(|registry| registry.slashed_funds = 3 * LAMPORTS_PER_SOL)
+        .await;
+
+    let ix = env
+        .client
+        .audit_solvency(&[audited(&first), audited(&second)]);
+    let report: SolvencyReport = env.view(ix).await;
+    assert_eq!(report.slashed_funds, 3 * LAMPORTS_PER_SOL);
+    assert_eq!(report.deficit, 4 * LAMPORTS_PER_SOL);
+    assert_eq!(report.insolvent_services, vec![first.service_id]);
+    assert_eq!(
+        report.escrow_balance + LAMPORTS_PER_SOL,
+        report.security_deposits + report.bonds
+    );
+
+    // Pages are audited independently
+    let ix = env.client.audit_solvency(&[audited(&second)]);
+    let report: SolvencyReport = env.view(ix).await;
+    assert_eq!(report.services, 1);
+    assert_eq!(report.deficit, 3 * LAMPORTS_PER_SOL);
+    assert!(report.insolvent_services.is_empty());
+}
+
+#[tokio::test]
+async fn audit_rejects_accounts_of_other_services() {
+    let mut env = setup().await;
+    let first = env.register_service([144u8; 32], 1, 1, 1).await;
+    let second = env.register_service([145u8; 32], 2, 2, 1).await;
+    let audit = env.client.audit_solvency(&[audited(&first)]);
+
+    // accounts: registry, wallet, service, escrow, bond token setting, operator bond
+    let mut ix = audit.clone();
+    ix.accounts[3].pubkey = env.client.service_escrow(second.service_id);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::InvalidPda);
+
+    let mut ix = audit.clone();
+    ix.accounts[5].pubkey = env.operator_bond(second.service_id, &second.operator.pubkey());
+    assert_error(env.send_as_manager(ix).await, ErrorCode::InvalidPda);
+
+    env.send_as_manager(audit).await.unwrap();
+}
+
+#[tokio::test]
+async fn audit_counts_pending_withdrawals_as_owed() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let ix = env.client.set_unbonding_delay(&owner.pubkey(), 3600);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    // Agent id 1 bonds 1 SOL, left in the escrow once unbonded
+    let service = env.register_service([146u8; 32], 1, 1, 1).await;
+    let operator = service.operator.pubkey();
+    let ixs = [
+        env.client.terminate(
+            &manager.pubkey(),
+            &service.service,
+            service.service_id,
+            &env.service_owner.pubkey(),
+            None,
+        ),
+        env.client.unbond(
+            &manager.pubkey(),
+            &service.service,
+            service.service_id,
+            &operator,
+            &service.registered_instances(),
+        ),
+    ];
+    env.send(&ixs, &[&manager]).await.unwrap();
+
+    let audited = AuditedService {
+        service_id: service.service_id,
+        bond_mint: None,
+        operators: vec![],
+        pending_withdrawals: vec![operator],
+    };
+    let ix = env.client.audit_solvency(std::slice::from_ref(&audited));
+    let report: SolvencyReport = env.view(ix).await;
+    assert_eq!(report.security_deposits + report.bonds, 0);
+    assert_eq!(report.pending_withdrawals, LAMPORTS_PER_SOL);
+    assert_eq!(report.escrow_balance, LAMPORTS_PER_SOL);
+    assert_eq!(report.deficit, 0);
+
+    // A withdrawal above what the escrow holds is a deficit of the service
+    let pending_key = pending_withdrawal_pda(
+        env.client.registry_seed(),
+        service.service_id,
+        &operator,
+        &registry::ID,
+    )
+    .0;
+    env.set_account(&pending_key, |pending: &mut PendingWithdrawal| {
+        pending.amount += LAMPORTS_PER_SOL
+    })
+    .await;
+    let ix = env.client.audit_solvency(&[audited]);
+    let report: SolvencyReport = env.view(ix).await;
+    assert_eq!(report.deficit, LAMPORTS_PER_SOL);
+    assert_eq!(report.insolvent_services, vec![service.service_id]);
+}
@@ -0,0 +1,221 @@
+mod common;
+
+use anchor_lang::prelude::Clock;
+use common::*;
+use registry::{
+    error::ErrorCode,
+    roles::Role,
+    state::{RegistryRoles, ServiceRegistry, Timelock, TimelockOperation},
+};
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+};
+
+const DELAY: i64 = 3600;
+
+async fn advance_clock(env: &mut TestEnv, seconds: i64) {
+    let mut clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp += seconds;
+    env.ctx.set_sysvar(&clock);
+}
+
+async fn setup_timelock() -> TestEnv {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let ix = env.client.initialize_timelock(&owner.pubkey(), DELAY);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    env
+}
+
+#[tokio::test]
+async fn the_timelock_takes_the_admin_roles() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let pauser = Keypair::new();
+    env.transfer(&pauser.pubkey(), LAMPORTS_PER_SOL).await;
+    let authority = env.client.timelock_authority();
+
+    // A role administered by another role than Admin is taken back by Admin
+    let ixs = [
+        env.client
+            .grant_role(&owner.pubkey(), Role::Pauser, &pauser.pubkey()),
+        env.client
+            .set_role_admin(&owner.pubkey(), Role::MultisigWhitelister, Role::Pauser),
+        env.client.initialize_timelock(&owner.pubkey(), DELAY),
+    ];
+    env.send(&ixs, &[&owner]).await.unwrap();
+
+    let roles: RegistryRoles = env.account(&env.client.roles()).await;
+    for role in Role::ALL {
+        assert_eq!(roles.admin_role(role), Role::Admin);
+        let timelocked = Role::TIMELOCKED.contains(&role);
+        assert_eq!(roles.has_role(role, &authority), timelocked);
+        assert_eq!(
+            roles.has_role(role, &owner.pubkey()),
+            !timelocked && Role::OWNER.contains(&role)
+        );
+    }
+
+    // None of the timelocked actions runs directly any more
+    let direct = [
+        env.client
+            .grant_role(&owner.pubkey(), Role::Admin, &owner.pubkey()),
+        env.client
+            .revoke_role(&owner.pubkey(), Role::Pauser, &pauser.pubkey()),
+        env.client
+            .set_role_admin(&owner.pubkey(), Role::Pauser, Role::Pauser),
+        env.client
+            .change_multisig_permission(&owner.pubkey(), &pauser.pubkey(), true),
+        env.client.set_base_uri(&owner.pubkey(), "owner_uri".into()),
+        env.client.propose_owner(&owner.pubkey(), &pauser.pubkey()),
+        env.client
+            .propose_manager(&owner.pubkey(), &pauser.pubkey()),
+        env.client
+            .propose_drainer(&owner.pubkey(), &pauser.pubkey()),
+        env.client.set_unbonding_delay(&owner.pubkey(), 0),
+    ];
+    for ix in direct {
+        assert_error(env.send(&[ix], &[&owner]).await, ErrorCode::MissingRole);
+    }
+    let ix = env.client.grant_role(
+        &pauser.pubkey(),
+        Role::MultisigWhitelister,
+        &pauser.pubkey(),
+    );
+    assert_error(env.send(&[ix], &[&pauser]).await, ErrorCode::MissingRole);
+}
+
+#[tokio::test]
+async fn queued_operations_execute_after_the_delay() {
+    let mut env = setup_timelock().await;
+    let owner = env.owner.insecure_clone();
+    let authority = env.client.timelock_authority();
+
+    let ix = env.client.set_base_uri(&owner.pubkey(), "owner_uri".into());
+    assert_error(env.send(&[ix], &[&owner]).await, ErrorCode::MissingRole);
+
+    let target = env.client.set_base_uri(&authority, "timelocked_uri".into());
+    let ix = env
+        .client
+        .queue_timelock_operation(&owner.pubkey(), 0, &target);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let operation: TimelockOperation = env
+        .account(&timelock_operation_pda(&env.client.timelock(), 0, &registry::ID).0)
+        .await;
+    assert_eq!(operation.proposer, owner.pubkey());
+
+    let ix = env
+        .client
+        .execute_timelock_operation(&owner.pubkey(), 0, &target);
+    assert_error(
+        env.send(&[ix], &[&owner]).await,
+        ErrorCode::TimelockNotReady,
+    );
+
+    advance_clock(&mut env, DELAY).await;
+    let ix = env
+        .client
+        .execute_timelock_operation(&owner.pubkey(), 0, &target);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.base_uri, "timelocked_uri");
+
+    let ix = env
+        .client
+        .execute_timelock_operation(&owner.pubkey(), 0, &target);
+    assert_error(
+        env.send(&[ix], &[&owner]).await,
+        ErrorCode::TimelockOperationDone,
+    );
+
+    // The delay itself only changes through the timelock
+    let target = env.client.set_timelock_delay(2 * DELAY);
+    let mut ix = target.clone();
+    ix.accounts[1].pubkey = owner.pubkey();
+    assert_error(
+        env.send(&[ix], &[&owner]).await,
+        ErrorCode::NotTimelockAuthority,
+    );
+    let ix = env
+        .client
+        .queue_timelock_operation(&owner.pubkey(), 1, &target);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    advance_clock(&mut env, DELAY).await;
+    let ix = env
+        .client
+        .execute_timelock_operation(&owner.pubkey(), 1, &target);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let timelock: Timelock = env.account(&env.client.timelock()).await;
+    assert_eq!(timelock.delay, 2 * DELAY);
+}
+
+#[tokio::test]
+async fn guardians_cancel_queued_operations() {
+    let mut env = setup_timelock().await;
+    let owner = env.owner.insecure_clone();
+    let stranger = Keypair::new();
+    env.transfer(&stranger.pubkey(), LAMPORTS_PER_SOL).await;
+    let authority = env.client.timelock_authority();
+
+    let target = env.client.propose_owner(&authority, &stranger.pubkey());
+    let ix = env
+        .client
+        .queue_timelock_operation(&stranger.pubkey(), 0, &target);
+    assert_error(env.send(&[ix], &[&stranger]).await, ErrorCode::MissingRole);
+    let ix = env
+        .client
+        .queue_timelock_operation(&owner.pubkey(), 0, &target);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    let ix = env.client.cancel_timelock_operation(&stranger.pubkey(), 0);
+    assert_error(env.send(&[ix], &[&stranger]).await, ErrorCode::MissingRole);
+    let ix = env.client.cancel_timelock_operation(&owner.pubkey(), 0);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    advance_clock(&mut env, DELAY).await;
+    let ix = env
+        .client
+        .execute_timelock_operation(&owner.pubkey(), 0, &target);
+    assert_error(
+        env.send(&[ix], &[&owner]).await,
+        ErrorCode::TimelockOperationDone,
+    );
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.pending_owner, Default::default());
+}
+
+#[tokio::test]
+async fn owner_handover_leaves_timelocked_roles_in_place() {
+    let mut env = setup_timelock().await;
+    let owner = env.owner.insecure_clone();
+    let new_owner = Keypair::new();
+    env.transfer(&new_owner.pubkey(), LAMPORTS_PER_SOL).await;
+    let authority = env.client.timelock_authority();
+
+    let target = env.client.propose_owner(&authority, &new_owner.pubkey());
+    let ix = env
+        .client
+        .queue_timelock_operation(&owner.pubkey(), 0, &target);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    advance_clock(&mut env, DELAY).await;
+    let ixs = [
+        env.client
+            .execute_timelock_operation(&owner.pubkey(), 0, &target),
+        env.client.accept_owner(&new_owner.pubkey()),
+    ];
+    env.send(&ixs, &[&owner, &new_owner]).await.unwrap();
+
+    // Only the roles the outgoing owner still held move to the new owner
+    let roles: RegistryRoles = env.account(&env.client.roles()).await;
+    for role in Role::OWNER {
+        let timelocked = Role::TIMELOCKED.contains(&role);
+        assert_eq!(roles.has_role(role, &new_owner.pubkey()), !timelocked);
+        assert_eq!(roles.has_role(role, &authority), timelocked);
+        assert!(!roles.has_role(role, &owner.pubkey()));
+    }
+    let ix = env
+        .client
+        .grant_role(&new_owner.pubkey(), Role::Admin, &new_owner.pubkey());
+    assert_error(env.send(&[ix], &[&new_owner]).await, ErrorCode::MissingRole);
+}
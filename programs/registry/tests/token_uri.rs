@@ -0,0 +1,45 @@
+mod common;
+
+use common::*;
+use registry_client::token_uri;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn token_uri_encodes_config_hash_as_cid() {
+    let mut env = setup().await;
+    let config_hash: [u8; 32] = std::array::from_fn(|i| i as u8 + 0xe0);
+    let (service_id, _) = env.create_service(config_hash).await;
+
+    let ix = env.client.token_uri(service_id);
+    let uri: String = env.view(ix).await;
+    assert_eq!(
+        uri,
+        "base_urif01701220e0e1e2e3e4e5e6e7e8e9eaebecedeeeff0f1f2f3f4f5f6f7f8f9fafbfcfdfeff"
+    );
+    assert_eq!(uri, token_uri("base_uri", &config_hash));
+}
+
+#[tokio::test]
+async fn token_uri_follows_base_uri() {
+    let mut env = setup().await;
+    let service = env.register_service([90u8; 32], 1, 1, 0).await;
+
+    let owner = env.owner.insecure_clone();
+    let ix = env
+        .client
+        .set_base_uri(&owner.pubkey(), "ipfs://".to_string());
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    let ix = env.client.token_uri(service.service_id);
+    let uri: String = env.view(ix).await;
+    assert_eq!(uri, token_uri("ipfs://", &[90u8; 32]));
+    assert!(uri.starts_with("ipfs://f01701220"));
+    assert_eq!(uri.len(), "ipfs://".len() + 9 + 64);
+}
+
+#[tokio::test]
+async fn token_uri_rejects_unknown_service() {
+    let mut env = setup().await;
+    let ix = env.client.token_uri(1);
+    assert!(env.send_as_manager(ix).await.is_err());
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use anchor_lang::{prelude::*, solana_program::program_pack::Pack};
+use anchor_spl::token::spl_token;
+use common::*;
+use registry::{error::ErrorCode, service_state::ServiceState, state::ServiceAccount};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    instruction::InstructionError,
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+async fn token_amount(env: &mut TestEnv, token_account: &Pubkey) -> Option<u64> {
+    let account = env.raw_account(token_account).await?;
+    Some(
+        spl_token::state::Account::unpack(&account.data)
+            .unwrap()
+            .amount,
+    )
+}
+
+fn assert_invalid_argument(result: std::result::Result<(), BanksClientError>) {
+    assert!(matches!(
+        result,
+        Err(BanksClientError::TransactionError(
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn transfers_a_running_service() {
+    let mut env = setup().await;
+    let service = env.register_service([110u8; 32], 1, 1, 0).await;
+    let service_id = service.service_id;
+    let previous_owner = env.service_owner.insecure_clone();
+    let new_owner = Keypair::new();
+
+    let ix = env
+        .client
+        .transfer_service(&previous_owner.pubkey(), service_id, &new_owner.pubkey());
+    env.send(&[ix], &[&previous_owner]).await.unwrap();
+
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.service_owner, new_owner.pubkey());
+    assert_eq!(service_account.state, ServiceState::ActiveRegistration);
+    let previous_token = env
+        .client
+        .service_token(service_id, &previous_owner.pubkey());
+    let new_token = env.client.service_token(service_id, &new_owner.pubkey());
+    assert_eq!(token_amount(&mut env, &previous_token).await, Some(0));
+    assert_eq!(token_amount(&mut env, &new_token).await, Some(1));
+
+    // The previous owner can no longer act on the service
+    let ix = env.client.transfer_service(
+        &previous_owner.pubkey(),
+        service_id,
+        &previous_owner.pubkey(),
+    );
+    assert_error(
+        env.send(&[ix], &[&previous_owner]).await,
+        ErrorCode::WrongServiceToken,
+    );
+
+    // The security deposit is refunded to the new owner
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service.service,
+        service_id,
+        &new_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(env.balance(&new_owner.pubkey()).await, LAMPORTS_PER_SOL);
+
+    // And the new owner can hand it back
+    let ix = env
+        .client
+        .transfer_service(&new_owner.pubkey(), service_id, &previous_owner.pubkey());
+    env.send(&[ix], &[&new_owner]).await.unwrap();
+    let service_account: ServiceAccount = env.account(&service.service).await;
+    assert_eq!(service_account.service_owner, previous_owner.pubkey());
+    assert_eq!(token_amount(&mut env, &previous_token).await, Some(1));
+}
+
+#[tokio::test]
+async fn transfer_service_errors() {
+    let mut env = setup().await;
+    let (service_id, _) = env.create_service([111u8; 32]).await;
+    let owner = env.service_owner.insecure_clone();
+
+    let ix = env
+        .client
+        .transfer_service(&owner.pubkey(), service_id, &Pubkey::default());
+    assert_invalid_argument(env.send(&[ix], &[&owner]).await);
+
+    // Signed by someone else, with the token account of the owner
+    let impostor = Keypair::new();
+    env.transfer(&impostor.pubkey(), LAMPORTS_PER_SOL).await;
+    let mut ix = env
+        .client
+        .transfer_service(&impostor.pubkey(), service_id, &impostor.pubkey());
+    ix.accounts[3].pubkey = env.client.service_token(service_id, &owner.pubkey());
+    assert_invalid_argument(env.send(&[ix], &[&impostor]).await);
+
+    let service_account: ServiceAccount = env.account(&env.client.service(service_id)).await;
+    assert_eq!(service_account.service_owner, owner.pubkey());
+}
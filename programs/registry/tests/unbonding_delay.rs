@@ -0,0 +1,303 @@
+mod common;
+
+use anchor_lang::prelude::{Clock, Pubkey};
+use common::*;
+use registry::{
+    error::ErrorCode,
+    state::{PendingWithdrawal, ServiceRegistry},
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::{
+    instruction::InstructionError,
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+    transaction::TransactionError,
+};
+
+const DELAY: i64 = 7 * 24 * 3600;
+
+async fn advance_clock(env: &mut TestEnv, seconds: i64) {
+    let mut clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp += seconds;
+    env.ctx.set_sysvar(&clock);
+}
+
+#[tokio::test]
+async fn only_admins_set_the_unbonding_delay() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+
+    let ix = env.client.set_unbonding_delay(&env.manager.pubkey(), DELAY);
+    assert_error(env.send_as_manager(ix).await, ErrorCode::MissingRole);
+
+    let ix = env.client.set_unbonding_delay(&owner.pubkey(), -1);
+    assert!(matches!(
+        env.send(&[ix], &[&owner]).await,
+        Err(BanksClientError::TransactionError(
+            TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+        ))
+    ));
+
+    let ix = env.client.set_unbonding_delay(&owner.pubkey(), DELAY);
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.unbonding_delay, DELAY);
+}
+
+#[tokio::test]
+async fn pending_withdrawals_stay_slashable_until_claimed() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let ix = env.client.set_unbonding_delay(&owner.pubkey(), DELAY);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    // Agent ids 1 and 2 bond 1 and 2 SOL
+    let service = env.register_service([230u8; 32], 2, 2, 2).await;
+    let multisig = env.deploy_builtin(&service).await;
+    for agent_instance in &service.agent_instances {
+        env.transfer(agent_instance, LAMPORTS_PER_SOL).await;
+    }
+    let ix = env.client.terminate(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+
+    // Unbonding moves the bond into a pending withdrawal instead of paying it out
+    let operator = service.operator.pubkey();
+    let operator_before = env.balance(&operator).await;
+    let ix = env.client.unbond(
+        &env.manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &operator,
+        &service.registered_instances(),
+    );
+    env.send_as_manager(ix).await.unwrap();
+    assert_eq!(env.balance(&operator).await, operator_before);
+    let pending_key = pending_withdrawal_pda(
+        env.client.registry_seed(),
+        service.service_id,
+        &operator,
+        &registry::ID,
+    )
+    .0;
+    let pending: PendingWithdrawal = env.account(&pending_key).await;
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    assert_eq!(pending.operator, operator);
+    assert_eq!(pending.amount, 3 * LAMPORTS_PER_SOL);
+    assert_eq!(pending.unlock_time, clock.unix_timestamp + DELAY);
+    assert_eq!(pending.payer, env.manager.pubkey());
+
+    let claim = env.client.claim_unbonded(
+        &env.manager.pubkey(),
+        service.service_id,
+        &operator,
+        &env.manager.pubkey(),
+        None,
+    );
+    assert_error(
+        env.send_as_manager(claim.clone()).await,
+        ErrorCode::UnbondingDelayNotExpired,
+    );
+
+    // The service multisig still reaches the bond during the delay
+    let wallet = env.registry_wallet();
+    let wallet_before = env.balance(&wallet).await;
+    let slash_amount = LAMPORTS_PER_SOL / 2;
+    let ix = env.client.slash_pending_withdrawals(
+        &multisig,
+        &service.service,
+        service.service_id,
+        &[(operator, 0)],
+        None,
+    );
+    assert_error(
+        env.execute_through_multisig(&service, multisig, &ix).await,
+        ErrorCode::InvalidSlashAmount,
+    );
+    let ix = env.client.slash_pending_withdrawals(
+        &multisig,
+        &service.service,
+        service.service_id,
+        &[(operator, slash_amount)],
+        None,
+    );
+    env.execute_through_multisig(&service, multisig, &ix)
+        .await
+        .unwrap();
+    assert_eq!(env.balance(&wallet).await, wallet_before + slash_amount);
+    let registry: ServiceRegistry = env.account(&env.registry.pubkey()).await;
+    assert_eq!(registry.slashed_funds, slash_amount);
+    let pending: PendingWithdrawal = env.account(&pending_key).await;
+    assert_eq!(pending.amount, 3 * LAMPORTS_PER_SOL - slash_amount);
+
+    // Once the delay passes the operator gets what is left
+    advance_clock(&mut env, DELAY).await;
+    env.send_as_manager(claim).await.unwrap();
+    assert_eq!(
+        env.balance(&operator).await,
+        operator_before + 3 * LAMPORTS_PER_SOL - slash_amount
+    );
+    assert!(env.raw_account(&pending_key).await.is_none());
+}
+
+#[tokio::test]
+async fn operators_claim_their_own_withdrawals() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let stranger = Keypair::new();
+    env.transfer(&stranger.pubkey(), LAMPORTS_PER_SOL).await;
+    let ix = env.client.set_unbonding_delay(&owner.pubkey(), DELAY);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    let service = env.register_service([231u8; 32], 1, 1, 1).await;
+    let operator = service.operator.insecure_clone();
+    env.transfer(&operator.pubkey(), LAMPORTS_PER_SOL).await;
+    let ixs = [
+        env.client.terminate(
+            &env.manager.pubkey(),
+            &service.service,
+            service.service_id,
+            &env.service_owner.pubkey(),
+            None,
+        ),
+        env.client.unbond(
+            &env.manager.pubkey(),
+            &service.service,
+            service.service_id,
+            &operator.pubkey(),
+            &service.registered_instances(),
+        ),
+    ];
+    let manager = env.manager.insecure_clone();
+    env.send(&ixs, &[&manager]).await.unwrap();
+
+    let claim = env.client.claim_unbonded(
+        &operator.pubkey(),
+        service.service_id,
+        &operator.pubkey(),
+        &env.manager.pubkey(),
+        None,
+    );
+    assert_error(
+        env.send(std::slice::from_ref(&claim), &[&operator]).await,
+        ErrorCode::UnbondingDelayNotExpired,
+    );
+    advance_clock(&mut env, DELAY).await;
+
+    // Nobody else but the manager claims for the operator
+    let ix = env.client.claim_unbonded(
+        &stranger.pubkey(),
+        service.service_id,
+        &operator.pubkey(),
+        &env.manager.pubkey(),
+        None,
+    );
+    assert_error(env.send(&[ix], &[&stranger]).await, ErrorCode::MissingRole);
+
+    // The rent of the pending withdrawal goes back to the manager that paid it
+    let ix = env.client.claim_unbonded(
+        &operator.pubkey(),
+        service.service_id,
+        &operator.pubkey(),
+        &operator.pubkey(),
+        None,
+    );
+    let err = env.send(&[ix], &[&operator]).await.unwrap_err();
+    assert_eq!(
+        error_code(err),
+        u32::from(anchor_lang::error::ErrorCode::ConstraintAddress)
+    );
+
+    let pending_key = pending_withdrawal_pda(
+        env.client.registry_seed(),
+        service.service_id,
+        &operator.pubkey(),
+        &registry::ID,
+    )
+    .0;
+    let rent = env.balance(&pending_key).await;
+    let operator_before = env.balance(&operator.pubkey()).await;
+    let manager_before = env.balance(&manager.pubkey()).await;
+    env.send(&[claim], &[&operator]).await.unwrap();
+    assert!(env.balance(&operator.pubkey()).await > operator_before + LAMPORTS_PER_SOL - rent);
+    assert!(env.balance(&operator.pubkey()).await < operator_before + LAMPORTS_PER_SOL);
+    assert_eq!(env.balance(&manager.pubkey()).await, manager_before + rent);
+    assert!(env.raw_account(&pending_key).await.is_none());
+}
+
+#[tokio::test]
+async fn each_unbond_batch_restarts_the_delay() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let manager = env.manager.insecure_clone();
+    let ix = env.client.set_unbonding_delay(&owner.pubkey(), DELAY);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    // Agent ids 1 and 2 bond 1 and 2 SOL
+    let service = env.register_service([232u8; 32], 2, 2, 2).await;
+    let operator = service.operator.pubkey();
+    let instances = service.registered_instances();
+    let ix = env.client.terminate(
+        &manager.pubkey(),
+        &service.service,
+        service.service_id,
+        &env.service_owner.pubkey(),
+        None,
+    );
+    env.send_as_manager(ix).await.unwrap();
+    let unbond = |env: &TestEnv, agent_instances: &[(Pubkey, u32)]| {
+        env.client.unbond(
+            &manager.pubkey(),
+            &service.service,
+            service.service_id,
+            &operator,
+            agent_instances,
+        )
+    };
+    let claim = env.client.claim_unbonded(
+        &manager.pubkey(),
+        service.service_id,
+        &operator,
+        &manager.pubkey(),
+        None,
+    );
+
+    env.send_as_manager(unbond(&env, &instances[..1]))
+        .await
+        .unwrap();
+    advance_clock(&mut env, DELAY / 2).await;
+    env.send_as_manager(unbond(&env, &instances[1..]))
+        .await
+        .unwrap();
+
+    // The first batch waits for the delay of the second along with it
+    let pending_key = pending_withdrawal_pda(
+        env.client.registry_seed(),
+        service.service_id,
+        &operator,
+        &registry::ID,
+    )
+    .0;
+    let pending: PendingWithdrawal = env.account(&pending_key).await;
+    let clock: Clock = env.ctx.banks_client.get_sysvar().await.unwrap();
+    assert_eq!(pending.amount, 3 * LAMPORTS_PER_SOL);
+    assert_eq!(pending.unlock_time, clock.unix_timestamp + DELAY);
+    advance_clock(&mut env, DELAY / 2).await;
+    assert_error(
+        env.send_as_manager(claim.clone()).await,
+        ErrorCode::UnbondingDelayNotExpired,
+    );
+
+    advance_clock(&mut env, DELAY / 2).await;
+    let operator_before = env.balance(&operator).await;
+    env.send_as_manager(claim).await.unwrap();
+    assert_eq!(
+        env.balance(&operator).await,
+        operator_before + 3 * LAMPORTS_PER_SOL
+    );
+}
@@ -0,0 +1,847 @@
+#![allow(unexpected_cfgs)]
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        program::{invoke, invoke_signed},
+        system_instruction::transfer,
+    },
+};
+use registry::{
+    cpi::accounts as registry_accounts,
+    program::Registry,
+    roles::Role,
+    state::{RegistryRoles, ServiceAccount, ServiceRegistry},
+    AgentParams,
+};
+
+declare_id!("31vD9A381bHzRm9Y9JppUD7vQoFPUxs1jXGuWKko6nw9");
+
+/// PDA seeds: ["manager_authority", registry]
+///
+/// System account the registry owner grants the manager role. It signs the registry
+/// instructions and fronts the lamports they take from their manager, which the caller
+/// settles in the same instruction, so it only needs a float covering account rent.
+pub fn manager_authority_pda(registry: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"manager_authority", registry.as_ref()], &ID)
+}
+
+/// User-facing entry point of the registry, holding the business rules of who may
+/// drive a service while the registry keeps the storage and the state machine.
+///
+/// Service owners create, activate, deploy and terminate their services, and operators
+/// register and unbond their agent instances. Services bonded in an SPL token are not
+/// supported: no bond token accounts are passed, so the registry rejects them.
+#[program]
+pub mod service_manager {
+    use super::*;
+
+    /// Creates a service owned by the caller with its agent ids, the caller paying the
+    /// creation fee of the registry. Remaining accounts are the agent param PDAs, as for
+    /// `register_agent_ids_to_service`.
+    pub fn create<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Create<'info>>,
+        config_hash: [u8; 32],
+        agent_ids: Vec<u32>,
+        agent_params: Vec<AgentParams>,
+        threshold: u32,
+    ) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let caller = accounts.caller.to_account_info();
+        let authority = accounts.manager_authority.to_account_info();
+        let registry_key = accounts.registry.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"manager_authority",
+            registry_key.as_ref(),
+            &[ctx.bumps.manager_authority],
+        ];
+        let balance = authority.lamports();
+
+        // The creation fee can exceed the float, so the caller hands it over first
+        if accounts.registry.creation_fee > 0 {
+            invoke(
+                &transfer(
+                    &caller.key(),
+                    &authority.key(),
+                    accounts.registry.creation_fee,
+                ),
+                &[
+                    caller.clone(),
+                    authority.clone(),
+                    accounts.system_program.to_account_info(),
+                ],
+            )?;
+        }
+
+        registry::cpi::create(
+            CpiContext::new_with_signer(
+                accounts.registry_program.to_account_info(),
+                registry_accounts::CreateService {
+                    registry: accounts.registry.to_account_info(),
+                    roles: accounts.roles.to_account_info(),
+                    service: accounts.service.to_account_info(),
+                    config_hash_history: accounts.config_hash_history.to_account_info(),
+                    service_escrow: accounts.service_escrow.to_account_info(),
+                    service_mint: accounts.service_mint.to_account_info(),
+                    service_owner: caller.clone(),
+                    service_token: accounts.service_token.to_account_info(),
+                    registry_wallet: accounts.registry_wallet.to_account_info(),
+                    user: authority.clone(),
+                    system_program: accounts.system_program.to_account_info(),
+                    token_program: accounts.token_program.to_account_info(),
+                    associated_token_program: accounts.associated_token_program.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            config_hash,
+            caller.key(),
+            Some(threshold),
+        )?;
+
+        registry::cpi::register_agent_ids_to_service(
+            CpiContext::new_with_signer(
+                accounts.registry_program.to_account_info(),
+                registry_accounts::RegisterAgentIdsToService {
+                    registry: accounts.registry.to_account_info(),
+                    roles: accounts.roles.to_account_info(),
+                    service: accounts.service.to_account_info(),
+                    service_agent_ids_index: accounts.service_agent_ids_index.to_account_info(),
+                    service_token: None,
+                    user: authority.clone(),
+                    system_program: accounts.system_program.to_account_info(),
+                },
+                &[signer_seeds],
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            caller.key(),
+            agent_ids,
+            agent_params,
+            Some(threshold),
+        )?;
+
+        settle(
+            &caller,
+            &authority,
+            &accounts.system_program,
+            balance,
+            signer_seeds,
+        )
+    }
+
+    /// Activates the registration of a service of the caller, who pays the security
+    /// deposit.
+    pub fn activate_registration(
+        ctx: Context<ActivateRegistration>,
+        service_id: u128,
+    ) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let caller = accounts.caller.to_account_info();
+        let authority = accounts.manager_authority.to_account_info();
+        let registry_key = accounts.registry.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"manager_authority",
+            registry_key.as_ref(),
+            &[ctx.bumps.manager_authority],
+        ];
+        let balance = authority.lamports();
+
+        // The deposit can exceed the float, so the caller hands it over first
+        invoke(
+            &transfer(
+                &caller.key(),
+                &authority.key(),
+                accounts.service.security_deposit,
+            ),
+            &[
+                caller.clone(),
+                authority.clone(),
+                accounts.system_program.to_account_info(),
+            ],
+        )?;
+
+        registry::cpi::activate_registration(
+            CpiContext::new_with_signer(
+                accounts.registry_program.to_account_info(),
+                registry_accounts::ActivateRegistration {
+                    registry: accounts.registry.to_account_info(),
+                    roles: accounts.roles.to_account_info(),
+                    service: accounts.service.to_account_info(),
+                    service_token: accounts.service_token.to_account_info(),
+                    service_escrow: accounts.service_escrow.to_account_info(),
+                    bond_token: lamport_bond_token(&accounts.service_bond_token),
+                    user: authority.clone(),
+                    system_program: accounts.system_program.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            service_id,
+            caller.key(),
+        )?;
+
+        settle(
+            &caller,
+            &authority,
+            &accounts.system_program,
+            balance,
+            signer_seeds,
+        )
+    }
+
+    /// Registers agent instances of the caller, who pays their bonds. Remaining accounts
+    /// are those of the registry `register_agents`.
+    pub fn register_agents<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RegisterAgents<'info>>,
+        agent_instances: Vec<Pubkey>,
+        agent_ids: Vec<u32>,
+    ) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let caller = accounts.caller.to_account_info();
+
+        registry::cpi::register_agents(
+            CpiContext::new(
+                accounts.registry_program.to_account_info(),
+                registry_accounts::RegisterAgentInstances {
+                    registry: accounts.registry.to_account_info(),
+                    service: accounts.service.to_account_info(),
+                    service_escrow: accounts.service_escrow.to_account_info(),
+                    bond_token: lamport_bond_token(&accounts.service_bond_token),
+                    operator_agent_instance_index: accounts
+                        .operator_agent_instance_index
+                        .to_account_info(),
+                    operator_whitelist: accounts.operator_whitelist.to_account_info(),
+                    user: caller.clone(),
+                    instructions: accounts.instructions.to_account_info(),
+                    system_program: accounts.system_program.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            caller.key(),
+            agent_instances,
+            agent_ids,
+        )
+    }
+
+    /// Deploys a service of the caller. Remaining accounts are those of the registry
+    /// `deploy`: the multisig, then the agent instances.
+    pub fn deploy<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Deploy<'info>>,
+        service_id: u128,
+        multisig_implementation: Pubkey,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let caller = accounts.caller.to_account_info();
+        let authority = accounts.manager_authority.to_account_info();
+        let registry_key = accounts.registry.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"manager_authority",
+            registry_key.as_ref(),
+            &[ctx.bumps.manager_authority],
+        ];
+        let balance = authority.lamports();
+
+        registry::cpi::deploy(
+            CpiContext::new_with_signer(
+                accounts.registry_program.to_account_info(),
+                registry_accounts::Deploy {
+                    registry: accounts.registry.to_account_info(),
+                    roles: accounts.roles.to_account_info(),
+                    service: accounts.service.to_account_info(),
+                    service_owner: caller.clone(),
+                    service_token: accounts.service_token.to_account_info(),
+                    registry_multisig: accounts.registry_multisig.to_account_info(),
+                    multisig_implementation: accounts.multisig_implementation.to_account_info(),
+                    agent_instances_index: accounts.agent_instances_index.to_account_info(),
+                    user: authority.clone(),
+                    system_program: accounts.system_program.to_account_info(),
+                },
+                &[signer_seeds],
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            service_id,
+            multisig_implementation,
+            data,
+        )?;
+
+        settle(
+            &caller,
+            &authority,
+            &accounts.system_program,
+            balance,
+            signer_seeds,
+        )
+    }
+
+    /// Terminates a service of the caller, who receives the security deposit.
+    pub fn terminate(ctx: Context<Terminate>, service_id: u128) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let caller = accounts.caller.to_account_info();
+        let authority = accounts.manager_authority.to_account_info();
+        let registry_key = accounts.registry.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"manager_authority",
+            registry_key.as_ref(),
+            &[ctx.bumps.manager_authority],
+        ];
+        let balance = authority.lamports();
+
+        registry::cpi::terminate(
+            CpiContext::new_with_signer(
+                accounts.registry_program.to_account_info(),
+                registry_accounts::TerminateService {
+                    registry: accounts.registry.to_account_info(),
+                    roles: accounts.roles.to_account_info(),
+                    service: accounts.service.to_account_info(),
+                    service_agent_ids_index: accounts.service_agent_ids_index.to_account_info(),
+                    service_escrow: accounts.service_escrow.to_account_info(),
+                    service_owner: caller.clone(),
+                    service_token: accounts.service_token.to_account_info(),
+                    bond_token: lamport_bond_token(&accounts.service_bond_token),
+                    user: authority.clone(),
+                },
+                &[signer_seeds],
+            ),
+            service_id,
+        )?;
+
+        settle(
+            &caller,
+            &authority,
+            &accounts.system_program,
+            balance,
+            signer_seeds,
+        )
+    }
+
+    /// Closes a batch of agent accounts of a terminated service of the caller, who
+    /// receives their rent. Remaining accounts are those of the registry
+    /// `cleanup_service`.
+    pub fn cleanup<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Cleanup<'info>>,
+        service_id: u128,
+    ) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let caller = accounts.caller.to_account_info();
+        let authority = accounts.manager_authority.to_account_info();
+        let registry_key = accounts.registry.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"manager_authority",
+            registry_key.as_ref(),
+            &[ctx.bumps.manager_authority],
+        ];
+        let balance = authority.lamports();
+
+        registry::cpi::cleanup_service(
+            CpiContext::new_with_signer(
+                accounts.registry_program.to_account_info(),
+                registry_accounts::CleanupService {
+                    registry: accounts.registry.to_account_info(),
+                    service: accounts.service.to_account_info(),
+                    service_agent_ids_index: accounts.service_agent_ids_index.to_account_info(),
+                    agent_instances_index: accounts.agent_instances_index.to_account_info(),
+                    service_owner: caller.clone(),
+                    service_token: accounts.service_token.to_account_info(),
+                    roles: accounts.roles.to_account_info(),
+                    user: authority.clone(),
+                },
+                &[signer_seeds],
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            service_id,
+        )?;
+
+        settle(
+            &caller,
+            &authority,
+            &accounts.system_program,
+            balance,
+            signer_seeds,
+        )
+    }
+
+    /// Unbonds the caller from a terminated service, returning the rent of the closed
+    /// accounts; its bond waits for `claim_unbonded`. Remaining accounts are those of the
+    /// registry `unbond`.
+    pub fn unbond<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Unbond<'info>>,
+        service_id: u128,
+    ) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let caller = accounts.caller.to_account_info();
+        let authority = accounts.manager_authority.to_account_info();
+        let registry_key = accounts.registry.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"manager_authority",
+            registry_key.as_ref(),
+            &[ctx.bumps.manager_authority],
+        ];
+        let balance = authority.lamports();
+
+        registry::cpi::unbond(
+            CpiContext::new_with_signer(
+                accounts.registry_program.to_account_info(),
+                registry_accounts::UnbondOperator {
+                    registry: accounts.registry.to_account_info(),
+                    roles: accounts.roles.to_account_info(),
+                    service: accounts.service.to_account_info(),
+                    service_agent_ids_index: accounts.service_agent_ids_index.to_account_info(),
+                    operator_agent_instance_index: accounts
+                        .operator_agent_instance_index
+                        .to_account_info(),
+                    operator_bond: accounts.operator_bond.to_account_info(),
+                    operator: caller.clone(),
+                    pending_withdrawal: accounts.pending_withdrawal.to_account_info(),
+                    user: authority.clone(),
+                    system_program: accounts.system_program.to_account_info(),
+                },
+                &[signer_seeds],
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            service_id,
+        )?;
+
+        settle(
+            &caller,
+            &authority,
+            &accounts.system_program,
+            balance,
+            signer_seeds,
+        )
+    }
+
+    /// Pays the caller its unbonded bond once the registry unbonding delay passed, along
+    /// with the rent it paid for the pending withdrawal in `unbond`.
+    pub fn claim_unbonded(ctx: Context<ClaimUnbonded>, service_id: u128) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let caller = accounts.caller.to_account_info();
+        let authority = accounts.manager_authority.to_account_info();
+        let registry_key = accounts.registry.key();
+        let signer_seeds: &[&[u8]] = &[
+            b"manager_authority",
+            registry_key.as_ref(),
+            &[ctx.bumps.manager_authority],
+        ];
+        let balance = authority.lamports();
+
+        registry::cpi::claim_unbonded(
+            CpiContext::new_with_signer(
+                accounts.registry_program.to_account_info(),
+                registry_accounts::ClaimUnbonded {
+                    registry: accounts.registry.to_account_info(),
+                    service: accounts.service.to_account_info(),
+                    pending_withdrawal: accounts.pending_withdrawal.to_account_info(),
+                    payer: authority.clone(),
+                    operator: caller.clone(),
+                    service_escrow: accounts.service_escrow.to_account_info(),
+                    bond_token: lamport_bond_token(&accounts.service_bond_token),
+                    roles: accounts.roles.to_account_info(),
+                    user: authority.clone(),
+                },
+                &[signer_seeds],
+            ),
+            service_id,
+        )?;
+
+        settle(
+            &caller,
+            &authority,
+            &accounts.system_program,
+            balance,
+            signer_seeds,
+        )
+    }
+}
+
+/// Bond token accounts of a service bonded in lamports.
+fn lamport_bond_token<'info>(
+    service_bond_token: &UncheckedAccount<'info>,
+) -> registry_accounts::BondToken<'info> {
+    registry_accounts::BondToken {
+        service_bond_token: service_bond_token.to_account_info(),
+        bond_token_vault: None,
+        vault_token: None,
+        escrow_token: None,
+        counterparty_token: None,
+        token_program: None,
+    }
+}
+
+/// Brings the manager authority back to `balance`: the caller pays what the registry
+/// took from it, and receives what the registry paid to it.
+fn settle<'info>(
+    caller: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    balance: u64,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let current = authority.lamports();
+    let accounts = [
+        caller.clone(),
+        authority.clone(),
+        system_program.to_account_info(),
+    ];
+
+    if current < balance {
+        invoke(
+            &transfer(caller.key, authority.key, balance - current),
+            &accounts,
+        )?;
+    } else if current > balance {
+        invoke_signed(
+            &transfer(authority.key, caller.key, current - balance),
+            &accounts,
+            &[signer_seeds],
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Create<'info> {
+    #[account(mut)]
+    pub registry: Box<Account<'info, ServiceRegistry>>,
+
+    /// CHECK: System account signing for the registry manager
+    #[account(mut, seeds = [b"manager_authority", registry.key().as_ref()], bump)]
+    pub manager_authority: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"roles", registry.key().as_ref()],
+        bump,
+        seeds::program = registry_program.key(),
+        constraint = roles.has_role(Role::ServiceManager, manager_authority.key)
+            @ ErrorCode::NotRegistryManager,
+    )]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The service owner
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// CHECK: Created by the registry
+    #[account(mut)]
+    pub service: UncheckedAccount<'info>,
+
+    /// CHECK: Created by the registry
+    #[account(mut)]
+    pub config_hash_history: UncheckedAccount<'info>,
+
+    /// CHECK: Created by the registry
+    #[account(mut)]
+    pub service_escrow: UncheckedAccount<'info>,
+
+    /// CHECK: Created by the registry
+    #[account(mut)]
+    pub service_mint: UncheckedAccount<'info>,
+
+    /// CHECK: Created by the registry
+    #[account(mut)]
+    pub service_token: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub registry_wallet: UncheckedAccount<'info>,
+
+    /// CHECK: Created by the registry
+    #[account(mut)]
+    pub service_agent_ids_index: UncheckedAccount<'info>,
+
+    pub registry_program: Program<'info, Registry>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: Checked by the registry
+    pub token_program: UncheckedAccount<'info>,
+    /// CHECK: Checked by the registry
+    pub associated_token_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ActivateRegistration<'info> {
+    #[account(mut)]
+    pub registry: Box<Account<'info, ServiceRegistry>>,
+
+    /// CHECK: System account signing for the registry manager
+    #[account(mut, seeds = [b"manager_authority", registry.key().as_ref()], bump)]
+    pub manager_authority: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"roles", registry.key().as_ref()],
+        bump,
+        seeds::program = registry_program.key(),
+        constraint = roles.has_role(Role::ServiceManager, manager_authority.key)
+            @ ErrorCode::NotRegistryManager,
+    )]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The service owner
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// Read for the security deposit, checked by the registry
+    #[account(mut)]
+    pub service: Box<Account<'info, ServiceAccount>>,
+
+    /// CHECK: Checked by the registry
+    pub service_token: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service_escrow: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub service_bond_token: UncheckedAccount<'info>,
+
+    pub registry_program: Program<'info, Registry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterAgents<'info> {
+    #[account(mut)]
+    pub registry: Box<Account<'info, ServiceRegistry>>,
+
+    /// The operator
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service_escrow: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub service_bond_token: UncheckedAccount<'info>,
+
+    /// CHECK: Created by the registry
+    #[account(mut)]
+    pub operator_agent_instance_index: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub operator_whitelist: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub instructions: UncheckedAccount<'info>,
+
+    pub registry_program: Program<'info, Registry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Deploy<'info> {
+    #[account(mut)]
+    pub registry: Box<Account<'info, ServiceRegistry>>,
+
+    /// CHECK: System account signing for the registry manager
+    #[account(mut, seeds = [b"manager_authority", registry.key().as_ref()], bump)]
+    pub manager_authority: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"roles", registry.key().as_ref()],
+        bump,
+        seeds::program = registry_program.key(),
+        constraint = roles.has_role(Role::ServiceManager, manager_authority.key)
+            @ ErrorCode::NotRegistryManager,
+    )]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The service owner
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub service_token: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub registry_multisig: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub multisig_implementation: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub agent_instances_index: UncheckedAccount<'info>,
+
+    pub registry_program: Program<'info, Registry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Terminate<'info> {
+    #[account(mut)]
+    pub registry: Box<Account<'info, ServiceRegistry>>,
+
+    /// CHECK: System account signing for the registry manager
+    #[account(mut, seeds = [b"manager_authority", registry.key().as_ref()], bump)]
+    pub manager_authority: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"roles", registry.key().as_ref()],
+        bump,
+        seeds::program = registry_program.key(),
+        constraint = roles.has_role(Role::ServiceManager, manager_authority.key)
+            @ ErrorCode::NotRegistryManager,
+    )]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The service owner
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service_agent_ids_index: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service_escrow: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub service_token: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub service_bond_token: UncheckedAccount<'info>,
+
+    pub registry_program: Program<'info, Registry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Cleanup<'info> {
+    pub registry: Box<Account<'info, ServiceRegistry>>,
+
+    /// CHECK: System account signing for the registry manager
+    #[account(mut, seeds = [b"manager_authority", registry.key().as_ref()], bump)]
+    pub manager_authority: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"roles", registry.key().as_ref()],
+        bump,
+        seeds::program = registry_program.key(),
+        constraint = roles.has_role(Role::ServiceManager, manager_authority.key)
+            @ ErrorCode::NotRegistryManager,
+    )]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The service owner
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service_agent_ids_index: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub agent_instances_index: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub service_token: UncheckedAccount<'info>,
+
+    pub registry_program: Program<'info, Registry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Unbond<'info> {
+    #[account(mut)]
+    pub registry: Box<Account<'info, ServiceRegistry>>,
+
+    /// CHECK: System account signing for the registry manager
+    #[account(mut, seeds = [b"manager_authority", registry.key().as_ref()], bump)]
+    pub manager_authority: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"roles", registry.key().as_ref()],
+        bump,
+        seeds::program = registry_program.key(),
+        constraint = roles.has_role(Role::ServiceManager, manager_authority.key)
+            @ ErrorCode::NotRegistryManager,
+    )]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The operator
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub service_agent_ids_index: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub operator_agent_instance_index: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub operator_bond: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub pending_withdrawal: UncheckedAccount<'info>,
+
+    pub registry_program: Program<'info, Registry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimUnbonded<'info> {
+    pub registry: Box<Account<'info, ServiceRegistry>>,
+
+    /// CHECK: System account signing for the registry manager
+    #[account(mut, seeds = [b"manager_authority", registry.key().as_ref()], bump)]
+    pub manager_authority: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"roles", registry.key().as_ref()],
+        bump,
+        seeds::program = registry_program.key(),
+        constraint = roles.has_role(Role::ServiceManager, manager_authority.key)
+            @ ErrorCode::NotRegistryManager,
+    )]
+    pub roles: Box<Account<'info, RegistryRoles>>,
+
+    /// The operator
+    #[account(mut)]
+    pub caller: Signer<'info>,
+
+    /// CHECK: Checked by the registry
+    pub service: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub pending_withdrawal: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    #[account(mut)]
+    pub service_escrow: UncheckedAccount<'info>,
+
+    /// CHECK: Checked by the registry
+    pub service_bond_token: UncheckedAccount<'info>,
+
+    pub registry_program: Program<'info, Registry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("Authority of this program does not hold the registry manager role")]
+    NotRegistryManager,
+}
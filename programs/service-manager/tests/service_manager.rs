@@ -0,0 +1,325 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{entrypoint::ProgramResult, instruction::Instruction},
+    AccountDeserialize,
+};
+use registry::{
+    roles::Role,
+    service_state::ServiceState,
+    state::{MultisigAccount, ServiceAccount, ServiceRegistry},
+    AgentParams,
+};
+use registry_client::{RegistryClient, ServiceManagerClient};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account as SolanaAccount,
+    instruction::InstructionError,
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+// Anchor entrypoints tie the accounts slice to the 'info lifetime, the test runtime does not
+fn registry_entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    registry::entry(program_id, accounts, data)
+}
+
+fn service_manager_entry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    service_manager::entry(program_id, accounts, data)
+}
+
+struct TestEnv {
+    ctx: ProgramTestContext,
+    client: ServiceManagerClient,
+    owner: Keypair,
+    service_owner: Keypair,
+    operator: Keypair,
+}
+
+impl TestEnv {
+    async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> std::result::Result<(), BanksClientError> {
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signers[0].pubkey()),
+            signers,
+            blockhash,
+        );
+        self.ctx.banks_client.process_transaction(tx).await
+    }
+
+    async fn account<T: AccountDeserialize>(&mut self, key: &Pubkey) -> T {
+        let account = self
+            .ctx
+            .banks_client
+            .get_account(*key)
+            .await
+            .unwrap()
+            .expect("account not found");
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    async fn balance(&mut self, key: &Pubkey) -> u64 {
+        self.ctx.banks_client.get_balance(*key).await.unwrap()
+    }
+}
+
+/// Registry managed by the service manager, whose authority holds a float for rent.
+async fn setup() -> TestEnv {
+    let mut program_test = ProgramTest::new("registry", registry::ID, processor!(registry_entry));
+    program_test.add_program(
+        "service_manager",
+        service_manager::ID,
+        processor!(service_manager_entry),
+    );
+
+    let owner = Keypair::new();
+    let service_owner = Keypair::new();
+    let operator = Keypair::new();
+    for key in [&owner, &service_owner, &operator] {
+        program_test.add_account(
+            key.pubkey(),
+            SolanaAccount {
+                lamports: 1_000 * LAMPORTS_PER_SOL,
+                ..SolanaAccount::default()
+            },
+        );
+    }
+
+    let ctx = program_test.start_with_context().await;
+    let registry = Keypair::new();
+    let registry_client = RegistryClient::new(registry.pubkey());
+    let mut env = TestEnv {
+        ctx,
+        client: ServiceManagerClient::new(registry_client.clone()),
+        owner: owner.insecure_clone(),
+        service_owner,
+        operator,
+    };
+
+    let manager_authority = env.client.manager_authority();
+    let ixs = [
+        registry_client.initialize(
+            &owner.pubkey(),
+            "test_token".into(),
+            "AUTO".into(),
+            "base_uri".into(),
+            &Keypair::new().pubkey(),
+            &owner.pubkey(),
+        ),
+        registry_client.grant_role(&owner.pubkey(), Role::ServiceManager, &manager_authority),
+        registry_client.change_multisig_permission(&owner.pubkey(), &registry::ID, true),
+        solana_sdk::system_instruction::transfer(
+            &owner.pubkey(),
+            &manager_authority,
+            LAMPORTS_PER_SOL,
+        ),
+    ];
+    env.send(&ixs, &[&owner, &registry]).await.unwrap();
+
+    env
+}
+
+#[tokio::test]
+async fn service_lifecycle_through_the_manager() {
+    let mut env = setup().await;
+    let registry = env.client.registry.clone();
+    let service_owner = env.service_owner.insecure_clone();
+    let operator = env.operator.insecure_clone();
+    let manager_authority = env.client.manager_authority();
+    let float = env.balance(&manager_authority).await;
+    let agent_ids = [1, 2];
+    let agent_params = [
+        AgentParams {
+            slots: 1,
+            bond: LAMPORTS_PER_SOL,
+        },
+        AgentParams {
+            slots: 1,
+            bond: 2 * LAMPORTS_PER_SOL,
+        },
+    ];
+
+    let service_id = 1;
+    let service = env.client.registry.service(service_id);
+    let ix = env.client.create(
+        &service_owner.pubkey(),
+        service_id,
+        [180u8; 32],
+        &agent_ids,
+        &agent_params,
+        2,
+    );
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+    let ix = env
+        .client
+        .activate_registration(&service_owner.pubkey(), service_id);
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+
+    let account: ServiceAccount = env.account(&service).await;
+    assert_eq!(account.service_owner, service_owner.pubkey());
+    assert_eq!(account.security_deposit, 2 * LAMPORTS_PER_SOL);
+    assert_eq!(account.state, ServiceState::ActiveRegistration);
+    assert_eq!(env.balance(&manager_authority).await, float);
+
+    let agent_instances = [Keypair::new().pubkey(), Keypair::new().pubkey()];
+    let ix =
+        env.client
+            .register_agents(&operator.pubkey(), service_id, &agent_instances, &agent_ids);
+    env.send(&[ix], &[&operator]).await.unwrap();
+
+    let multisig = registry.builtin_multisig(&agent_instances);
+    let ix = env.client.deploy(
+        &service_owner.pubkey(),
+        service_id,
+        &registry::ID,
+        &multisig,
+        &agent_instances,
+        vec![],
+    );
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+    let multisig_account: MultisigAccount = env.account(&multisig).await;
+    assert_eq!(multisig_account.threshold, 2);
+    let account: ServiceAccount = env.account(&service).await;
+    assert_eq!(account.state, ServiceState::Deployed);
+    assert_eq!(env.balance(&manager_authority).await, float);
+
+    // The service owner gets its deposit back, the operator its bonds
+    let owner_before = env.balance(&service_owner.pubkey()).await;
+    let ix = env.client.terminate(&service_owner.pubkey(), service_id);
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+    assert!(env.balance(&service_owner.pubkey()).await > owner_before + LAMPORTS_PER_SOL);
+
+    let registered: Vec<(Pubkey, u32)> = agent_instances
+        .iter()
+        .copied()
+        .zip(agent_ids.iter().copied())
+        .collect();
+    let operator_before = env.balance(&operator.pubkey()).await;
+    let ix = env
+        .client
+        .unbond(&operator.pubkey(), service_id, &registered);
+    env.send(&[ix], &[&operator]).await.unwrap();
+    let ix = env.client.claim_unbonded(&operator.pubkey(), service_id);
+    env.send(&[ix], &[&operator]).await.unwrap();
+    assert!(env.balance(&operator.pubkey()).await > operator_before + 2 * LAMPORTS_PER_SOL);
+
+    let account: ServiceAccount = env.account(&service).await;
+    assert_eq!(account.state, ServiceState::PreRegistration);
+    assert_eq!(env.balance(&manager_authority).await, float);
+
+    // The rent of the closed agent accounts goes to the service owner as well
+    let owner_before = env.balance(&service_owner.pubkey()).await;
+    let ix = env.client.cleanup(
+        &service_owner.pubkey(),
+        service_id,
+        &agent_ids,
+        &registered,
+        0..agent_ids.len() + registered.len(),
+    );
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+    assert!(env.balance(&service_owner.pubkey()).await > owner_before);
+    let account: ServiceAccount = env.account(&service).await;
+    assert!(!account.cleanup_pending);
+    assert_eq!(env.balance(&manager_authority).await, float);
+}
+
+#[tokio::test]
+async fn only_the_service_owner_drives_its_service() {
+    let mut env = setup().await;
+    let service_owner = env.service_owner.insecure_clone();
+    let stranger = env.operator.insecure_clone();
+    let ix = env.client.create(
+        &service_owner.pubkey(),
+        1,
+        [181u8; 32],
+        &[1],
+        &[AgentParams {
+            slots: 1,
+            bond: LAMPORTS_PER_SOL,
+        }],
+        1,
+    );
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+
+    // Someone else signing with the token account of the service owner
+    let mut ix = env.client.activate_registration(&stranger.pubkey(), 1);
+    // service token account, after the registry, authority, roles, caller and service
+    ix.accounts[5].pubkey = env
+        .client
+        .registry
+        .service_token(1, &service_owner.pubkey());
+    assert_eq!(
+        env.send(&[ix], &[&stranger]).await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+    );
+
+    // The manager only acts for registries granting it the manager role
+    let owner = env.owner.insecure_clone();
+    let ix = env.client.registry.revoke_role(
+        &owner.pubkey(),
+        Role::ServiceManager,
+        &env.client.manager_authority(),
+    );
+    env.send(&[ix], &[&owner]).await.unwrap();
+    let ix = env.client.activate_registration(&service_owner.pubkey(), 1);
+    assert_eq!(
+        env.send(&[ix], &[&service_owner])
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(service_manager::ErrorCode::NotRegistryManager.into())
+        )
+    );
+}
+
+#[tokio::test]
+async fn the_caller_pays_the_creation_fee() {
+    let mut env = setup().await;
+    let owner = env.owner.insecure_clone();
+    let service_owner = env.service_owner.insecure_clone();
+    let manager_authority = env.client.manager_authority();
+    let float = env.balance(&manager_authority).await;
+    // Above the float of the manager authority
+    let creation_fee = 2 * LAMPORTS_PER_SOL;
+    let ix = env
+        .client
+        .registry
+        .set_permissionless(&owner.pubkey(), true, creation_fee);
+    env.send(&[ix], &[&owner]).await.unwrap();
+
+    let wallet = env.client.registry.registry_wallet();
+    let wallet_before = env.balance(&wallet).await;
+    let caller_before = env.balance(&service_owner.pubkey()).await;
+    let ix = env.client.create(
+        &service_owner.pubkey(),
+        1,
+        [182u8; 32],
+        &[1],
+        &[AgentParams {
+            slots: 1,
+            bond: LAMPORTS_PER_SOL,
+        }],
+        1,
+    );
+    env.send(&[ix], &[&service_owner]).await.unwrap();
+
+    assert_eq!(env.balance(&wallet).await, wallet_before + creation_fee);
+    let registry_key = env.client.registry.registry;
+    let registry: ServiceRegistry = env.account(&registry_key).await;
+    assert_eq!(registry.accrued_fees, creation_fee);
+    assert!(env.balance(&service_owner.pubkey()).await < caller_before - creation_fee);
+    assert_eq!(env.balance(&manager_authority).await, float);
+}
@@ -9,6 +9,13 @@ const provider = anchor.AnchorProvider.env();
 anchor.setProvider(provider);
 const connection = anchor.getProvider().connection;
 
+const TOKEN_PROGRAM_ID = new anchor.web3.PublicKey(
+  'TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA'
+);
+const ASSOCIATED_TOKEN_PROGRAM_ID = new anchor.web3.PublicKey(
+  'ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL'
+);
+
 describe('registry', () => {
   const name = 'test_token';
   const symbol = 'AUTO';
@@ -18,7 +25,8 @@ describe('registry', () => {
   const manager = anchor.web3.Keypair.generate();
   const drainer = anchor.web3.Keypair.generate();
   const ownerService = anchor.web3.Keypair.generate();
-  const multisigImplementation = anchor.web3.Keypair.generate();
+  // The registry program itself is the built-in multisig implementation
+  const multisigImplementation = program.programId;
 
   it('Initializes Registry', async () => {
     const registryAccount = anchor.web3.Keypair.generate();
@@ -126,19 +134,17 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(1);
 
-      const [servicePda, _bump] = anchor.web3.PublicKey.findProgramAddressSync(
-        [
-          Buffer.from('service'),
-          Buffer.from(config_hash.buffer, config_hash.byteOffset, 7),
-        ],
-        program.programId
-      );
+      const [servicePda, _bump] = await nextServicePda(registryAccount);
 
       // Create first service
       await program.methods
         .create(Array.from(config_hash), ownerService.publicKey, null)
         .accounts({
           registry: registryAccount.publicKey,
+          serviceMint: (await nextServiceMintPda(registryAccount))[0],
+          serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+          registryWallet: registryWalletPda(registryAccount),
+          serviceOwner: ownerService.publicKey,
           service: servicePda,
           user: manager.publicKey,
         })
@@ -167,6 +173,7 @@ describe('registry', () => {
           anchor.web3.PublicKey.findProgramAddressSync(
             [
               Buffer.from('agent_param'),
+              registryAccount.publicKey.toBuffer(),
               serviceId.toArrayLike(Buffer, 'le', 16), // service_id as little-endian 16 bytes
               agentId.toArrayLike(Buffer, 'le', 4), // agent_id as little-endian 4 bytes
             ],
@@ -180,6 +187,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -219,17 +227,7 @@ describe('registry', () => {
       const second_config_hash = new Uint8Array(32).fill(2);
 
       const [second_servicePda, second__bump] =
-        anchor.web3.PublicKey.findProgramAddressSync(
-          [
-            Buffer.from('service'),
-            Buffer.from(
-              second_config_hash.buffer,
-              second_config_hash.byteOffset,
-              7
-            ),
-          ],
-          program.programId
-        );
+        await nextServicePda(registryAccount);
 
       await program.methods
         .create(
@@ -239,6 +237,10 @@ describe('registry', () => {
         )
         .accounts({
           registry: registryAccount.publicKey,
+          serviceMint: (await nextServiceMintPda(registryAccount))[0],
+          serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+          registryWallet: registryWalletPda(registryAccount),
+          serviceOwner: ownerService.publicKey,
           service: second_servicePda,
           user: manager.publicKey,
         })
@@ -265,16 +267,17 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(3); // Unique config for this test
 
-      const [servicePda] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('service'), config_hash.slice(0, 7)],
-        program.programId
-      );
+      const [servicePda] = await nextServicePda(registryAccount);
 
       try {
         await program.methods
           .create(Array.from(config_hash), ownerService.publicKey, null)
           .accounts({
             registry: registryAccount.publicKey,
+            serviceMint: (await nextServiceMintPda(registryAccount))[0],
+            serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+            registryWallet: registryWalletPda(registryAccount),
+            serviceOwner: ownerService.publicKey,
             service: servicePda,
             user: manager.publicKey,
           })
@@ -296,7 +299,12 @@ describe('registry', () => {
         const agentIdSeed = agentBN.toArrayLike(Buffer, 'le', 4);
 
         const [agent_param_pda] = anchor.web3.PublicKey.findProgramAddressSync(
-          [Buffer.from('agent_param'), serviceIdSeed, agentIdSeed],
+          [
+            Buffer.from('agent_param'),
+            registryAccount.publicKey.toBuffer(),
+            serviceIdSeed,
+            agentIdSeed,
+          ],
           program.programId
         );
         pdaList.push(agent_param_pda);
@@ -306,6 +314,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -357,7 +366,12 @@ describe('registry', () => {
         const agentIdSeed = agentBN.toArrayLike(Buffer, 'le', 4);
 
         const [agent_param_pda] = anchor.web3.PublicKey.findProgramAddressSync(
-          [Buffer.from('agent_param'), serviceIdSeed, agentIdSeed],
+          [
+            Buffer.from('agent_param'),
+            registryAccount.publicKey.toBuffer(),
+            serviceIdSeed,
+            agentIdSeed,
+          ],
           program.programId
         );
         updatedPdas.push(agent_param_pda);
@@ -432,19 +446,17 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(4); // Unique config for this test
 
-      const [servicePda] = anchor.web3.PublicKey.findProgramAddressSync(
-        [
-          Buffer.from('service'),
-          Buffer.from(config_hash.buffer, config_hash.byteOffset, 7),
-        ],
-        program.programId
-      );
+      const [servicePda] = await nextServicePda(registryAccount);
 
       try {
         await program.methods
           .create(Array.from(config_hash), ownerService.publicKey, null)
           .accounts({
             registry: registryAccount.publicKey,
+            serviceMint: (await nextServiceMintPda(registryAccount))[0],
+            serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+            registryWallet: registryWalletPda(registryAccount),
+            serviceOwner: ownerService.publicKey,
             service: servicePda,
             user: manager.publicKey,
           })
@@ -468,7 +480,12 @@ describe('registry', () => {
         const agentIdSeed = agentBN.toArrayLike(Buffer, 'le', 4);
 
         const [agent_param_pda] = anchor.web3.PublicKey.findProgramAddressSync(
-          [Buffer.from('agent_param'), serviceIdSeed, agentIdSeed],
+          [
+            Buffer.from('agent_param'),
+            registryAccount.publicKey.toBuffer(),
+            serviceIdSeed,
+            agentIdSeed,
+          ],
           program.programId
         );
         pdaList.push(agent_param_pda);
@@ -478,6 +495,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -526,7 +544,12 @@ describe('registry', () => {
       const agentIdSeed = agentBN.toArrayLike(Buffer, 'le', 4);
 
       const [agent_param_pda] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('agent_param'), serviceIdSeed, agentIdSeed],
+        [
+          Buffer.from('agent_param'),
+          registryAccount.publicKey.toBuffer(),
+          serviceIdSeed,
+          agentIdSeed,
+        ],
         program.programId
       );
       updatedPdas.push(agent_param_pda);
@@ -602,16 +625,17 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(5); // Unique config for this test
 
-      const [servicePda] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('service'), config_hash.slice(0, 7)],
-        program.programId
-      );
+      const [servicePda] = await nextServicePda(registryAccount);
 
       try {
         await program.methods
           .create(Array.from(config_hash), ownerService.publicKey, null)
           .accounts({
             registry: registryAccount.publicKey,
+            serviceMint: (await nextServiceMintPda(registryAccount))[0],
+            serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+            registryWallet: registryWalletPda(registryAccount),
+            serviceOwner: ownerService.publicKey,
             service: servicePda,
             user: manager.publicKey,
           })
@@ -633,7 +657,12 @@ describe('registry', () => {
         const agentIdSeed = agentBN.toArrayLike(Buffer, 'le', 4);
 
         const [agent_param_pda] = anchor.web3.PublicKey.findProgramAddressSync(
-          [Buffer.from('agent_param'), serviceIdSeed, agentIdSeed],
+          [
+            Buffer.from('agent_param'),
+            registryAccount.publicKey.toBuffer(),
+            serviceIdSeed,
+            agentIdSeed,
+          ],
           program.programId
         );
         pdaList.push(agent_param_pda);
@@ -643,6 +672,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -694,7 +724,12 @@ describe('registry', () => {
       const agentIdSeed = agentBN.toArrayLike(Buffer, 'le', 4);
 
       const [agent_param_pda] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('agent_param'), serviceIdSeed, agentIdSeed],
+        [
+          Buffer.from('agent_param'),
+          registryAccount.publicKey.toBuffer(),
+          serviceIdSeed,
+          agentIdSeed,
+        ],
         program.programId
       );
       updatedPdas.push(agent_param_pda);
@@ -763,15 +798,16 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(6); // Unique config for this test
 
-      const [servicePda] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('service'), config_hash.slice(0, 7)],
-        program.programId
-      );
+      const [servicePda] = await nextServicePda(registryAccount);
 
       await program.methods
         .create(Array.from(config_hash), ownerService.publicKey, null)
         .accounts({
           registry: registryAccount.publicKey,
+          serviceMint: (await nextServiceMintPda(registryAccount))[0],
+          serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+          registryWallet: registryWalletPda(registryAccount),
+          serviceOwner: ownerService.publicKey,
           service: servicePda,
           user: manager.publicKey,
         })
@@ -790,7 +826,12 @@ describe('registry', () => {
         const agentIdSeed = agentBN.toArrayLike(Buffer, 'le', 4);
 
         const [agent_param_pda] = anchor.web3.PublicKey.findProgramAddressSync(
-          [Buffer.from('agent_param'), serviceIdSeed, agentIdSeed],
+          [
+            Buffer.from('agent_param'),
+            registryAccount.publicKey.toBuffer(),
+            serviceIdSeed,
+            agentIdSeed,
+          ],
           program.programId
         );
         pdaList.push(agent_param_pda);
@@ -800,6 +841,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -838,7 +880,12 @@ describe('registry', () => {
       const agentIdSeed = agentBN.toArrayLike(Buffer, 'le', 4);
 
       const [agentToDeletePda] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('agent_param'), serviceIdSeed, agentIdSeed],
+        [
+          Buffer.from('agent_param'),
+          registryAccount.publicKey.toBuffer(),
+          serviceIdSeed,
+          agentIdSeed,
+        ],
         program.programId
       );
 
@@ -886,10 +933,7 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(7); // Unique config
 
-      const [servicePda] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('service'), config_hash.slice(0, 7)],
-        program.programId
-      );
+      const [servicePda] = await nextServicePda(registryAccount);
 
       // Create service
       try {
@@ -897,6 +941,10 @@ describe('registry', () => {
           .create(Array.from(config_hash), ownerService.publicKey, null)
           .accounts({
             registry: registryAccount.publicKey,
+            serviceMint: (await nextServiceMintPda(registryAccount))[0],
+            serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+            registryWallet: registryWalletPda(registryAccount),
+            serviceOwner: ownerService.publicKey,
             service: servicePda,
             user: manager.publicKey,
           })
@@ -918,7 +966,12 @@ describe('registry', () => {
         const agentIdSeed = agentBN.toArrayLike(Buffer, 'le', 4);
 
         const [agent_param_pda] = anchor.web3.PublicKey.findProgramAddressSync(
-          [Buffer.from('agent_param'), serviceIdSeed, agentIdSeed],
+          [
+            Buffer.from('agent_param'),
+            registryAccount.publicKey.toBuffer(),
+            serviceIdSeed,
+            agentIdSeed,
+          ],
           program.programId
         );
         pdaList.push(agent_param_pda);
@@ -928,6 +981,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -972,7 +1026,12 @@ describe('registry', () => {
       const agentIdSeed = agentBN.toArrayLike(Buffer, 'le', 4);
 
       const [newAgentParamPDA] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('agent_param'), serviceIdSeed, agentIdSeed],
+        [
+          Buffer.from('agent_param'),
+          registryAccount.publicKey.toBuffer(),
+          serviceIdSeed,
+          agentIdSeed,
+        ],
         program.programId
       );
 
@@ -1028,19 +1087,17 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(8);
 
-      const [servicePda, _bump] = anchor.web3.PublicKey.findProgramAddressSync(
-        [
-          Buffer.from('service'),
-          Buffer.from(config_hash.buffer, config_hash.byteOffset, 7),
-        ],
-        program.programId
-      );
+      const [servicePda, _bump] = await nextServicePda(registryAccount);
 
       // Create the first service
       await program.methods
         .create(Array.from(config_hash), ownerService.publicKey, null)
         .accounts({
           registry: registryAccount.publicKey,
+          serviceMint: (await nextServiceMintPda(registryAccount))[0],
+          serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+          registryWallet: registryWalletPda(registryAccount),
+          serviceOwner: ownerService.publicKey,
           service: servicePda,
           user: manager.publicKey,
         })
@@ -1063,6 +1120,7 @@ describe('registry', () => {
           anchor.web3.PublicKey.findProgramAddressSync(
             [
               Buffer.from('agent_param'),
+              registryAccount.publicKey.toBuffer(),
               serviceId.toArrayLike(Buffer, 'le', 16), // service_id as little-endian 16 bytes
               agentId.toArrayLike(Buffer, 'le', 4), // agent_id as little-endian 4 bytes
             ],
@@ -1076,6 +1134,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -1119,6 +1178,7 @@ describe('registry', () => {
         .accounts({
           registry: registryAccount.publicKey,
           service: servicePda,
+          serviceToken: await serviceTokenAccount(servicePda),
           user: manager.publicKey, // manager updates the service
         })
         .signers([manager])
@@ -1135,6 +1195,19 @@ describe('registry', () => {
       );
       assert.equal(updatedServiceAccount.threshold, newThreshold);
 
+      // Both the creation and the update config hashes are on record
+      const [configHashHistoryPda] =
+        anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from('config_hash_history'), servicePda.toBytes()],
+          program.programId
+        );
+      const history =
+        await program.account.configHashHistory.fetch(configHashHistoryPda);
+      assert.deepEqual(
+        history.records.map((record) => record.configHash),
+        [Array.from(config_hash), Array.from(newConfigHash)]
+      );
+
       // Test for threshold validation (should fail if threshold is invalid)
       const invalidThreshold = 10; // Invalid threshold (greater than max_num_agent_instances)
 
@@ -1148,6 +1221,7 @@ describe('registry', () => {
           .accounts({
             registry: registryAccount.publicKey,
             service: servicePda,
+            serviceToken: await serviceTokenAccount(servicePda),
             user: manager.publicKey,
           })
           .signers([manager])
@@ -1181,19 +1255,17 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(10);
 
-      const [servicePda, _bump] = anchor.web3.PublicKey.findProgramAddressSync(
-        [
-          Buffer.from('service'),
-          Buffer.from(config_hash.buffer, config_hash.byteOffset, 7),
-        ],
-        program.programId
-      );
+      const [servicePda, _bump] = await nextServicePda(registryAccount);
 
       // Create first service
       await program.methods
         .create(Array.from(config_hash), ownerService.publicKey, null)
         .accounts({
           registry: registryAccount.publicKey,
+          serviceMint: (await nextServiceMintPda(registryAccount))[0],
+          serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+          registryWallet: registryWalletPda(registryAccount),
+          serviceOwner: ownerService.publicKey,
           service: servicePda,
           user: manager.publicKey,
         })
@@ -1222,6 +1294,7 @@ describe('registry', () => {
           anchor.web3.PublicKey.findProgramAddressSync(
             [
               Buffer.from('agent_param'),
+              registryAccount.publicKey.toBuffer(),
               serviceId.toArrayLike(Buffer, 'le', 16), // service_id as little-endian 16 bytes
               agentId.toArrayLike(Buffer, 'le', 4), // agent_id as little-endian 4 bytes
             ],
@@ -1235,6 +1308,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -1316,19 +1390,17 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(11);
 
-      const [servicePda, _bump] = anchor.web3.PublicKey.findProgramAddressSync(
-        [
-          Buffer.from('service'),
-          Buffer.from(config_hash.buffer, config_hash.byteOffset, 7),
-        ],
-        program.programId
-      );
+      const [servicePda, _bump] = await nextServicePda(registryAccount);
 
       // Create first service
       await program.methods
         .create(Array.from(config_hash), ownerService.publicKey, null)
         .accounts({
           registry: registryAccount.publicKey,
+          serviceMint: (await nextServiceMintPda(registryAccount))[0],
+          serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+          registryWallet: registryWalletPda(registryAccount),
+          serviceOwner: ownerService.publicKey,
           service: servicePda,
           user: manager.publicKey,
         })
@@ -1357,6 +1429,7 @@ describe('registry', () => {
           anchor.web3.PublicKey.findProgramAddressSync(
             [
               Buffer.from('agent_param'),
+              registryAccount.publicKey.toBuffer(),
               serviceId.toArrayLike(Buffer, 'le', 16), // service_id as little-endian 16 bytes
               agentId.toArrayLike(Buffer, 'le', 4), // agent_id as little-endian 4 bytes
             ],
@@ -1370,6 +1443,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -1412,6 +1486,7 @@ describe('registry', () => {
         const [agent_param_pda] = anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('agent_param'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
             agentIdBN.toArrayLike(Buffer, 'le', 4),
           ],
@@ -1462,19 +1537,17 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(12);
 
-      const [servicePda, _bump] = anchor.web3.PublicKey.findProgramAddressSync(
-        [
-          Buffer.from('service'),
-          Buffer.from(config_hash.buffer, config_hash.byteOffset, 7),
-        ],
-        program.programId
-      );
+      const [servicePda, _bump] = await nextServicePda(registryAccount);
 
       // Create first service
       await program.methods
         .create(Array.from(config_hash), ownerService.publicKey, null)
         .accounts({
           registry: registryAccount.publicKey,
+          serviceMint: (await nextServiceMintPda(registryAccount))[0],
+          serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+          registryWallet: registryWalletPda(registryAccount),
+          serviceOwner: ownerService.publicKey,
           service: servicePda,
           user: manager.publicKey,
         })
@@ -1503,6 +1576,7 @@ describe('registry', () => {
           anchor.web3.PublicKey.findProgramAddressSync(
             [
               Buffer.from('agent_param'),
+              registryAccount.publicKey.toBuffer(),
               serviceId.toArrayLike(Buffer, 'le', 16), // service_id as little-endian 16 bytes
               agentId.toArrayLike(Buffer, 'le', 4), // agent_id as little-endian 4 bytes
             ],
@@ -1516,6 +1590,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -1570,6 +1645,7 @@ describe('registry', () => {
         const [agent_param_pda] = anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('agent_param'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
             agentId.toArrayLike(Buffer, 'le', 4),
           ],
@@ -1622,19 +1698,17 @@ describe('registry', () => {
 
       const config_hash = new Uint8Array(32).fill(13);
 
-      const [servicePda, _bump] = anchor.web3.PublicKey.findProgramAddressSync(
-        [
-          Buffer.from('service'),
-          Buffer.from(config_hash.buffer, config_hash.byteOffset, 7),
-        ],
-        program.programId
-      );
+      const [servicePda, _bump] = await nextServicePda(registryAccount);
 
       // Create first service
       await program.methods
         .create(Array.from(config_hash), ownerService.publicKey, null)
         .accounts({
           registry: registryAccount.publicKey,
+          serviceMint: (await nextServiceMintPda(registryAccount))[0],
+          serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+          registryWallet: registryWalletPda(registryAccount),
+          serviceOwner: ownerService.publicKey,
           service: servicePda,
           user: manager.publicKey,
         })
@@ -1663,6 +1737,7 @@ describe('registry', () => {
           anchor.web3.PublicKey.findProgramAddressSync(
             [
               Buffer.from('agent_param'),
+              registryAccount.publicKey.toBuffer(),
               serviceId.toArrayLike(Buffer, 'le', 16), // service_id as little-endian 16 bytes
               agentId.toArrayLike(Buffer, 'le', 4), // agent_id as little-endian 4 bytes
             ],
@@ -1676,6 +1751,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_ids_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
           ],
           program.programId
@@ -1724,10 +1800,15 @@ describe('registry', () => {
         await program.methods
           .activateRegistration(serviceId, ownerService.publicKey)
           .accounts({
+            bondToken: lamportBondToken(registryAccount.publicKey, serviceId),
             registry: registryAccount.publicKey,
             service: servicePda,
+            serviceToken: await serviceTokenAccount(servicePda),
             user: manager.publicKey,
-            registryWallet: programWalletPda,
+            serviceEscrow: serviceEscrowPda(
+              registryAccount.publicKey,
+              serviceId
+            )[0],
           })
           .signers([manager])
           .rpc();
@@ -1800,8 +1881,60 @@ describe('registry', () => {
       expect(operatorBond.bond.toNumber()).to.equal(instancesBond.toNumber());
     });
 
+    it('Audits the solvency of a service escrow', async () => {
+      const config_hash = new Uint8Array(32).fill(17);
+      const { serviceId, servicePda, operatorBondPda, programWalletPda } =
+        await registerMultipleAgentInstances(
+          registryAccount,
+          config_hash,
+          2,
+          2,
+          1
+        );
+      const [serviceBondToken] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from('service_bond_token'),
+          registryAccount.publicKey.toBuffer(),
+          new anchor.BN(serviceId).toArrayLike(Buffer, 'le', 16),
+        ],
+        program.programId
+      );
+
+      const report = await program.methods
+        .auditSolvency(Buffer.from([1]), Buffer.from([0]))
+        .accounts({
+          registry: registryAccount.publicKey,
+          registryWallet: programWalletPda,
+        })
+        .remainingAccounts(
+          [
+            servicePda,
+            serviceEscrowPda(registryAccount.publicKey, serviceId)[0],
+            serviceBondToken,
+            operatorBondPda,
+          ].map((pubkey) => ({ pubkey, isSigner: false, isWritable: false }))
+        )
+        .view();
+
+      const serviceAccount =
+        await program.account.serviceAccount.fetch(servicePda);
+      const operatorBond =
+        await program.account.operatorBondAccount.fetch(operatorBondPda);
+      expect(report.services).to.equal(1);
+      expect(report.securityDeposits.toString()).to.equal(
+        serviceAccount.securityDeposit.toString()
+      );
+      expect(report.bonds.toString()).to.equal(operatorBond.bond.toString());
+      expect(report.escrowBalance.toString()).to.equal(
+        serviceAccount.securityDeposit.add(operatorBond.bond).toString()
+      );
+      expect(report.insolventServices).to.be.empty;
+    });
+
     it('Deploys a service', async function () {
-      const agent_ids_per_service = 4;
+      // Deploy needs every agent instance slot filled, so the service has as
+      // many agent ids as registered instances
+      const agent_ids_per_service = 3;
       const threshold = 3;
       const agentsToRegister = 3;
       const config_hash = new Uint8Array(32).fill(17);
@@ -1844,10 +1977,48 @@ describe('registry', () => {
       assert.equal(updatedService.multisig.toBase58(), multisigPda.toBase58());
     });
 
-    it('Slashes agent instances', async function () {
+    it('Does not deploy a service before registration finishes', async function () {
       const agent_ids_per_service = 4;
       const threshold = 3;
       const agentsToRegister = 3;
+      const config_hash = new Uint8Array(32).fill(18);
+
+      // Enough instances for the threshold, but one slot is still open
+      const { serviceId, servicePda, agentInstances } =
+        await registerMultipleAgentInstances(
+          registryAccount,
+          config_hash,
+          agent_ids_per_service,
+          threshold,
+          agentsToRegister
+        );
+
+      try {
+        await deployService({
+          program,
+          registryAccount,
+          serviceId,
+          servicePda,
+          multisigImplementation,
+          ownerRegistry,
+          ownerService,
+          manager,
+          agentInstances,
+        });
+        assert.fail('Transaction should have failed due to the service state');
+      } catch (error) {
+        assert.equal(
+          error.message,
+          'AnchorError occurred. Error Code: WrongServiceState. Error Number: 6009. Error Message: Wrong service state.'
+        );
+      }
+    });
+
+    it('Slashes agent instances', async function () {
+      // Slashing needs a deployed service, so every agent instance slot is filled
+      const agent_ids_per_service = 3;
+      const threshold = 3;
+      const agentsToRegister = 3;
       const agentsToSlash = 2;
       const slashAmount = 0.1 * anchor.web3.LAMPORTS_PER_SOL;
       const config_hash = new Uint8Array(32).fill(20);
@@ -2004,14 +2175,9 @@ describe('registry', () => {
 
       assert.ok(serviceOwnerBalanceAfter > serviceOwnerBalanceBefore);
 
-      try {
-        await program.account.serviceAgentIdsIndex.fetch(
-          serviceAgentIdsIndexPDA
-        );
-        assert.fail('serviceAgentIdsIndex account should be closed');
-      } catch (err) {
-        assert.ok('Account closed as expected');
-      }
+      // The agent accounts stay until the operators unbonded and cleanup ran
+      await program.account.serviceAgentIdsIndex.fetch(serviceAgentIdsIndexPDA);
+      expect(serviceAfter.cleanupPending).to.be.true;
     });
 
     it('Unbonds a service', async () => {
@@ -2048,7 +2214,7 @@ describe('registry', () => {
         agentInstances,
       });
 
-      await terminateService({
+      const { serviceAgentIdsIndexPDA } = await terminateService({
         program,
         registryAccount,
         serviceId,
@@ -2064,6 +2230,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('operator_agent_instance_index'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
             operator.publicKey.toBuffer(),
           ],
@@ -2097,11 +2264,18 @@ describe('registry', () => {
               operatorAgentInstancePda
             );
           if (operatorAgentInstance.operator.equals(operator.publicKey)) {
-            remainingAccounts.push({
-              pubkey: operatorAgentInstancePda,
-              isWritable: true,
-              isSigner: false,
-            });
+            remainingAccounts.push(
+              {
+                pubkey: operatorAgentInstancePda,
+                isWritable: true,
+                isSigner: false,
+              },
+              {
+                pubkey: operatorAgentInstance.serviceAgentInstance,
+                isWritable: false,
+                isSigner: false,
+              }
+            );
           }
         } catch (e) {
           console.warn(
@@ -2112,7 +2286,19 @@ describe('registry', () => {
         }
       }
 
-      assert.equal(remainingAccounts.length, agentInstances.length);
+      // Each instance comes with the service agent instance holding its agent id
+      assert.equal(remainingAccounts.length, 2 * agentInstances.length);
+
+      const [pendingWithdrawalPda] =
+        anchor.web3.PublicKey.findProgramAddressSync(
+          [
+            Buffer.from('pending_withdrawal'),
+            registryAccount.publicKey.toBuffer(),
+            serviceId.toArrayLike(Buffer, 'le', 16),
+            operator.publicKey.toBuffer(),
+          ],
+          program.programId
+        );
 
       await program.methods
         .unbond(serviceId)
@@ -2122,13 +2308,41 @@ describe('registry', () => {
           operator: operator.publicKey,
           operatorBond: operatorBondPda,
           operatorAgentInstanceIndex: operatorAgentInstanceIndexPda,
+          serviceAgentIdsIndex: serviceAgentIdsIndexPDA,
+          pendingWithdrawal: pendingWithdrawalPda,
           user: manager.publicKey,
-          registryWallet: programWalletPda,
         })
         .remainingAccounts(remainingAccounts)
         .signers([manager])
         .rpc();
 
+      // The refund waits in the pending withdrawal until claimed
+      const pendingWithdrawal =
+        await program.account.pendingWithdrawal.fetch(pendingWithdrawalPda);
+      expect(pendingWithdrawal.amount.toNumber()).to.equal(
+        instancesBond.toNumber()
+      );
+
+      await program.methods
+        .claimUnbonded(serviceId)
+        .accounts({
+          registry: registryAccount.publicKey,
+          service: servicePda,
+          pendingWithdrawal: pendingWithdrawalPda,
+          payer: manager.publicKey,
+          operator: operator.publicKey,
+          serviceEscrow: serviceEscrowPda(
+            registryAccount.publicKey,
+            serviceId
+          )[0],
+          bondToken: lamportBondToken(registryAccount.publicKey, serviceId),
+          user: manager.publicKey,
+        })
+        .signers([manager])
+        .rpc();
+      expect(await provider.connection.getAccountInfo(pendingWithdrawalPda)).to
+        .be.null;
+
       // Operator bond must be wiped
       let deletedAccount =
         await provider.connection.getAccountInfo(operatorBondPda);
@@ -2153,10 +2367,28 @@ describe('registry', () => {
         operatorAgentInstanceIndexPda
       );
       expect(deletedAccount).to.be.null;
+
+      // Unbonded, the service agent accounts can be cleaned up
+      await cleanupService({
+        program,
+        registryAccount,
+        serviceId,
+        servicePda,
+        agent_ids_per_service,
+        agentInstances,
+        ownerService,
+        manager,
+        programWalletPda,
+      });
+      deletedAccount = await provider.connection.getAccountInfo(
+        serviceAgentIdsIndexPDA
+      );
+      expect(deletedAccount).to.be.null;
     });
 
     it('Drains the registry slashed funds', async function () {
-      const agent_ids_per_service = 4;
+      // Slashing needs a deployed service, so every agent instance slot is filled
+      const agent_ids_per_service = 3;
       const threshold = 3;
       const agentsToRegister = 3;
       const agentsToSlash = 2;
@@ -2219,6 +2451,10 @@ describe('registry', () => {
       await program.methods
         .drain()
         .accounts({
+          bondTokenVault: null,
+          vaultToken: null,
+          drainerToken: null,
+          tokenProgram: null,
           registry: registryAccount.publicKey,
           drainer: drainer.publicKey,
           registryWallet: programWalletPda,
@@ -2280,19 +2516,25 @@ describe('registry', () => {
     });
 
     it('Changes the drainer of the registry', async function () {
-      const anotherDrainer = anchor.web3.Keypair.generate().publicKey;
-      try {
-        await program.methods
-          .changeDrainer(anotherDrainer)
-          .accounts({
-            registry: registryAccount.publicKey,
-            user: ownerRegistry.publicKey,
-          })
-          .signers([ownerRegistry])
-          .rpc();
-      } catch (error) {
-        console.error('Transaction failed:', error);
-      }
+      const anotherDrainer = anchor.web3.Keypair.generate();
+      await program.methods
+        .proposeDrainer(anotherDrainer.publicKey)
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: ownerRegistry.publicKey,
+        })
+        .signers([ownerRegistry])
+        .rpc();
+
+      // The drainer changes once the proposed one accepts
+      await program.methods
+        .acceptDrainer()
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: anotherDrainer.publicKey,
+        })
+        .signers([anotherDrainer])
+        .rpc();
 
       // Fetch the updated registry account
       const registry = await program.account.serviceRegistry.fetch(
@@ -2300,19 +2542,29 @@ describe('registry', () => {
       );
 
       // Check that the drainer has been updated correctly
-      expect(registry.drainer.toBase58()).to.equal(anotherDrainer.toBase58());
+      expect(registry.drainer.toBase58()).to.equal(
+        anotherDrainer.publicKey.toBase58()
+      );
     });
 
     it('Changes the owner of the registry', async function () {
       const newOwner = anchor.web3.Keypair.generate();
       await program.methods
-        .changeOwner(newOwner.publicKey)
+        .proposeOwner(newOwner.publicKey)
         .accounts({
           registry: registryAccount.publicKey,
           user: ownerRegistry.publicKey,
         })
         .signers([ownerRegistry])
         .rpc();
+      await program.methods
+        .acceptOwner()
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: newOwner.publicKey,
+        })
+        .signers([newOwner])
+        .rpc();
 
       // Fetch the updated registry account
       const registry = await program.account.serviceRegistry.fetch(
@@ -2324,19 +2576,35 @@ describe('registry', () => {
 
       // Revert change for other tests
       await program.methods
-        .changeOwner(ownerRegistry.publicKey)
+        .proposeOwner(ownerRegistry.publicKey)
         .accounts({
           registry: registryAccount.publicKey,
           user: newOwner.publicKey,
         })
         .signers([newOwner])
         .rpc();
+      await program.methods
+        .acceptOwner()
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: ownerRegistry.publicKey,
+        })
+        .signers([ownerRegistry])
+        .rpc();
     });
 
-    it('Changes the manager of the registry', async function () {
-      const newManager = anchor.web3.Keypair.generate().publicKey;
+    it('Cancels a proposed manager of the registry', async function () {
+      const newManager = anchor.web3.Keypair.generate();
+      await program.methods
+        .proposeManager(newManager.publicKey)
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: ownerRegistry.publicKey,
+        })
+        .signers([ownerRegistry])
+        .rpc();
       await program.methods
-        .changeManager(newManager)
+        .cancelManager()
         .accounts({
           registry: registryAccount.publicKey,
           user: ownerRegistry.publicKey,
@@ -2349,8 +2617,11 @@ describe('registry', () => {
         registryAccount.publicKey
       );
 
-      // Check that the owner has been updated correctly
-      expect(registry.manager.toBase58()).to.equal(newManager.toBase58());
+      // Check that the manager is unchanged and nothing is pending
+      expect(registry.manager.toBase58()).to.equal(manager.publicKey.toBase58());
+      expect(registry.pendingManager.toBase58()).to.equal(
+        anchor.web3.PublicKey.default.toBase58()
+      );
     });
 
     it('Changes the base uri of the registry', async function () {
@@ -2372,6 +2643,221 @@ describe('registry', () => {
       // Check that the owner has been updated correctly
       expect(registry.baseUri).to.equal(new_base_uri);
     });
+
+    it('Lets service owners create their own services', async function () {
+      const creationFee = new anchor.BN(anchor.web3.LAMPORTS_PER_SOL / 10);
+      await program.methods
+        .setPermissionless(true, creationFee)
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: ownerRegistry.publicKey,
+        })
+        .signers([ownerRegistry])
+        .rpc();
+
+      const registry = await program.account.serviceRegistry.fetch(
+        registryAccount.publicKey
+      );
+      expect(registry.permissionless).to.be.true;
+      expect(registry.creationFee.toString()).to.equal(creationFee.toString());
+    });
+
+    it('Grants a role through the roles account', async function () {
+      const editor = anchor.web3.Keypair.generate().publicKey;
+      await program.methods
+        .grantRole({ metadataEditor: {} }, editor)
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: ownerRegistry.publicKey,
+        })
+        .signers([ownerRegistry])
+        .rpc();
+
+      const [rolesPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('roles'), registryAccount.publicKey.toBytes()],
+        program.programId
+      );
+      const roles = await program.account.registryRoles.fetch(rolesPda);
+      // Roles are indexed in declaration order, MetadataEditor sixth
+      expect(
+        roles.roles[5].members.map((key) => key.toString())
+      ).to.include(editor.toString());
+    });
+
+    it('Queues an operation in the timelock', async function () {
+      await program.methods
+        .initializeTimelock(new anchor.BN(3600))
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: ownerRegistry.publicKey,
+        })
+        .signers([ownerRegistry])
+        .rpc();
+
+      const [timelockPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('timelock'), registryAccount.publicKey.toBytes()],
+        program.programId
+      );
+      const [authorityPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('timelock_authority'), registryAccount.publicKey.toBytes()],
+        program.programId
+      );
+      const target = await program.methods
+        .setBaseUri('timelocked_uri')
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: authorityPda,
+        })
+        .instruction();
+      await program.methods
+        .queueTimelockOperation(
+          target.keys.map((key) => ({
+            pubkey: key.pubkey,
+            isSigner: key.isSigner,
+            isWritable: key.isWritable,
+          })),
+          target.data
+        )
+        .accounts({
+          timelock: timelockPda,
+          user: ownerRegistry.publicKey,
+        })
+        .signers([ownerRegistry])
+        .rpc();
+
+      const timelock = await program.account.timelock.fetch(timelockPda);
+      expect(timelock.operationCount.toNumber()).to.equal(1);
+
+      // The timelock authority alone holds Admin, first in declaration order
+      const [rolesPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('roles'), registryAccount.publicKey.toBytes()],
+        program.programId
+      );
+      const roles = await program.account.registryRoles.fetch(rolesPda);
+      expect(roles.roles[0].members.map((key) => key.toString())).to.deep.equal([
+        authorityPda.toString(),
+      ]);
+    });
+
+    it('Pauses and resumes service creation', async function () {
+      await program.methods
+        .setPaused({ creation: {} }, true)
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: ownerRegistry.publicKey,
+        })
+        .signers([ownerRegistry])
+        .rpc();
+      let registry = await program.account.serviceRegistry.fetch(
+        registryAccount.publicKey
+      );
+      expect(registry.paused).to.equal(1);
+
+      await program.methods
+        .setPaused({ creation: {} }, false)
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: ownerRegistry.publicKey,
+        })
+        .signers([ownerRegistry])
+        .rpc();
+      registry = await program.account.serviceRegistry.fetch(
+        registryAccount.publicKey
+      );
+      expect(registry.paused).to.equal(0);
+    });
+
+    it('Requires instance proofs in a service', async function () {
+      const config_hash = new Uint8Array(32).fill(0xac);
+      const { serviceId, servicePda } = await createService(
+        registryAccount,
+        config_hash
+      );
+
+      await program.methods
+        .setInstanceProofs(serviceId, true)
+        .accounts({
+          registry: registryAccount.publicKey,
+          user: manager.publicKey,
+        })
+        .signers([manager])
+        .rpc();
+
+      const service = await program.account.serviceAccount.fetch(servicePda);
+      expect(service.requireInstanceProofs).to.be.true;
+    });
+
+    it('Whitelists operators of a service', async function () {
+      const config_hash = new Uint8Array(32).fill(0xad);
+      const { serviceId, servicePda } = await createService(
+        registryAccount,
+        config_hash
+      );
+      const operator = anchor.web3.Keypair.generate().publicKey;
+
+      await program.methods
+        .setOperatorsStatuses(serviceId, [operator], [true], true)
+        .accounts({
+          registry: registryAccount.publicKey,
+          operatorWhitelist: operatorWhitelistPda(
+            registryAccount.publicKey,
+            serviceId
+          )[0],
+          serviceOwner: ownerService.publicKey,
+          serviceToken: await serviceTokenAccount(servicePda),
+        })
+        .signers([ownerService])
+        .rpc();
+
+      const whitelist = await program.account.operatorWhitelist.fetch(
+        operatorWhitelistPda(registryAccount.publicKey, serviceId)[0]
+      );
+      expect(whitelist.check).to.be.true;
+      expect(whitelist.operators.map((key) => key.toString())).to.deep.equal([
+        operator.toString(),
+      ]);
+    });
+
+    it('Computes the token uri of a service', async function () {
+      const config_hash = new Uint8Array(32).fill(0xab);
+      const { serviceId } = await createService(registryAccount, config_hash);
+
+      const tokenUri = await program.methods
+        .tokenUri(serviceId)
+        .accounts({ registry: registryAccount.publicKey })
+        .view();
+
+      expect(tokenUri).to.equal(
+        'new_base_uri' + 'f01701220' + Buffer.from(config_hash).toString('hex')
+      );
+    });
+
+    it('Transfers a service to a new owner', async function () {
+      const config_hash = new Uint8Array(32).fill(0xac);
+      const { servicePda, serviceId } = await createService(
+        registryAccount,
+        config_hash
+      );
+      const newOwner = anchor.web3.Keypair.generate().publicKey;
+
+      await program.methods
+        .transferService(serviceId, newOwner)
+        .accounts({
+          registry: registryAccount.publicKey,
+          serviceMint: serviceMintPda(registryAccount.publicKey, serviceId)[0],
+          serviceOwner: ownerService.publicKey,
+          serviceToken: await serviceTokenAccount(servicePda),
+          newServiceOwner: newOwner,
+        })
+        .signers([ownerService])
+        .rpc();
+
+      const serviceAccount =
+        await program.account.serviceAccount.fetch(servicePda);
+      expect(serviceAccount.serviceOwner.toString()).to.equal(
+        newOwner.toString()
+      );
+    });
   });
 
   async function registerMultipleAgentInstances(
@@ -2480,6 +2966,7 @@ describe('registry', () => {
     const operatorBondPda = anchor.web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from('operator_bond'),
+        registryAccount.publicKey.toBuffer(),
         serviceIdBn.toArrayLike(Buffer, 'le', 16),
         operator.publicKey.toBuffer(),
       ],
@@ -2503,9 +2990,14 @@ describe('registry', () => {
         amounts.map((a) => new anchor.BN(a))
       )
       .accounts({
+        bondToken: lamportBondToken(registryAccount.publicKey, serviceIdBn),
         registry: registryAccount.publicKey,
         service: servicePda,
         registryWallet,
+        serviceEscrow: serviceEscrowPda(
+          registryAccount.publicKey,
+          serviceIdBn
+        )[0],
         user: new_multisig.publicKey,
       })
       .remainingAccounts(
@@ -2526,22 +3018,153 @@ describe('registry', () => {
       .rpc();
   }
 
-  async function createService(
-    registryAccount: anchor.web3.Keypair,
-    config_hash: Uint8Array<ArrayBuffer>
-  ) {
-    const [servicePda] = anchor.web3.PublicKey.findProgramAddressSync(
+  // Services are addressed by the id they are created with
+  async function nextServicePda(
+    registryAccount: anchor.web3.Keypair
+  ): Promise<[anchor.web3.PublicKey, number]> {
+    const registry = await program.account.serviceRegistry.fetch(
+      registryAccount.publicKey
+    );
+    const serviceId = registry.totalSupply.add(new anchor.BN(1));
+    return anchor.web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from('service'),
-        Buffer.from(config_hash.buffer, config_hash.byteOffset, 7),
+        registryAccount.publicKey.toBuffer(),
+        serviceId.toArrayLike(Buffer, 'le', 16),
+      ],
+      program.programId
+    );
+  }
+
+  async function nextServiceMintPda(
+    registryAccount: anchor.web3.Keypair
+  ): Promise<[anchor.web3.PublicKey, number]> {
+    const registry = await program.account.serviceRegistry.fetch(
+      registryAccount.publicKey
+    );
+    return serviceMintPda(
+      registryAccount.publicKey,
+      registry.totalSupply.add(new anchor.BN(1))
+    );
+  }
+
+  async function nextServiceEscrowPda(
+    registryAccount: anchor.web3.Keypair
+  ): Promise<[anchor.web3.PublicKey, number]> {
+    const registry = await program.account.serviceRegistry.fetch(
+      registryAccount.publicKey
+    );
+    return serviceEscrowPda(
+      registryAccount.publicKey,
+      registry.totalSupply.add(new anchor.BN(1))
+    );
+  }
+
+  function registryWalletPda(
+    registryAccount: anchor.web3.Keypair
+  ): anchor.web3.PublicKey {
+    return anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('registry_wallet'), registryAccount.publicKey.toBuffer()],
+      program.programId
+    )[0];
+  }
+
+  function serviceMintPda(
+    registry: anchor.web3.PublicKey,
+    serviceId: anchor.BN | number
+  ): [anchor.web3.PublicKey, number] {
+    return anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('service_mint'),
+        registry.toBuffer(),
+        new anchor.BN(serviceId).toArrayLike(Buffer, 'le', 16),
       ],
       program.programId
     );
+  }
+
+  function serviceEscrowPda(
+    registry: anchor.web3.PublicKey,
+    serviceId: anchor.BN | number
+  ): [anchor.web3.PublicKey, number] {
+    return anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('service_escrow'),
+        registry.toBuffer(),
+        new anchor.BN(serviceId).toArrayLike(Buffer, 'le', 16),
+      ],
+      program.programId
+    );
+  }
+
+  function operatorWhitelistPda(
+    registry: anchor.web3.PublicKey,
+    serviceId: anchor.BN | number
+  ): [anchor.web3.PublicKey, number] {
+    return anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('operator_whitelist'),
+        registry.toBuffer(),
+        new anchor.BN(serviceId).toArrayLike(Buffer, 'le', 16),
+      ],
+      program.programId
+    );
+  }
+
+  // Bond token accounts of a service bonded in lamports
+  function lamportBondToken(
+    registry: anchor.web3.PublicKey,
+    serviceId: anchor.BN | number
+  ) {
+    const [serviceBondToken] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('service_bond_token'),
+        registry.toBuffer(),
+        new anchor.BN(serviceId).toArrayLike(Buffer, 'le', 16),
+      ],
+      program.programId
+    );
+    return {
+      serviceBondToken,
+      bondTokenVault: null,
+      vaultToken: null,
+      escrowToken: null,
+      counterpartyToken: null,
+      tokenProgram: null,
+    };
+  }
+
+  // Associated token account of the service owner for the service token
+  async function serviceTokenAccount(
+    servicePda: anchor.web3.PublicKey
+  ): Promise<anchor.web3.PublicKey> {
+    const service = await program.account.serviceAccount.fetch(servicePda);
+    const [serviceMint] = serviceMintPda(service.registry, service.serviceId);
+    const [serviceToken] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        service.serviceOwner.toBytes(),
+        TOKEN_PROGRAM_ID.toBytes(),
+        serviceMint.toBytes(),
+      ],
+      ASSOCIATED_TOKEN_PROGRAM_ID
+    );
+    return serviceToken;
+  }
+
+  async function createService(
+    registryAccount: anchor.web3.Keypair,
+    config_hash: Uint8Array<ArrayBuffer>
+  ) {
+    const [servicePda] = await nextServicePda(registryAccount);
 
     await program.methods
       .create(Array.from(config_hash), ownerService.publicKey, null)
       .accounts({
         registry: registryAccount.publicKey,
+        serviceMint: (await nextServiceMintPda(registryAccount))[0],
+        serviceEscrow: (await nextServiceEscrowPda(registryAccount))[0],
+        registryWallet: registryWalletPda(registryAccount),
+        serviceOwner: ownerService.publicKey,
         service: servicePda,
         user: manager.publicKey,
       })
@@ -2565,6 +3188,7 @@ describe('registry', () => {
       anchor.web3.PublicKey.findProgramAddressSync(
         [
           Buffer.from('service_agent_ids_index'),
+          registryAccount.publicKey.toBuffer(),
           serviceId.toArrayLike(Buffer, 'le', 16),
         ],
         program.programId
@@ -2575,6 +3199,7 @@ describe('registry', () => {
       const [pda] = anchor.web3.PublicKey.findProgramAddressSync(
         [
           Buffer.from('agent_param'),
+          registryAccount.publicKey.toBuffer(),
           serviceId.toArrayLike(Buffer, 'le', 16),
           agentId.toArrayLike(Buffer, 'le', 4),
         ],
@@ -2623,10 +3248,15 @@ describe('registry', () => {
       await program.methods
         .activateRegistration(serviceId, ownerService.publicKey)
         .accounts({
+          bondToken: lamportBondToken(registryAccount.publicKey, serviceId),
           registry: registryAccount.publicKey,
           service: servicePda,
+          serviceToken: await serviceTokenAccount(servicePda),
           user: manager.publicKey,
-          registryWallet: programWalletPda,
+          serviceEscrow: serviceEscrowPda(
+            registryAccount.publicKey,
+            serviceId
+          )[0],
         })
         .signers([manager])
         .rpc();
@@ -2655,9 +3285,10 @@ describe('registry', () => {
       (_, i) => new anchor.BN(anchor.web3.LAMPORTS_PER_SOL * (i + 1))
     ).reduce((acc, bond) => acc.add(bond), new anchor.BN(0));
 
+    // The operator registers itself, paying its bond and the registration accounts
     const airdropSignature = await connection.requestAirdrop(
       operator.publicKey,
-      instancesBond.toNumber()
+      instancesBond.toNumber() + anchor.web3.LAMPORTS_PER_SOL
     );
     await connection.confirmTransaction(airdropSignature);
 
@@ -2673,6 +3304,7 @@ describe('registry', () => {
     const [agentInstancesPda] = anchor.web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from('agent_instances_index'),
+        registryAccount.publicKey.toBuffer(),
         serviceId.toArrayLike(Buffer, 'le', 16),
       ],
       program.programId
@@ -2689,6 +3321,7 @@ describe('registry', () => {
       const [slotCounterPda] = anchor.web3.PublicKey.findProgramAddressSync(
         [
           Buffer.from('service_agent_slot'),
+          registryAccount.publicKey.toBuffer(),
           serviceId.toArrayLike(Buffer, 'le', 16),
           agentId.toArrayLike(Buffer, 'le', 4),
         ],
@@ -2699,6 +3332,7 @@ describe('registry', () => {
         anchor.web3.PublicKey.findProgramAddressSync(
           [
             Buffer.from('service_agent_instance_account'),
+            registryAccount.publicKey.toBuffer(),
             serviceId.toArrayLike(Buffer, 'le', 16),
             agentId.toArrayLike(Buffer, 'le', 4),
             agentInstance.publicKey.toBuffer(),
@@ -2726,6 +3360,7 @@ describe('registry', () => {
     const [operatorBondPda] = anchor.web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from('operator_bond'),
+        registryAccount.publicKey.toBuffer(),
         serviceId.toArrayLike(Buffer, 'le', 16),
         operator.publicKey.toBuffer(),
       ],
@@ -2737,6 +3372,7 @@ describe('registry', () => {
       anchor.web3.PublicKey.findProgramAddressSync(
         [
           Buffer.from('operator_agent_instance_index'),
+          registryAccount.publicKey.toBuffer(),
           serviceId.toArrayLike(Buffer, 'le', 16),
           operator.publicKey.toBuffer(),
         ],
@@ -2750,11 +3386,19 @@ describe('registry', () => {
         agent_ids.slice(0, agentsToRegister)
       )
       .accounts({
+        bondToken: lamportBondToken(registryAccount.publicKey, serviceId),
         registry: registryAccount.publicKey,
         service: servicePda,
-        user: manager.publicKey,
-        registryWallet: programWalletPda,
+        user: operator.publicKey,
+        serviceEscrow: serviceEscrowPda(
+          registryAccount.publicKey,
+          serviceId
+        )[0],
         operatorAgentInstanceIndex: operatorAgentInstanceIndexPda,
+        operatorWhitelist: operatorWhitelistPda(
+          registryAccount.publicKey,
+          serviceId
+        )[0],
       })
       .remainingAccounts(
         pdaList.map((pda, idx) => ({
@@ -2763,7 +3407,7 @@ describe('registry', () => {
           isWritable: idx >= paramPdas.length,
         }))
       )
-      .signers([manager])
+      .signers([operator])
       .rpc();
 
     return { agentInstances, operator, instancesBond, operatorBondPda };
@@ -2794,6 +3438,62 @@ describe('registry', () => {
       anchor.web3.PublicKey.findProgramAddressSync(
         [
           Buffer.from('service_agent_ids_index'),
+          registryAccount.publicKey.toBuffer(),
+          serviceId.toArrayLike(Buffer, 'le', 16),
+        ],
+        program.programId
+      );
+
+    await program.methods
+      .terminate(new anchor.BN(serviceId))
+      .accounts({
+        bondToken: lamportBondToken(registryAccount.publicKey, serviceId),
+        registry: registryAccount.publicKey,
+        service: servicePda,
+        serviceToken: await serviceTokenAccount(servicePda),
+        serviceOwner: ownerService.publicKey,
+        serviceAgentIdsIndex: serviceAgentIdsIndexPDA,
+        user: manager.publicKey,
+        serviceEscrow: serviceEscrowPda(
+          registryAccount.publicKey,
+          serviceId
+        )[0],
+      })
+      .signers([manager])
+      .rpc();
+
+    return {
+      serviceAgentIdsIndexPDA,
+    };
+  }
+
+  // Closes the agent accounts of a terminated service whose operators all unbonded
+  async function cleanupService({
+    program,
+    registryAccount,
+    serviceId,
+    servicePda,
+    agent_ids_per_service,
+    agentInstances,
+    ownerService,
+    manager,
+    programWalletPda,
+  }: {
+    program: any;
+    registryAccount: any;
+    serviceId: anchor.BN;
+    servicePda: anchor.web3.PublicKey;
+    agent_ids_per_service: number;
+    agentInstances: anchor.web3.Keypair[];
+    ownerService: anchor.web3.Keypair;
+    manager: anchor.web3.Keypair;
+    programWalletPda: anchor.web3.PublicKey;
+  }) {
+    const [serviceAgentIdsIndexPDA] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from('service_agent_ids_index'),
+          registryAccount.publicKey.toBuffer(),
           serviceId.toArrayLike(Buffer, 'le', 16),
         ],
         program.programId
@@ -2804,12 +3504,10 @@ describe('registry', () => {
       (_, i) => i + 1
     );
 
-    const slotCounterAccounts = [];
-    const serviceAgentInstancePDAs = [];
-
     const [agentInstancesPda] = anchor.web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from('agent_instances_index'),
+        registryAccount.publicKey.toBuffer(),
         serviceId.toArrayLike(Buffer, 'le', 16),
       ],
       program.programId
@@ -2823,60 +3521,52 @@ describe('registry', () => {
       serviceAgentInstancesIndex.serviceAgentInstances.length
     );
 
-    for (const agent_id of agent_ids) {
-      const agentId = new anchor.BN(agent_id);
+    // Slot counters first, then each instance under the agent id it filled
+    const cleanupAccounts = agent_ids.map((agent_id) => {
       const [slotCounterPda] = anchor.web3.PublicKey.findProgramAddressSync(
         [
           Buffer.from('service_agent_slot'),
+          registryAccount.publicKey.toBuffer(),
           serviceId.toArrayLike(Buffer, 'le', 16),
-          agentId.toArrayLike(Buffer, 'le', 4),
+          new anchor.BN(agent_id).toArrayLike(Buffer, 'le', 4),
         ],
         program.programId
       );
-      slotCounterAccounts.push({
-        pubkey: slotCounterPda,
-        isSigner: false,
-        isWritable: true,
-      });
-
-      for (const agentInstance of serviceAgentInstancesIndex.serviceAgentInstances) {
+      return { pubkey: slotCounterPda, isSigner: false, isWritable: true };
+    });
+    serviceAgentInstancesIndex.serviceAgentInstances.forEach(
+      (agentInstance, index) => {
         const [serviceAgentInstancePda] =
           anchor.web3.PublicKey.findProgramAddressSync(
             [
               Buffer.from('service_agent_instance_account'),
+              registryAccount.publicKey.toBuffer(),
               serviceId.toArrayLike(Buffer, 'le', 16),
-              agentId.toArrayLike(Buffer, 'le', 4),
+              new anchor.BN(agent_ids[index]).toArrayLike(Buffer, 'le', 4),
               agentInstance.toBuffer(),
             ],
             program.programId
           );
-        serviceAgentInstancePDAs.push({
+        cleanupAccounts.push({
           pubkey: serviceAgentInstancePda,
           isSigner: false,
           isWritable: true,
         });
       }
-    }
+    );
 
     await program.methods
-      .terminate(new anchor.BN(serviceId))
+      .cleanupService(new anchor.BN(serviceId))
       .accounts({
         registry: registryAccount.publicKey,
         service: servicePda,
-        serviceOwner: ownerService.publicKey,
         serviceAgentIdsIndex: serviceAgentIdsIndexPDA,
+        agentInstancesIndex: agentInstancesPda,
+        serviceOwner: ownerService.publicKey,
+        serviceToken: await serviceTokenAccount(servicePda),
         user: manager.publicKey,
-        registryWallet: programWalletPda,
       })
-      .remainingAccounts([
-        {
-          pubkey: agentInstancesPda,
-          isSigner: false,
-          isWritable: true,
-        },
-        ...slotCounterAccounts,
-        ...serviceAgentInstancePDAs,
-      ])
+      .remainingAccounts(cleanupAccounts)
       .signers([manager])
       .rpc();
 
@@ -2904,7 +3594,7 @@ describe('registry', () => {
 
     // Whitelist the multisig implementation
     await program.methods
-      .changeMultisigPermission(multisigImplementation.publicKey, true)
+      .changeMultisigPermission(multisigImplementation, true)
       .accounts({
         registry: registryAccount.publicKey,
         registryMultisig: registryMultisigPda,
@@ -2919,6 +3609,7 @@ describe('registry', () => {
     const [agentInstancesPda] = anchor.web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from('agent_instances_index'),
+        registryAccount.publicKey.toBuffer(),
         serviceId.toArrayLike(Buffer, 'le', 16),
       ],
       program.programId
@@ -2979,12 +3670,15 @@ describe('registry', () => {
 
     // Deploy the service
     await program.methods
-      .deploy(serviceId, multisigImplementation.publicKey, Buffer.from([]))
+      .deploy(serviceId, multisigImplementation, Buffer.from([]))
       .accounts({
         registry: registryAccount.publicKey,
         service: servicePda,
+        serviceToken: await serviceTokenAccount(servicePda),
         serviceOwner: ownerService.publicKey,
         registryMultisig: registryMultisigPda,
+        multisigImplementation,
+        agentInstancesIndex: agentInstancesPda,
         user: manager.publicKey,
       })
       .remainingAccounts(remainingAccounts)